@@ -1,20 +1,64 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
+use actix::Message;
+use actix::MessageResult;
 use actix::Syn;
+use ledger_file::Direction;
+use ledger_file::LedgerContents;
+use ledger_file::LedgerEntry;
+use ledger_file::LedgerFile;
+use ledger_file::LedgerFileError;
+use ledger_file::Service;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 use sub_lib::accountant::AccountantConfig;
 use sub_lib::accountant::AccountantSubs;
+use sub_lib::accountant::LedgerQueryMessage;
+use sub_lib::accountant::LedgerTotals;
+use sub_lib::accountant::ReportExitServiceConsumedMessage;
+use sub_lib::accountant::ReportExitServiceMessage;
+use sub_lib::accountant::ReportRoutingServiceConsumedMessage;
+use sub_lib::accountant::ReportRoutingServiceMessage;
+use sub_lib::accountant::ServiceTotals;
 use sub_lib::logger::Logger;
 use sub_lib::peer_actors::BindMessage;
+use sub_lib::wallet::Wallet;
+
+pub const LEDGER_FLUSH_INTERVAL_MILLIS: u64 = 1000;
 
 pub struct Accountant {
+    wallet: Option<Wallet>,
+    receivable: HashMap<Wallet, ServiceTotals>,
+    payable: HashMap<Wallet, ServiceTotals>,
+    ledger_file: Option<LedgerFile>,
     logger: Logger,
 }
 
 impl Actor for Accountant {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.notify_later(
+            FlushLedgerMessage {},
+            Duration::from_millis(LEDGER_FLUSH_INTERVAL_MILLIS),
+        );
+    }
+}
+
+// The Accountant sends this to itself every LEDGER_FLUSH_INTERVAL_MILLIS to write out the debts it's recorded since
+// the last time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlushLedgerMessage {}
+
+impl Message for FlushLedgerMessage {
+    type Result = ();
 }
 
 impl Handler<BindMessage> for Accountant {
@@ -26,16 +70,271 @@ impl Handler<BindMessage> for Accountant {
     }
 }
 
+impl Handler<FlushLedgerMessage> for Accountant {
+    type Result = ();
+
+    fn handle(&mut self, _msg: FlushLedgerMessage, ctx: &mut Self::Context) -> Self::Result {
+        let result = match self.ledger_file {
+            Some(ref mut ledger_file) => ledger_file.flush(),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            self.logger
+                .error(format!("Debts will be forgotten on restart: {:?}", e));
+        }
+        ctx.notify_later(
+            FlushLedgerMessage {},
+            Duration::from_millis(LEDGER_FLUSH_INTERVAL_MILLIS),
+        );
+        ()
+    }
+}
+
+impl Handler<ReportRoutingServiceMessage> for Accountant {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ReportRoutingServiceMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.record_receivable(msg.consuming_wallet, Service::Routing, msg.payload_size);
+        ()
+    }
+}
+
+impl Handler<ReportExitServiceMessage> for Accountant {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportExitServiceMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.record_receivable(msg.consuming_wallet, Service::Exit, msg.payload_size);
+        ()
+    }
+}
+
+impl Handler<ReportRoutingServiceConsumedMessage> for Accountant {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ReportRoutingServiceConsumedMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.record_payable(msg.earning_wallet, Service::Routing, msg.payload_size);
+        ()
+    }
+}
+
+impl Handler<ReportExitServiceConsumedMessage> for Accountant {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ReportExitServiceConsumedMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.record_payable(msg.earning_wallet, Service::Exit, msg.payload_size);
+        ()
+    }
+}
+
+impl Handler<LedgerQueryMessage> for Accountant {
+    type Result = MessageResult<LedgerQueryMessage>;
+
+    fn handle(
+        &mut self,
+        msg: LedgerQueryMessage,
+        _ctx: &mut Self::Context,
+    ) -> <Self as Handler<LedgerQueryMessage>>::Result {
+        MessageResult(LedgerTotals {
+            receivable: Accountant::totals_for(&self.receivable, &msg.wallet_opt),
+            payable: Accountant::totals_for(&self.payable, &msg.wallet_opt),
+        })
+    }
+}
+
 impl Accountant {
-    pub fn new(_config: AccountantConfig) -> Accountant {
-        Accountant {
+    pub fn new(config: AccountantConfig) -> Accountant {
+        let mut accountant = Accountant {
+            wallet: config.wallet,
+            receivable: HashMap::new(),
+            payable: HashMap::new(),
+            ledger_file: None,
             logger: Logger::new("Accountant"),
+        };
+        if let Some(data_directory) = config.data_directory_opt {
+            let (ledger_file, contents) =
+                Accountant::open_ledger(&accountant.logger, &data_directory);
+            if let Some(version) = contents.migrated_from {
+                accountant.logger.info(format!(
+                    "Brought accounting ledger {} up to date from version {}",
+                    ledger_file.path().display(),
+                    version
+                ));
+            }
+            if contents.discarded_bytes > 0 {
+                accountant.logger.warning(format!(
+                    "Discarded {} bytes of incomplete or damaged entries from the end of {}",
+                    contents.discarded_bytes,
+                    ledger_file.path().display()
+                ));
+            }
+            contents.entries.into_iter().for_each(|entry| {
+                let ledger = match entry.direction {
+                    Direction::Receivable => &mut accountant.receivable,
+                    Direction::Payable => &mut accountant.payable,
+                };
+                Accountant::record(ledger, entry.wallet, entry.service, entry.payload_size);
+            });
+            accountant.ledger_file = Some(ledger_file);
+        }
+        accountant
+    }
+
+    // A ledger that isn't one is set aside for a fresh one, but any other failure means debts that might still be
+    // there can't be read, and starting without them would forget them for good.
+    fn open_ledger(logger: &Logger, data_directory: &Path) -> (LedgerFile, LedgerContents) {
+        match LedgerFile::open(data_directory) {
+            Ok(opened) => opened,
+            Err(LedgerFileError::Corrupt(reason)) => {
+                logger.error(format!("Accounting ledger is corrupt: {}", reason));
+                let aside_path = LedgerFile::set_aside(data_directory)
+                    .unwrap_or_else(|e| Accountant::refuse_to_start(logger, format!("{:?}", e)));
+                logger.error(format!(
+                    "Set the corrupt ledger aside as {}; starting a fresh one",
+                    aside_path.display()
+                ));
+                LedgerFile::open(data_directory)
+                    .unwrap_or_else(|e| Accountant::refuse_to_start(logger, format!("{:?}", e)))
+            }
+            Err(e) => Accountant::refuse_to_start(logger, format!("{:?}", e)),
         }
     }
 
+    fn refuse_to_start(logger: &Logger, reason: String) -> ! {
+        let msg = format!(
+            "Could not open accounting ledger: {}; refusing to start rather than forget debts",
+            reason
+        );
+        logger.error(msg.clone());
+        panic!("{}", msg)
+    }
+
     pub fn make_subs_from(addr: &Addr<Syn, Accountant>) -> AccountantSubs {
         AccountantSubs {
             bind: addr.clone().recipient::<BindMessage>(),
+            report_routing_service: addr.clone().recipient::<ReportRoutingServiceMessage>(),
+            report_exit_service: addr.clone().recipient::<ReportExitServiceMessage>(),
+            report_routing_service_consumed: addr
+                .clone()
+                .recipient::<ReportRoutingServiceConsumedMessage>(),
+            report_exit_service_consumed: addr
+                .clone()
+                .recipient::<ReportExitServiceConsumedMessage>(),
+            ledger_query: addr.clone().recipient::<LedgerQueryMessage>(),
+        }
+    }
+
+    fn record_receivable(
+        &mut self,
+        consuming_wallet: Wallet,
+        service: Service,
+        payload_size: usize,
+    ) {
+        if self.is_own_wallet(&consuming_wallet) {
+            return;
+        }
+        self.logger.debug(format!(
+            "{} owes us for {:?} service of {} bytes",
+            consuming_wallet.address, service, payload_size
+        ));
+        self.persist(
+            Direction::Receivable,
+            &consuming_wallet,
+            service,
+            payload_size,
+        );
+        Accountant::record(
+            &mut self.receivable,
+            consuming_wallet,
+            service,
+            payload_size as u64,
+        );
+    }
+
+    fn record_payable(&mut self, earning_wallet: Wallet, service: Service, payload_size: usize) {
+        if self.is_own_wallet(&earning_wallet) {
+            return;
+        }
+        self.logger.debug(format!(
+            "We owe {} for {:?} service of {} bytes",
+            earning_wallet.address, service, payload_size
+        ));
+        self.persist(Direction::Payable, &earning_wallet, service, payload_size);
+        Accountant::record(
+            &mut self.payable,
+            earning_wallet,
+            service,
+            payload_size as u64,
+        );
+    }
+
+    // Traffic a Node carries for itself, as in zero-hop mode, is nobody's debt
+    fn is_own_wallet(&self, wallet: &Wallet) -> bool {
+        self.wallet.as_ref() == Some(wallet)
+    }
+
+    fn persist(
+        &mut self,
+        direction: Direction,
+        wallet: &Wallet,
+        service: Service,
+        payload_size: usize,
+    ) {
+        let result = match self.ledger_file {
+            Some(ref mut ledger_file) => ledger_file.append(&LedgerEntry {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|since| since.as_secs())
+                    .unwrap_or(0),
+                direction,
+                wallet: wallet.clone(),
+                service,
+                payload_size: payload_size as u64,
+            }),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            self.logger
+                .error(format!("Debt will be forgotten on restart: {:?}", e));
+        }
+    }
+
+    fn record(
+        ledger: &mut HashMap<Wallet, ServiceTotals>,
+        wallet: Wallet,
+        service: Service,
+        payload_size: u64,
+    ) {
+        let totals = ledger.entry(wallet).or_insert_with(ServiceTotals::default);
+        match service {
+            Service::Routing => totals.bytes_routed += payload_size,
+            Service::Exit => totals.bytes_exited += payload_size,
+        }
+    }
+
+    fn totals_for(
+        ledger: &HashMap<Wallet, ServiceTotals>,
+        wallet_opt: &Option<Wallet>,
+    ) -> ServiceTotals {
+        match wallet_opt {
+            Some(wallet) => ledger.get(wallet).cloned().unwrap_or_default(),
+            None => ledger
+                .values()
+                .fold(ServiceTotals::default(), |so_far, totals| ServiceTotals {
+                    bytes_routed: so_far.bytes_routed + totals.bytes_routed,
+                    bytes_exited: so_far.bytes_exited + totals.bytes_exited,
+                }),
         }
     }
 }
@@ -46,9 +345,15 @@ mod tests {
     use actix::msgs;
     use actix::Arbiter;
     use actix::System;
+    use futures::Future;
+    use ledger_file::LEDGER_FILENAME;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
     use test_utils::logging::init_test_logging;
     use test_utils::logging::TestLogHandler;
     use test_utils::recorder::make_peer_actors;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
 
     #[test]
     fn bind_message_is_received() {
@@ -56,7 +361,8 @@ mod tests {
         // TODO: This test can be removed once behavior dependent on the reception of the BindMessage
         // is driven in
         let config = AccountantConfig {
-            replace_me: String::new(),
+            wallet: None,
+            data_directory_opt: None,
         };
         let system = System::new("bind_message_is_received");
         let subject = Accountant::new(config);
@@ -69,4 +375,263 @@ mod tests {
         system.run();
         TestLogHandler::new().exists_log_containing("INFO: Accountant: Accountant bound");
     }
+
+    fn query_after(
+        config: AccountantConfig,
+        reports: fn(&AccountantSubs),
+        wallet_opt: Option<Wallet>,
+    ) -> LedgerTotals {
+        let system = System::new("query_after");
+        let subject = Accountant::new(config);
+        let subject_addr: Addr<Syn, Accountant> = subject.start();
+        let subs = Accountant::make_subs_from(&subject_addr);
+
+        reports(&subs);
+        let future = subs.ledger_query.send(LedgerQueryMessage { wallet_opt });
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+        future.wait().unwrap()
+    }
+
+    fn report_some_services(subs: &AccountantSubs) {
+        subs.report_routing_service
+            .try_send(ReportRoutingServiceMessage {
+                consuming_wallet: Wallet::new("booga"),
+                payload_size: 100,
+            })
+            .unwrap();
+        subs.report_routing_service
+            .try_send(ReportRoutingServiceMessage {
+                consuming_wallet: Wallet::new("booga"),
+                payload_size: 20,
+            })
+            .unwrap();
+        subs.report_exit_service
+            .try_send(ReportExitServiceMessage {
+                consuming_wallet: Wallet::new("booga"),
+                payload_size: 3,
+            })
+            .unwrap();
+        subs.report_exit_service
+            .try_send(ReportExitServiceMessage {
+                consuming_wallet: Wallet::new("agoob"),
+                payload_size: 4000,
+            })
+            .unwrap();
+        subs.report_routing_service_consumed
+            .try_send(ReportRoutingServiceConsumedMessage {
+                earning_wallet: Wallet::new("booga"),
+                payload_size: 50000,
+            })
+            .unwrap();
+        subs.report_exit_service_consumed
+            .try_send(ReportExitServiceConsumedMessage {
+                earning_wallet: Wallet::new("agoob"),
+                payload_size: 600000,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn ledgers_are_kept_per_wallet() {
+        let result = query_after(
+            AccountantConfig {
+                wallet: None,
+                data_directory_opt: None,
+            },
+            report_some_services,
+            Some(Wallet::new("booga")),
+        );
+
+        assert_eq!(
+            result,
+            LedgerTotals {
+                receivable: ServiceTotals {
+                    bytes_routed: 120,
+                    bytes_exited: 3,
+                },
+                payable: ServiceTotals {
+                    bytes_routed: 50000,
+                    bytes_exited: 0,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn ledger_totals_can_be_queried_for_all_wallets_together() {
+        let result = query_after(
+            AccountantConfig {
+                wallet: None,
+                data_directory_opt: None,
+            },
+            report_some_services,
+            None,
+        );
+
+        assert_eq!(
+            result,
+            LedgerTotals {
+                receivable: ServiceTotals {
+                    bytes_routed: 120,
+                    bytes_exited: 4003,
+                },
+                payable: ServiceTotals {
+                    bytes_routed: 50000,
+                    bytes_exited: 600000,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_wallet_has_empty_ledgers() {
+        let result = query_after(
+            AccountantConfig {
+                wallet: None,
+                data_directory_opt: None,
+            },
+            report_some_services,
+            Some(Wallet::new("unknown")),
+        );
+
+        assert_eq!(result, LedgerTotals::default());
+    }
+
+    #[test]
+    fn services_a_node_provides_for_itself_are_not_recorded() {
+        let result = query_after(
+            AccountantConfig {
+                wallet: Some(Wallet::new("booga")),
+                data_directory_opt: None,
+            },
+            report_some_services,
+            None,
+        );
+
+        assert_eq!(
+            result,
+            LedgerTotals {
+                receivable: ServiceTotals {
+                    bytes_routed: 0,
+                    bytes_exited: 4000,
+                },
+                payable: ServiceTotals {
+                    bytes_routed: 0,
+                    bytes_exited: 600000,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn ledgers_survive_a_restart() {
+        let data_directory =
+            ensure_node_home_directory_exists("accountant", "ledgers_survive_a_restart");
+        let config = AccountantConfig {
+            wallet: None,
+            data_directory_opt: Some(data_directory),
+        };
+        query_after(config.clone(), report_some_services, None);
+
+        let result = query_after(config, |_| (), Some(Wallet::new("booga")));
+
+        assert_eq!(
+            result,
+            LedgerTotals {
+                receivable: ServiceTotals {
+                    bytes_routed: 120,
+                    bytes_exited: 3,
+                },
+                payable: ServiceTotals {
+                    bytes_routed: 50000,
+                    bytes_exited: 0,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn recorded_debts_are_flushed_to_the_ledger_periodically() {
+        let data_directory =
+            ensure_node_home_directory_exists("accountant", "recorded_debts_are_flushed");
+        let path = data_directory.join(LEDGER_FILENAME);
+        let config = AccountantConfig {
+            wallet: None,
+            data_directory_opt: Some(data_directory),
+        };
+        thread::spawn(move || {
+            let system = System::new("recorded_debts_are_flushed_to_the_ledger_periodically");
+            let subject = Accountant::new(config);
+            let subject_addr: Addr<Syn, Accountant> = subject.start();
+            report_some_services(&Accountant::make_subs_from(&subject_addr));
+            system.run();
+        });
+        thread::sleep(Duration::from_millis(100));
+        let before = fs::read(&path).unwrap();
+
+        thread::sleep(Duration::from_millis(LEDGER_FLUSH_INTERVAL_MILLIS + 500));
+
+        let after = fs::read(&path).unwrap();
+        assert_eq!(before, b"SUBLEDGR\x01".to_vec());
+        assert!(after.len() > before.len());
+    }
+
+    #[test]
+    fn corrupt_ledger_is_set_aside_for_a_fresh_one() {
+        init_test_logging();
+        let data_directory =
+            ensure_node_home_directory_exists("accountant", "corrupt_ledger_is_set_aside");
+        let path = data_directory.join(LEDGER_FILENAME);
+        fs::write(&path, b"booga").unwrap();
+        let config = AccountantConfig {
+            wallet: None,
+            data_directory_opt: Some(data_directory.clone()),
+        };
+
+        let result = query_after(config, report_some_services, Some(Wallet::new("agoob")));
+
+        assert_eq!(result.receivable.bytes_exited, 4000);
+        let set_aside: Vec<PathBuf> = fs::read_dir(&data_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|entry_path| {
+                entry_path
+                    .file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .starts_with("accountant_ledger.log.corrupt-")
+            })
+            .collect();
+        assert_eq!(set_aside.len(), 1);
+        assert_eq!(fs::read(&set_aside[0]).unwrap(), b"booga".to_vec());
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(&format!(
+            "ERROR: Accountant: Accounting ledger is corrupt: {} is not an accounting ledger",
+            path.display()
+        ));
+        tlh.exists_log_containing(&format!(
+            "ERROR: Accountant: Set the corrupt ledger aside as {}; starting a fresh one",
+            set_aside[0].display()
+        ));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Could not open accounting ledger: UnsupportedVersion(2); refusing to start rather than forget debts"
+    )]
+    fn ledger_from_a_newer_node_keeps_the_accountant_from_starting() {
+        let data_directory =
+            ensure_node_home_directory_exists("accountant", "ledger_from_a_newer_node");
+        let path = data_directory.join(LEDGER_FILENAME);
+        fs::write(&path, b"SUBLEDGR\x02").unwrap();
+        let config = AccountantConfig {
+            wallet: None,
+            data_directory_opt: Some(data_directory),
+        };
+
+        Accountant::new(config);
+    }
 }
@@ -0,0 +1,798 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use serde_cbor;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use sub_lib::wallet::Wallet;
+
+pub const LEDGER_FILENAME: &str = "accountant_ledger.log";
+const LEDGER_MAGIC: &[u8] = b"SUBLEDGR";
+const LEDGER_VERSION: u8 = 1;
+// Each of these turns the body of a record written by one version of the ledger into the body the next version
+// would have written: the first takes version 1 to version 2, and so on. Changing the format means adding one here
+// along with bumping LEDGER_VERSION, so that a Node finding an older ledger brings it up to date.
+const MIGRATIONS: &[Migration] = &[];
+const LEDGER_HEADER_LEN: usize = 9;
+const RECORD_HEADER_LEN: usize = 8;
+const MAX_RECORD_LEN: usize = 0x10000;
+// Appended records wait in memory until there are this many bytes of them, or until someone calls flush()
+const MAX_PENDING_LEN: usize = 0x10000;
+// A ledger this long, and at least twice as long as it was after its last compaction, is compacted
+const COMPACTION_THRESHOLD: u64 = 0x100000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    Receivable,
+    Payable,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Service {
+    Routing,
+    Exit,
+}
+
+/// One debt, in either direction; timestamp is in seconds since the Unix epoch, or 0 if unknown
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub timestamp: u64,
+    pub direction: Direction,
+    pub wallet: Wallet,
+    pub service: Service,
+    pub payload_size: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LedgerFileError {
+    IoError(String),
+    Corrupt(String),
+    UnsupportedVersion(u8),
+}
+
+/// What was in a ledger when it was opened, and what had to be done to it first
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerContents {
+    pub entries: Vec<LedgerEntry>,
+    pub discarded_bytes: usize,
+    pub migrated_from: Option<u8>,
+}
+
+type LedgerKey = (Direction, Wallet, Service);
+
+pub type Migration = fn(&[u8]) -> Option<Vec<u8>>;
+
+// The file is a header (LEDGER_MAGIC and a version byte) followed by records, each of which is a four-byte
+// big-endian length, a four-byte big-endian CRC-32 of the body, and a CBOR-serialized LedgerEntry as the body.
+// Records are only ever appended, so a writer that dies can damage nothing but the last one; whatever follows
+// the last intact record is cut off the next time the ledger is opened.
+//
+// Appended records are written out in batches, so a crash can lose those that haven't been flushed yet. When the
+// file grows long enough, it's replaced by a snapshot: one record per wallet, service and direction, carrying the
+// total of all the debts it replaces and the timestamp of the latest of them.
+pub struct LedgerFile {
+    path: PathBuf,
+    version: u8,
+    file: File,
+    len: u64,
+    pending: Vec<u8>,
+    totals: HashMap<LedgerKey, LedgerEntry>,
+    compacted_len: u64,
+    compaction_threshold: u64,
+}
+
+impl LedgerFile {
+    pub fn open(data_directory: &Path) -> Result<(LedgerFile, LedgerContents), LedgerFileError> {
+        LedgerFile::open_with_migrations(data_directory, MIGRATIONS)
+    }
+
+    // A ledger can be read if its version is one the migrations start from or lead to; the last of those is the
+    // version the ledger is kept at.
+    fn open_with_migrations(
+        data_directory: &Path,
+        migrations: &[Migration],
+    ) -> Result<(LedgerFile, LedgerContents), LedgerFileError> {
+        let version = migrations.len() as u8 + 1;
+        let path = data_directory.join(LEDGER_FILENAME);
+        let io_error = |e: ::std::io::Error| {
+            LedgerFileError::IoError(format!("Could not open {}: {}", path.display(), e))
+        };
+        fs::create_dir_all(data_directory).map_err(io_error)?;
+        let scratch_path = path.with_extension("tmp");
+        if scratch_path.exists() {
+            // Left over from a compaction or migration that didn't finish; the ledger itself is still intact
+            fs::remove_file(&scratch_path).map_err(io_error)?;
+        }
+        let bytes = if path.exists() {
+            fs::read(&path).map_err(io_error)?
+        } else {
+            vec![]
+        };
+        let contents = if bytes.is_empty() {
+            LedgerFile::write_atomically(&path, &LedgerFile::header(version))?;
+            LedgerContents {
+                entries: vec![],
+                discarded_bytes: 0,
+                migrated_from: None,
+            }
+        } else {
+            let file_version = LedgerFile::read_header(&path, &bytes[..], version)?;
+            let pending_migrations = &migrations[((file_version - 1) as usize)..];
+            let (entries, good_len) = LedgerFile::read_records(&bytes[..], pending_migrations);
+            if file_version < version {
+                let mut migrated = LedgerFile::header(version);
+                entries
+                    .iter()
+                    .for_each(|entry| migrated.extend(LedgerFile::encode_record(entry)));
+                LedgerFile::write_atomically(&path, &migrated[..])?;
+            } else if good_len < bytes.len() {
+                let file = OpenOptions::new()
+                    .write(true)
+                    .open(&path)
+                    .map_err(io_error)?;
+                file.set_len(good_len as u64).map_err(io_error)?;
+                file.sync_all().map_err(io_error)?;
+            }
+            LedgerContents {
+                entries,
+                discarded_bytes: bytes.len() - good_len,
+                migrated_from: if file_version < version {
+                    Some(file_version)
+                } else {
+                    None
+                },
+            }
+        };
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(io_error)?;
+        let len = file.metadata().map_err(io_error)?.len();
+        let mut ledger_file = LedgerFile {
+            path,
+            version,
+            file,
+            len,
+            pending: vec![],
+            totals: HashMap::new(),
+            compacted_len: LEDGER_HEADER_LEN as u64,
+            compaction_threshold: COMPACTION_THRESHOLD,
+        };
+        contents
+            .entries
+            .iter()
+            .for_each(|entry| ledger_file.add_to_totals(entry));
+        ledger_file.compact_if_too_long()?;
+        Ok((ledger_file, contents))
+    }
+
+    // For a ledger that turns out not to be one: it's moved aside under a name no earlier one has, where someone can
+    // look at it later, and the next open() starts a new one in its place.
+    pub fn set_aside(data_directory: &Path) -> Result<PathBuf, LedgerFileError> {
+        let path = data_directory.join(LEDGER_FILENAME);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        let aside_path = (0..)
+            .map(|attempt| match attempt {
+                0 => data_directory.join(format!("{}.corrupt-{}", LEDGER_FILENAME, timestamp)),
+                _ => data_directory.join(format!(
+                    "{}.corrupt-{}-{}",
+                    LEDGER_FILENAME, timestamp, attempt
+                )),
+            })
+            .find(|aside_path| !aside_path.exists())
+            .expect("Internal error");
+        fs::rename(&path, &aside_path).map_err(|e| {
+            LedgerFileError::IoError(format!("Could not move {} aside: {}", path.display(), e))
+        })?;
+        Ok(aside_path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&mut self, entry: &LedgerEntry) -> Result<(), LedgerFileError> {
+        self.pending.extend(LedgerFile::encode_record(entry));
+        self.add_to_totals(entry);
+        if self.pending.len() >= MAX_PENDING_LEN {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    // Writes out whatever has been appended since the last flush, compacting the ledger if it's grown too long.
+    // If the write fails, what was pending is lost from the file, though not from the totals a compaction writes.
+    pub fn flush(&mut self) -> Result<(), LedgerFileError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = self.pending.split_off(0);
+        match self
+            .file
+            .write_all(&pending[..])
+            .and_then(|_| self.file.sync_data())
+        {
+            Ok(()) => self.len += pending.len() as u64,
+            Err(e) => {
+                // Don't leave a partial record in front of the ones that come after it
+                let _ = self.file.set_len(self.len);
+                return Err(LedgerFileError::IoError(format!(
+                    "Could not write to {}: {}",
+                    self.path.display(),
+                    e
+                )));
+            }
+        }
+        self.compact_if_too_long()
+    }
+
+    pub fn encode_record(entry: &LedgerEntry) -> Vec<u8> {
+        let body = serde_cbor::ser::to_vec(entry).expect("Internal error");
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + body.len());
+        write_u32(&mut record, body.len() as u32);
+        write_u32(&mut record, crc32(&body[..]));
+        record.extend(body);
+        record
+    }
+
+    fn header(version: u8) -> Vec<u8> {
+        let mut header = LEDGER_MAGIC.to_vec();
+        header.push(version);
+        header
+    }
+
+    fn add_to_totals(&mut self, entry: &LedgerEntry) {
+        let total = self
+            .totals
+            .entry((entry.direction, entry.wallet.clone(), entry.service))
+            .or_insert_with(|| LedgerEntry {
+                payload_size: 0,
+                ..entry.clone()
+            });
+        total.payload_size += entry.payload_size;
+        total.timestamp = total.timestamp.max(entry.timestamp);
+    }
+
+    fn compact_if_too_long(&mut self) -> Result<(), LedgerFileError> {
+        if (self.len < self.compaction_threshold) || (self.len < self.compacted_len * 2) {
+            return Ok(());
+        }
+        self.compact()
+    }
+
+    // Pending records go into the snapshot along with everything else.
+    fn compact(&mut self) -> Result<(), LedgerFileError> {
+        let mut totals: Vec<&LedgerEntry> = self.totals.values().collect();
+        totals.sort_by(|a, b| {
+            (a.direction as u8, &a.wallet.address, a.service as u8).cmp(&(
+                b.direction as u8,
+                &b.wallet.address,
+                b.service as u8,
+            ))
+        });
+        let mut snapshot = LedgerFile::header(self.version);
+        totals
+            .into_iter()
+            .for_each(|entry| snapshot.extend(LedgerFile::encode_record(entry)));
+        LedgerFile::write_atomically(&self.path, &snapshot[..])?;
+        self.file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| {
+                LedgerFileError::IoError(format!("Could not open {}: {}", self.path.display(), e))
+            })?;
+        self.pending.clear();
+        self.len = snapshot.len() as u64;
+        self.compacted_len = self.len;
+        Ok(())
+    }
+
+    fn read_header(path: &Path, bytes: &[u8], version: u8) -> Result<u8, LedgerFileError> {
+        if bytes.len() < LEDGER_HEADER_LEN || &bytes[0..LEDGER_MAGIC.len()] != LEDGER_MAGIC {
+            return Err(LedgerFileError::Corrupt(format!(
+                "{} is not an accounting ledger",
+                path.display()
+            )));
+        }
+        match bytes[LEDGER_MAGIC.len()] {
+            file_version if file_version == 0 || file_version > version => {
+                Err(LedgerFileError::UnsupportedVersion(file_version))
+            }
+            file_version => Ok(file_version),
+        }
+    }
+
+    // Returns every entry up to the first record that is incomplete or damaged, and the length of the file
+    // up to that record.
+    fn read_records(bytes: &[u8], migrations: &[Migration]) -> (Vec<LedgerEntry>, usize) {
+        let mut entries = vec![];
+        let mut offset = LEDGER_HEADER_LEN;
+        while let Some((entry, next_offset)) = LedgerFile::read_record(bytes, offset, migrations) {
+            entries.push(entry);
+            offset = next_offset;
+        }
+        (entries, offset)
+    }
+
+    // A record is brought up to date by each migration in turn before it's read.
+    fn read_record(
+        bytes: &[u8],
+        offset: usize,
+        migrations: &[Migration],
+    ) -> Option<(LedgerEntry, usize)> {
+        if bytes.len() < offset + RECORD_HEADER_LEN {
+            return None;
+        }
+        let body_len = read_u32(&bytes[offset..(offset + 4)]) as usize;
+        let checksum = read_u32(&bytes[(offset + 4)..(offset + 8)]);
+        let body_start = offset + RECORD_HEADER_LEN;
+        if body_len > MAX_RECORD_LEN || bytes.len() < body_start + body_len {
+            return None;
+        }
+        let body = &bytes[body_start..(body_start + body_len)];
+        if crc32(body) != checksum {
+            return None;
+        }
+        let body = migrations
+            .iter()
+            .fold(Some(body.to_vec()), |body_opt, migration| {
+                body_opt.and_then(|body| migration(&body[..]))
+            })?;
+        let entry = serde_cbor::de::from_slice::<LedgerEntry>(&body[..]).ok()?;
+        Some((entry, body_start + body_len))
+    }
+
+    // Writing to a scratch file and renaming it means a crash can't leave a half-written ledger.
+    fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), LedgerFileError> {
+        let io_error = |e: ::std::io::Error| {
+            LedgerFileError::IoError(format!("Could not write {}: {}", path.display(), e))
+        };
+        let scratch_path = path.with_extension("tmp");
+        {
+            let mut file = File::create(&scratch_path).map_err(io_error)?;
+            file.write_all(bytes).map_err(io_error)?;
+            file.sync_all().map_err(io_error)?;
+        }
+        fs::rename(&scratch_path, path).map_err(io_error)
+    }
+}
+
+// Whatever is still pending when the ledger is closed goes out then.
+impl Drop for LedgerFile {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0u32, |so_far, byte| (so_far << 8) | (*byte as u32))
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    (0..4)
+        .rev()
+        .for_each(|shift| bytes.push((value >> (shift * 8)) as u8));
+}
+
+// CRC-32 as used by Ethernet and zip
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(0xFFFFFFFFu32, |so_far, byte| {
+        (0..8).fold(so_far ^ (*byte as u32), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
+
+    fn make_entry(timestamp: u64, direction: Direction, wallet: &str) -> LedgerEntry {
+        LedgerEntry {
+            timestamp,
+            direction,
+            wallet: Wallet::new(wallet),
+            service: Service::Routing,
+            payload_size: timestamp * 10,
+        }
+    }
+
+    fn append_to_file(path: &Path, bytes: &[u8]) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn crc32_matches_the_standard_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn new_ledger_is_empty() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "new_ledger");
+
+        let (subject, contents) = LedgerFile::open(&data_directory).unwrap();
+
+        assert_eq!(
+            contents,
+            LedgerContents {
+                entries: vec![],
+                discarded_bytes: 0,
+                migrated_from: None,
+            }
+        );
+        assert_eq!(
+            subject.path(),
+            data_directory.join(LEDGER_FILENAME).as_path()
+        );
+        assert_eq!(fs::read(subject.path()).unwrap(), b"SUBLEDGR\x01".to_vec());
+    }
+
+    #[test]
+    fn appended_entries_survive_reopening() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "reopening");
+        let entries = vec![
+            make_entry(1, Direction::Receivable, "booga"),
+            make_entry(2, Direction::Payable, "agoob"),
+            make_entry(3, Direction::Receivable, "agoob"),
+        ];
+        {
+            let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+            entries
+                .iter()
+                .for_each(|entry| subject.append(entry).unwrap());
+        }
+
+        let (_, contents) = LedgerFile::open(&data_directory).unwrap();
+
+        assert_eq!(contents.entries, entries);
+        assert_eq!(contents.discarded_bytes, 0);
+    }
+
+    #[test]
+    fn writer_killed_anywhere_in_a_record_loses_only_that_record() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "killed_writer");
+        let survivors = vec![
+            make_entry(1, Direction::Receivable, "booga"),
+            make_entry(2, Direction::Payable, "agoob"),
+        ];
+        let victim = LedgerFile::encode_record(&make_entry(3, Direction::Payable, "booga"));
+        let intact_bytes = {
+            let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+            survivors
+                .iter()
+                .for_each(|entry| subject.append(entry).unwrap());
+            subject.flush().unwrap();
+            fs::read(subject.path()).unwrap()
+        };
+        let path = data_directory.join(LEDGER_FILENAME);
+
+        (1..victim.len()).for_each(|written| {
+            fs::write(&path, &intact_bytes[..]).unwrap();
+            append_to_file(&path, &victim[0..written]);
+
+            let (_, contents) = LedgerFile::open(&data_directory).unwrap();
+
+            assert_eq!(
+                contents.entries, survivors,
+                "killed after {} bytes",
+                written
+            );
+            assert_eq!(contents.discarded_bytes, written);
+            assert_eq!(fs::read(&path).unwrap(), intact_bytes);
+        });
+    }
+
+    #[test]
+    fn ledger_recovered_from_a_killed_writer_can_be_appended_to() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "recovered_append");
+        {
+            let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+            subject
+                .append(&make_entry(1, Direction::Receivable, "booga"))
+                .unwrap();
+            subject.flush().unwrap();
+            let victim = LedgerFile::encode_record(&make_entry(2, Direction::Payable, "agoob"));
+            append_to_file(subject.path(), &victim[0..(victim.len() / 2)]);
+        }
+        {
+            let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+            subject
+                .append(&make_entry(3, Direction::Payable, "agoob"))
+                .unwrap();
+        }
+
+        let (_, contents) = LedgerFile::open(&data_directory).unwrap();
+
+        assert_eq!(
+            contents.entries,
+            vec![
+                make_entry(1, Direction::Receivable, "booga"),
+                make_entry(3, Direction::Payable, "agoob"),
+            ]
+        );
+        assert_eq!(contents.discarded_bytes, 0);
+    }
+
+    #[test]
+    fn damaged_record_ends_the_ledger() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "damaged_record");
+        let path = {
+            let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+            (1..4).for_each(|timestamp| {
+                subject
+                    .append(&make_entry(timestamp, Direction::Receivable, "booga"))
+                    .unwrap()
+            });
+            subject.path().to_path_buf()
+        };
+        let mut bytes = fs::read(&path).unwrap();
+        let record_len =
+            LedgerFile::encode_record(&make_entry(1, Direction::Receivable, "booga")).len();
+        let damaged_byte = LEDGER_HEADER_LEN + record_len + RECORD_HEADER_LEN + 1;
+        bytes[damaged_byte] ^= 0xFF;
+        fs::write(&path, &bytes[..]).unwrap();
+
+        let (_, contents) = LedgerFile::open(&data_directory).unwrap();
+
+        assert_eq!(
+            contents.entries,
+            vec![make_entry(1, Direction::Receivable, "booga")]
+        );
+        assert_eq!(
+            contents.discarded_bytes,
+            bytes.len() - LEDGER_HEADER_LEN - record_len
+        );
+    }
+
+    #[test]
+    fn appended_entries_wait_for_a_flush() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "flush");
+        let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+        let entry = make_entry(1, Direction::Receivable, "booga");
+
+        subject.append(&entry).unwrap();
+
+        assert_eq!(fs::read(subject.path()).unwrap(), b"SUBLEDGR\x01".to_vec());
+        subject.flush().unwrap();
+        let mut expected_bytes = b"SUBLEDGR\x01".to_vec();
+        expected_bytes.extend(LedgerFile::encode_record(&entry));
+        assert_eq!(fs::read(subject.path()).unwrap(), expected_bytes);
+    }
+
+    #[test]
+    fn appended_entries_are_written_without_a_flush_once_enough_are_pending() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "full_batch");
+        let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+        let entry = make_entry(1, Direction::Receivable, "booga");
+        let record_len = LedgerFile::encode_record(&entry).len();
+        let batch_size = (MAX_PENDING_LEN + record_len - 1) / record_len;
+
+        (0..(batch_size - 1)).for_each(|_| subject.append(&entry).unwrap());
+        let before = fs::read(subject.path()).unwrap().len();
+        subject.append(&entry).unwrap();
+        let after = fs::read(subject.path()).unwrap().len();
+
+        assert_eq!(before, LEDGER_HEADER_LEN);
+        assert_eq!(after, LEDGER_HEADER_LEN + (batch_size * record_len));
+    }
+
+    #[test]
+    fn long_ledger_is_compacted_into_a_snapshot_of_its_totals() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "compaction");
+        let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+        subject.compaction_threshold = 1000;
+        let entry_1 = make_entry(1, Direction::Receivable, "booga");
+        let entry_2 = make_entry(2, Direction::Payable, "booga");
+        let entry_3 = make_entry(3, Direction::Receivable, "booga");
+        let entry_4 = make_entry(4, Direction::Receivable, "agoob");
+        (0..20).for_each(|_| {
+            vec![&entry_1, &entry_2, &entry_3, &entry_4]
+                .into_iter()
+                .for_each(|entry| subject.append(entry).unwrap())
+        });
+
+        subject.flush().unwrap();
+
+        let expected_entries = vec![
+            LedgerEntry {
+                timestamp: 4,
+                payload_size: 800,
+                ..entry_4.clone()
+            },
+            LedgerEntry {
+                timestamp: 3,
+                payload_size: 800,
+                ..entry_1.clone()
+            },
+            LedgerEntry {
+                timestamp: 2,
+                payload_size: 400,
+                ..entry_2.clone()
+            },
+        ];
+        let mut expected_bytes = b"SUBLEDGR\x01".to_vec();
+        expected_entries
+            .iter()
+            .for_each(|entry| expected_bytes.extend(LedgerFile::encode_record(entry)));
+        assert_eq!(fs::read(subject.path()).unwrap(), expected_bytes);
+        subject.append(&entry_2).unwrap();
+        drop(subject);
+        let (_, contents) = LedgerFile::open(&data_directory).unwrap();
+        let mut expected_after_reopening = expected_entries.clone();
+        expected_after_reopening.push(entry_2);
+        assert_eq!(contents.entries, expected_after_reopening);
+    }
+
+    #[test]
+    fn compaction_killed_before_it_finishes_leaves_the_ledger_intact() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "killed_compaction");
+        let entry = make_entry(1, Direction::Receivable, "booga");
+        {
+            let (mut subject, _) = LedgerFile::open(&data_directory).unwrap();
+            subject.append(&entry).unwrap();
+        }
+        let path = data_directory.join(LEDGER_FILENAME);
+        fs::write(path.with_extension("tmp"), b"SUBLEDGR\x01\x00\x00").unwrap();
+
+        let (_, contents) = LedgerFile::open(&data_directory).unwrap();
+
+        assert_eq!(contents.entries, vec![entry]);
+        assert_eq!(path.with_extension("tmp").exists(), false);
+    }
+
+    #[test]
+    fn corrupt_ledgers_are_set_aside_without_replacing_each_other() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "set_aside");
+        let path = data_directory.join(LEDGER_FILENAME);
+        fs::write(&path, b"booga").unwrap();
+        let first = LedgerFile::set_aside(&data_directory).unwrap();
+        fs::write(&path, b"agoob").unwrap();
+
+        let second = LedgerFile::set_aside(&data_directory).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(fs::read(&first).unwrap(), b"booga".to_vec());
+        assert_eq!(fs::read(&second).unwrap(), b"agoob".to_vec());
+        assert!(first
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("accountant_ledger.log.corrupt-"));
+        assert_eq!(path.exists(), false);
+        let (subject, contents) = LedgerFile::open(&data_directory).unwrap();
+        assert_eq!(contents.entries, vec![]);
+        assert_eq!(fs::read(subject.path()).unwrap(), b"SUBLEDGR\x01".to_vec());
+    }
+
+    // What a ledger entry looked like before it had a timestamp, for trying out the migration hook
+    #[derive(Serialize, Deserialize)]
+    struct UntimedLedgerEntry {
+        direction: Direction,
+        wallet: Wallet,
+        service: Service,
+        payload_size: u64,
+    }
+
+    fn add_timestamp(body: &[u8]) -> Option<Vec<u8>> {
+        let old = serde_cbor::de::from_slice::<UntimedLedgerEntry>(body).ok()?;
+        serde_cbor::ser::to_vec(&LedgerEntry {
+            timestamp: 0,
+            direction: old.direction,
+            wallet: old.wallet,
+            service: old.service,
+            payload_size: old.payload_size,
+        })
+        .ok()
+    }
+
+    #[test]
+    fn older_ledger_is_migrated_to_the_version_its_migrations_lead_to() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "migration");
+        let path = data_directory.join(LEDGER_FILENAME);
+        let mut bytes = b"SUBLEDGR\x01".to_vec();
+        vec![
+            UntimedLedgerEntry {
+                direction: Direction::Receivable,
+                wallet: Wallet::new("booga"),
+                service: Service::Exit,
+                payload_size: 1234,
+            },
+            UntimedLedgerEntry {
+                direction: Direction::Payable,
+                wallet: Wallet::new("agoob"),
+                service: Service::Routing,
+                payload_size: 4321,
+            },
+        ]
+        .iter()
+        .for_each(|entry| {
+            let body = serde_cbor::ser::to_vec(entry).unwrap();
+            write_u32(&mut bytes, body.len() as u32);
+            write_u32(&mut bytes, crc32(&body[..]));
+            bytes.extend(body);
+        });
+        fs::write(&path, &bytes[..]).unwrap();
+        let migrations: &[Migration] = &[add_timestamp];
+
+        let (_, contents) = LedgerFile::open_with_migrations(&data_directory, migrations).unwrap();
+
+        let expected_entries = vec![
+            LedgerEntry {
+                timestamp: 0,
+                direction: Direction::Receivable,
+                wallet: Wallet::new("booga"),
+                service: Service::Exit,
+                payload_size: 1234,
+            },
+            LedgerEntry {
+                timestamp: 0,
+                direction: Direction::Payable,
+                wallet: Wallet::new("agoob"),
+                service: Service::Routing,
+                payload_size: 4321,
+            },
+        ];
+        assert_eq!(contents.entries, expected_entries);
+        assert_eq!(contents.migrated_from, Some(1));
+        assert_eq!(
+            &fs::read(&path).unwrap()[0..LEDGER_HEADER_LEN],
+            b"SUBLEDGR\x02"
+        );
+        let (_, reopened_contents) =
+            LedgerFile::open_with_migrations(&data_directory, migrations).unwrap();
+        assert_eq!(reopened_contents.entries, expected_entries);
+        assert_eq!(reopened_contents.migrated_from, None);
+    }
+
+    #[test]
+    fn there_is_a_migration_to_the_current_version_from_every_earlier_one() {
+        assert_eq!(MIGRATIONS.len(), (LEDGER_VERSION - 1) as usize);
+    }
+
+    #[test]
+    fn ledger_from_a_newer_node_is_rejected_and_left_alone() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "newer_version");
+        let path = data_directory.join(LEDGER_FILENAME);
+        fs::write(&path, b"SUBLEDGR\x02booga").unwrap();
+
+        let result = LedgerFile::open(&data_directory);
+
+        assert_eq!(
+            result.err().unwrap(),
+            LedgerFileError::UnsupportedVersion(2)
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"SUBLEDGR\x02booga".to_vec());
+    }
+
+    #[test]
+    fn file_that_is_not_a_ledger_is_reported_as_corrupt() {
+        let data_directory = ensure_node_home_directory_exists("ledger_file", "not_a_ledger");
+        fs::write(data_directory.join(LEDGER_FILENAME), b"booga").unwrap();
+
+        let result = LedgerFile::open(&data_directory);
+
+        match result {
+            Err(LedgerFileError::Corrupt(_)) => (),
+            Err(e) => panic!("Expected Corrupt, got {:?}", e),
+            Ok(_) => panic!("Expected Corrupt, got Ok"),
+        }
+    }
+}
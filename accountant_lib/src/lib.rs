@@ -1,9 +1,16 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
 extern crate actix;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_cbor;
 extern crate sub_lib;
 
+#[cfg(test)]
+extern crate futures;
 #[cfg(test)]
 extern crate test_utils;
 
 pub mod accountant;
+pub mod ledger_file;
@@ -1,4 +1,8 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use dns_tcp_server::DnsTcpServer;
+use pass_through::PassThrough;
+use pass_through::PassThroughRule;
+use pass_through::QueryOrigin;
 use processor::ProcessorReal;
 use processor::ProcessorTrait;
 use std::borrow::BorrowMut;
@@ -10,6 +14,7 @@ use std::str::FromStr;
 use sub_lib::logger::Logger;
 use sub_lib::main_tools::StdStreams;
 use sub_lib::socket_server::SocketServer;
+use sub_lib::tcp_listener_wrapper::TcpListenerWrapperReal;
 use sub_lib::udp_socket_wrapper::UdpSocketWrapperReal;
 use sub_lib::udp_socket_wrapper::UdpSocketWrapperTrait;
 use tokio::prelude::Async;
@@ -18,7 +23,9 @@ use tokio::prelude::Future;
 pub struct DnsSocketServer {
     dns_target: Option<IpAddr>,
     socket_wrapper: Box<UdpSocketWrapperTrait>,
+    tcp_server: DnsTcpServer,
     processor: Option<Box<ProcessorTrait>>,
+    pass_through: Option<PassThrough>,
     buf: Option<[u8; 65536]>,
 }
 
@@ -28,13 +35,31 @@ impl Future for DnsSocketServer {
 
     fn poll(&mut self) -> Result<Async<<Self as Future>::Item>, <Self as Future>::Error> {
         let logger = Logger::new("EntryDnsServer");
+        let DnsSocketServer {
+            ref mut socket_wrapper,
+            ref mut tcp_server,
+            ref processor,
+            ref mut pass_through,
+            ref buf,
+            ..
+        } = *self;
+        let processor = processor
+            .as_ref()
+            .expect("Missing Processor - was initialized_as_privileged called?")
+            .as_ref();
         loop {
-            let mut buffer = self
-                .buf
-                .expect("Missing buffer - was initialize_as_privileged called?");
-            let (len, socket_addr) = match self.socket_wrapper.recv_from(buffer.borrow_mut()) {
+            let mut buffer = buf.expect("Missing buffer - was initialize_as_privileged called?");
+            if let Some(ref mut pass_through) = *pass_through {
+                pass_through
+                    .relay_answers(buffer.borrow_mut(), socket_wrapper, &logger)?
+                    .into_iter()
+                    .for_each(|(connection_id, answer)| {
+                        tcp_server.add_answer(connection_id, &answer)
+                    });
+            }
+            let (len, socket_addr) = match socket_wrapper.recv_from(buffer.borrow_mut()) {
                 Ok(Async::Ready((len, socket_addr))) => (len, socket_addr),
-                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::NotReady) => break,
                 Err(e) => {
                     logger.error(format!(
                         "Unrecoverable error receiving from UdpSocket: {}",
@@ -43,16 +68,18 @@ impl Future for DnsSocketServer {
                     return Err(());
                 }
             };
-            let processor_unwrapped = self
-                .processor
-                .as_ref()
-                .expect("Missing Processor - was initialized_as_privileged called?");
-            let response_length =
-                processor_unwrapped.process(buffer.borrow_mut(), len, &socket_addr, &logger);
-            match self
-                .socket_wrapper
-                .send_to(&buffer[0..response_length], socket_addr)
-            {
+            let response_length = match dispatch(
+                pass_through,
+                processor,
+                buffer.borrow_mut(),
+                len,
+                QueryOrigin::Udp(socket_addr),
+                &logger,
+            ) {
+                Some(response_length) => response_length,
+                None => continue,
+            };
+            match socket_wrapper.send_to(&buffer[0..response_length], socket_addr) {
                 Err(e) => {
                     logger.error(format!("Unrecoverable error sending to UdpSocket: {}", e));
                     return Err(());
@@ -60,9 +87,35 @@ impl Future for DnsSocketServer {
                 Ok(_) => {}
             }
         }
+        tcp_server.poll(
+            &mut |buf: &mut [u8], length: usize, origin: QueryOrigin| {
+                dispatch(pass_through, processor, buf, length, origin, &logger)
+            },
+            &logger,
+        );
+        Ok(Async::NotReady)
     }
 }
 
+// Every query, whether it came over UDP or TCP, is either passed through to a real DNS server or answered here.
+// Returns the length of the answer left in the buffer, or None if the answer will come from the real DNS server.
+fn dispatch(
+    pass_through_opt: &mut Option<PassThrough>,
+    processor: &ProcessorTrait,
+    buf: &mut [u8],
+    length: usize,
+    origin: QueryOrigin,
+    logger: &Logger,
+) -> Option<usize> {
+    if let Some(ref mut pass_through) = *pass_through_opt {
+        if pass_through.should_pass_through(buf, length) {
+            pass_through.forward(buf, length, origin, logger);
+            return None;
+        }
+    }
+    Some(processor.process(buf, length, &origin.client_addr(), logger))
+}
+
 impl SocketServer for DnsSocketServer {
     fn name(&self) -> String {
         String::from("EntryDnsServer")
@@ -70,11 +123,24 @@ impl SocketServer for DnsSocketServer {
 
     fn initialize_as_privileged(&mut self, args: &Vec<String>, _streams: &mut StdStreams) {
         self.dns_target = Some(get_dns_target(args));
+        let pass_through_rules = get_pass_through_rules(args);
+        if !pass_through_rules.is_empty() {
+            let mut pass_through = PassThrough::new(
+                pass_through_rules,
+                get_dns_servers(args),
+                Box::new(UdpSocketWrapperReal::new()),
+            );
+            pass_through.bind();
+            self.pass_through = Some(pass_through);
+        }
         let socket_addr = SocketAddr::new(V4(Ipv4Addr::from(0)), get_dns_port(args));
         // The following expect() will cause an appropriate panic if the port can't be opened
         self.socket_wrapper
             .bind(socket_addr)
             .expect(&format!("Cannot bind socket to {:?}", socket_addr));
+        self.tcp_server
+            .bind(socket_addr)
+            .expect(&format!("Cannot bind TCP listener to {:?}", socket_addr));
     }
 
     fn initialize_as_unprivileged(&mut self) {
@@ -92,7 +158,9 @@ pub fn new_dns_socket_server() -> DnsSocketServer {
     DnsSocketServer {
         dns_target: None,
         socket_wrapper: Box::new(UdpSocketWrapperReal::new()),
+        tcp_server: DnsTcpServer::new(Box::new(TcpListenerWrapperReal::new())),
         processor: None,
+        pass_through: None,
         buf: None,
     }
 }
@@ -106,12 +174,45 @@ fn get_dns_target(args: &Vec<String>) -> IpAddr {
         Some(s) => s,
         None => String::from("127.0.0.1"),
     };
-    match Ipv4Addr::from_str(&ip_addr_str) {
-        Ok(ip_addr) => V4(ip_addr),
+    match IpAddr::from_str(&ip_addr_str) {
+        Ok(ip_addr) => ip_addr,
         Err(_) => panic!("Invalid IP address for --dns_target: {}", ip_addr_str),
     }
 }
 
+fn get_pass_through_rules(args: &Vec<String>) -> Vec<PassThroughRule> {
+    let finder = ParameterFinder::new(args);
+    match finder.find_value_after(
+        "--dns_pass_through",
+        "must be followed by a comma-separated list of domain suffixes and <IP address>/<prefix length> subnets",
+    ) {
+        Some(s) => s
+            .split(",")
+            .map(|rule_str| match PassThroughRule::from_str(rule_str) {
+                Ok(rule) => rule,
+                Err(msg) => panic!("Invalid rule for --dns_pass_through: {}", msg),
+            })
+            .collect(),
+        None => vec![],
+    }
+}
+
+// These are the same DNS servers the Bootstrapper puts in BootstrapperConfig::dns_servers.
+fn get_dns_servers(args: &Vec<String>) -> Vec<SocketAddr> {
+    let finder = ParameterFinder::new(args);
+    let usage = "must be followed by a comma-separated list of IP addresses";
+    match finder.find_value_after("--dns_servers", usage) {
+        Some(s) => s
+            .split(",")
+            .map(|ip_addr_str| match IpAddr::from_str(ip_addr_str) {
+                Ok(ip_addr) => SocketAddr::new(ip_addr, 53),
+                Err(_) => panic!("Invalid IP address for --dns_servers: {}", ip_addr_str),
+            })
+            .collect(),
+        None => panic!("--dns_pass_through needs --dns_servers to pass queries through to"),
+    }
+}
+
 fn get_dns_port(args: &Vec<String>) -> u16 {
     let finder = ParameterFinder::new(args);
     let port_str = match finder.find_value_after(
@@ -179,12 +280,15 @@ mod tests {
     use std::sync::Mutex;
     use test_utils::logging::init_test_logging;
     use test_utils::logging::TestLogHandler;
+    use test_utils::tcp_listener_wrapper_mock::TcpListenerWrapperMock;
     use test_utils::test_utils::FakeStreamHolder;
+    use test_utils::tokio_wrapper_mocks::ReadHalfWrapperMock;
+    use test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
     use tokio;
 
     struct UdpSocketWrapperMockGuts {
         log: Vec<String>,
-        buf: [u8; 12],
+        buf: Vec<u8>,
     }
 
     #[derive(Clone)]
@@ -230,29 +334,14 @@ mod tests {
 
     impl UdpSocketWrapperMock {
         fn new(buf: &[u8]) -> UdpSocketWrapperMock {
-            assert_eq!(
-                buf.len() <= 12,
-                true,
-                "Mock accepts buffer of up to 12 bytes, not {}",
-                buf.len()
-            );
-            let result = UdpSocketWrapperMock {
+            UdpSocketWrapperMock {
                 guts: Arc::new(Mutex::new(UdpSocketWrapperMockGuts {
                     log: Vec::new(),
-                    buf: [0; 12],
+                    buf: buf.to_vec(),
                 })),
                 recv_from_results: Arc::new(Mutex::new(vec![])),
                 send_to_results: Arc::new(Mutex::new(vec![])),
-            };
-            result
-                .guts
-                .lock()
-                .unwrap()
-                .borrow_mut()
-                .deref_mut()
-                .buf
-                .copy_from_slice(&buf);
-            result
+            }
         }
 
         fn copy(destination: &mut [u8], source: &[u8]) {
@@ -379,6 +468,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn accepts_valid_ipv6_dns_target() {
+        let mut holder = FakeStreamHolder::new();
+        let mut subject = make_instrumented_subject(make_socket_wrapper_mock());
+
+        subject.initialize_as_privileged(
+            &vec![String::from("--dns_target"), String::from("2001:db8::1")],
+            &mut holder.streams(),
+        );
+
+        assert_eq!(
+            subject.dns_target,
+            Some(IpAddr::from_str("2001:db8::1").unwrap())
+        );
+    }
+
     #[test]
     fn defaults_unspecified_dns_target() {
         let mut holder = FakeStreamHolder::new();
@@ -477,6 +582,70 @@ mod tests {
         assert_eq!(log[0], "bind ('V4(0.0.0.0:53)')")
     }
 
+    #[test]
+    fn tcp_listener_binds_to_the_same_port() {
+        let mut holder = FakeStreamHolder::new();
+        let listener_wrapper = TcpListenerWrapperMock::new();
+        let listener_log = listener_wrapper.log.clone();
+        let mut subject = make_instrumented_subject(make_socket_wrapper_mock());
+        subject.tcp_server = DnsTcpServer::new(Box::new(listener_wrapper));
+
+        subject.initialize_as_privileged(
+            &vec![String::from("--dns_port"), String::from("5454")],
+            &mut holder.streams(),
+        );
+
+        assert_eq!(
+            *listener_log.lock().unwrap(),
+            vec![String::from("bind (0.0.0.0:5454)")]
+        );
+    }
+
+    #[test]
+    fn queries_over_tcp_are_answered_with_a_length_prefix() {
+        let client_addr = SocketAddr::from_str("127.0.0.1:5432").unwrap();
+        let mut query = [0u8; 100];
+        let query_length = {
+            let mut facade = PacketFacade::new(&mut query, 100);
+            facade.set_transaction_id(0x1234);
+            facade.set_query(true);
+            facade.set_opcode(0x0);
+            facade.add_query("booga.com", 0x0001, 0x0001);
+            facade.get_length()
+        };
+        let mut framed_query = vec![0x00, query_length as u8];
+        framed_query.extend_from_slice(&query[0..query_length]);
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(framed_query)
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_result(Ok(Async::NotReady));
+        let socket_wrapper = make_socket_wrapper_mock();
+        socket_wrapper
+            .recv_from_results
+            .lock()
+            .unwrap()
+            .push(Ok(Async::NotReady));
+        let mut subject = make_instrumented_subject(socket_wrapper);
+        subject.tcp_server = DnsTcpServer::new(Box::new(
+            TcpListenerWrapperMock::new().poll_accept_ok(reader, writer, client_addr),
+        ));
+        subject.dns_target = Some(V4(Ipv4Addr::from_str("1.2.3.4").unwrap()));
+        subject.initialize_as_unprivileged();
+
+        let result = subject.poll();
+
+        assert_eq!(result, Ok(Async::NotReady));
+        let mut response = written.lock().unwrap().remove(0);
+        let response_length = ((response[0] as usize) << 8) | (response[1] as usize);
+        assert_eq!(response.len(), response_length + 2);
+        let facade = PacketFacade::new(&mut response[2..], response_length);
+        assert_eq!(facade.get_transaction_id(), Some(0x1234));
+        assert_eq!(facade.get_answers().unwrap()[0].get_rdata(), &[1, 2, 3, 4]);
+    }
+
     #[test]
     fn serves_multiple_requests_then_short_circuit_on_error() {
         init_test_logging();
@@ -561,6 +730,225 @@ mod tests {
         TestLogHandler::new ().await_log_matching (r"\d\d\d\d-\d\d-\d\d \d\d:\d\d:\d\d\.\d\d\d ThreadId\(\d+\): INFO: EntryDnsServer: \d+ns: 0\.0\.0\.0:0 RQF \(\) -> RS4 \(\)", 1000);
     }
 
+    #[test]
+    fn queries_that_match_pass_through_rules_are_passed_through_instead_of_answered() {
+        let client_addr = SocketAddr::from_str("127.0.0.1:5432").unwrap();
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let mut query = [0u8; 100];
+        let query_length = {
+            let mut facade = PacketFacade::new(&mut query, 100);
+            facade.set_transaction_id(0x1234);
+            facade.set_query(true);
+            facade.set_opcode(0x0);
+            facade.add_query("printer.local", 0x0001, 0x0001);
+            facade.get_length()
+        };
+        let socket_wrapper = Box::new(UdpSocketWrapperMock::new(&query[0..query_length]));
+        {
+            let mut results = socket_wrapper.recv_from_results.lock().unwrap();
+            results.push(Ok(Async::Ready((query_length, client_addr))));
+            results.push(Ok(Async::NotReady));
+            results.push(Ok(Async::NotReady));
+        }
+        socket_wrapper
+            .send_to_results
+            .lock()
+            .unwrap()
+            .push(Ok(Async::Ready(query_length)));
+        let upstream_socket_wrapper =
+            Box::new(UdpSocketWrapperMock::new(&[0u8; 100][0..query_length]));
+        {
+            let mut results = upstream_socket_wrapper.recv_from_results.lock().unwrap();
+            results.push(Ok(Async::NotReady));
+            results.push(Ok(Async::NotReady));
+            results.push(Ok(Async::Ready((query_length, dns_server))));
+            results.push(Ok(Async::NotReady));
+        }
+        upstream_socket_wrapper
+            .send_to_results
+            .lock()
+            .unwrap()
+            .push(Ok(Async::Ready(query_length)));
+        let mut subject = make_instrumented_subject(socket_wrapper.clone());
+        subject.dns_target = Some(V4(Ipv4Addr::from_str("1.2.3.4").unwrap()));
+        subject.pass_through = Some(PassThrough::new(
+            vec![PassThroughRule::from_str("local").unwrap()],
+            vec![dns_server],
+            upstream_socket_wrapper.clone(),
+        ));
+        subject.initialize_as_unprivileged();
+
+        let first_result = subject.poll();
+        let log_after_first_poll = socket_wrapper.guts.lock().unwrap().log.clone();
+        let second_result = subject.poll();
+
+        assert_eq!(first_result, Ok(Async::NotReady));
+        assert_eq!(second_result, Ok(Async::NotReady));
+        assert_eq!(
+            log_after_first_poll
+                .iter()
+                .filter(|entry| entry.starts_with("send_to"))
+                .count(),
+            0
+        );
+        assert_eq!(
+            upstream_socket_wrapper
+                .guts
+                .lock()
+                .unwrap()
+                .log
+                .iter()
+                .filter(|entry| entry.starts_with("send_to"))
+                .collect::<Vec<&String>>(),
+            vec![&format!("send_to (buf, {:?})", dns_server)]
+        );
+        let mut guts = socket_wrapper.guts.lock().unwrap();
+        assert_eq!(
+            guts.log.last(),
+            Some(&String::from("recv_from (Ok(NotReady))"))
+        );
+        assert!(guts
+            .log
+            .contains(&format!("send_to (buf, {:?})", client_addr)));
+        let facade = PacketFacade::new(&mut guts.buf, query_length);
+        assert_eq!(facade.get_transaction_id(), Some(0x1234));
+        assert_eq!(facade.get_answers().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn queries_over_tcp_that_match_pass_through_rules_are_passed_through_too() {
+        let client_addr = SocketAddr::from_str("127.0.0.1:5432").unwrap();
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let mut query = [0u8; 100];
+        let query_length = {
+            let mut facade = PacketFacade::new(&mut query, 100);
+            facade.set_transaction_id(0x1234);
+            facade.set_query(true);
+            facade.set_opcode(0x0);
+            facade.add_query("printer.local", 0x0001, 0x0001);
+            facade.get_length()
+        };
+        let mut framed_query = vec![0x00, query_length as u8];
+        framed_query.extend_from_slice(&query[0..query_length]);
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(framed_query.clone())
+            .poll_read_result(vec![], Ok(Async::NotReady))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(query_length + 2);
+        let socket_wrapper = make_socket_wrapper_mock();
+        {
+            let mut results = socket_wrapper.recv_from_results.lock().unwrap();
+            results.push(Ok(Async::NotReady));
+            results.push(Ok(Async::NotReady));
+        }
+        let upstream_socket_wrapper =
+            Box::new(UdpSocketWrapperMock::new(&[0u8; 100][0..query_length]));
+        {
+            let mut results = upstream_socket_wrapper.recv_from_results.lock().unwrap();
+            results.push(Ok(Async::NotReady));
+            results.push(Ok(Async::Ready((query_length, dns_server))));
+            results.push(Ok(Async::NotReady));
+        }
+        upstream_socket_wrapper
+            .send_to_results
+            .lock()
+            .unwrap()
+            .push(Ok(Async::Ready(query_length)));
+        let mut subject = make_instrumented_subject(socket_wrapper);
+        subject.tcp_server = DnsTcpServer::new(Box::new(
+            TcpListenerWrapperMock::new().poll_accept_ok(reader, writer, client_addr),
+        ));
+        subject.dns_target = Some(V4(Ipv4Addr::from_str("1.2.3.4").unwrap()));
+        subject.pass_through = Some(PassThrough::new(
+            vec![PassThroughRule::from_str("local").unwrap()],
+            vec![dns_server],
+            upstream_socket_wrapper.clone(),
+        ));
+        subject.initialize_as_unprivileged();
+
+        subject.poll().unwrap();
+        let written_after_first_poll = written.lock().unwrap().len();
+        subject.poll().unwrap();
+
+        assert_eq!(written_after_first_poll, 0);
+        assert!(upstream_socket_wrapper
+            .guts
+            .lock()
+            .unwrap()
+            .log
+            .contains(&format!("send_to (buf, {:?})", dns_server)));
+        let mut response = written.lock().unwrap().remove(0);
+        assert_eq!(response.len(), query_length + 2);
+        let facade = PacketFacade::new(&mut response[2..], query_length);
+        assert_eq!(facade.get_transaction_id(), Some(0x1234));
+        assert_eq!(facade.get_answers().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn accepts_pass_through_rules() {
+        let rules = get_pass_through_rules(&vec![
+            String::from("--dns_pass_through"),
+            String::from("corp.example.com,192.168.0.0/16"),
+        ]);
+
+        assert_eq!(
+            rules,
+            vec![
+                PassThroughRule::from_str("corp.example.com").unwrap(),
+                PassThroughRule::from_str("192.168.0.0/16").unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn pass_through_rules_default_to_none() {
+        let rules = get_pass_through_rules(&vec![]);
+
+        assert_eq!(rules, vec![]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Invalid rule for --dns_pass_through: Pass-through rule should be a domain suffix or '<IP address>/<prefix length>', not '10.0.0.0/33'"
+    )]
+    fn complains_about_bad_pass_through_rule() {
+        get_pass_through_rules(&vec![
+            String::from("--dns_pass_through"),
+            String::from("local,10.0.0.0/33"),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "--dns_pass_through needs --dns_servers to pass queries through to")]
+    fn pass_through_needs_dns_servers() {
+        let mut holder = FakeStreamHolder::new();
+        let mut subject = make_instrumented_subject(make_socket_wrapper_mock());
+
+        subject.initialize_as_privileged(
+            &vec![String::from("--dns_pass_through"), String::from("local")],
+            &mut holder.streams(),
+        );
+    }
+
+    #[test]
+    fn accepts_dns_servers_for_pass_through() {
+        let dns_servers = get_dns_servers(&vec![
+            String::from("--dns_servers"),
+            String::from("8.8.8.8,2001:4860:4860::8888"),
+        ]);
+
+        assert_eq!(
+            dns_servers,
+            vec![
+                SocketAddr::from_str("8.8.8.8:53").unwrap(),
+                SocketAddr::from_str("[2001:4860:4860::8888]:53").unwrap()
+            ]
+        );
+    }
+
     #[test]
     fn poll_handles_error_receiving_from_udp_socket_wrapper() {
         init_test_logging();
@@ -621,7 +1009,9 @@ mod tests {
         DnsSocketServer {
             dns_target: None,
             socket_wrapper,
+            tcp_server: DnsTcpServer::new(Box::new(TcpListenerWrapperMock::new())),
             processor: None,
+            pass_through: None,
             buf: None,
         }
     }
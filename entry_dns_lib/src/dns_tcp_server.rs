@@ -0,0 +1,646 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use pass_through::QueryOrigin;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::time::Instant;
+use sub_lib::logger::Logger;
+use sub_lib::tcp_listener_wrapper::TcpListenerWrapperTrait;
+use sub_lib::tokio_wrappers::ReadHalfWrapper;
+use sub_lib::tokio_wrappers::WriteHalfWrapper;
+use tokio::prelude::Async;
+use wakeup::Wakeup;
+
+// Anything shorter than a DNS header can't be a DNS message
+const DNS_HEADER_LENGTH: usize = 12;
+const READ_BUFFER_SIZE: usize = 4096;
+pub const MAX_TCP_CONNECTIONS: usize = 64;
+// RFC 7766 section 6.2: idle connections should be closed after a few seconds
+pub const TCP_IDLE_TIMEOUT_SECS: u64 = 10;
+// A client that sends queries faster than it takes its answers isn't read from until it has caught up
+pub const MAX_OUTBOUND_LENGTH: usize = 65536;
+
+// Serves DNS over TCP (RFC 7766), where each message is preceded by its length as a two-byte big-endian number.
+// Clients may send several queries on one connection without waiting for the answers, and must be ready for the
+// answers to come back in a different order than the queries went out.
+pub struct DnsTcpServer {
+    listener_wrapper: Box<TcpListenerWrapperTrait>,
+    connections: Vec<DnsTcpConnection>,
+    next_connection_id: u64,
+    max_connections: usize,
+    idle_timeout: Duration,
+    max_outbound_length: usize,
+    wakeup: Wakeup,
+}
+
+impl DnsTcpServer {
+    pub fn new(listener_wrapper: Box<TcpListenerWrapperTrait>) -> DnsTcpServer {
+        DnsTcpServer {
+            listener_wrapper,
+            connections: vec![],
+            next_connection_id: 0,
+            max_connections: MAX_TCP_CONNECTIONS,
+            idle_timeout: Duration::from_secs(TCP_IDLE_TIMEOUT_SECS),
+            max_outbound_length: MAX_OUTBOUND_LENGTH,
+            wakeup: Wakeup::new(),
+        }
+    }
+
+    pub fn bind(&mut self, addr: SocketAddr) -> io::Result<bool> {
+        self.listener_wrapper.bind(addr)
+    }
+
+    // Accepts whatever connections are waiting and serves all the open ones as far as they can go without blocking.
+    // Each query is handed to answer(), which gives back the length of the answer it left in the buffer, or None if
+    // the answer will come later through add_answer().
+    pub fn poll(
+        &mut self,
+        answer: &mut FnMut(&mut [u8], usize, QueryOrigin) -> Option<usize>,
+        logger: &Logger,
+    ) {
+        let now = Instant::now();
+        loop {
+            match self.listener_wrapper.poll_accept() {
+                Ok(Async::Ready((_, _, peer_addr)))
+                    if self.connections.len() >= self.max_connections =>
+                {
+                    logger.warning(format!(
+                        "Refusing TCP connection from {}: already serving {}",
+                        peer_addr,
+                        self.connections.len()
+                    ))
+                }
+                Ok(Async::Ready((reader, writer, peer_addr))) => {
+                    let id = self.next_connection_id;
+                    self.next_connection_id += 1;
+                    self.connections.push(DnsTcpConnection {
+                        idle_timeout: self.idle_timeout,
+                        max_outbound_length: self.max_outbound_length,
+                        ..DnsTcpConnection::new(id, reader, writer, peer_addr, now)
+                    })
+                }
+                Ok(Async::NotReady) => break,
+                Err(e) => logger.error(format!("Could not accept TCP connection: {}", e)),
+            }
+        }
+        let connections: Vec<DnsTcpConnection> = self.connections.drain(..).collect();
+        self.connections = connections
+            .into_iter()
+            .filter_map(|mut connection| {
+                if connection.serve(answer, now, logger) {
+                    Some(connection)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let idle_timeout = self.idle_timeout;
+        self.wakeup.arrange(
+            self.connections
+                .iter()
+                .map(|connection| connection.last_active + idle_timeout)
+                .min(),
+        );
+    }
+
+    // An answer that came later, for a query from the numbered connection; if the connection has gone, so has the
+    // client that wanted it.
+    pub fn add_answer(&mut self, connection_id: u64, answer: &[u8]) {
+        if let Some(connection) = self
+            .connections
+            .iter_mut()
+            .find(|connection| connection.id == connection_id)
+        {
+            connection.awaiting_answers = connection.awaiting_answers.saturating_sub(1);
+            connection.add_answer(answer);
+        }
+    }
+}
+
+struct DnsTcpConnection {
+    id: u64,
+    reader: Box<ReadHalfWrapper>,
+    writer: Box<WriteHalfWrapper>,
+    peer_addr: SocketAddr,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+    awaiting_answers: usize,
+    finished_reading: bool,
+    last_active: Instant,
+    idle_timeout: Duration,
+    max_outbound_length: usize,
+}
+
+impl DnsTcpConnection {
+    fn new(
+        id: u64,
+        reader: Box<ReadHalfWrapper>,
+        writer: Box<WriteHalfWrapper>,
+        peer_addr: SocketAddr,
+        now: Instant,
+    ) -> DnsTcpConnection {
+        DnsTcpConnection {
+            id,
+            reader,
+            writer,
+            peer_addr,
+            inbound: vec![],
+            outbound: vec![],
+            awaiting_answers: 0,
+            finished_reading: false,
+            last_active: now,
+            idle_timeout: Duration::from_secs(TCP_IDLE_TIMEOUT_SECS),
+            max_outbound_length: MAX_OUTBOUND_LENGTH,
+        }
+    }
+
+    // Returns false when the connection is finished and should be dropped.
+    fn serve(
+        &mut self,
+        answer: &mut FnMut(&mut [u8], usize, QueryOrigin) -> Option<usize>,
+        now: Instant,
+        logger: &Logger,
+    ) -> bool {
+        if !self.finished_reading && !self.read(answer, now, logger) {
+            return false;
+        }
+        if !self.write(now, logger) {
+            return false;
+        }
+        if now.duration_since(self.last_active) > self.idle_timeout {
+            logger.debug(format!(
+                "Dropping TCP connection from {}: idle for more than {:?}",
+                self.peer_addr, self.idle_timeout
+            ));
+            return false;
+        }
+        !(self.finished_reading && self.outbound.is_empty() && (self.awaiting_answers == 0))
+    }
+
+    fn read(
+        &mut self,
+        answer: &mut FnMut(&mut [u8], usize, QueryOrigin) -> Option<usize>,
+        now: Instant,
+        logger: &Logger,
+    ) -> bool {
+        let mut buf = [0u8; READ_BUFFER_SIZE];
+        while self.outbound.len() < self.max_outbound_length {
+            match self.reader.poll_read(&mut buf) {
+                Ok(Async::Ready(0)) => {
+                    self.finished_reading = true;
+                    return true;
+                }
+                Ok(Async::Ready(length)) => {
+                    self.last_active = now;
+                    self.inbound.extend_from_slice(&buf[0..length]);
+                    if !self.answer(answer, logger) {
+                        return false;
+                    }
+                }
+                Ok(Async::NotReady) => return true,
+                Err(e) => {
+                    logger.debug(format!(
+                        "Dropping TCP connection from {}: {}",
+                        self.peer_addr, e
+                    ));
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // Answers every complete query that has arrived so far.
+    fn answer(
+        &mut self,
+        answer: &mut FnMut(&mut [u8], usize, QueryOrigin) -> Option<usize>,
+        logger: &Logger,
+    ) -> bool {
+        while self.inbound.len() >= 2 {
+            let length = ((self.inbound[0] as usize) << 8) | (self.inbound[1] as usize);
+            if length < DNS_HEADER_LENGTH {
+                logger.warning(format!(
+                    "Dropping TCP connection from {}: a {}-byte message can't be DNS",
+                    self.peer_addr, length
+                ));
+                return false;
+            }
+            if self.inbound.len() < (length + 2) {
+                break;
+            }
+            let mut buf = vec![0u8; 65536];
+            buf[0..length].copy_from_slice(&self.inbound[2..(length + 2)]);
+            self.inbound.drain(0..(length + 2));
+            match answer(&mut buf, length, QueryOrigin::Tcp(self.peer_addr, self.id)) {
+                Some(response_length) => self.add_answer(&buf[0..response_length]),
+                None => self.awaiting_answers += 1,
+            }
+        }
+        true
+    }
+
+    fn add_answer(&mut self, answer: &[u8]) {
+        self.outbound.push((answer.len() >> 8) as u8);
+        self.outbound.push((answer.len() & 0xFF) as u8);
+        self.outbound.extend_from_slice(answer);
+    }
+
+    fn write(&mut self, now: Instant, logger: &Logger) -> bool {
+        while !self.outbound.is_empty() {
+            match self.writer.poll_write(&self.outbound) {
+                Ok(Async::Ready(0)) => return false,
+                Ok(Async::Ready(length)) => {
+                    self.last_active = now;
+                    self.outbound.drain(0..length);
+                }
+                Ok(Async::NotReady) => return true,
+                Err(e) => {
+                    logger.debug(format!(
+                        "Dropping TCP connection from {}: {}",
+                        self.peer_addr, e
+                    ));
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use packet_facade::PacketFacade;
+    use processor::ProcessorReal;
+    use processor::ProcessorTrait;
+    use std::io::Error;
+    use std::io::ErrorKind;
+    use std::net::IpAddr;
+    use std::str::FromStr;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+    use test_utils::logging::init_test_logging;
+    use test_utils::logging::TestLogHandler;
+    use test_utils::tcp_listener_wrapper_mock::TcpListenerWrapperMock;
+    use test_utils::tokio_wrapper_mocks::ReadHalfWrapperMock;
+    use test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
+    use tokio::prelude::future;
+    use tokio::runtime::current_thread::Runtime;
+
+    fn make_query(transaction_id: u16, name: &str) -> Vec<u8> {
+        let mut buf = [0u8; 500];
+        let length = {
+            let mut facade = PacketFacade::new(&mut buf, 500);
+            facade.set_transaction_id(transaction_id);
+            facade.set_query(true);
+            facade.set_opcode(0x0);
+            facade.add_query(name, 0x0001, 0x0001);
+            facade.get_length()
+        };
+        buf[0..length].to_vec()
+    }
+
+    fn frame(message: &[u8]) -> Vec<u8> {
+        let mut framed = vec![(message.len() >> 8) as u8, (message.len() & 0xFF) as u8];
+        framed.extend_from_slice(message);
+        framed
+    }
+
+    fn framed_answer(query: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0u8; 65536];
+        buf[0..query.len()].copy_from_slice(query);
+        let length = processor().process(&mut buf, query.len(), &peer_addr(), &Logger::new("test"));
+        frame(&buf[0..length])
+    }
+
+    fn processor() -> ProcessorReal {
+        ProcessorReal::new(IpAddr::from_str("1.2.3.4").unwrap())
+    }
+
+    fn peer_addr() -> SocketAddr {
+        SocketAddr::from_str("127.0.0.1:5432").unwrap()
+    }
+
+    fn poll_answering_locally(subject: &mut DnsTcpServer, logger: &Logger) {
+        let processor = processor();
+        subject.poll(
+            &mut |buf: &mut [u8], length: usize, origin: QueryOrigin| {
+                Some(processor.process(buf, length, &origin.client_addr(), logger))
+            },
+            logger,
+        );
+    }
+
+    fn make_subject(connections: Vec<(ReadHalfWrapperMock, WriteHalfWrapperMock)>) -> DnsTcpServer {
+        let listener_wrapper = connections.into_iter().fold(
+            TcpListenerWrapperMock::new(),
+            |listener_wrapper, (reader, writer)| {
+                listener_wrapper.poll_accept_ok(reader, writer, peer_addr())
+            },
+        );
+        DnsTcpServer::new(Box::new(listener_wrapper))
+    }
+
+    #[test]
+    fn pipelined_queries_are_all_answered_with_length_prefixes() {
+        let (one_query, another_query) = (
+            make_query(0x1111, "ooga.com"),
+            make_query(0x2222, "booga.com"),
+        );
+        let mut inbound = frame(&one_query);
+        inbound.extend(frame(&another_query));
+        let mut expected = framed_answer(&one_query);
+        expected.extend(framed_answer(&another_query));
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(inbound)
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(expected.len());
+        let mut subject = make_subject(vec![(reader, writer)]);
+
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+
+        assert_eq!(*written.lock().unwrap(), vec![expected]);
+        assert_eq!(subject.connections.len(), 1);
+    }
+
+    #[test]
+    fn query_split_across_reads_is_answered_once_it_is_complete() {
+        let query = make_query(0x1111, "ooga.com");
+        let inbound = frame(&query);
+        let expected = framed_answer(&query);
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(inbound[0..1].to_vec())
+            .poll_read_result(vec![], Ok(Async::NotReady))
+            .poll_read_ok(inbound[1..10].to_vec())
+            .poll_read_ok(inbound[10..].to_vec())
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(expected.len());
+        let mut subject = make_subject(vec![(reader, writer)]);
+
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+        let written_after_first_poll = written.lock().unwrap().len();
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+
+        assert_eq!(written_after_first_poll, 0);
+        assert_eq!(*written.lock().unwrap(), vec![expected]);
+    }
+
+    #[test]
+    fn answers_that_do_not_fit_are_written_later() {
+        let query = make_query(0x1111, "ooga.com");
+        let expected = framed_answer(&query);
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(frame(&query))
+            .poll_read_result(vec![], Ok(Async::NotReady))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(5)
+            .poll_write_result(Ok(Async::NotReady))
+            .poll_write_ok(expected.len() - 5);
+        let mut subject = make_subject(vec![(reader, writer)]);
+
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![
+                expected.clone(),
+                expected[5..].to_vec(),
+                expected[5..].to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn connection_is_dropped_once_the_client_is_done_and_has_its_answers() {
+        let query = make_query(0x1111, "ooga.com");
+        let expected = framed_answer(&query);
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(frame(&query))
+            .poll_read_result(vec![], Ok(Async::Ready(0)));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(expected.len());
+        let mut subject = make_subject(vec![(reader, writer)]);
+
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+
+        assert_eq!(*written.lock().unwrap(), vec![expected]);
+        assert_eq!(subject.connections.len(), 0);
+    }
+
+    #[test]
+    fn answers_that_come_later_are_sent_on_the_connection_that_asked() {
+        let query = make_query(0x1111, "printer.local");
+        let answer = make_query(0x1111, "later.local");
+        let origins = Arc::new(Mutex::new(vec![]));
+        let written = Arc::new(Mutex::new(vec![]));
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(frame(&query))
+            .poll_read_result(vec![], Ok(Async::Ready(0)));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(answer.len() + 2);
+        let mut subject = make_subject(vec![(reader, writer)]);
+        let logger = Logger::new("test");
+
+        subject.poll(
+            &mut |_: &mut [u8], _: usize, origin: QueryOrigin| {
+                origins.lock().unwrap().push(origin);
+                None
+            },
+            &logger,
+        );
+        let connections_while_waiting = subject.connections.len();
+        subject.add_answer(1, &answer);
+        subject.add_answer(0, &answer);
+        subject.poll(&mut |_: &mut [u8], _: usize, _: QueryOrigin| None, &logger);
+
+        assert_eq!(
+            *origins.lock().unwrap(),
+            vec![QueryOrigin::Tcp(peer_addr(), 0)]
+        );
+        assert_eq!(connections_while_waiting, 1);
+        assert_eq!(*written.lock().unwrap(), vec![frame(&answer)]);
+        assert_eq!(subject.connections.len(), 0);
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_are_refused() {
+        init_test_logging();
+        let mut subject = make_subject(vec![
+            (
+                ReadHalfWrapperMock::new().poll_read_result(vec![], Ok(Async::NotReady)),
+                WriteHalfWrapperMock::new(),
+            ),
+            (ReadHalfWrapperMock::new(), WriteHalfWrapperMock::new()),
+        ]);
+        subject.max_connections = 1;
+
+        poll_answering_locally(&mut subject, &Logger::new("DnsTcpServer"));
+
+        assert_eq!(subject.connections.len(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: DnsTcpServer: Refusing TCP connection from 127.0.0.1:5432: already serving 1",
+        );
+    }
+
+    #[test]
+    fn idle_connections_are_dropped() {
+        init_test_logging();
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_result(vec![], Ok(Async::NotReady))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let mut subject = make_subject(vec![(reader, WriteHalfWrapperMock::new())]);
+        subject.idle_timeout = Duration::from_millis(0);
+
+        poll_answering_locally(&mut subject, &Logger::new("DnsTcpServer"));
+        let connections_after_first_poll = subject.connections.len();
+        thread::sleep(Duration::from_millis(1));
+        poll_answering_locally(&mut subject, &Logger::new("DnsTcpServer"));
+
+        assert_eq!(connections_after_first_poll, 1);
+        assert_eq!(subject.connections.len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "DEBUG: DnsTcpServer: Dropping TCP connection from 127.0.0.1:5432: idle for more than 0ns",
+        );
+    }
+
+    #[test]
+    fn idle_connections_are_dropped_even_when_nothing_else_happens() {
+        init_test_logging();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            // Futures may be polled now and then for no reason, but not over and over.
+            let reader = (0..10).fold(ReadHalfWrapperMock::new(), |reader, _| {
+                reader.poll_read_result(vec![], Ok(Async::NotReady))
+            });
+            let mut subject = make_subject(vec![(reader, WriteHalfWrapperMock::new())]);
+            subject.idle_timeout = Duration::from_millis(50);
+            let logger = Logger::new("DnsTcpServer");
+            let started = Instant::now();
+            let mut runtime = Runtime::new().unwrap();
+            runtime
+                .block_on(future::poll_fn(|| -> Result<Async<()>, ()> {
+                    poll_answering_locally(&mut subject, &logger);
+                    if subject.connections.is_empty() {
+                        Ok(Async::Ready(()))
+                    } else {
+                        Ok(Async::NotReady)
+                    }
+                }))
+                .unwrap();
+            tx.send(started.elapsed()).unwrap();
+        });
+
+        let elapsed = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("Idle connection was never dropped");
+
+        assert!(elapsed >= Duration::from_millis(50), "{:?}", elapsed);
+        TestLogHandler::new().exists_log_containing(
+            "DEBUG: DnsTcpServer: Dropping TCP connection from 127.0.0.1:5432: idle for more than 50ms",
+        );
+    }
+
+    #[test]
+    fn client_is_not_read_from_while_its_answers_are_piling_up() {
+        let (one_query, another_query) = (
+            make_query(0x1111, "ooga.com"),
+            make_query(0x2222, "booga.com"),
+        );
+        let one_answer = framed_answer(&one_query);
+        let another_answer = framed_answer(&another_query);
+        let written = Arc::new(Mutex::new(vec![]));
+        // Reading past what's expected here would panic
+        let reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(frame(&one_query))
+            .poll_read_ok(frame(&another_query))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_result(Ok(Async::NotReady))
+            .poll_write_ok(one_answer.len())
+            .poll_write_ok(another_answer.len());
+        let mut subject = make_subject(vec![(reader, writer)]);
+        subject.max_outbound_length = 1;
+
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![one_answer.clone(), one_answer, another_answer]
+        );
+    }
+
+    #[test]
+    fn message_too_short_for_dns_gets_the_connection_dropped() {
+        init_test_logging();
+        let reader = ReadHalfWrapperMock::new().poll_read_ok(vec![0x00, 0x03, 0x01, 0x02, 0x03]);
+        let mut subject = make_subject(vec![(reader, WriteHalfWrapperMock::new())]);
+
+        poll_answering_locally(&mut subject, &Logger::new("DnsTcpServer"));
+
+        assert_eq!(subject.connections.len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: DnsTcpServer: Dropping TCP connection from 127.0.0.1:5432: a 3-byte message can't be DNS",
+        );
+    }
+
+    #[test]
+    fn errors_on_one_connection_do_not_affect_the_others() {
+        let query = make_query(0x1111, "ooga.com");
+        let expected = framed_answer(&query);
+        let written = Arc::new(Mutex::new(vec![]));
+        let failing_reader = ReadHalfWrapperMock::new()
+            .poll_read_result(vec![], Err(Error::from(ErrorKind::ConnectionReset)));
+        let good_reader = ReadHalfWrapperMock::new()
+            .poll_read_ok(frame(&query))
+            .poll_read_result(vec![], Ok(Async::NotReady));
+        let good_writer = WriteHalfWrapperMock::new()
+            .poll_write_params(&written)
+            .poll_write_ok(expected.len());
+        let mut subject = make_subject(vec![
+            (failing_reader, WriteHalfWrapperMock::new()),
+            (good_reader, good_writer),
+        ]);
+
+        poll_answering_locally(&mut subject, &Logger::new("test"));
+
+        assert_eq!(subject.connections.len(), 1);
+        assert_eq!(*written.lock().unwrap(), vec![expected]);
+    }
+
+    #[test]
+    fn accept_errors_are_logged_and_do_not_stop_the_server() {
+        init_test_logging();
+        let listener_wrapper = TcpListenerWrapperMock::new()
+            .poll_accept_result(Err(Error::from(ErrorKind::ConnectionAborted)));
+        let log = listener_wrapper.log.clone();
+        let mut subject = DnsTcpServer::new(Box::new(listener_wrapper));
+
+        poll_answering_locally(&mut subject, &Logger::new("DnsTcpServer"));
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![String::from("poll_accept"), String::from("poll_accept")]
+        );
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: DnsTcpServer: Could not accept TCP connection: connection aborted",
+        );
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 #[cfg(unix)]
 extern crate daemonize;
+extern crate rand;
 extern crate sub_lib;
 extern crate tokio;
 
@@ -11,4 +12,7 @@ extern crate test_utils;
 pub mod packet_facade;
 
 pub mod dns_socket_server;
+pub mod dns_tcp_server;
+pub mod pass_through;
 pub mod processor;
+pub mod wakeup;
@@ -0,0 +1,1017 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use packet_facade::PacketFacade;
+use rand;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+use sub_lib::logger::Logger;
+use sub_lib::udp_socket_wrapper::UdpSocketWrapperTrait;
+use tokio::prelude::Async;
+use wakeup::Wakeup;
+
+// A passed-through query that hasn't been answered by now is forgotten
+pub const PASS_THROUGH_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PassThroughRule {
+    // Matches the domain itself and everything under it
+    DomainSuffix(String),
+    // Matches reverse lookups (in-addr.arpa and ip6.arpa) for addresses in the subnet
+    Subnet(IpAddr, u8),
+}
+
+impl FromStr for PassThroughRule {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<PassThroughRule, String> {
+        let bad_rule = || {
+            Err(format!(
+                "Pass-through rule should be a domain suffix or '<IP address>/<prefix length>', not '{}'",
+                input
+            ))
+        };
+        if input.contains("/") {
+            let pieces: Vec<&str> = input.splitn(2, "/").collect();
+            let ip_addr = match IpAddr::from_str(pieces[0]) {
+                Ok(ip_addr) => ip_addr,
+                Err(_) => return bad_rule(),
+            };
+            let max_prefix_length = match ip_addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            };
+            match pieces[1].parse::<u8>() {
+                Ok(prefix_length) if prefix_length <= max_prefix_length => {
+                    Ok(PassThroughRule::Subnet(ip_addr, prefix_length))
+                }
+                _ => bad_rule(),
+            }
+        } else {
+            let domain = normalize_name(input);
+            if domain.is_empty()
+                || !domain
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || (c == '-') || (c == '.'))
+            {
+                return bad_rule();
+            }
+            Ok(PassThroughRule::DomainSuffix(domain))
+        }
+    }
+}
+
+impl PassThroughRule {
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            PassThroughRule::DomainSuffix(domain) => {
+                let name = normalize_name(name);
+                (&name == domain) || name.ends_with(&format!(".{}", domain))
+            }
+            PassThroughRule::Subnet(subnet, prefix_length) => {
+                match (reverse_lookup_ip(name), subnet) {
+                    (Some(IpAddr::V4(ip)), IpAddr::V4(subnet)) => {
+                        let mask = if *prefix_length == 0 {
+                            0
+                        } else {
+                            !0u32 << (32 - prefix_length)
+                        };
+                        (u32::from(ip) & mask) == (u32::from(*subnet) & mask)
+                    }
+                    (Some(IpAddr::V6(ip)), IpAddr::V6(subnet)) => {
+                        let mask = if *prefix_length == 0 {
+                            0
+                        } else {
+                            !0u128 << (128 - prefix_length)
+                        };
+                        (u128::from(ip) & mask) == (u128::from(*subnet) & mask)
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+// Where a query came from, so that its answer can go back the same way
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryOrigin {
+    Udp(SocketAddr),
+    // The client's address, and the DnsTcpServer's number for its connection
+    Tcp(SocketAddr, u64),
+}
+
+impl QueryOrigin {
+    pub fn client_addr(&self) -> SocketAddr {
+        match self {
+            QueryOrigin::Udp(client_addr) => *client_addr,
+            QueryOrigin::Tcp(client_addr, _) => *client_addr,
+        }
+    }
+}
+
+struct PendingQuery {
+    origin: QueryOrigin,
+    transaction_id: u16,
+    dns_server: SocketAddr,
+    sent: Instant,
+}
+
+// Sends queries that match the rules to a real DNS server instead of answering them, and relays the real answers
+// back to the clients that asked. Each passed-through query gets a random transaction ID of our own, so that queries
+// from different clients can't be confused and answers can't easily be forged. Queries go to the first of the DNS
+// servers until it fails to take one or lets one go unanswered; then the next one gets its turn.
+pub struct PassThrough {
+    rules: Vec<PassThroughRule>,
+    dns_servers: Vec<SocketAddr>,
+    socket_wrapper: Box<UdpSocketWrapperTrait>,
+    pending: HashMap<u16, PendingQuery>,
+    current_server_index: usize,
+    wakeup: Wakeup,
+}
+
+impl PassThrough {
+    pub fn new(
+        rules: Vec<PassThroughRule>,
+        dns_servers: Vec<SocketAddr>,
+        socket_wrapper: Box<UdpSocketWrapperTrait>,
+    ) -> PassThrough {
+        PassThrough {
+            rules,
+            dns_servers,
+            socket_wrapper,
+            pending: HashMap::new(),
+            current_server_index: 0,
+            wakeup: Wakeup::new(),
+        }
+    }
+
+    pub fn bind(&mut self) -> SocketAddr {
+        let wildcard = match self.dns_servers.first() {
+            Some(SocketAddr::V6(_)) => IpAddr::V6(Ipv6Addr::from(0)),
+            _ => IpAddr::V4(Ipv4Addr::from(0)),
+        };
+        let socket_addr = SocketAddr::new(wildcard, 0);
+        self.socket_wrapper.bind(socket_addr).expect(&format!(
+            "Cannot bind pass-through socket to {:?}",
+            socket_addr
+        ));
+        socket_addr
+    }
+
+    pub fn should_pass_through(&self, buf: &mut [u8], length: usize) -> bool {
+        let facade = PacketFacade::new(buf, length);
+        match facade.get_queries() {
+            None => false,
+            Some(queries) => queries.iter().any(|query| {
+                self.rules
+                    .iter()
+                    .any(|rule| rule.matches(query.get_query_name()))
+            }),
+        }
+    }
+
+    pub fn forward(&mut self, buf: &mut [u8], length: usize, origin: QueryOrigin, logger: &Logger) {
+        let now = Instant::now();
+        self.forget_unanswered_queries(now, logger);
+        let transaction_id = match self.unused_transaction_id() {
+            Some(transaction_id) => transaction_id,
+            None => {
+                logger.warning(format!(
+                    "Dropping query from {}: {} queries are already waiting for answers",
+                    origin.client_addr(),
+                    self.pending.len()
+                ));
+                return;
+            }
+        };
+        let original_transaction_id = {
+            let mut facade = PacketFacade::new(buf, length);
+            let original_transaction_id = match facade.get_transaction_id() {
+                Some(transaction_id) => transaction_id,
+                None => return,
+            };
+            facade.set_transaction_id(transaction_id);
+            original_transaction_id
+        };
+        for _ in 0..self.dns_servers.len() {
+            let dns_server = self.dns_servers[self.current_server_index];
+            match self.socket_wrapper.send_to(&buf[0..length], dns_server) {
+                Err(e) => {
+                    logger.warning(format!(
+                        "Could not pass query from {} through to {}: {}",
+                        origin.client_addr(),
+                        dns_server,
+                        e
+                    ));
+                    self.next_server();
+                }
+                Ok(_) => {
+                    logger.info(format!(
+                        "Passed query from {} through to {}",
+                        origin.client_addr(),
+                        dns_server
+                    ));
+                    self.pending.insert(
+                        transaction_id,
+                        PendingQuery {
+                            origin,
+                            transaction_id: original_transaction_id,
+                            dns_server,
+                            sent: now,
+                        },
+                    );
+                    self.arrange_wakeup();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn forget_unanswered_queries(&mut self, now: Instant, logger: &Logger) {
+        let timeout = Duration::from_secs(PASS_THROUGH_TIMEOUT_SECS);
+        let current_server = self.dns_servers[self.current_server_index];
+        let before = self.pending.len();
+        self.pending.retain(|_, pending_query| {
+            (pending_query.dns_server != current_server)
+                || (now.duration_since(pending_query.sent) < timeout)
+        });
+        if self.pending.len() < before {
+            logger.warning(format!(
+                "{} left {} queries unanswered; trying another DNS server",
+                current_server,
+                before - self.pending.len()
+            ));
+            self.next_server();
+        }
+        self.pending
+            .retain(|_, pending_query| now.duration_since(pending_query.sent) < timeout);
+    }
+
+    // A DNS server that goes quiet must be given up on even if no more queries or answers come along to wake us.
+    fn arrange_wakeup(&mut self) {
+        let timeout = Duration::from_secs(PASS_THROUGH_TIMEOUT_SECS);
+        self.wakeup.arrange(
+            self.pending
+                .values()
+                .map(|pending_query| pending_query.sent + timeout)
+                .min(),
+        );
+    }
+
+    fn next_server(&mut self) {
+        self.current_server_index = (self.current_server_index + 1) % self.dns_servers.len();
+    }
+
+    fn unused_transaction_id(&self) -> Option<u16> {
+        if self.pending.len() > (u16::max_value() as usize) {
+            return None;
+        }
+        loop {
+            let transaction_id = rand::random::<u16>();
+            if !self.pending.contains_key(&transaction_id) {
+                return Some(transaction_id);
+            }
+        }
+    }
+
+    // Relays whatever answers have arrived from the DNS servers, until there are no more. Answers to queries that
+    // came over UDP go straight back to their clients; answers to queries that came over TCP are returned, with the
+    // numbers of their connections, for the DnsTcpServer to send. Then gives up on queries that have waited too
+    // long.
+    pub fn relay_answers(
+        &mut self,
+        buf: &mut [u8],
+        client_socket_wrapper: &mut Box<UdpSocketWrapperTrait>,
+        logger: &Logger,
+    ) -> Result<Vec<(u64, Vec<u8>)>, ()> {
+        let mut tcp_answers = vec![];
+        loop {
+            let (length, server_addr) = match self.socket_wrapper.recv_from(buf) {
+                Ok(Async::Ready((length, server_addr))) => (length, server_addr),
+                Ok(Async::NotReady) => {
+                    self.forget_unanswered_queries(Instant::now(), logger);
+                    self.arrange_wakeup();
+                    return Ok(tcp_answers);
+                }
+                Err(e) => {
+                    logger.error(format!(
+                        "Unrecoverable error receiving from pass-through UdpSocket: {}",
+                        e
+                    ));
+                    return Err(());
+                }
+            };
+            if !self.dns_servers.contains(&server_addr) {
+                logger.warning(format!(
+                    "Ignoring DNS answer from {}, which is not a DNS server we use",
+                    server_addr
+                ));
+                continue;
+            }
+            let pending_query = {
+                let mut facade = PacketFacade::new(buf, length);
+                let pending_query_opt = match facade.get_transaction_id() {
+                    Some(transaction_id) => self.pending.remove(&transaction_id),
+                    None => None,
+                };
+                match pending_query_opt {
+                    Some(pending_query) => {
+                        facade.set_transaction_id(pending_query.transaction_id);
+                        pending_query
+                    }
+                    None => {
+                        logger.debug(format!(
+                            "Ignoring DNS answer from {} to a query we didn't pass through or have given up on",
+                            server_addr
+                        ));
+                        continue;
+                    }
+                }
+            };
+            let client_addr = match pending_query.origin {
+                QueryOrigin::Udp(client_addr) => client_addr,
+                QueryOrigin::Tcp(_, connection_id) => {
+                    tcp_answers.push((connection_id, buf[0..length].to_vec()));
+                    continue;
+                }
+            };
+            match client_socket_wrapper.send_to(&buf[0..length], client_addr) {
+                Err(e) => {
+                    logger.error(format!("Unrecoverable error sending to UdpSocket: {}", e));
+                    return Err(());
+                }
+                Ok(_) => (),
+            }
+        }
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim_matches('.').to_lowercase()
+}
+
+fn reverse_lookup_ip(name: &str) -> Option<IpAddr> {
+    let name = normalize_name(name);
+    if name.ends_with(".in-addr.arpa") {
+        let labels: Vec<&str> = name[..(name.len() - ".in-addr.arpa".len())]
+            .split(".")
+            .collect();
+        if labels.len() != 4 {
+            return None;
+        }
+        let mut octets = [0u8; 4];
+        for (index, label) in labels.iter().rev().enumerate() {
+            octets[index] = label.parse::<u8>().ok()?;
+        }
+        Some(IpAddr::V4(Ipv4Addr::from(octets)))
+    } else if name.ends_with(".ip6.arpa") {
+        let labels: Vec<&str> = name[..(name.len() - ".ip6.arpa".len())]
+            .split(".")
+            .collect();
+        if labels.len() != 32 {
+            return None;
+        }
+        let mut address = 0u128;
+        for label in labels.iter().rev() {
+            if label.len() != 1 {
+                return None;
+            }
+            address = (address << 4) | u128::from_str_radix(label, 16).ok()?;
+        }
+        Some(IpAddr::V6(Ipv6Addr::from(address)))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::io::Error;
+    use std::io::ErrorKind;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+    use test_utils::logging::init_test_logging;
+    use test_utils::logging::TestLogHandler;
+    use tokio::prelude::future;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[derive(Clone)]
+    struct UdpSocketWrapperMock {
+        log: Arc<Mutex<Vec<String>>>,
+        sent: Arc<Mutex<Vec<Vec<u8>>>>,
+        recv_from_results: Arc<Mutex<Vec<Result<Async<(Vec<u8>, SocketAddr)>, Error>>>>,
+        send_to_results: Arc<Mutex<Vec<Result<Async<usize>, Error>>>>,
+    }
+
+    impl UdpSocketWrapperTrait for UdpSocketWrapperMock {
+        fn bind(&mut self, addr: SocketAddr) -> io::Result<bool> {
+            self.log.lock().unwrap().push(format!("bind ({})", addr));
+            Ok(true)
+        }
+
+        fn recv_from(&mut self, buf: &mut [u8]) -> Result<Async<(usize, SocketAddr)>, Error> {
+            match self.recv_from_results.lock().unwrap().remove(0) {
+                Ok(Async::Ready((data, addr))) => {
+                    buf[0..data.len()].copy_from_slice(&data);
+                    Ok(Async::Ready((data.len(), addr)))
+                }
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Err(e) => Err(e),
+            }
+        }
+
+        fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> Result<Async<usize>, Error> {
+            self.log.lock().unwrap().push(format!("send_to ({})", addr));
+            self.sent.lock().unwrap().push(buf.to_vec());
+            self.send_to_results.lock().unwrap().remove(0)
+        }
+    }
+
+    impl UdpSocketWrapperMock {
+        fn new() -> UdpSocketWrapperMock {
+            UdpSocketWrapperMock {
+                log: Arc::new(Mutex::new(vec![])),
+                sent: Arc::new(Mutex::new(vec![])),
+                recv_from_results: Arc::new(Mutex::new(vec![])),
+                send_to_results: Arc::new(Mutex::new(vec![])),
+            }
+        }
+
+        fn recv_from_result(self, result: Result<Async<(Vec<u8>, SocketAddr)>, Error>) -> Self {
+            self.recv_from_results.lock().unwrap().push(result);
+            self
+        }
+
+        fn send_to_result(self, result: Result<Async<usize>, Error>) -> Self {
+            self.send_to_results.lock().unwrap().push(result);
+            self
+        }
+    }
+
+    fn make_query(transaction_id: u16, name: &str) -> Vec<u8> {
+        let mut buf = [0u8; 500];
+        let length = {
+            let mut facade = PacketFacade::new(&mut buf, 500);
+            facade.set_transaction_id(transaction_id);
+            facade.set_query(true);
+            facade.set_opcode(0x0);
+            facade.add_query(name, 0x0001, 0x0001);
+            facade.get_length()
+        };
+        buf[0..length].to_vec()
+    }
+
+    fn make_answer(query: &[u8], rdata: &[u8]) -> Vec<u8> {
+        let mut buf = [0u8; 500];
+        buf[0..query.len()].copy_from_slice(query);
+        let length = {
+            let mut facade = PacketFacade::new(&mut buf, query.len());
+            facade.set_query(false);
+            let name = facade.get_queries().unwrap()[0]
+                .get_query_name()
+                .to_string();
+            facade.add_answer(&name, 0x0001, 0x0001, 300, rdata);
+            facade.get_length()
+        };
+        buf[0..length].to_vec()
+    }
+
+    fn transaction_id_of(packet: &[u8]) -> u16 {
+        let mut buf = packet.to_vec();
+        let length = buf.len();
+        PacketFacade::new(&mut buf, length)
+            .get_transaction_id()
+            .unwrap()
+    }
+
+    fn rule(s: &str) -> PassThroughRule {
+        PassThroughRule::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn rules_parse_domain_suffixes_and_subnets() {
+        assert_eq!(
+            rule(".Corp.Example.com."),
+            PassThroughRule::DomainSuffix(String::from("corp.example.com"))
+        );
+        assert_eq!(
+            rule("192.168.0.0/16"),
+            PassThroughRule::Subnet(IpAddr::from_str("192.168.0.0").unwrap(), 16)
+        );
+        assert_eq!(
+            rule("fd00::/8"),
+            PassThroughRule::Subnet(IpAddr::from_str("fd00::").unwrap(), 8)
+        );
+    }
+
+    #[test]
+    fn rules_reject_nonsense() {
+        let results: Vec<Result<PassThroughRule, String>> = vec![
+            "",
+            "booga booga",
+            "1.2.3.4/33",
+            "fd00::/129",
+            "1.2.3/8",
+            "1.2.3.4/x",
+        ]
+        .into_iter()
+        .map(PassThroughRule::from_str)
+        .collect();
+
+        results.iter().for_each(|result| {
+            assert!(
+                result
+                    .clone()
+                    .err()
+                    .unwrap()
+                    .starts_with("Pass-through rule should be a domain suffix"),
+                "{:?}",
+                result
+            )
+        });
+    }
+
+    #[test]
+    fn domain_suffix_rules_match_the_domain_and_everything_under_it() {
+        let subject = rule("corp.example.com");
+
+        assert_eq!(subject.matches("corp.example.com"), true);
+        assert_eq!(subject.matches("Intranet.CORP.example.com."), true);
+        assert_eq!(subject.matches("notcorp.example.com"), false);
+        assert_eq!(subject.matches("example.com"), false);
+    }
+
+    #[test]
+    fn subnet_rules_match_reverse_lookups_in_the_subnet() {
+        let ipv4_subject = rule("192.168.0.0/16");
+        let ipv6_subject = rule("fd00::/8");
+        let ipv6_in_subnet =
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.d.f.ip6.arpa";
+        let ipv6_out_of_subnet =
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.e.f.ip6.arpa";
+
+        assert_eq!(ipv4_subject.matches("4.3.168.192.in-addr.arpa"), true);
+        assert_eq!(ipv4_subject.matches("4.3.169.192.in-addr.arpa."), false);
+        assert_eq!(ipv4_subject.matches("168.192.in-addr.arpa"), false);
+        assert_eq!(ipv4_subject.matches("192.168.3.4"), false);
+        assert_eq!(ipv4_subject.matches(ipv6_in_subnet), false);
+        assert_eq!(ipv6_subject.matches(ipv6_in_subnet), true);
+        assert_eq!(ipv6_subject.matches(ipv6_out_of_subnet), false);
+        assert_eq!(rule("0.0.0.0/0").matches("1.1.1.1.in-addr.arpa"), true);
+    }
+
+    #[test]
+    fn only_queries_that_match_a_rule_are_passed_through() {
+        let subject = PassThrough::new(
+            vec![rule("local"), rule("10.0.0.0/8")],
+            vec![SocketAddr::from_str("8.8.8.8:53").unwrap()],
+            Box::new(UdpSocketWrapperMock::new()),
+        );
+        let check = |name: &str| {
+            let mut query = make_query(0x1234, name);
+            let length = query.len();
+            subject.should_pass_through(&mut query, length)
+        };
+
+        assert_eq!(check("printer.local"), true);
+        assert_eq!(check("4.3.2.10.in-addr.arpa"), true);
+        assert_eq!(check("www.example.com"), false);
+    }
+
+    #[test]
+    fn bind_uses_the_address_family_of_the_dns_servers() {
+        let ipv4_socket_wrapper = UdpSocketWrapperMock::new();
+        let ipv4_log = ipv4_socket_wrapper.log.clone();
+        let ipv6_socket_wrapper = UdpSocketWrapperMock::new();
+        let ipv6_log = ipv6_socket_wrapper.log.clone();
+        let mut ipv4_subject = PassThrough::new(
+            vec![],
+            vec![SocketAddr::from_str("8.8.8.8:53").unwrap()],
+            Box::new(ipv4_socket_wrapper),
+        );
+        let mut ipv6_subject = PassThrough::new(
+            vec![],
+            vec![SocketAddr::from_str("[2001:4860:4860::8888]:53").unwrap()],
+            Box::new(ipv6_socket_wrapper),
+        );
+
+        ipv4_subject.bind();
+        ipv6_subject.bind();
+
+        assert_eq!(*ipv4_log.lock().unwrap(), vec!["bind (0.0.0.0:0)"]);
+        assert_eq!(*ipv6_log.lock().unwrap(), vec!["bind ([::]:0)"]);
+    }
+
+    #[test]
+    fn queries_are_passed_through_and_answers_relayed_back_with_the_original_transaction_id() {
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let one_client = SocketAddr::from_str("127.0.0.1:5432").unwrap();
+        let another_client = SocketAddr::from_str("127.0.0.1:6543").unwrap();
+        let one_query = make_query(0x1234, "printer.local");
+        let another_query = make_query(0x1234, "nas.local");
+        let upstream_socket_wrapper = UdpSocketWrapperMock::new()
+            .send_to_result(Ok(Async::Ready(one_query.len())))
+            .send_to_result(Ok(Async::Ready(another_query.len())));
+        let upstream_log = upstream_socket_wrapper.log.clone();
+        let upstream_sent = upstream_socket_wrapper.sent.clone();
+        let upstream_recv_from_results = upstream_socket_wrapper.recv_from_results.clone();
+        let client_socket_wrapper = UdpSocketWrapperMock::new()
+            .send_to_result(Ok(Async::Ready(0)))
+            .send_to_result(Ok(Async::Ready(0)));
+        let client_log = client_socket_wrapper.log.clone();
+        let client_sent = client_socket_wrapper.sent.clone();
+        let mut client_socket_wrapper: Box<UdpSocketWrapperTrait> = Box::new(client_socket_wrapper);
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![dns_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let mut buf = [0u8; 500];
+
+        buf[0..one_query.len()].copy_from_slice(&one_query);
+        subject.forward(
+            &mut buf,
+            one_query.len(),
+            QueryOrigin::Udp(one_client),
+            &Logger::new("test"),
+        );
+        buf[0..another_query.len()].copy_from_slice(&another_query);
+        subject.forward(
+            &mut buf,
+            another_query.len(),
+            QueryOrigin::Udp(another_client),
+            &Logger::new("test"),
+        );
+        let forwarded = upstream_sent.lock().unwrap().clone();
+        {
+            let mut results = upstream_recv_from_results.lock().unwrap();
+            results.push(Ok(Async::Ready((
+                make_answer(&forwarded[1], &[5, 6, 7, 8]),
+                dns_server,
+            ))));
+            results.push(Ok(Async::Ready((
+                make_answer(&forwarded[0], &[1, 2, 3, 4]),
+                dns_server,
+            ))));
+            results.push(Ok(Async::NotReady));
+        }
+        let result =
+            subject.relay_answers(&mut buf, &mut client_socket_wrapper, &Logger::new("test"));
+
+        assert_eq!(result, Ok(vec![]));
+        assert_eq!(
+            *upstream_log.lock().unwrap(),
+            vec!["send_to (8.8.8.8:53)", "send_to (8.8.8.8:53)"]
+        );
+        assert_ne!(
+            transaction_id_of(&forwarded[0]),
+            transaction_id_of(&forwarded[1])
+        );
+        assert_eq!(
+            *client_log.lock().unwrap(),
+            vec!["send_to (127.0.0.1:6543)", "send_to (127.0.0.1:5432)"]
+        );
+        let relayed = client_sent.lock().unwrap().clone();
+        assert_eq!(transaction_id_of(&relayed[0]), 0x1234);
+        assert_eq!(relayed[0], make_answer(&another_query, &[5, 6, 7, 8]));
+        assert_eq!(transaction_id_of(&relayed[1]), 0x1234);
+        assert_eq!(relayed[1], make_answer(&one_query, &[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn answers_to_queries_that_came_over_tcp_are_returned_for_their_connections() {
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let client = SocketAddr::from_str("127.0.0.1:5432").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper =
+            UdpSocketWrapperMock::new().send_to_result(Ok(Async::Ready(query.len())));
+        let upstream_sent = upstream_socket_wrapper.sent.clone();
+        let upstream_recv_from_results = upstream_socket_wrapper.recv_from_results.clone();
+        let client_socket_wrapper = UdpSocketWrapperMock::new();
+        let client_log = client_socket_wrapper.log.clone();
+        let mut client_socket_wrapper: Box<UdpSocketWrapperTrait> = Box::new(client_socket_wrapper);
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![dns_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let mut buf = [0u8; 500];
+        buf[0..query.len()].copy_from_slice(&query);
+
+        subject.forward(
+            &mut buf,
+            query.len(),
+            QueryOrigin::Tcp(client, 7),
+            &Logger::new("test"),
+        );
+        let forwarded = upstream_sent.lock().unwrap()[0].clone();
+        {
+            let mut results = upstream_recv_from_results.lock().unwrap();
+            results.push(Ok(Async::Ready((
+                make_answer(&forwarded, &[1, 2, 3, 4]),
+                dns_server,
+            ))));
+            results.push(Ok(Async::NotReady));
+        }
+        let result =
+            subject.relay_answers(&mut buf, &mut client_socket_wrapper, &Logger::new("test"));
+
+        assert_eq!(result, Ok(vec![(7, make_answer(&query, &[1, 2, 3, 4]))]));
+        assert_eq!(*client_log.lock().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn answers_from_strangers_and_to_unknown_queries_are_ignored() {
+        init_test_logging();
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper = UdpSocketWrapperMock::new()
+            .recv_from_result(Ok(Async::Ready((
+                make_answer(&query, &[1, 2, 3, 4]),
+                SocketAddr::from_str("6.6.6.6:53").unwrap(),
+            ))))
+            .recv_from_result(Ok(Async::Ready((
+                make_answer(&query, &[1, 2, 3, 4]),
+                dns_server,
+            ))))
+            .recv_from_result(Ok(Async::NotReady));
+        let client_socket_wrapper = UdpSocketWrapperMock::new();
+        let client_log = client_socket_wrapper.log.clone();
+        let mut client_socket_wrapper: Box<UdpSocketWrapperTrait> = Box::new(client_socket_wrapper);
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![dns_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let mut buf = [0u8; 500];
+
+        let result = subject.relay_answers(
+            &mut buf,
+            &mut client_socket_wrapper,
+            &Logger::new("PassThrough"),
+        );
+
+        assert_eq!(result, Ok(vec![]));
+        assert_eq!(*client_log.lock().unwrap(), Vec::<String>::new());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: PassThrough: Ignoring DNS answer from 6.6.6.6:53, which is not a DNS server we use",
+        );
+    }
+
+    #[test]
+    fn failure_to_pass_a_query_through_is_not_fatal() {
+        init_test_logging();
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let client = SocketAddr::from_str("127.0.0.1:5432").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper =
+            UdpSocketWrapperMock::new().send_to_result(Err(Error::from(ErrorKind::BrokenPipe)));
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![dns_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let mut buf = [0u8; 500];
+        buf[0..query.len()].copy_from_slice(&query);
+
+        subject.forward(
+            &mut buf,
+            query.len(),
+            QueryOrigin::Udp(client),
+            &Logger::new("PassThrough"),
+        );
+
+        assert_eq!(subject.pending.len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: PassThrough: Could not pass query from 127.0.0.1:5432 through to 8.8.8.8:53: broken pipe",
+        );
+    }
+
+    fn make_pending_query(dns_server: SocketAddr, sent: Instant) -> PendingQuery {
+        PendingQuery {
+            origin: QueryOrigin::Udp(SocketAddr::from_str("127.0.0.1:5432").unwrap()),
+            transaction_id: 0x1234,
+            dns_server,
+            sent,
+        }
+    }
+
+    #[test]
+    fn transaction_ids_of_queries_still_waiting_for_answers_are_not_reused() {
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper =
+            UdpSocketWrapperMock::new().send_to_result(Ok(Async::Ready(query.len())));
+        let upstream_sent = upstream_socket_wrapper.sent.clone();
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![dns_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let now = Instant::now();
+        (0..=u16::max_value())
+            .filter(|transaction_id| *transaction_id != 0x4321)
+            .for_each(|transaction_id| {
+                subject
+                    .pending
+                    .insert(transaction_id, make_pending_query(dns_server, now));
+            });
+        let mut buf = [0u8; 500];
+        buf[0..query.len()].copy_from_slice(&query);
+
+        subject.forward(
+            &mut buf,
+            query.len(),
+            QueryOrigin::Udp(SocketAddr::from_str("127.0.0.1:6543").unwrap()),
+            &Logger::new("test"),
+        );
+
+        assert_eq!(transaction_id_of(&upstream_sent.lock().unwrap()[0]), 0x4321);
+        assert_eq!(subject.pending.len(), 0x10000);
+    }
+
+    #[test]
+    fn query_is_dropped_when_every_transaction_id_is_in_use() {
+        init_test_logging();
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper = UdpSocketWrapperMock::new();
+        let upstream_log = upstream_socket_wrapper.log.clone();
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![dns_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let now = Instant::now();
+        (0..=u16::max_value()).for_each(|transaction_id| {
+            subject
+                .pending
+                .insert(transaction_id, make_pending_query(dns_server, now));
+        });
+        let mut buf = [0u8; 500];
+        buf[0..query.len()].copy_from_slice(&query);
+
+        subject.forward(
+            &mut buf,
+            query.len(),
+            QueryOrigin::Udp(SocketAddr::from_str("127.0.0.1:6543").unwrap()),
+            &Logger::new("PassThrough"),
+        );
+
+        assert_eq!(*upstream_log.lock().unwrap(), Vec::<String>::new());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: PassThrough: Dropping query from 127.0.0.1:6543: 65536 queries are already waiting for answers",
+        );
+    }
+
+    #[test]
+    fn query_that_one_dns_server_will_not_take_goes_to_the_next() {
+        let one_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let another_server = SocketAddr::from_str("8.8.4.4:53").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper = UdpSocketWrapperMock::new()
+            .send_to_result(Err(Error::from(ErrorKind::BrokenPipe)))
+            .send_to_result(Ok(Async::Ready(query.len())))
+            .send_to_result(Ok(Async::Ready(query.len())));
+        let upstream_log = upstream_socket_wrapper.log.clone();
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![one_server, another_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let client = QueryOrigin::Udp(SocketAddr::from_str("127.0.0.1:5432").unwrap());
+        let mut buf = [0u8; 500];
+
+        buf[0..query.len()].copy_from_slice(&query);
+        subject.forward(&mut buf, query.len(), client, &Logger::new("test"));
+        buf[0..query.len()].copy_from_slice(&query);
+        subject.forward(&mut buf, query.len(), client, &Logger::new("test"));
+
+        assert_eq!(
+            *upstream_log.lock().unwrap(),
+            vec![
+                "send_to (8.8.8.8:53)",
+                "send_to (8.8.4.4:53)",
+                "send_to (8.8.4.4:53)"
+            ]
+        );
+        assert_eq!(subject.pending.len(), 2);
+    }
+
+    #[test]
+    fn dns_server_that_leaves_queries_unanswered_is_passed_over() {
+        init_test_logging();
+        let one_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let another_server = SocketAddr::from_str("8.8.4.4:53").unwrap();
+        let query = make_query(0x1234, "printer.local");
+        let upstream_socket_wrapper =
+            UdpSocketWrapperMock::new().send_to_result(Ok(Async::Ready(query.len())));
+        let upstream_log = upstream_socket_wrapper.log.clone();
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![one_server, another_server],
+            Box::new(upstream_socket_wrapper),
+        );
+        let long_ago = Instant::now() - Duration::from_secs(PASS_THROUGH_TIMEOUT_SECS + 1);
+        subject
+            .pending
+            .insert(0x1111, make_pending_query(one_server, long_ago));
+        let mut buf = [0u8; 500];
+        buf[0..query.len()].copy_from_slice(&query);
+
+        subject.forward(
+            &mut buf,
+            query.len(),
+            QueryOrigin::Udp(SocketAddr::from_str("127.0.0.1:5432").unwrap()),
+            &Logger::new("PassThrough"),
+        );
+
+        assert_eq!(*upstream_log.lock().unwrap(), vec!["send_to (8.8.4.4:53)"]);
+        assert_eq!(subject.pending.contains_key(&0x1111), false);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: PassThrough: 8.8.8.8:53 left 1 queries unanswered; trying another DNS server",
+        );
+    }
+
+    #[test]
+    fn dns_server_that_goes_quiet_is_passed_over_even_when_nothing_else_happens() {
+        init_test_logging();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let one_server = SocketAddr::from_str("9.9.9.9:53").unwrap();
+            let another_server = SocketAddr::from_str("149.112.112.112:53").unwrap();
+            // Futures may be polled now and then for no reason, but not over and over.
+            let upstream_socket_wrapper = (0..10)
+                .fold(UdpSocketWrapperMock::new(), |wrapper, _| {
+                    wrapper.recv_from_result(Ok(Async::NotReady))
+                });
+            let mut subject = PassThrough::new(
+                vec![rule("local")],
+                vec![one_server, another_server],
+                Box::new(upstream_socket_wrapper),
+            );
+            let almost_too_long_ago = Instant::now()
+                - Duration::from_secs(PASS_THROUGH_TIMEOUT_SECS)
+                + Duration::from_millis(50);
+            subject
+                .pending
+                .insert(0x1111, make_pending_query(one_server, almost_too_long_ago));
+            let mut client_socket_wrapper: Box<UdpSocketWrapperTrait> =
+                Box::new(UdpSocketWrapperMock::new());
+            let logger = Logger::new("PassThrough");
+            let mut buf = [0u8; 500];
+            let mut runtime = Runtime::new().unwrap();
+            runtime
+                .block_on(future::poll_fn(|| -> Result<Async<()>, ()> {
+                    subject.relay_answers(&mut buf, &mut client_socket_wrapper, &logger)?;
+                    if subject.pending.is_empty() {
+                        Ok(Async::Ready(()))
+                    } else {
+                        Ok(Async::NotReady)
+                    }
+                }))
+                .unwrap();
+            tx.send(subject.current_server_index).unwrap();
+        });
+
+        let current_server_index = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("Unanswered query was never given up on");
+
+        assert_eq!(current_server_index, 1);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: PassThrough: 9.9.9.9:53 left 1 queries unanswered; trying another DNS server",
+        );
+    }
+
+    #[test]
+    fn unrecoverable_error_receiving_answers_is_reported() {
+        let upstream_socket_wrapper =
+            UdpSocketWrapperMock::new().recv_from_result(Err(Error::from(ErrorKind::BrokenPipe)));
+        let mut client_socket_wrapper: Box<UdpSocketWrapperTrait> =
+            Box::new(UdpSocketWrapperMock::new());
+        let mut subject = PassThrough::new(
+            vec![rule("local")],
+            vec![SocketAddr::from_str("8.8.8.8:53").unwrap()],
+            Box::new(upstream_socket_wrapper),
+        );
+        let mut buf = [0u8; 500];
+
+        let result = subject.relay_answers(
+            &mut buf,
+            &mut client_socket_wrapper,
+            &Logger::new("PassThrough"),
+        );
+
+        assert_eq!(result, Err(()));
+    }
+}
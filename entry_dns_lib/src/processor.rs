@@ -3,10 +3,20 @@ use packet_facade::PacketFacade;
 use packet_facade::Query;
 use packet_facade::ResourceRecord;
 use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::net::SocketAddr;
 use std::time::Instant;
 use sub_lib::logger::Logger;
 
+const TYPE_A: u16 = 0x0001;
+const TYPE_MX: u16 = 0x000F;
+const TYPE_TXT: u16 = 0x0010;
+const TYPE_AAAA: u16 = 0x001C;
+const TYPE_SVCB: u16 = 0x0040;
+const TYPE_HTTPS: u16 = 0x0041;
+const TYPE_ANY: u16 = 0x00FF;
+
 pub trait ProcessorTrait: Send + Sync {
     fn process(&self, buf: &mut [u8], length: usize, addr: &SocketAddr, logger: &Logger) -> usize;
 }
@@ -57,18 +67,21 @@ impl ProcessorTrait for ProcessorReal {
                 Some(q) => q,
             };
             for query in queries {
-                if query.get_query_type() != 0x0001 {
-                    return ProcessorReal::make_not_implemented_error(&mut facade);
-                }
                 if query.get_query_class() != 0x0001 {
                     return ProcessorReal::make_not_implemented_error(&mut facade);
                 }
-                let octets = match self.target_ip {
-                    IpAddr::V4(ipv4) => ipv4.octets(),
-                    // crashpoint - make a card
-                    IpAddr::V6(_ipv6) => unimplemented!(),
-                };
-                facade.add_answer(&query.get_query_name(), 0x0001, 0x0001, 3600, &octets);
+                match query.get_query_type() {
+                    TYPE_A | TYPE_AAAA | TYPE_ANY => {
+                        // A query for the other address family gets no answer rather than an error, so that
+                        // resolvers don't give up on the name.
+                        if let Some((rtype, rdata)) = self.address_record(query.get_query_type()) {
+                            facade.add_answer(&query.get_query_name(), rtype, 0x0001, 3600, &rdata);
+                        }
+                    }
+                    // Browsers ask for these too; there's nothing to tell them, but no reason to fail either.
+                    TYPE_MX | TYPE_TXT | TYPE_SVCB | TYPE_HTTPS => (),
+                    _ => return ProcessorReal::make_not_implemented_error(&mut facade),
+                }
             }
 
             result = facade.get_length();
@@ -86,6 +99,19 @@ impl ProcessorTrait for ProcessorReal {
 }
 
 impl ProcessorReal {
+    // ANY gets whichever address record there is, rather than everything (see RFC 8482).
+    fn address_record(&self, query_type: u16) -> Option<(u16, Vec<u8>)> {
+        match (query_type, self.target_ip) {
+            (TYPE_A, IpAddr::V4(ipv4)) | (TYPE_ANY, IpAddr::V4(ipv4)) => {
+                Some((TYPE_A, ipv4.octets().to_vec()))
+            }
+            (TYPE_AAAA, IpAddr::V6(ipv6)) | (TYPE_ANY, IpAddr::V6(ipv6)) => {
+                Some((TYPE_AAAA, ipv6.octets().to_vec()))
+            }
+            _ => None,
+        }
+    }
+
     fn make_format_error(facade: &mut PacketFacade) -> usize {
         facade.set_query(false);
         facade.set_authoritative_answer(false);
@@ -128,15 +154,25 @@ impl ProcessorReal {
             if !answer_list.is_empty() {
                 answer_list += ", "
             }
-            let rdata = answer.get_rdata();
-            // TODO: What if there aren't four elements in this array?
-            answer_list += &format!("{}.{}.{}.{}", rdata[0], rdata[1], rdata[2], rdata[3])
+            answer_list += &ProcessorReal::format_rdata(answer.get_rdata())
         }
         logger.info(format!(
             "{}ns: {} RQ{:X} ({}) -> RS{:X} ({})",
             to.latency_ns, addr, from.opcode, &query_list, to.rcode, &answer_list
         ));
     }
+
+    fn format_rdata(rdata: &[u8]) -> String {
+        match rdata.len() {
+            4 => format!("{}", Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+            16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(rdata);
+                format!("{}", Ipv6Addr::from(octets))
+            }
+            _ => format!("{:?}", rdata),
+        }
+    }
 }
 
 struct RequestRecord {
@@ -337,6 +373,120 @@ mod tests {
         tlh.exists_log_containing ("101.102.103.104:53 RQ0 (1/1/ooga.com, 1/1/booga.com) -> RS0 (18.52.86.120, 18.52.86.120)");
     }
 
+    #[test]
+    fn aaaa_query_is_answered_with_an_ipv6_target() {
+        init_test_logging();
+        let mut buf: [u8; 500] = [0; 500];
+        let req_length = {
+            let mut request = PacketFacade::new(&mut buf, 500);
+            request.set_transaction_id(0x4321);
+            request.set_query(true);
+            request.set_opcode(0x0);
+            request.add_query("ooga.com", 0x001C, 0x0001);
+            request.get_length()
+        };
+        let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 105), 53));
+        let subject = ProcessorReal::new(IpAddr::from_str("2001:db8::1234").unwrap());
+
+        let rsp_length = subject.process(
+            &mut buf,
+            req_length,
+            &addr,
+            &Logger::new("aaaa_query_is_answered_with_an_ipv6_target"),
+        );
+
+        let response = PacketFacade::new(&mut buf, rsp_length);
+        assert_eq!(response.get_rcode(), Some(0x0000));
+        let answers = response.get_answers().unwrap();
+        assert_eq!(answers[0].get_name(), "ooga.com");
+        assert_eq!(answers[0].get_resource_type(), 0x001C);
+        assert_eq!(answers[0].get_resource_class(), 0x0001);
+        assert_eq!(
+            answers[0].get_rdata(),
+            &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x12, 0x34][..]
+        );
+        assert_eq!(answers.len(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "101.102.103.105:53 RQ0 (28/1/ooga.com) -> RS0 (2001:db8::1234)",
+        );
+    }
+
+    #[test]
+    fn queries_for_the_other_address_family_get_no_answers_but_no_error() {
+        let mut buf: [u8; 500] = [0; 500];
+        let req_length = {
+            let mut request = PacketFacade::new(&mut buf, 500);
+            request.set_transaction_id(0x4321);
+            request.set_query(true);
+            request.set_opcode(0x0);
+            request.add_query("ooga.com", 0x001C, 0x0001);
+            request.get_length()
+        };
+        let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
+        let subject = ProcessorReal::new(IpAddr::from_str("18.52.86.120").unwrap());
+
+        let rsp_length = subject.process(&mut buf, req_length, &addr, &Logger::new(""));
+
+        let response = PacketFacade::new(&mut buf, rsp_length);
+        assert_eq!(response.get_transaction_id(), Some(0x4321));
+        assert_eq!(response.get_rcode(), Some(0x0000));
+        assert_eq!(response.get_queries().unwrap().len(), 1);
+        assert_eq!(response.get_answers().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn https_svcb_mx_and_txt_queries_get_empty_answers_but_no_error() {
+        let subject = ProcessorReal::new(IpAddr::from_str("18.52.86.120").unwrap());
+
+        let results: Vec<(Option<u8>, Vec<(u16, Vec<u8>)>)> = vec![0x0041, 0x0040, 0x000F, 0x0010]
+            .into_iter()
+            .map(|query_type| process_single_query(&subject, query_type))
+            .collect();
+
+        assert_eq!(
+            results,
+            vec![
+                (Some(0x0), vec![]),
+                (Some(0x0), vec![]),
+                (Some(0x0), vec![]),
+                (Some(0x0), vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn any_query_gets_the_address_record_for_the_target() {
+        let ipv4_subject = ProcessorReal::new(IpAddr::from_str("18.52.86.120").unwrap());
+        let ipv6_subject = ProcessorReal::new(IpAddr::from_str("2001:db8::1234").unwrap());
+
+        let ipv4_result = process_single_query(&ipv4_subject, 0x00FF);
+        let ipv6_result = process_single_query(&ipv6_subject, 0x00FF);
+
+        assert_eq!(
+            ipv4_result,
+            (Some(0x0), vec![(0x0001, vec![0x12, 0x34, 0x56, 0x78])])
+        );
+        assert_eq!(
+            ipv6_result,
+            (
+                Some(0x0),
+                vec![(
+                    0x001C,
+                    vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x12, 0x34]
+                )]
+            )
+        );
+    }
+
+    #[test]
+    fn a_query_gets_an_empty_answer_with_an_ipv6_target() {
+        let subject = ProcessorReal::new(IpAddr::from_str("2001:db8::1234").unwrap());
+
+        let result = process_single_query(&subject, 0x0001);
+
+        assert_eq!(result, (Some(0x0), vec![]));
+    }
+
     #[test]
     fn write_log_produces_correct_text() {
         init_test_logging();
@@ -383,6 +533,39 @@ mod tests {
         TestLogHandler::new ().exists_log_containing("2345ns: 101.102.103.104:53 RQ2 (4660/9029/first, 13398/17767/second) -> RS3 (123.124.125.126, 124.125.126.127)");
     }
 
+    fn process_single_query(
+        subject: &ProcessorReal,
+        query_type: u16,
+    ) -> (Option<u8>, Vec<(u16, Vec<u8>)>) {
+        let mut buf: [u8; 500] = [0; 500];
+        let req_length = {
+            let mut request = PacketFacade::new(&mut buf, 500);
+            request.set_transaction_id(0x4321);
+            request.set_query(true);
+            request.set_opcode(0x0);
+            request.add_query("ooga.com", query_type, 0x0001);
+            request.get_length()
+        };
+        let addr = SocketAddr::from(SocketAddrV4::new(Ipv4Addr::new(101, 102, 103, 104), 53));
+
+        let rsp_length = subject.process(&mut buf, req_length, &addr, &Logger::new(""));
+
+        let response = PacketFacade::new(&mut buf, rsp_length);
+        assert_eq!(response.get_transaction_id(), Some(0x4321));
+        assert_eq!(response.get_queries().unwrap().len(), 1);
+        let answers = response
+            .get_answers()
+            .unwrap()
+            .iter()
+            .map(|answer| {
+                assert_eq!(answer.get_name(), "ooga.com");
+                assert_eq!(answer.get_resource_class(), 0x0001);
+                (answer.get_resource_type(), answer.get_rdata().to_vec())
+            })
+            .collect();
+        (response.get_rcode(), answers)
+    }
+
     fn check_format_error_message(mut buf: &mut [u8], transaction_id: u16) {
         let facade = PacketFacade::new(&mut buf, 12);
         assert_eq!(facade.get_transaction_id(), Some(transaction_id));
@@ -0,0 +1,40 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::time::Duration;
+use std::time::Instant;
+use tokio::prelude::task;
+use tokio::prelude::Async;
+use tokio::prelude::Future;
+use tokio::timer::Delay;
+
+// Timeouts are only checked when the server is polled, and nothing but a timer will poll it when a client or a DNS
+// server simply goes quiet. This arranges for the current task to be polled again just after a deadline.
+pub struct Wakeup {
+    delay_opt: Option<Delay>,
+}
+
+impl Wakeup {
+    pub fn new() -> Wakeup {
+        Wakeup { delay_opt: None }
+    }
+
+    // Replaces whatever wakeup was arranged before. Outside a Tokio runtime there's no timer, and nothing happens.
+    pub fn arrange(&mut self, deadline_opt: Option<Instant>) {
+        let deadline = match deadline_opt {
+            Some(deadline) => deadline,
+            None => {
+                self.delay_opt = None;
+                return;
+            }
+        };
+        // Deadlines are exceeded only once they've passed.
+        let wake_at = deadline + Duration::from_millis(1);
+        let mut delay = self.delay_opt.take().unwrap_or_else(|| Delay::new(wake_at));
+        if delay.deadline() != wake_at {
+            delay.reset(wake_at);
+        }
+        if let Ok(Async::Ready(())) = delay.poll() {
+            task::current().notify();
+        }
+        self.delay_opt = Some(delay);
+    }
+}
@@ -3,6 +3,7 @@
 use actix::Recipient;
 use actix::Syn;
 use live_cores_package::LiveCoresPackage;
+use payload_cell::PayloadCell;
 use std::borrow::Borrow;
 use std::net::SocketAddr;
 use std::str::FromStr;
@@ -45,8 +46,52 @@ impl ConsumingService {
             "Received IncipientCoresPackage with {}-byte payload",
             incipient_cores_package.payload.data.len()
         ));
-        let (live_package, next_node_key) =
-            LiveCoresPackage::from_incipient(incipient_cores_package, self.cryptde.borrow());
+        match incipient_cores_package.route.cell_size_opt {
+            None => self.consume_package(incipient_cores_package),
+            Some(cell_size) => self.consume_cells(incipient_cores_package, cell_size),
+        }
+    }
+
+    fn consume_cells(&self, incipient_cores_package: IncipientCoresPackage, cell_size: usize) {
+        let mut package_id_bytes = [0u8; 8];
+        self.cryptde.random(&mut package_id_bytes);
+        let package_id = package_id_bytes
+            .iter()
+            .fold(0u64, |so_far, byte| (so_far << 8) | (*byte as u64));
+        let cells =
+            match PayloadCell::split(&incipient_cores_package.payload, cell_size, package_id) {
+                Ok(cells) => cells,
+                Err(e) => {
+                    self.logger
+                        .error(format!("Couldn't split payload into cells: {:?}", e));
+                    return ();
+                }
+            };
+        self.logger.debug(format!(
+            "Split payload into {} {}-byte cells",
+            cells.len(),
+            cell_size
+        ));
+        cells.into_iter().for_each(|cell| {
+            self.consume_package(IncipientCoresPackage {
+                payload: cell,
+                ..incipient_cores_package.clone()
+            })
+        });
+    }
+
+    fn consume_package(&self, incipient_cores_package: IncipientCoresPackage) {
+        let (live_package, next_node_key) = match LiveCoresPackage::from_incipient(
+            incipient_cores_package,
+            self.cryptde.borrow(),
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                self.logger
+                    .error(format!("Couldn't make LiveCoresPackage: {:?}", e));
+                return ();
+            }
+        };
 
         let encrypted_package = match self.serialize_and_encrypt_lcp(live_package, &next_node_key) {
             Ok(p) => p,
@@ -165,6 +210,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             cryptde,
+            None,
         )
         .unwrap();
         let payload = PlainData::new(&b"abcd"[..]);
@@ -185,7 +231,9 @@ mod tests {
         dispatcher_awaiter.await_message_count(1);
         let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
         let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
-        let expected_lcp = LiveCoresPackage::from_incipient(incipient_cores_package_a, cryptde).0;
+        let expected_lcp = LiveCoresPackage::from_incipient(incipient_cores_package_a, cryptde)
+            .unwrap()
+            .0;
         let expected_lcp_ser = PlainData::new(&serde_cbor::ser::to_vec(&expected_lcp).unwrap());
         let expected_lcp_enc = cryptde.encode(&destination_key, &expected_lcp_ser).unwrap();
         assert_eq!(
@@ -209,7 +257,8 @@ mod tests {
         let payload = PlainData::new(&b"abcd"[..]);
         let incipient_cores_package = IncipientCoresPackage::new(route, payload, &destination_key);
         let incipient_cores_package_a = incipient_cores_package.clone();
-        let (lcp, _key) = LiveCoresPackage::from_incipient(incipient_cores_package_a, cryptde);
+        let (lcp, _key) =
+            LiveCoresPackage::from_incipient(incipient_cores_package_a, cryptde).unwrap();
         thread::spawn(move || {
             let system = System::new ("hopper_sends_incipient_cores_package_to_recipient_component_when_next_hop_key_is_the_same_as_the_public_key_of_this_node");
             let mut peer_actors =
@@ -227,7 +276,84 @@ mod tests {
         component_awaiter.await_message_count(1);
         let component_recording = component_recording_arc.lock().unwrap();
         let record = component_recording.get_record::<ExpiredCoresPackage>(0);
-        let expected_ecp = lcp.to_expired(cryptde);
+        let expected_ecp = lcp.to_expired(cryptde).unwrap();
         assert_eq!(*record, expected_ecp);
     }
+    #[test]
+    fn payload_on_cell_route_is_sent_to_dispatcher_in_cells_of_uniform_size() {
+        let cryptde = cryptde();
+        let dispatcher = Recorder::new();
+        let dispatcher_recording_arc = dispatcher.get_recording();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let destination_key = Key::new(&[65, 65, 65]);
+        let mut route = Route::new(
+            vec![RouteSegment::new(
+                vec![&cryptde.public_key(), &destination_key.clone()],
+                Component::Neighborhood,
+            )],
+            cryptde,
+            None,
+        )
+        .unwrap();
+        route.cell_size_opt = Some(64);
+        let incipient_cores_package =
+            IncipientCoresPackage::new(route, PlainData::new(&[7; 150]), &destination_key);
+        thread::spawn(move || {
+            let system =
+                System::new("payload_on_cell_route_is_sent_to_dispatcher_in_cells_of_uniform_size");
+            let peer_actors = make_peer_actors_from(None, Some(dispatcher), None, None, None, None);
+            let subject = Hopper::new(cryptde, false);
+            let subject_addr: Addr<Syn, Hopper> = subject.start();
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(incipient_cores_package).unwrap();
+
+            system.run();
+        });
+        dispatcher_awaiter.await_message_count(3);
+        let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
+        let lengths: Vec<usize> = (0..3)
+            .map(|index| {
+                dispatcher_recording
+                    .get_record::<TransmitDataMsg>(index)
+                    .data
+                    .len()
+            })
+            .collect();
+        assert_eq!(lengths[0], lengths[1]);
+        assert_eq!(lengths[1], lengths[2]);
+    }
+
+    #[test]
+    fn zero_hop_payload_on_cell_route_is_reassembled_before_it_reaches_recipient_component() {
+        let cryptde = cryptde();
+        let (component, component_awaiter, component_recording_arc) = make_recorder();
+        let destination_key = cryptde.public_key();
+        let mut route = zero_hop_route_response(&cryptde.public_key(), cryptde).route;
+        route.cell_size_opt = Some(64);
+        let incipient_cores_package =
+            IncipientCoresPackage::new(route, PlainData::new(&[7; 150]), &destination_key);
+        let expected_payload = incipient_cores_package.payload.clone();
+        thread::spawn(move || {
+            let system = System::new(
+                "zero_hop_payload_on_cell_route_is_reassembled_before_it_reaches_recipient_component",
+            );
+            let mut peer_actors =
+                make_peer_actors_from(None, None, None, Some(component), None, None);
+            let subject = Hopper::new(cryptde, false);
+            let subject_addr: Addr<Syn, Hopper> = subject.start();
+            let subject_subs = Hopper::make_subs_from(&subject_addr);
+            peer_actors.hopper = subject_subs;
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(incipient_cores_package).unwrap();
+
+            system.run();
+        });
+        component_awaiter.await_message_count(1);
+        let component_recording = component_recording_arc.lock().unwrap();
+        assert_eq!(component_recording.len(), 1);
+        let record = component_recording.get_record::<ExpiredCoresPackage>(0);
+        assert_eq!(record.payload, expected_payload);
+    }
 }
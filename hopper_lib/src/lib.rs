@@ -12,4 +12,5 @@ extern crate test_utils;
 pub mod consuming_service;
 pub mod hopper;
 pub mod live_cores_package;
+pub mod payload_cell;
 pub mod routing_service;
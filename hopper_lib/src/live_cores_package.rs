@@ -8,6 +8,7 @@ use sub_lib::hop::Hop;
 use sub_lib::hopper::ExpiredCoresPackage;
 use sub_lib::hopper::IncipientCoresPackage;
 use sub_lib::route::Route;
+use sub_lib::route::RouteError;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LiveCoresPackage {
@@ -23,53 +24,36 @@ impl LiveCoresPackage {
     pub fn from_incipient(
         incipient: IncipientCoresPackage,
         cryptde: &CryptDE,
-    ) -> (LiveCoresPackage, Key) {
-        // crashpoint - should discuss as a team
+    ) -> Result<(LiveCoresPackage, Key), RouteError> {
         let encrypted_payload = cryptde
             .encode(&incipient.payload_destination_key, &incipient.payload)
-            .expect("Encode error");
+            .map_err(RouteError::HopEncodingFailed)?;
         let mut route = incipient.route.clone();
-        let next_hop = match route.shift(cryptde) {
-            // crashpoint - should discuss as a team
-            None => unimplemented!("no next_hop shifted out of route"),
-            Some(h) => h,
-        };
+        let next_hop = route.shift(cryptde)?;
 
-        (
+        Ok((
             LiveCoresPackage::new(route, encrypted_payload),
             next_hop.public_key,
-        )
+        ))
     }
 
-    pub fn to_expired(self, cryptde: &CryptDE) -> ExpiredCoresPackage {
-        let payload = match cryptde.decode(&self.payload) {
-            Ok(payload) => payload,
-            // crashpoint - should discuss as a team
-            Err(e) => panic!("{:?}", e),
-        };
-        ExpiredCoresPackage::new(self.route, payload)
+    pub fn to_expired(self, cryptde: &CryptDE) -> Result<ExpiredCoresPackage, CryptdecError> {
+        let payload = cryptde.decode(&self.payload)?;
+        Ok(ExpiredCoresPackage::new(self.route, payload))
     }
 
     pub fn to_next_live(
         mut self,
         cryptde: &CryptDE,
-    ) -> Result<(Key, LiveCoresPackage), CryptdecError> {
-        let next_hop = match self.route.shift(cryptde) {
-            // crashpoint - should discuss as a team
-            None => unimplemented!(),
-            Some(h) => h,
-        };
+    ) -> Result<(Key, LiveCoresPackage), RouteError> {
+        let next_hop = self.route.shift(cryptde)?;
         let next_key = next_hop.public_key;
         let next_live = LiveCoresPackage::new(self.route, self.payload);
         Ok((next_key, next_live))
     }
 
-    pub fn next_hop(&self, cryptde: &CryptDE) -> Hop {
-        match self.route.next_hop(cryptde) {
-            // crashpoint - should discuss as a team
-            None => unimplemented!(),
-            Some(h) => h,
-        }
+    pub fn next_hop(&self, cryptde: &CryptDE) -> Result<Hop, RouteError> {
+        self.route.next_hop(cryptde)
     }
 }
 
@@ -96,6 +80,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             cryptde,
+            None,
         )
         .unwrap();
 
@@ -117,12 +102,13 @@ mod tests {
                 Component::Neighborhood,
             )],
             cryptde,
+            None,
         )
         .unwrap();
         let payload = PayloadMock::new();
         let incipient = IncipientCoresPackage::new(route.clone(), payload.clone(), &key56);
 
-        let (subject, next_stop) = LiveCoresPackage::from_incipient(incipient, cryptde);
+        let (subject, next_stop) = LiveCoresPackage::from_incipient(incipient, cryptde).unwrap();
 
         assert_eq!(next_stop, key34);
         route.shift(cryptde).unwrap();
@@ -138,6 +124,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_incipient_complains_about_empty_route() {
+        let cryptde = cryptde();
+        let incipient = IncipientCoresPackage::new(
+            Route {
+                hops: vec![],
+                cell_size_opt: None,
+            },
+            PayloadMock::new(),
+            &cryptde.public_key(),
+        );
+
+        let result = LiveCoresPackage::from_incipient(incipient, cryptde);
+
+        assert_eq!(result, Err(RouteError::EmptyRoute));
+    }
+
+    #[test]
+    fn from_incipient_complains_about_payload_it_cannot_encode() {
+        let cryptde = cryptde();
+        let incipient = IncipientCoresPackage::new(
+            make_meaningless_route(),
+            PayloadMock::new(),
+            &Key::new(b""),
+        );
+
+        let result = LiveCoresPackage::from_incipient(incipient, cryptde);
+
+        assert_eq!(
+            result,
+            Err(RouteError::HopEncodingFailed(CryptdecError::EmptyKey))
+        );
+    }
+
+    #[test]
+    fn to_next_live_complains_about_empty_route() {
+        let cryptde = cryptde();
+        let subject = LiveCoresPackage::new(
+            Route {
+                hops: vec![],
+                cell_size_opt: None,
+            },
+            CryptData::new(&[1, 2]),
+        );
+
+        let result = subject.to_next_live(cryptde);
+
+        assert_eq!(result, Err(RouteError::EmptyRoute));
+    }
+
+    #[test]
+    fn next_hop_complains_about_garbage_route() {
+        let cryptde = cryptde();
+        let subject = LiveCoresPackage::new(
+            Route {
+                hops: vec![CryptData::new(&[])],
+                cell_size_opt: None,
+            },
+            CryptData::new(&[1, 2]),
+        );
+
+        let result = subject.next_hop(cryptde);
+
+        assert_eq!(
+            result,
+            Err(RouteError::HopDecodingFailed(CryptdecError::EmptyData))
+        );
+    }
+
     #[test]
     fn live_cores_package_serialization_deserialization() {
         let original = LiveCoresPackage {
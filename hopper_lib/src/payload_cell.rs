@@ -0,0 +1,418 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use sub_lib::cryptde::PlainData;
+
+pub const CELL_HEADER_LEN: usize = 14;
+pub const MIN_CELL_SIZE: usize = 64;
+pub const MAX_CELL_SIZE: usize = 0xFFFF;
+const MAX_CELL_COUNT: usize = 0xFFFF;
+const MAX_PENDING_PACKAGES: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CellError {
+    PayloadTooLarge(usize),
+    InvalidCellSize(usize),
+    WrongCellLength(usize),
+    Malformed(String),
+}
+
+// Every cell on a route is CELL_HEADER_LEN + cell_size bytes long, no matter how much of it is payload:
+// 8 bytes of package ID, 2 of cell index, 2 of cell count and 2 of payload length, all big-endian.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PayloadCell {
+    pub package_id: u64,
+    pub index: u16,
+    pub count: u16,
+    pub data: Vec<u8>,
+}
+
+impl PayloadCell {
+    pub fn split(
+        payload: &PlainData,
+        cell_size: usize,
+        package_id: u64,
+    ) -> Result<Vec<PlainData>, CellError> {
+        let cell_length = PayloadCell::cell_length(cell_size)?;
+        let chunks: Vec<&[u8]> = if payload.data.is_empty() {
+            vec![&payload.data[..]]
+        } else {
+            payload.data.chunks(cell_size).collect()
+        };
+        if chunks.len() > MAX_CELL_COUNT {
+            return Err(CellError::PayloadTooLarge(payload.data.len()));
+        }
+        let count = chunks.len() as u16;
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                PayloadCell {
+                    package_id,
+                    index: index as u16,
+                    count,
+                    data: chunk.to_vec(),
+                }
+                .to_plain(cell_length)
+            })
+            .collect())
+    }
+
+    pub fn from_plain(plain: &PlainData, cell_size: usize) -> Result<PayloadCell, CellError> {
+        let bytes = &plain.data[..];
+        if bytes.len() != PayloadCell::cell_length(cell_size)? {
+            return Err(CellError::WrongCellLength(bytes.len()));
+        }
+        let package_id = bytes[0..8]
+            .iter()
+            .fold(0u64, |so_far, byte| (so_far << 8) | (*byte as u64));
+        let index = PayloadCell::read_u16(&bytes[8..10]);
+        let count = PayloadCell::read_u16(&bytes[10..12]);
+        let data_len = PayloadCell::read_u16(&bytes[12..14]) as usize;
+        if count == 0 || index >= count {
+            return Err(CellError::Malformed(format!(
+                "cell {} of {} is impossible",
+                index, count
+            )));
+        }
+        if data_len > cell_size {
+            return Err(CellError::Malformed(format!(
+                "{}-byte payload can't fit in {}-byte cell",
+                data_len, cell_size
+            )));
+        }
+        Ok(PayloadCell {
+            package_id,
+            index,
+            count,
+            data: bytes[CELL_HEADER_LEN..(CELL_HEADER_LEN + data_len)].to_vec(),
+        })
+    }
+
+    // The cell size comes from the route, which anybody could have written, so it mustn't be trusted to be sane.
+    pub fn check_cell_size(cell_size: usize) -> Result<(), CellError> {
+        if (cell_size >= MIN_CELL_SIZE) && (cell_size <= MAX_CELL_SIZE) {
+            Ok(())
+        } else {
+            Err(CellError::InvalidCellSize(cell_size))
+        }
+    }
+
+    fn cell_length(cell_size: usize) -> Result<usize, CellError> {
+        match CELL_HEADER_LEN.checked_add(cell_size) {
+            Some(cell_length) if cell_size > 0 => Ok(cell_length),
+            _ => Err(CellError::InvalidCellSize(cell_size)),
+        }
+    }
+
+    fn to_plain(&self, cell_length: usize) -> PlainData {
+        let mut bytes: Vec<u8> = Vec::with_capacity(cell_length);
+        (0..8)
+            .rev()
+            .for_each(|shift| bytes.push((self.package_id >> (shift * 8)) as u8));
+        PayloadCell::write_u16(&mut bytes, self.index);
+        PayloadCell::write_u16(&mut bytes, self.count);
+        PayloadCell::write_u16(&mut bytes, self.data.len() as u16);
+        bytes.extend_from_slice(&self.data[..]);
+        bytes.resize(cell_length, 0);
+        PlainData::new(&bytes[..])
+    }
+
+    fn read_u16(bytes: &[u8]) -> u16 {
+        ((bytes[0] as u16) << 8) | (bytes[1] as u16)
+    }
+
+    fn write_u16(bytes: &mut Vec<u8>, value: u16) {
+        bytes.push((value >> 8) as u8);
+        bytes.push(value as u8);
+    }
+}
+
+struct PendingPackage {
+    cells: Vec<Option<Vec<u8>>>,
+    received: usize,
+}
+
+pub struct CellAssembler {
+    pending: HashMap<u64, PendingPackage>,
+    arrival_order: VecDeque<u64>,
+}
+
+impl CellAssembler {
+    pub fn new() -> CellAssembler {
+        CellAssembler {
+            pending: HashMap::new(),
+            arrival_order: VecDeque::new(),
+        }
+    }
+
+    pub fn add(&mut self, cell: PayloadCell) -> Result<Option<PlainData>, CellError> {
+        if !self.pending.contains_key(&cell.package_id) {
+            if self.arrival_order.len() >= MAX_PENDING_PACKAGES {
+                if let Some(oldest) = self.arrival_order.pop_front() {
+                    self.pending.remove(&oldest);
+                }
+            }
+            self.pending.insert(
+                cell.package_id,
+                PendingPackage {
+                    cells: vec![None; cell.count as usize],
+                    received: 0,
+                },
+            );
+            self.arrival_order.push_back(cell.package_id);
+        }
+        let complete = {
+            let package = self
+                .pending
+                .get_mut(&cell.package_id)
+                .expect("Pending package disappeared");
+            if package.cells.len() != cell.count as usize {
+                return Err(CellError::Malformed(format!(
+                    "cell claims {} cells in a package of {}",
+                    cell.count,
+                    package.cells.len()
+                )));
+            }
+            let slot = &mut package.cells[cell.index as usize];
+            if slot.is_none() {
+                *slot = Some(cell.data);
+                package.received += 1;
+            }
+            package.received == package.cells.len()
+        };
+        if !complete {
+            return Ok(None);
+        }
+        let package_id = cell.package_id;
+        let package = self
+            .pending
+            .remove(&package_id)
+            .expect("Pending package disappeared");
+        self.arrival_order.retain(|id| *id != package_id);
+        let payload: Vec<u8> = package
+            .cells
+            .into_iter()
+            .flat_map(|data| data.expect("Incomplete package").into_iter())
+            .collect();
+        Ok(Some(PlainData::new(&payload[..])))
+    }
+
+    pub fn pending_package_count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_pads_every_cell_to_the_same_length() {
+        let payload = PlainData::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        let result = PayloadCell::split(&payload, 4, 0x0102030405060708).unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                PlainData::new(&[1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 3, 0, 4, 1, 2, 3, 4]),
+                PlainData::new(&[1, 2, 3, 4, 5, 6, 7, 8, 0, 1, 0, 3, 0, 4, 5, 6, 7, 8]),
+                PlainData::new(&[1, 2, 3, 4, 5, 6, 7, 8, 0, 2, 0, 3, 0, 2, 9, 10, 0, 0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_makes_one_cell_for_empty_payload() {
+        let result = PayloadCell::split(&PlainData::new(&[]), 4, 1).unwrap();
+
+        assert_eq!(
+            result,
+            vec![PlainData::new(&[
+                0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0
+            ])]
+        );
+    }
+
+    #[test]
+    fn split_refuses_payload_that_needs_too_many_cells() {
+        let payload = PlainData::new(&vec![0; MAX_CELL_COUNT + 1][..]);
+
+        let result = PayloadCell::split(&payload, 1, 1);
+
+        assert_eq!(result, Err(CellError::PayloadTooLarge(MAX_CELL_COUNT + 1)));
+    }
+
+    #[test]
+    fn split_and_from_plain_refuse_cell_sizes_that_make_no_sense() {
+        let payload = PlainData::new(&[1, 2, 3, 4]);
+
+        assert_eq!(
+            PayloadCell::split(&payload, 0, 1),
+            Err(CellError::InvalidCellSize(0))
+        );
+        assert_eq!(
+            PayloadCell::from_plain(&payload, 0),
+            Err(CellError::InvalidCellSize(0))
+        );
+        assert_eq!(
+            PayloadCell::from_plain(&payload, usize::max_value()),
+            Err(CellError::InvalidCellSize(usize::max_value()))
+        );
+    }
+
+    #[test]
+    fn check_cell_size_accepts_only_the_configurable_range() {
+        assert_eq!(
+            PayloadCell::check_cell_size(MIN_CELL_SIZE - 1),
+            Err(CellError::InvalidCellSize(MIN_CELL_SIZE - 1))
+        );
+        assert_eq!(PayloadCell::check_cell_size(MIN_CELL_SIZE), Ok(()));
+        assert_eq!(PayloadCell::check_cell_size(MAX_CELL_SIZE), Ok(()));
+        assert_eq!(
+            PayloadCell::check_cell_size(MAX_CELL_SIZE + 1),
+            Err(CellError::InvalidCellSize(MAX_CELL_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn from_plain_reverses_split() {
+        let payload = PlainData::new(&[1, 2, 3, 4, 5, 6]);
+        let cells = PayloadCell::split(&payload, 4, 1234).unwrap();
+
+        let result: Vec<PayloadCell> = cells
+            .iter()
+            .map(|cell| PayloadCell::from_plain(cell, 4).unwrap())
+            .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                PayloadCell {
+                    package_id: 1234,
+                    index: 0,
+                    count: 2,
+                    data: vec![1, 2, 3, 4],
+                },
+                PayloadCell {
+                    package_id: 1234,
+                    index: 1,
+                    count: 2,
+                    data: vec![5, 6],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn from_plain_rejects_cell_of_the_wrong_length() {
+        let result = PayloadCell::from_plain(&PlainData::new(&[0; 17]), 4);
+
+        assert_eq!(result, Err(CellError::WrongCellLength(17)));
+    }
+
+    #[test]
+    fn from_plain_rejects_impossible_index() {
+        let result = PayloadCell::from_plain(
+            &PlainData::new(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 2, 0, 2, 0, 0, 0, 0, 0, 0]),
+            4,
+        );
+
+        assert_eq!(
+            result,
+            Err(CellError::Malformed(String::from(
+                "cell 2 of 2 is impossible"
+            )))
+        );
+    }
+
+    #[test]
+    fn from_plain_rejects_overlong_payload_length() {
+        let result = PayloadCell::from_plain(
+            &PlainData::new(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 5, 0, 0, 0, 0]),
+            4,
+        );
+
+        assert_eq!(
+            result,
+            Err(CellError::Malformed(String::from(
+                "5-byte payload can't fit in 4-byte cell"
+            )))
+        );
+    }
+
+    #[test]
+    fn assembler_reassembles_cells_that_arrive_out_of_order() {
+        let payload = PlainData::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let cells: Vec<PayloadCell> = PayloadCell::split(&payload, 4, 99)
+            .unwrap()
+            .iter()
+            .map(|cell| PayloadCell::from_plain(cell, 4).unwrap())
+            .collect();
+        let mut subject = CellAssembler::new();
+
+        let first = subject.add(cells[2].clone()).unwrap();
+        let second = subject.add(cells[0].clone()).unwrap();
+        let duplicate = subject.add(cells[0].clone()).unwrap();
+        let third = subject.add(cells[1].clone()).unwrap();
+
+        assert_eq!(first, None);
+        assert_eq!(second, None);
+        assert_eq!(duplicate, None);
+        assert_eq!(third, Some(payload));
+        assert_eq!(subject.pending_package_count(), 0);
+    }
+
+    #[test]
+    fn assembler_rejects_cell_that_disagrees_about_cell_count() {
+        let mut subject = CellAssembler::new();
+        subject
+            .add(PayloadCell {
+                package_id: 1,
+                index: 0,
+                count: 3,
+                data: vec![1],
+            })
+            .unwrap();
+
+        let result = subject.add(PayloadCell {
+            package_id: 1,
+            index: 1,
+            count: 2,
+            data: vec![2],
+        });
+
+        assert_eq!(
+            result,
+            Err(CellError::Malformed(String::from(
+                "cell claims 2 cells in a package of 3"
+            )))
+        );
+    }
+
+    #[test]
+    fn assembler_forgets_oldest_incomplete_package_when_too_many_are_pending() {
+        let mut subject = CellAssembler::new();
+        (0..(MAX_PENDING_PACKAGES as u64 + 1)).for_each(|package_id| {
+            subject
+                .add(PayloadCell {
+                    package_id,
+                    index: 0,
+                    count: 2,
+                    data: vec![1],
+                })
+                .unwrap();
+        });
+
+        let result = subject.add(PayloadCell {
+            package_id: 0,
+            index: 1,
+            count: 2,
+            data: vec![2],
+        });
+
+        assert_eq!(result, Ok(None));
+        assert_eq!(subject.pending_package_count(), MAX_PENDING_PACKAGES);
+    }
+}
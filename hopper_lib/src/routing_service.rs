@@ -3,11 +3,18 @@
 use actix::Recipient;
 use actix::Syn;
 use live_cores_package::LiveCoresPackage;
+use payload_cell::CellAssembler;
+use payload_cell::CellError;
+use payload_cell::PayloadCell;
 use std::borrow::Borrow;
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::net::IpAddr;
+use sub_lib::accountant::ReportRoutingServiceMessage;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::CryptData;
 use sub_lib::cryptde::CryptdecError;
+use sub_lib::cryptde::Key;
 use sub_lib::cryptde::PlainData;
 use sub_lib::dispatcher::Component;
 use sub_lib::dispatcher::Endpoint;
@@ -16,7 +23,21 @@ use sub_lib::hop::Hop;
 use sub_lib::hopper::ExpiredCoresPackage;
 use sub_lib::hopper::ExpiredCoresPackagePackage;
 use sub_lib::logger::Logger;
+use sub_lib::route::RouteError;
 use sub_lib::stream_handler_pool::TransmitDataMsg;
+use sub_lib::wallet::Wallet;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoutingError {
+    UndecryptablePackage(CryptdecError),
+    UndeserializablePackage(String),
+    InvalidRoute(RouteError),
+    UnserializablePackage(String),
+    UnencryptablePackage(CryptdecError),
+    UndecryptablePayload(CryptdecError),
+    UnroutableComponent(Component),
+    InvalidCell(CellError),
+}
 
 pub struct RoutingService {
     cryptde: &'static CryptDE,
@@ -25,6 +46,9 @@ pub struct RoutingService {
     to_proxy_server: Recipient<Syn, ExpiredCoresPackage>,
     to_neighborhood: Recipient<Syn, ExpiredCoresPackagePackage>,
     to_dispatcher: Recipient<Syn, TransmitDataMsg>,
+    to_accountant: Recipient<Syn, ReportRoutingServiceMessage>,
+    cell_assembler: RefCell<CellAssembler>,
+    dropped_package_count: Cell<usize>,
     logger: Logger,
 }
 
@@ -36,6 +60,7 @@ impl RoutingService {
         to_proxy_server: Recipient<Syn, ExpiredCoresPackage>,
         to_neighborhood: Recipient<Syn, ExpiredCoresPackagePackage>,
         to_dispatcher: Recipient<Syn, TransmitDataMsg>,
+        to_accountant: Recipient<Syn, ReportRoutingServiceMessage>,
     ) -> RoutingService {
         RoutingService {
             cryptde,
@@ -44,6 +69,9 @@ impl RoutingService {
             to_proxy_server,
             to_neighborhood,
             to_dispatcher,
+            to_accountant,
+            cell_assembler: RefCell::new(CellAssembler::new()),
+            dropped_package_count: Cell::new(0),
             logger: Logger::new("RoutingService"),
         }
     }
@@ -53,22 +81,37 @@ impl RoutingService {
             "Received {} bytes of InboundClientData from Dispatcher",
             ibcd.data.len()
         ));
+        match self.try_route(ibcd) {
+            Ok(()) => (),
+            Err(e) => self.drop_package(e),
+        }
+    }
+
+    pub fn dropped_package_count(&self) -> usize {
+        self.dropped_package_count.get()
+    }
+
+    fn try_route(&self, ibcd: InboundClientData) -> Result<(), RoutingError> {
         let sender_ip = ibcd.peer_addr.ip();
         let last_data = ibcd.last_data;
-        let live_package = match self.decrypt_and_deserialize_lcp(ibcd) {
-            Ok(package) => package,
-            Err(_) => {
-                // TODO what should we do here? (nothing is unbound --so we don't need to blow up-- but we can't send this package)
-                return ();
-            }
-        };
+        let live_package = self.decrypt_and_deserialize_lcp(ibcd)?;
 
-        let next_hop = live_package.next_hop(self.cryptde.borrow());
+        let next_hop = live_package
+            .next_hop(self.cryptde.borrow())
+            .map_err(RoutingError::InvalidRoute)?;
 
         if self.should_route_data(next_hop.component) {
-            self.route_data(sender_ip, next_hop, live_package, last_data);
+            self.route_data(sender_ip, next_hop, live_package, last_data)
+        } else {
+            Ok(())
         }
-        ()
+    }
+
+    fn drop_package(&self, error: RoutingError) {
+        self.dropped_package_count
+            .set(self.dropped_package_count.get() + 1);
+        self.logger
+            .error(format!("Dropping CORES package: {:?}", error));
     }
 
     fn route_data(
@@ -77,45 +120,56 @@ impl RoutingService {
         next_hop: Hop,
         live_package: LiveCoresPackage,
         last_data: bool,
-    ) {
+    ) -> Result<(), RoutingError> {
         if next_hop.component == Component::Hopper {
-            self.route_data_externally(live_package, last_data);
+            self.route_data_externally(live_package, next_hop.consuming_wallet, last_data)
         } else {
-            self.route_data_internally(next_hop.component, sender_ip, live_package)
+            self.route_data_internally(next_hop, sender_ip, live_package)
         }
     }
 
     fn route_data_internally(
         &self,
-        component: Component,
+        next_hop: Hop,
         sender_ip: IpAddr,
         live_package: LiveCoresPackage,
-    ) {
-        match component {
-            Component::ProxyServer => {
-                self.handle_endpoint(component, &self.to_proxy_server, live_package)
-            }
-            Component::ProxyClient => {
-                self.handle_endpoint(component, &self.to_proxy_client, live_package)
-            }
-            Component::Neighborhood => {
-                self.handle_ip_endpoint(component, &self.to_neighborhood, live_package, sender_ip)
-            }
-            component => {
-                unimplemented!(
-                    "Data targets {:?}, but we don't handle that yet: log and ignore",
-                    component
-                );
-            }
+    ) -> Result<(), RoutingError> {
+        let consuming_wallet = next_hop.consuming_wallet;
+        match next_hop.component {
+            Component::ProxyServer => self.handle_endpoint(
+                Component::ProxyServer,
+                &self.to_proxy_server,
+                live_package,
+                consuming_wallet,
+            ),
+            Component::ProxyClient => self.handle_endpoint(
+                Component::ProxyClient,
+                &self.to_proxy_client,
+                live_package,
+                consuming_wallet,
+            ),
+            Component::Neighborhood => self.handle_ip_endpoint(
+                Component::Neighborhood,
+                &self.to_neighborhood,
+                live_package,
+                consuming_wallet,
+                sender_ip,
+            ),
+            component => Err(RoutingError::UnroutableComponent(component)),
         }
     }
 
-    fn route_data_externally(&self, live_package: LiveCoresPackage, last_data: bool) {
-        let transmit_msg = match self.to_transmit_data_msg(live_package, last_data) {
-            // crashpoint - need to figure out how to bubble up different kinds of errors, or just log and return
-            Err(_) => unimplemented!(),
-            Ok(m) => m,
-        };
+    fn route_data_externally(
+        &self,
+        live_package: LiveCoresPackage,
+        consuming_wallet: Option<Wallet>,
+        last_data: bool,
+    ) -> Result<(), RoutingError> {
+        let payload_size = live_package.payload.data.len();
+        let (next_key, next_live_package) = live_package
+            .to_next_live(self.cryptde.borrow())
+            .map_err(RoutingError::InvalidRoute)?;
+        let transmit_msg = self.to_transmit_data_msg(next_key, next_live_package, last_data)?;
         self.logger.debug(format!(
             "Relaying {}-byte LiveCoresPackage Dispatcher inside a TransmitDataMsg",
             transmit_msg.data.len()
@@ -123,31 +177,29 @@ impl RoutingService {
         self.to_dispatcher
             .try_send(transmit_msg)
             .expect("Dispatcher is dead");
+        if let Some(consuming_wallet) = consuming_wallet {
+            self.to_accountant
+                .try_send(ReportRoutingServiceMessage {
+                    consuming_wallet,
+                    payload_size,
+                })
+                .expect("Accountant is dead");
+        }
+        Ok(())
     }
 
     fn to_transmit_data_msg(
         &self,
-        live_package: LiveCoresPackage,
+        next_key: Key,
+        next_live_package: LiveCoresPackage,
         last_data: bool,
-    ) -> Result<TransmitDataMsg, CryptdecError> {
-        let (next_key, next_live_package) = match live_package.to_next_live(self.cryptde.borrow()) {
-            // crashpoint - log error and return None?
-            Err(_) => unimplemented!(),
-            Ok(p) => p,
-        };
-        let next_live_package_ser = match serde_cbor::ser::to_vec(&next_live_package) {
-            // crashpoint - log error and return None?
-            Err(_) => unimplemented!(),
-            Ok(p) => p,
-        };
-        let next_live_package_enc = match self
+    ) -> Result<TransmitDataMsg, RoutingError> {
+        let next_live_package_ser = serde_cbor::ser::to_vec(&next_live_package)
+            .map_err(|e| RoutingError::UnserializablePackage(format!("{}", e)))?;
+        let next_live_package_enc = self
             .cryptde
             .encode(&next_key, &PlainData::new(&next_live_package_ser[..]))
-        {
-            // crashpoint - log error and return None?
-            Err(_) => unimplemented!(),
-            Ok(p) => p,
-        };
+            .map_err(RoutingError::UnencryptablePackage)?;
         Ok(TransmitDataMsg {
             endpoint: Endpoint::Key(next_key),
             last_data,
@@ -175,15 +227,21 @@ impl RoutingService {
         component: Component,
         recipient: &Recipient<Syn, ExpiredCoresPackage>,
         live_package: LiveCoresPackage,
-    ) {
-        let expired_package = live_package.to_expired(self.cryptde.borrow());
+        consuming_wallet: Option<Wallet>,
+    ) -> Result<(), RoutingError> {
+        let expired_package =
+            match self.to_expired_and_reassembled(live_package, consuming_wallet)? {
+                Some(expired_package) => expired_package,
+                None => return Ok(()),
+            };
         self.logger.trace(format!(
             "Forwarding ExpiredCoresPackage to {:?}: {:?}",
             component, expired_package
         ));
         recipient
             .try_send(expired_package)
-            .expect(&format!("{:?} is dead", component))
+            .expect(&format!("{:?} is dead", component));
+        Ok(())
     }
 
     fn handle_ip_endpoint(
@@ -191,9 +249,14 @@ impl RoutingService {
         component: Component,
         recipient: &Recipient<Syn, ExpiredCoresPackagePackage>,
         live_package: LiveCoresPackage,
+        consuming_wallet: Option<Wallet>,
         sender_ip: IpAddr,
-    ) {
-        let expired_package = live_package.to_expired(self.cryptde.borrow());
+    ) -> Result<(), RoutingError> {
+        let expired_package =
+            match self.to_expired_and_reassembled(live_package, consuming_wallet)? {
+                Some(expired_package) => expired_package,
+                None => return Ok(()),
+            };
         let expired_package_package = ExpiredCoresPackagePackage {
             expired_cores_package: expired_package,
             sender_ip,
@@ -204,28 +267,47 @@ impl RoutingService {
         ));
         recipient
             .try_send(expired_package_package)
-            .expect(&format!("{:?} is dead", component))
+            .expect(&format!("{:?} is dead", component));
+        Ok(())
     }
 
-    fn decrypt_and_deserialize_lcp(&self, ibcd: InboundClientData) -> Result<LiveCoresPackage, ()> {
-        let decrypted_package = match self.cryptde.decode(&CryptData::new(&ibcd.data[..])) {
-            Ok(package) => package,
-            Err(e) => {
-                self.logger
-                    .error(format!("Couldn't decrypt CORES package: {:?}", e));
-                return Err(());
-            }
+    fn to_expired_and_reassembled(
+        &self,
+        live_package: LiveCoresPackage,
+        consuming_wallet: Option<Wallet>,
+    ) -> Result<Option<ExpiredCoresPackage>, RoutingError> {
+        let mut expired_package = live_package
+            .to_expired(self.cryptde.borrow())
+            .map_err(RoutingError::UndecryptablePayload)?;
+        expired_package.consuming_wallet = consuming_wallet;
+        let cell_size = match expired_package.remaining_route.cell_size_opt {
+            None => return Ok(Some(expired_package)),
+            Some(cell_size) => cell_size,
         };
-        let live_package =
-            match serde_cbor::de::from_slice::<LiveCoresPackage>(&decrypted_package.data[..]) {
-                Ok(package) => package,
-                Err(e) => {
-                    self.logger
-                        .error(format!("Couldn't deserialize CORES package: {}", e));
-                    return Err(());
-                }
-            };
-        return Ok(live_package);
+        PayloadCell::check_cell_size(cell_size).map_err(RoutingError::InvalidCell)?;
+        let cell = PayloadCell::from_plain(&expired_package.payload, cell_size)
+            .map_err(RoutingError::InvalidCell)?;
+        let payload_opt = self
+            .cell_assembler
+            .borrow_mut()
+            .add(cell)
+            .map_err(RoutingError::InvalidCell)?;
+        Ok(payload_opt.map(|payload| ExpiredCoresPackage {
+            payload,
+            ..expired_package
+        }))
+    }
+
+    fn decrypt_and_deserialize_lcp(
+        &self,
+        ibcd: InboundClientData,
+    ) -> Result<LiveCoresPackage, RoutingError> {
+        let decrypted_package = self
+            .cryptde
+            .decode(&CryptData::new(&ibcd.data[..]))
+            .map_err(RoutingError::UndecryptablePackage)?;
+        serde_cbor::de::from_slice::<LiveCoresPackage>(&decrypted_package.data[..])
+            .map_err(|e| RoutingError::UndeserializablePackage(format!("{}", e)))
     }
 }
 
@@ -238,6 +320,8 @@ mod tests {
     use actix::Arbiter;
     use actix::System;
     use hopper::Hopper;
+    use payload_cell::MAX_CELL_SIZE;
+    use payload_cell::MIN_CELL_SIZE;
     use std::net::SocketAddr;
     use std::str::FromStr;
     use std::thread;
@@ -292,7 +376,7 @@ mod tests {
         component_awaiter.await_message_count(1);
         let component_recording = component_recording_arc.lock().unwrap();
         let record = component_recording.get_record::<ExpiredCoresPackage>(0);
-        let expected_ecp = lcp_a.to_expired(cryptde);
+        let expected_ecp = lcp_a.to_expired(cryptde).unwrap();
         assert_eq!(*record, expected_ecp);
     }
 
@@ -333,7 +417,7 @@ mod tests {
         component_awaiter.await_message_count(1);
         let component_recording = component_recording_arc.lock().unwrap();
         let record = component_recording.get_record::<ExpiredCoresPackage>(0);
-        let expected_ecp = lcp_a.to_expired(cryptde);
+        let expected_ecp = lcp_a.to_expired(cryptde).unwrap();
         assert_eq!(*record, expected_ecp);
     }
 
@@ -417,6 +501,7 @@ mod tests {
                 Component::Hopper,
             )],
             cryptde,
+            None,
         )
         .unwrap();
         let payload = PlainData::new(&b"abcd"[..]);
@@ -459,9 +544,10 @@ mod tests {
                 Component::Neighborhood,
             )],
             cryptde,
+            None,
         )
         .unwrap();
-        route.shift(cryptde);
+        route.shift(cryptde).unwrap();
         let payload = PlainData::new(&b"abcd"[..]);
         let lcp = LiveCoresPackage::new(
             route,
@@ -513,9 +599,10 @@ mod tests {
                 Component::ProxyClient,
             )],
             cryptde,
+            None,
         )
         .unwrap();
-        route.shift(cryptde);
+        route.shift(cryptde).unwrap();
         let payload = PlainData::new(&b"abcd"[..]);
         let lcp = LiveCoresPackage::new(
             route,
@@ -563,6 +650,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             cryptde,
+            None,
         )
         .unwrap();
         let payload = PlainData::new(&b"abcd"[..]);
@@ -605,4 +693,349 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn reports_routing_service_to_accountant_when_relaying_package_with_consuming_wallet() {
+        let cryptde = cryptde();
+        let accountant = Recorder::new();
+        let accountant_recording_arc = accountant.get_recording();
+        let accountant_awaiter = accountant.get_awaiter();
+        let next_key = Key::new(&[65, 65, 65]);
+        let route = Route::new(
+            vec![RouteSegment::new(
+                vec![&cryptde.public_key(), &next_key],
+                Component::Neighborhood,
+            )],
+            cryptde,
+            Some(Wallet::new("consumer")),
+        )
+        .unwrap();
+        let payload = PlainData::new(&b"abcd"[..]);
+        let lcp = LiveCoresPackage::new(route, cryptde.encode(&next_key, &payload).unwrap());
+        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.data,
+        };
+        thread::spawn(move || {
+            let system = System::new(
+                "reports_routing_service_to_accountant_when_relaying_package_with_consuming_wallet",
+            );
+            let peer_actors = make_peer_actors_from(None, None, None, None, None, Some(accountant));
+            let subject = Hopper::new(cryptde, false);
+            let subject_addr: Addr<Syn, Hopper> = subject.start();
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(inbound_client_data).unwrap();
+
+            system.run();
+        });
+        accountant_awaiter.await_message_count(1);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ReportRoutingServiceMessage>(0),
+            &ReportRoutingServiceMessage {
+                consuming_wallet: Wallet::new("consumer"),
+                payload_size: lcp.payload.data.len(),
+            }
+        );
+    }
+
+    #[test]
+    fn expired_package_carries_consuming_wallet_to_proxy_client() {
+        let system = System::new("expired_package_carries_consuming_wallet_to_proxy_client");
+        let cryptde = cryptde();
+        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
+        let peer_actors = make_peer_actors_from(None, None, None, Some(proxy_client), None, None);
+        let subject = RoutingService::new(
+            cryptde,
+            false,
+            peer_actors.proxy_client.from_hopper,
+            peer_actors.proxy_server.from_hopper,
+            peer_actors.neighborhood.from_hopper,
+            peer_actors.dispatcher.from_dispatcher_client,
+            peer_actors.accountant.report_routing_service,
+        );
+        let mut route = Route::new(
+            vec![RouteSegment::new(
+                vec![&cryptde.public_key(), &cryptde.public_key()],
+                Component::ProxyClient,
+            )],
+            cryptde,
+            Some(Wallet::new("consumer")),
+        )
+        .unwrap();
+        route.shift(cryptde).unwrap();
+        let lcp = LiveCoresPackage::new(
+            route,
+            cryptde
+                .encode(&cryptde.public_key(), &PlainData::new(b"abcd"))
+                .unwrap(),
+        );
+        let serialized = serde_cbor::ser::to_vec(&lcp).unwrap();
+
+        subject.route(make_ibcd(encrypt_for(cryptde, &serialized[..])));
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+        let proxy_client_recording = proxy_client_recording_arc.lock().unwrap();
+        assert_eq!(
+            proxy_client_recording
+                .get_record::<ExpiredCoresPackage>(0)
+                .consuming_wallet,
+            Some(Wallet::new("consumer"))
+        );
+    }
+
+    #[test]
+    fn logs_and_ignores_package_with_undecodable_route() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let lcp = LiveCoresPackage::new(
+            Route {
+                hops: vec![CryptData::new(&[])],
+                cell_size_opt: None,
+            },
+            cryptde
+                .encode(&cryptde.public_key(), &PlainData::new(&b"abcd"[..]))
+                .unwrap(),
+        );
+        let data_ser = PlainData::new(&serde_cbor::ser::to_vec(&lcp).unwrap()[..]);
+        let data_enc = cryptde.encode(&cryptde.public_key(), &data_ser).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.data,
+        };
+        let system = System::new("logs_and_ignores_package_with_undecodable_route");
+        let subject = Hopper::new(cryptde, false);
+        let subject_addr: Addr<Syn, Hopper> = subject.start();
+        let peer_actors = make_peer_actors();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(inbound_client_data).unwrap();
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: RoutingService: Dropping CORES package: InvalidRoute(HopDecodingFailed(EmptyData))",
+        );
+    }
+
+    fn make_subject(cryptde: &'static CryptDE) -> RoutingService {
+        let peer_actors = make_peer_actors();
+        RoutingService::new(
+            cryptde,
+            false,
+            peer_actors.proxy_client.from_hopper,
+            peer_actors.proxy_server.from_hopper,
+            peer_actors.neighborhood.from_hopper,
+            peer_actors.dispatcher.from_dispatcher_client,
+            peer_actors.accountant.report_routing_service,
+        )
+    }
+
+    fn make_ibcd(data: Vec<u8>) -> InboundClientData {
+        InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data,
+        }
+    }
+
+    fn encrypt_for(cryptde: &CryptDE, data: &[u8]) -> Vec<u8> {
+        cryptde
+            .encode(&cryptde.public_key(), &PlainData::new(data))
+            .unwrap()
+            .data
+    }
+
+    #[test]
+    fn route_drops_package_that_cannot_be_decrypted() {
+        init_test_logging();
+        let _system = System::new("route_drops_package_that_cannot_be_decrypted");
+        let subject = make_subject(cryptde());
+
+        subject.route(make_ibcd(vec![]));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: RoutingService: Dropping CORES package: UndecryptablePackage(EmptyData)",
+        );
+    }
+
+    #[test]
+    fn route_drops_package_that_is_not_cbor() {
+        init_test_logging();
+        let _system = System::new("route_drops_package_that_is_not_cbor");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+
+        subject.route(make_ibcd(encrypt_for(
+            cryptde,
+            &[0xFF, 0xFE, 0x1C, 0x00, 0x7F],
+        )));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: RoutingService: Dropping CORES package: UndeserializablePackage(",
+        );
+    }
+
+    #[test]
+    fn route_drops_cbor_that_is_not_a_live_cores_package() {
+        init_test_logging();
+        let _system = System::new("route_drops_cbor_that_is_not_a_live_cores_package");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+        let hostile = serde_cbor::ser::to_vec(&vec!["booga", "booga", "booga"]).unwrap();
+
+        subject.route(make_ibcd(encrypt_for(cryptde, &hostile[..])));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+    }
+
+    #[test]
+    fn route_drops_truncated_live_cores_package() {
+        init_test_logging();
+        let _system = System::new("route_drops_truncated_live_cores_package");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+        let lcp = LiveCoresPackage::new(
+            route_to_proxy_client(&cryptde.public_key(), cryptde),
+            cryptde
+                .encode(&cryptde.public_key(), &PlainData::new(b"abcd"))
+                .unwrap(),
+        );
+        let serialized = serde_cbor::ser::to_vec(&lcp).unwrap();
+
+        subject.route(make_ibcd(encrypt_for(
+            cryptde,
+            &serialized[..(serialized.len() / 2)],
+        )));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+    }
+
+    #[test]
+    fn route_drops_package_with_payload_that_cannot_be_decrypted() {
+        init_test_logging();
+        let _system = System::new("route_drops_package_with_payload_that_cannot_be_decrypted");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+        let lcp = LiveCoresPackage::new(
+            route_to_proxy_client(&cryptde.public_key(), cryptde),
+            CryptData::new(&[]),
+        );
+        let serialized = serde_cbor::ser::to_vec(&lcp).unwrap();
+
+        subject.route(make_ibcd(encrypt_for(cryptde, &serialized[..])));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: RoutingService: Dropping CORES package: UndecryptablePayload(EmptyData)",
+        );
+    }
+
+    #[test]
+    fn route_drops_package_on_cell_route_whose_payload_is_not_a_cell() {
+        init_test_logging();
+        let _system = System::new("route_drops_package_on_cell_route_whose_payload_is_not_a_cell");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+        let mut route = route_to_proxy_client(&cryptde.public_key(), cryptde);
+        route.cell_size_opt = Some(64);
+        let lcp = LiveCoresPackage::new(
+            route,
+            cryptde
+                .encode(&cryptde.public_key(), &PlainData::new(b"abcd"))
+                .unwrap(),
+        );
+        let serialized = serde_cbor::ser::to_vec(&lcp).unwrap();
+
+        subject.route(make_ibcd(encrypt_for(cryptde, &serialized[..])));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: RoutingService: Dropping CORES package: InvalidCell(WrongCellLength(4))",
+        );
+    }
+
+    #[test]
+    fn route_drops_package_on_route_with_hostile_cell_size() {
+        init_test_logging();
+        let _system = System::new("route_drops_package_on_route_with_hostile_cell_size");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+        vec![0, MIN_CELL_SIZE - 1, MAX_CELL_SIZE + 1, usize::max_value()]
+            .into_iter()
+            .for_each(|cell_size| {
+                let mut route = route_to_proxy_client(&cryptde.public_key(), cryptde);
+                route.cell_size_opt = Some(cell_size);
+                let lcp = LiveCoresPackage::new(
+                    route,
+                    cryptde
+                        .encode(&cryptde.public_key(), &PlainData::new(b"abcd"))
+                        .unwrap(),
+                );
+                let serialized = serde_cbor::ser::to_vec(&lcp).unwrap();
+
+                subject.route(make_ibcd(encrypt_for(cryptde, &serialized[..])));
+            });
+
+        assert_eq!(subject.dropped_package_count(), 4);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: RoutingService: Dropping CORES package: InvalidCell(InvalidCellSize({}))",
+            usize::max_value()
+        ));
+    }
+
+    #[test]
+    fn route_drops_package_that_cannot_be_encrypted_for_next_hop() {
+        init_test_logging();
+        let _system = System::new("route_drops_package_that_cannot_be_encrypted_for_next_hop");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+        let lcp = LiveCoresPackage::new(
+            Route {
+                hops: vec![Hop::new(&Key::new(b""), Component::Hopper)
+                    .encode(&cryptde.public_key(), cryptde)
+                    .unwrap()],
+                cell_size_opt: None,
+            },
+            CryptData::new(&[1, 2, 3]),
+        );
+        let serialized = serde_cbor::ser::to_vec(&lcp).unwrap();
+
+        subject.route(make_ibcd(encrypt_for(cryptde, &serialized[..])));
+
+        assert_eq!(subject.dropped_package_count(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: RoutingService: Dropping CORES package: UnencryptablePackage(EmptyKey)",
+        );
+    }
+
+    #[test]
+    fn route_counts_every_dropped_package() {
+        let _system = System::new("route_counts_every_dropped_package");
+        let cryptde = cryptde();
+        let subject = make_subject(cryptde);
+
+        subject.route(make_ibcd(vec![]));
+        subject.route(make_ibcd(encrypt_for(cryptde, b"booga")));
+        subject.route(make_ibcd(vec![]));
+
+        assert_eq!(subject.dropped_package_count(), 3);
+    }
 }
@@ -7,6 +7,7 @@ use sub_lib::cryptde::Key;
 use sub_lib::cryptde_null::CryptDENull;
 use sub_lib::dispatcher::Component;
 use sub_lib::hopper::IncipientCoresPackage;
+use sub_lib::neighborhood::RatePack;
 use sub_lib::route::Route;
 use sub_lib::route::RouteSegment;
 use substratum_node::SubstratumNode;
@@ -36,6 +37,8 @@ impl GossipBuilder {
                 wallet: None,
                 neighbors: vec![],
                 version: 0,
+                supports_cells: false,
+                rate_pack: RatePack::default(),
             },
             cryptde: Box::new(CryptDENull::from(&node.public_key())),
         });
@@ -92,6 +95,7 @@ impl GossipBuilder {
             Route::new(
                 vec![RouteSegment::new(vec![from, to], Component::Neighborhood)],
                 &CryptDENull::from(from),
+                None,
             )
             .unwrap(),
             gossip,
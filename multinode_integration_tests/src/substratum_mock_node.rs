@@ -137,6 +137,7 @@ impl SubstratumMockNode {
                 Component::Neighborhood,
             )],
             self.cryptde(),
+            None,
         )
         .unwrap();
         let package = IncipientCoresPackage::new(route, gossip, &node.public_key());
@@ -167,7 +168,7 @@ impl SubstratumMockNode {
         target_key: &Key,
         target_addr: SocketAddr,
     ) -> Result<(), io::Error> {
-        let (lcp, _) = LiveCoresPackage::from_incipient(package, self.cryptde());
+        let (lcp, _) = LiveCoresPackage::from_incipient(package, self.cryptde()).unwrap();
         let lcp_data = serde_cbor::ser::to_vec(&lcp).unwrap();
         let encrypted_data = self
             .cryptde()
@@ -228,7 +229,7 @@ impl SubstratumMockNode {
         let masquerader = JsonMasquerader::new();
         match self.wait_for_package(&masquerader, timeout) {
             Ok((_, _, package)) => {
-                let incoming_cores_package = package.to_expired(self.cryptde());
+                let incoming_cores_package = package.to_expired(self.cryptde()).unwrap();
                 incoming_cores_package.payload::<Gossip>().ok()
             }
             Err(_) => None,
@@ -73,6 +73,8 @@ impl NodeStartupConfig {
         args.push(format!("{}", self.dns_port));
         args.push("--log_level".to_string());
         args.push("trace".to_string());
+        args.push("--cryptde".to_string());
+        args.push("null".to_string());
         args
     }
 
@@ -539,7 +541,9 @@ mod tests {
                 "--dns_port",
                 "53",
                 "--log_level",
-                "trace"
+                "trace",
+                "--cryptde",
+                "null"
             ))
         );
     }
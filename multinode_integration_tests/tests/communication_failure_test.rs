@@ -96,6 +96,7 @@ fn neighborhood_notified_of_missing_node_when_connection_refused() {
                 Component::ProxyClient,
             )],
             mock_bootstrap.cryptde(),
+            None,
         )
         .unwrap(),
         String::from("Meaningless payload"),
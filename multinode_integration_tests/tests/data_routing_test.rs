@@ -29,6 +29,7 @@ use sub_lib::cryptde_null::CryptDENull;
 use sub_lib::dispatcher::Component;
 use sub_lib::hopper::IncipientCoresPackage;
 use sub_lib::http_server_impersonator;
+use sub_lib::neighborhood::RatePack;
 use sub_lib::proxy_client::ClientResponsePayload;
 use sub_lib::proxy_server::ClientRequestPayload;
 use sub_lib::proxy_server::ProxyProtocol;
@@ -54,7 +55,7 @@ fn http_request_to_cores_package_and_cores_package_to_http_response_test() {
     let (_, _, package) = mock_bootstrap
         .wait_for_package(&masquerader, Duration::from_millis(1000))
         .unwrap();
-    let incoming_cores_package = package.to_expired(mock_bootstrap.cryptde());
+    let incoming_cores_package = package.to_expired(mock_bootstrap.cryptde()).unwrap();
     let incoming_gossip = incoming_cores_package.payload::<Gossip>().unwrap();
     let inner = NodeRecordInner {
         public_key: subject.public_key(),
@@ -63,6 +64,8 @@ fn http_request_to_cores_package_and_cores_package_to_http_response_test() {
         wallet: None,
         neighbors: vec![mock_bootstrap.public_key()],
         version: 0,
+        supports_cells: false,
+        rate_pack: RatePack::default(),
     };
     let cryptde = CryptDENull::from(&subject.public_key());
     let complete_signature = inner.generate_signature(&cryptde);
@@ -74,6 +77,8 @@ fn http_request_to_cores_package_and_cores_package_to_http_response_test() {
         wallet: None,
         neighbors: vec![mock_bootstrap.public_key()],
         version: 0,
+        supports_cells: false,
+        rate_pack: RatePack::default(),
     };
     let obscured_signature = obscured_inner.generate_signature(&cryptde);
     assert_eq!(
@@ -106,6 +111,7 @@ fn http_request_to_cores_package_and_cores_package_to_http_response_test() {
             Component::Neighborhood,
         )],
         mock_bootstrap.cryptde(),
+        None,
     )
     .unwrap();
     let outgoing_package =
@@ -135,7 +141,9 @@ fn http_request_to_cores_package_and_cores_package_to_http_response_test() {
     let (_, _, package) = mock_standard
         .wait_for_package(&masquerader, Duration::from_millis(1000))
         .unwrap();
-    let incoming_cores_package = package.to_expired(&CryptDENull::from(&ne1_noderef.public_key));
+    let incoming_cores_package = package
+        .to_expired(&CryptDENull::from(&ne1_noderef.public_key))
+        .unwrap();
     let request_payload = incoming_cores_package
         .payload::<ClientRequestPayload>()
         .unwrap();
@@ -159,6 +167,7 @@ fn http_request_to_cores_package_and_cores_package_to_http_response_test() {
             Component::ProxyServer,
         )],
         mock_standard.cryptde(),
+        None,
     )
     .unwrap();
     let response_payload = ClientResponsePayload {
@@ -227,6 +236,7 @@ fn cores_package_to_http_request_and_http_response_to_cores_package_test() {
             Component::Neighborhood,
         )],
         mock_bootstrap.cryptde(),
+        None,
     )
     .unwrap();
     let outgoing_package =
@@ -275,6 +285,7 @@ fn cores_package_to_http_request_and_http_response_to_cores_package_test() {
             ),
         ],
         mock_standard.cryptde(),
+        None,
     )
     .unwrap();
     let outgoing_package =
@@ -292,7 +303,9 @@ fn cores_package_to_http_request_and_http_response_to_cores_package_test() {
     let (_, _, package) = mock_standard
         .wait_for_package(&masquerader, Duration::from_millis(1000))
         .unwrap();
-    let incoming_cores_package = package.to_expired(&CryptDENull::from(&ne1_noderef.public_key));
+    let incoming_cores_package = package
+        .to_expired(&CryptDENull::from(&ne1_noderef.public_key))
+        .unwrap();
     let client_response_payload = incoming_cores_package
         .payload::<ClientResponsePayload>()
         .unwrap();
@@ -458,6 +471,8 @@ fn make_gossip(pairs: Vec<(&NodeReference, bool)>) -> Gossip {
             wallet: None,
             neighbors: vec![],
             version: 0,
+            supports_cells: false,
+            rate_pack: RatePack::default(),
         };
         let (complete_signature, obscured_signature) = {
             let mut nr = NodeRecord::new(
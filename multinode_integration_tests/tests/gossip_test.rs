@@ -21,6 +21,7 @@ use neighborhood_lib::neighborhood_database::NodeSignatures;
 use std::thread;
 use std::time::Duration;
 use sub_lib::cryptde_null::CryptDENull;
+use sub_lib::neighborhood::RatePack;
 use test_utils::test_utils::assert_contains;
 
 #[test]
@@ -36,7 +37,7 @@ fn when_bootstrapping_from_a_node_then_the_node_sends_gossip_upon_startup() {
     );
 
     let package = server.wait_for_package(Duration::from_millis(1000));
-    let cores_package = package.to_expired(server.cryptde());
+    let cores_package = package.to_expired(server.cryptde()).unwrap();
     let gossip: Gossip = cores_package.payload().unwrap();
     let node_ref = subject.node_reference();
     let inner = NodeRecordInner {
@@ -46,6 +47,8 @@ fn when_bootstrapping_from_a_node_then_the_node_sends_gossip_upon_startup() {
         wallet: None,
         neighbors: vec![bootstrap_node_ref.public_key.clone()],
         version: 0,
+        supports_cells: false,
+        rate_pack: RatePack::default(),
     };
     let (complete_signature, obscured_signature) = {
         let mut nr = NodeRecord::new(
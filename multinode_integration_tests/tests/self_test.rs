@@ -101,6 +101,7 @@ fn server_relays_cores_package() {
             Component::Neighborhood,
         )],
         cryptde,
+        None,
     )
     .unwrap();
     let payload = String::from("Booga booga!");
@@ -108,9 +109,9 @@ fn server_relays_cores_package() {
 
     client.transmit_package(incipient, &masquerader, cryptde.public_key());
     let package = server.wait_for_package(Duration::from_millis(1000));
-    let expired = package.to_expired(server.cryptde());
+    let expired = package.to_expired(server.cryptde()).unwrap();
 
-    route.shift(cryptde);
+    route.shift(cryptde).unwrap();
     assert_eq!(expired.remaining_route, route);
     assert_eq!(
         serde_cbor::de::from_slice::<String>(&expired.payload.data[..]).unwrap(),
@@ -133,6 +134,7 @@ fn one_mock_node_talks_to_another() {
             Component::Hopper,
         )],
         &cryptde,
+        None,
     )
     .unwrap();
     let incipient_cores_package =
@@ -150,7 +152,7 @@ fn one_mock_node_talks_to_another() {
     let (package_from, package_to, package) = mock_node_2
         .wait_for_package(&masquerader, Duration::from_millis(1000))
         .unwrap();
-    let expired_cores_package = package.to_expired(mock_node_2.cryptde());
+    let expired_cores_package = package.to_expired(mock_node_2.cryptde()).unwrap();
 
     assert_eq!(package_from.ip(), mock_node_1.ip_address());
     assert_eq!(package_to, mock_node_2.socket_addr(PortSelector::First));
@@ -0,0 +1,491 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use neighborhood_database::NeighborhoodDatabase;
+use neighborhood_database::NodeRecord;
+use neighborhood_database::NodeRecordInner;
+use neighborhood_database::NodeSignatures;
+use serde_cbor;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use sub_lib::cryptde::CryptDE;
+use sub_lib::cryptde::Key;
+
+pub const SNAPSHOT_FILENAME: &str = "neighborhood_database.cbor";
+const SNAPSHOT_VERSION: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnapshotError {
+    IoError(String),
+    Corrupt(String),
+    UnsupportedVersion(u8),
+    ForeignRoot(Key),
+}
+
+/// A Node as it was saved: its record exactly as its owner signed it, and when we last heard about it in Gossip
+/// (in seconds since the Unix epoch)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotNode {
+    pub inner: NodeRecordInner,
+    pub signatures: Option<NodeSignatures>,
+    pub last_heard: u64,
+}
+
+/// Everything a NeighborhoodDatabase knows that's worth keeping across a restart
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseSnapshot {
+    pub version: u8,
+    pub root_key: Key,
+    pub nodes: Vec<SnapshotNode>,
+}
+
+/// What happened to the Nodes in a snapshot when it was restored
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub rejected: usize,
+    pub pruned: usize,
+}
+
+impl DatabaseSnapshot {
+    pub fn take(database: &NeighborhoodDatabase) -> DatabaseSnapshot {
+        let mut keys: Vec<&Key> = database.keys().into_iter().collect();
+        keys.sort_by(|a, b| a.data.cmp(&b.data));
+        DatabaseSnapshot {
+            version: SNAPSHOT_VERSION,
+            root_key: database.root().public_key().clone(),
+            nodes: keys
+                .into_iter()
+                .map(|key| {
+                    let node_record = database
+                        .node_by_key(key)
+                        .expect("Key magically disappeared");
+                    SnapshotNode {
+                        inner: node_record.inner().clone(),
+                        signatures: node_record.signatures(),
+                        last_heard: to_epoch_secs(
+                            database.last_heard(key).unwrap_or_else(SystemTime::now),
+                        ),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    // Written to a scratch file that replaces the old snapshot only once it's complete, so that a Node
+    // that dies while saving leaves the previous snapshot intact.
+    pub fn save(&self, data_directory: &Path) -> Result<(), SnapshotError> {
+        let path = data_directory.join(SNAPSHOT_FILENAME);
+        let io_error = |e: ::std::io::Error| {
+            SnapshotError::IoError(format!("Could not write {}: {}", path.display(), e))
+        };
+        let bytes = serde_cbor::ser::to_vec(self)
+            .expect("NeighborhoodDatabase snapshot could not be serialized");
+        fs::create_dir_all(data_directory).map_err(io_error)?;
+        let scratch_path = path.with_extension("tmp");
+        {
+            let mut file = File::create(&scratch_path).map_err(io_error)?;
+            file.write_all(&bytes[..]).map_err(io_error)?;
+            file.sync_all().map_err(io_error)?;
+        }
+        fs::rename(&scratch_path, &path).map_err(io_error)
+    }
+
+    pub fn load(data_directory: &Path) -> Result<Option<DatabaseSnapshot>, SnapshotError> {
+        let path = data_directory.join(SNAPSHOT_FILENAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&path).map_err(|e| {
+            SnapshotError::IoError(format!("Could not read {}: {}", path.display(), e))
+        })?;
+        let snapshot: DatabaseSnapshot = serde_cbor::de::from_slice(&bytes[..])
+            .map_err(|e| SnapshotError::Corrupt(format!("{} is damaged: {}", path.display(), e)))?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(Some(snapshot))
+    }
+
+    // Adds the Nodes in the snapshot to a database that already contains this Node and the Nodes it was configured
+    // with. Nodes whose signatures don't verify are rejected, and Nodes that haven't been heard from since
+    // max_age before now are pruned. The root's record is this Node's own business: only its version and the
+    // neighbors that survived are taken from the snapshot, and it's re-signed with a new version so that the
+    // rest of the network believes whatever it says next.
+    pub fn restore_into(
+        self,
+        database: &mut NeighborhoodDatabase,
+        cryptde: &CryptDE,
+        max_age: Duration,
+        now: SystemTime,
+    ) -> Result<RestoreReport, SnapshotError> {
+        let root_key = database.root().public_key().clone();
+        if self.root_key != root_key {
+            return Err(SnapshotError::ForeignRoot(self.root_key));
+        }
+        let cutoff = to_epoch_secs(now).saturating_sub(max_age.as_secs());
+        let mut report = RestoreReport::default();
+        let mut root_opt: Option<NodeRecordInner> = None;
+        self.nodes.into_iter().for_each(|node| {
+            if node.inner.public_key == root_key {
+                root_opt = Some(node.inner);
+                return;
+            }
+            if node.last_heard < cutoff {
+                report.pruned += 1;
+                return;
+            }
+            let node_record = NodeRecord::from_inner(node.inner, node.signatures);
+            let public_key = node_record.public_key().clone();
+            let existing_version_opt = database.node_by_key(&public_key).map(|n| n.version());
+            if node_record.signatures().is_none() && existing_version_opt.is_some() {
+                // Never signed, because it came from our own configuration, which has already been applied
+                return;
+            }
+            if !node_record.has_valid_signatures(cryptde) {
+                report.rejected += 1;
+                return;
+            }
+            match existing_version_opt {
+                None => database
+                    .add_node(&node_record)
+                    .expect("Key magically appeared"),
+                Some(version) if version < node_record.version() => database
+                    .replace_node(&node_record)
+                    .expect("Key magically disappeared"),
+                Some(_) => (),
+            }
+            database.heard_from(&public_key, from_epoch_secs(node.last_heard));
+            report.restored += 1;
+        });
+        if let Some(root_inner) = root_opt {
+            root_inner.neighbors.iter().for_each(|neighbor| {
+                if database.node_by_key(neighbor).is_some() {
+                    database
+                        .add_neighbor(&root_key, neighbor)
+                        .expect("Node magically disappeared");
+                }
+            });
+            let root = database.root_mut();
+            if root.version() < root_inner.version {
+                root.set_version(root_inner.version);
+            }
+            root.increment_version();
+            root.sign(cryptde);
+        }
+        Ok(report)
+    }
+}
+
+fn to_epoch_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+}
+
+fn from_epoch_secs(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neighborhood_test_utils::make_genuinely_signed_node_record;
+    use neighborhood_test_utils::make_node_record;
+    use sub_lib::cryptde_null::CryptDENull;
+    use sub_lib::cryptde_real::CryptDEReal;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
+
+    const HOUR: u64 = 3600;
+
+    fn make_database(root: &NodeRecord, cryptde: &CryptDE) -> NeighborhoodDatabase {
+        NeighborhoodDatabase::new(
+            root.public_key(),
+            root.node_addr_opt().as_ref().unwrap(),
+            root.wallet(),
+            root.is_bootstrap_node(),
+            cryptde,
+        )
+    }
+
+    fn make_root() -> (NodeRecord, CryptDEReal) {
+        make_genuinely_signed_node_record(1000, true)
+    }
+
+    #[test]
+    fn loading_from_a_directory_without_a_snapshot_produces_nothing() {
+        let data_directory = ensure_node_home_directory_exists(
+            "database_snapshot",
+            "loading_from_a_directory_without_a_snapshot_produces_nothing",
+        );
+
+        let result = DatabaseSnapshot::load(&data_directory);
+
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn snapshots_survive_a_round_trip_through_the_file_system() {
+        let data_directory = ensure_node_home_directory_exists(
+            "database_snapshot",
+            "snapshots_survive_a_round_trip_through_the_file_system",
+        );
+        let (root, cryptde) = make_root();
+        let (neighbor, _) = make_genuinely_signed_node_record(2000, true);
+        let (distant, _) = make_genuinely_signed_node_record(3000, false);
+        let mut database = make_database(&root, &cryptde);
+        database.add_node(&neighbor).unwrap();
+        database.add_node(&distant).unwrap();
+        database
+            .add_neighbor(root.public_key(), neighbor.public_key())
+            .unwrap();
+        let subject = DatabaseSnapshot::take(&database);
+
+        subject.save(&data_directory).unwrap();
+        let result = DatabaseSnapshot::load(&data_directory).unwrap().unwrap();
+
+        assert_eq!(result, subject);
+        assert_eq!(result.root_key, root.public_key().clone());
+        assert_eq!(result.nodes.len(), 3);
+        assert_eq!(
+            data_directory.join("neighborhood_database.tmp").exists(),
+            false
+        );
+    }
+
+    #[test]
+    fn restoring_a_snapshot_brings_back_nodes_edges_and_versions() {
+        let (root, cryptde) = make_root();
+        let (mut neighbor, neighbor_cryptde) = make_genuinely_signed_node_record(2000, true);
+        let (mut distant, distant_cryptde) = make_genuinely_signed_node_record(3000, false);
+        neighbor.neighbors_mut().push(distant.public_key().clone());
+        neighbor.increment_version();
+        neighbor.sign(&neighbor_cryptde);
+        distant.neighbors_mut().push(neighbor.public_key().clone());
+        distant.increment_version();
+        distant.sign(&distant_cryptde);
+        let mut old_database = make_database(&root, &cryptde);
+        old_database.add_node(&neighbor).unwrap();
+        old_database.add_node(&distant).unwrap();
+        old_database
+            .add_neighbor(root.public_key(), neighbor.public_key())
+            .unwrap();
+        old_database.root_mut().set_version(5);
+        let snapshot = DatabaseSnapshot::take(&old_database);
+        let mut subject = make_database(&root, &cryptde);
+
+        let result = snapshot.restore_into(
+            &mut subject,
+            &cryptde,
+            Duration::from_secs(HOUR),
+            SystemTime::now(),
+        );
+
+        assert_eq!(
+            result,
+            Ok(RestoreReport {
+                restored: 2,
+                rejected: 0,
+                pruned: 0
+            })
+        );
+        assert_eq!(subject.node_by_key(neighbor.public_key()), Some(&neighbor));
+        assert_eq!(subject.node_by_key(distant.public_key()), Some(&distant));
+        assert_eq!(
+            subject.root().neighbors(),
+            &vec![neighbor.public_key().clone()]
+        );
+        assert_eq!(subject.root().version(), 6);
+        assert_eq!(subject.root().has_valid_signatures(&cryptde), true);
+        assert_eq!(
+            subject.node_by_ip(&neighbor.node_addr_opt().unwrap().ip_addr()),
+            Some(&neighbor)
+        );
+    }
+
+    #[test]
+    fn restoring_a_snapshot_rejects_nodes_whose_signatures_do_not_verify() {
+        let (root, cryptde) = make_root();
+        let (honest, _) = make_genuinely_signed_node_record(2000, true);
+        let (tampered, _) = make_genuinely_signed_node_record(3000, true);
+        let mut old_database = make_database(&root, &cryptde);
+        old_database.add_node(&honest).unwrap();
+        old_database.add_node(&tampered).unwrap();
+        let mut snapshot = DatabaseSnapshot::take(&old_database);
+        snapshot
+            .nodes
+            .iter_mut()
+            .filter(|node| &node.inner.public_key == tampered.public_key())
+            .for_each(|node| node.inner.rate_pack.routing_byte_rate = 1);
+        let mut subject = make_database(&root, &cryptde);
+
+        let result = snapshot.restore_into(
+            &mut subject,
+            &cryptde,
+            Duration::from_secs(HOUR),
+            SystemTime::now(),
+        );
+
+        assert_eq!(
+            result,
+            Ok(RestoreReport {
+                restored: 1,
+                rejected: 1,
+                pruned: 0
+            })
+        );
+        assert_eq!(subject.node_by_key(honest.public_key()), Some(&honest));
+        assert_eq!(subject.node_by_key(tampered.public_key()), None);
+    }
+
+    #[test]
+    fn restoring_a_snapshot_prunes_nodes_that_have_not_been_heard_from_lately() {
+        let (root, cryptde) = make_root();
+        let (fresh, _) = make_genuinely_signed_node_record(2000, true);
+        let (stale, _) = make_genuinely_signed_node_record(3000, true);
+        let now = SystemTime::now();
+        let mut old_database = make_database(&root, &cryptde);
+        old_database.add_node(&fresh).unwrap();
+        old_database.add_node(&stale).unwrap();
+        old_database
+            .add_neighbor(root.public_key(), fresh.public_key())
+            .unwrap();
+        old_database
+            .add_neighbor(root.public_key(), stale.public_key())
+            .unwrap();
+        old_database.heard_from(fresh.public_key(), now - Duration::from_secs(HOUR - 60));
+        old_database.heard_from(stale.public_key(), now - Duration::from_secs(HOUR + 60));
+        let snapshot = DatabaseSnapshot::take(&old_database);
+        let mut subject = make_database(&root, &cryptde);
+
+        let result = snapshot.restore_into(&mut subject, &cryptde, Duration::from_secs(HOUR), now);
+
+        assert_eq!(
+            result,
+            Ok(RestoreReport {
+                restored: 1,
+                rejected: 0,
+                pruned: 1
+            })
+        );
+        assert_eq!(subject.node_by_key(stale.public_key()), None);
+        assert_eq!(
+            subject.root().neighbors(),
+            &vec![fresh.public_key().clone()]
+        );
+        assert_eq!(
+            subject.last_heard(fresh.public_key()),
+            Some(from_epoch_secs(to_epoch_secs(
+                now - Duration::from_secs(HOUR - 60)
+            )))
+        );
+    }
+
+    #[test]
+    fn restoring_a_snapshot_keeps_configured_neighbors_unless_the_snapshot_has_newer_ones() {
+        let cryptde = CryptDENull::new();
+        let root = make_node_record(1000, true, false);
+        let as_configured = |n: u16| {
+            let template = make_node_record(n, true, true);
+            NodeRecord::new(
+                template.public_key(),
+                template.node_addr_opt().as_ref(),
+                None,
+                true,
+                None,
+                0,
+            )
+        };
+        let unchanged = as_configured(2000);
+        let mut updated = make_node_record(3000, true, true);
+        updated.increment_version();
+        updated.sign(&CryptDENull::from(updated.public_key()));
+        let mut old_database = make_database(&root, &cryptde);
+        old_database.add_node(&unchanged).unwrap();
+        old_database.add_node(&updated).unwrap();
+        let snapshot = DatabaseSnapshot::take(&old_database);
+        let mut subject = make_database(&root, &cryptde);
+        subject.add_node(&unchanged).unwrap();
+        subject.add_node(&as_configured(3000)).unwrap();
+
+        let result = snapshot.restore_into(
+            &mut subject,
+            &cryptde,
+            Duration::from_secs(HOUR),
+            SystemTime::now(),
+        );
+
+        assert_eq!(
+            result,
+            Ok(RestoreReport {
+                restored: 1,
+                rejected: 0,
+                pruned: 0
+            })
+        );
+        assert_eq!(
+            subject.node_by_key(unchanged.public_key()),
+            Some(&unchanged)
+        );
+        assert_eq!(subject.node_by_key(updated.public_key()), Some(&updated));
+    }
+
+    #[test]
+    fn restoring_another_nodes_snapshot_is_refused() {
+        let (root, cryptde) = make_root();
+        let (other_root, other_cryptde) = make_genuinely_signed_node_record(2000, true);
+        let snapshot = DatabaseSnapshot::take(&make_database(&other_root, &other_cryptde));
+        let mut subject = make_database(&root, &cryptde);
+
+        let result = snapshot.restore_into(
+            &mut subject,
+            &cryptde,
+            Duration::from_secs(HOUR),
+            SystemTime::now(),
+        );
+
+        assert_eq!(
+            result,
+            Err(SnapshotError::ForeignRoot(other_root.public_key().clone()))
+        );
+        assert_eq!(subject.keys().len(), 1);
+    }
+
+    #[test]
+    fn loading_a_damaged_snapshot_is_an_error() {
+        let data_directory = ensure_node_home_directory_exists(
+            "database_snapshot",
+            "loading_a_damaged_snapshot_is_an_error",
+        );
+        fs::write(data_directory.join(SNAPSHOT_FILENAME), b"not a snapshot").unwrap();
+
+        let result = DatabaseSnapshot::load(&data_directory);
+
+        match result {
+            Err(SnapshotError::Corrupt(_)) => (),
+            x => panic!("Expected Corrupt, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn loading_a_snapshot_from_the_future_is_an_error() {
+        let data_directory = ensure_node_home_directory_exists(
+            "database_snapshot",
+            "loading_a_snapshot_from_the_future_is_an_error",
+        );
+        let root = make_node_record(1000, true, false);
+        let mut snapshot = DatabaseSnapshot::take(&make_database(&root, &CryptDENull::new()));
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        snapshot.save(&data_directory).unwrap();
+
+        let result = DatabaseSnapshot::load(&data_directory);
+
+        assert_eq!(
+            result,
+            Err(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1))
+        );
+    }
+}
@@ -2,6 +2,7 @@
 use neighborhood_database::NodeRecord;
 use neighborhood_database::NodeRecordInner;
 use neighborhood_database::NodeSignatures;
+use serde_cbor;
 use std::collections::HashSet;
 use sub_lib::cryptde::Key;
 
@@ -38,6 +39,8 @@ impl GossipNodeRecord {
                 is_bootstrap_node: node_record_ref.is_bootstrap_node(),
                 neighbors: node_record_ref.neighbors().clone(),
                 version: node_record_ref.version(),
+                supports_cells: node_record_ref.supports_cells(),
+                rate_pack: *node_record_ref.rate_pack(),
             },
             // crashpoint
             signatures: node_record_ref
@@ -58,6 +61,8 @@ impl GossipNodeRecord {
         node_record
             .neighbors_mut()
             .extend(self.inner.neighbors.clone());
+        node_record.set_supports_cells(self.inner.supports_cells);
+        node_record.set_rate_pack(self.inner.rate_pack);
         node_record
     }
 
@@ -103,6 +108,43 @@ pub struct Gossip {
     pub node_records: Vec<GossipNodeRecord>,
 }
 
+// The most a CBOR array header can grow beyond its one-byte minimum for any record count we could send
+const ARRAY_HEADER_ALLOWANCE: usize = 8;
+
+impl Gossip {
+    // Splits this Gossip into as few pieces as possible that each serialize to no more than max_size bytes,
+    // keeping records whole. A record too big to fit in max_size by itself still gets a piece of its own, which
+    // its target will probably reject.
+    pub fn chunks(self, max_size: usize) -> Vec<Gossip> {
+        let overhead = serde_cbor::ser::to_vec(&Gossip {
+            node_records: vec![],
+        })
+        .expect("Serialization failure")
+        .len()
+            + ARRAY_HEADER_ALLOWANCE;
+        let mut chunks: Vec<Gossip> = vec![];
+        let mut chunk_size = 0;
+        for gnr in self.node_records {
+            let record_size = serde_cbor::ser::to_vec(&gnr)
+                .expect("Serialization failure")
+                .len();
+            if chunks.is_empty() || (chunk_size + record_size > max_size) {
+                chunks.push(Gossip {
+                    node_records: vec![],
+                });
+                chunk_size = overhead;
+            }
+            chunks
+                .last_mut()
+                .expect("Chunk magically disappeared")
+                .node_records
+                .push(gnr);
+            chunk_size += record_size;
+        }
+        chunks
+    }
+}
+
 pub fn to_dot_graph(gossip: Gossip, target: &Key, source: Key) -> String {
     let mut bootstrap_keys = vec![];
     for item in gossip.node_records.clone() {
@@ -341,4 +383,97 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    fn make_big_gossip(node_count: u16) -> Gossip {
+        (0..node_count)
+            .fold(GossipBuilder::new(), |builder, n| {
+                builder.node(&make_node_record(1000 + n, true, false), true)
+            })
+            .build()
+    }
+
+    fn serialized_size(gossip: &Gossip) -> usize {
+        serde_cbor::ser::to_vec(gossip).unwrap().len()
+    }
+
+    #[test]
+    fn empty_gossip_has_no_chunks() {
+        let subject = Gossip {
+            node_records: vec![],
+        };
+
+        let result = subject.chunks(1000);
+
+        assert_eq!(result, vec![]);
+    }
+
+    #[test]
+    fn gossip_that_fits_is_a_single_chunk() {
+        let subject = make_big_gossip(10);
+
+        let result = subject
+            .clone()
+            .chunks(serialized_size(&subject) + ARRAY_HEADER_ALLOWANCE);
+
+        assert_eq!(result, vec![subject]);
+    }
+
+    #[test]
+    fn gossip_about_a_very_large_neighborhood_is_split_into_chunks_that_fit() {
+        let subject = make_big_gossip(5000);
+        let max_size = 16384;
+
+        let result = subject.clone().chunks(max_size);
+
+        assert!(result.len() > 1, "Gossip was not split");
+        result.iter().for_each(|chunk| {
+            let size = serialized_size(chunk);
+            assert!(size <= max_size, "Chunk of {} bytes is too big", size);
+        });
+        let rejoined: Vec<GossipNodeRecord> = result
+            .into_iter()
+            .flat_map(|chunk| chunk.node_records)
+            .collect();
+        assert_eq!(rejoined, subject.node_records);
+    }
+
+    #[test]
+    fn record_too_big_for_any_chunk_gets_a_chunk_of_its_own() {
+        let small = make_node_record(1234, true, false);
+        let mut big = make_node_record(2345, true, false);
+        (0..100).for_each(|n| {
+            big.neighbors_mut().push(
+                make_node_record(3000 + n, false, false)
+                    .public_key()
+                    .clone(),
+            )
+        });
+        let another_small = make_node_record(4567, true, false);
+        let subject = GossipBuilder::new()
+            .node(&small, true)
+            .node(&big, true)
+            .node(&another_small, true)
+            .build();
+
+        let result = subject.chunks(500);
+
+        let chunk_keys: Vec<Vec<Key>> = result
+            .iter()
+            .map(|chunk| {
+                chunk
+                    .node_records
+                    .iter()
+                    .map(|gnr| gnr.inner.public_key.clone())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            chunk_keys,
+            vec![
+                vec![small.public_key().clone()],
+                vec![big.public_key().clone()],
+                vec![another_small.public_key().clone()],
+            ]
+        );
+    }
 }
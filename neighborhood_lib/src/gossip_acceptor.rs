@@ -4,8 +4,14 @@ use gossip::GossipNodeRecord;
 use neighborhood_database::NeighborhoodDatabase;
 use neighborhood_database::NeighborhoodDatabaseError;
 use neighborhood_database::NodeRecord;
+use peer_reputation::Offense;
+use peer_reputation::PeerReputation;
+use peer_reputation::BAN_DURATION_SECS;
 use std::collections::HashSet;
+use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::time::Instant;
+use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::Key;
 use sub_lib::logger::Logger;
 use sub_lib::tcp_wrappers::TcpStreamWrapperFactory;
@@ -16,12 +22,46 @@ pub trait GossipAcceptor {
     // malformed Gossip for security reasons. Do whatever's easiest. An attacker might send
     // malformed Gossip accidentally at the beginning, but he will soon learn to generate valid
     // Gossip, whereupon effort spent detecting malformed Gossip will be wasted.
-    fn handle(&self, database: &mut NeighborhoodDatabase, gossip: Gossip) -> bool;
+    fn handle(
+        &mut self,
+        database: &mut NeighborhoodDatabase,
+        gossip: Gossip,
+        sender_ip: IpAddr,
+    ) -> GossipAcceptance;
+    // Senders banned since the last call, so that the ban can be enforced before their traffic gets this far.
+    fn take_new_bans(&mut self) -> Vec<IpAddr>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GossipAcceptance {
+    // The sender is banned, over its rate limit, or sent too much; nothing in the Gossip was looked at, and nothing
+    // about it should be taken as a sign of life.
+    Refused,
+    // Only the Nodes in `verified_keys` signed their own records; the rest of the Gossip was dropped.
+    Accepted {
+        db_changed: bool,
+        verified_keys: Vec<Key>,
+    },
+}
+
+impl GossipAcceptance {
+    pub fn db_changed(&self) -> bool {
+        match self {
+            GossipAcceptance::Refused => false,
+            GossipAcceptance::Accepted { db_changed, .. } => *db_changed,
+        }
+    }
 }
 
+// Gossip about more Nodes than this is assumed to be an attempt to bloat the database.
+pub const MAX_GOSSIP_NODE_RECORDS: usize = 5000;
+
 pub struct GossipAcceptorReal {
     pub logger: Logger,
     pub tcp_stream_factory: Box<TcpStreamWrapperFactory>,
+    cryptde: &'static CryptDE,
+    rejected_record_count: usize,
+    reputation: PeerReputation,
 }
 
 impl GossipAcceptor for GossipAcceptorReal {
@@ -34,21 +74,133 @@ impl GossipAcceptor for GossipAcceptorReal {
         params:
             `database`: the DB that contains this node's known neighborhood
             `gossip`: the Gossip message with which to update the DB
+            `sender_ip`: the IP address the Gossip came from, which is held responsible for its contents
     */
-    fn handle(&self, database: &mut NeighborhoodDatabase, gossip: Gossip) -> bool {
-        let mut changed = self.handle_node_records(database, &gossip);
-        changed = self.add_ip_neighbors(database, &gossip) || changed;
-        self.logger
-            .debug(format!("Database after accepting Gossip: {:?}", database));
-        changed
+    fn handle(
+        &mut self,
+        database: &mut NeighborhoodDatabase,
+        gossip: Gossip,
+        sender_ip: IpAddr,
+    ) -> GossipAcceptance {
+        self.handle_at(database, gossip, sender_ip, Instant::now())
+    }
+
+    fn take_new_bans(&mut self) -> Vec<IpAddr> {
+        self.reputation.take_new_bans()
     }
 }
 
 impl GossipAcceptorReal {
-    pub fn new() -> GossipAcceptorReal {
+    pub fn new(cryptde: &'static CryptDE) -> GossipAcceptorReal {
         GossipAcceptorReal {
             logger: Logger::new("GossipAcceptorReal"),
             tcp_stream_factory: Box::new(TcpStreamWrapperFactoryReal {}),
+            cryptde,
+            rejected_record_count: 0,
+            reputation: PeerReputation::new(),
+        }
+    }
+
+    // Like `handle`, but as if at a particular time, so that rate limits can be tested without waiting
+    pub fn handle_at(
+        &mut self,
+        database: &mut NeighborhoodDatabase,
+        gossip: Gossip,
+        sender_ip: IpAddr,
+        now: Instant,
+    ) -> GossipAcceptance {
+        if !self.admitted(&gossip, sender_ip, now) {
+            return GossipAcceptance::Refused;
+        }
+        let gossip = self.verified(gossip, sender_ip, now);
+        let mut changed = self.handle_node_records(database, &gossip);
+        changed = self.add_ip_neighbors(database, &gossip) || changed;
+        self.logger
+            .debug(format!("Database after accepting Gossip: {:?}", database));
+        GossipAcceptance::Accepted {
+            db_changed: changed,
+            verified_keys: gossip
+                .node_records
+                .iter()
+                .map(|gnr| gnr.inner.public_key.clone())
+                .collect(),
+        }
+    }
+
+    pub fn rejected_record_count(&self) -> usize {
+        self.rejected_record_count
+    }
+
+    // Gossip from a banned sender is ignored without being looked at. Otherwise the sender has to be within its
+    // rate limit, which counts against it if not, and the Gossip has to be of a believable size. Senders don't know
+    // what size we believe, so going over it doesn't count against them.
+    fn admitted(&mut self, gossip: &Gossip, sender_ip: IpAddr, now: Instant) -> bool {
+        if self.reputation.is_banned(&sender_ip, now) {
+            self.logger
+                .debug(format!("Ignoring Gossip from banned sender {}", sender_ip));
+            false
+        } else if !self.reputation.allow_gossip(&sender_ip, now) {
+            self.logger.warning(format!(
+                "Ignoring Gossip from {}: it is sending too much too fast",
+                sender_ip
+            ));
+            self.penalize(sender_ip, Offense::RateLimitExceeded, now);
+            false
+        } else if gossip.node_records.len() > MAX_GOSSIP_NODE_RECORDS {
+            self.logger.warning(format!(
+                "Ignoring Gossip from {} about {} Nodes: no more than {} are allowed",
+                sender_ip,
+                gossip.node_records.len(),
+                MAX_GOSSIP_NODE_RECORDS
+            ));
+            false
+        } else {
+            true
+        }
+    }
+
+    // Anybody can put anything in Gossip, so a record is only believed if it was signed by the Node it describes.
+    // Both signatures are checked, except that the complete signature can't be when the NodeAddr is hidden.
+    // Records that fail never get near the database: not even their NodeAddrs are used. Neither do records
+    // that are invalid. Each invalid record counts against the sender, but a forged record only does if it's the
+    // sender's own: a Node that relays Gossip about third parties can't check their signatures any better than we can.
+    fn verified(&mut self, gossip: Gossip, sender_ip: IpAddr, now: Instant) -> Gossip {
+        let cryptde = self.cryptde;
+        let (signed, forgeries): (Vec<GossipNodeRecord>, Vec<GossipNodeRecord>) = gossip
+            .node_records
+            .into_iter()
+            .partition(|gnr| gnr.signatures.verify(cryptde, &gnr.inner));
+        forgeries.iter().for_each(|gnr| {
+            self.rejected_record_count += 1;
+            self.logger.error(format!(
+                "Rejecting GossipNodeRecord for {} with signatures that don't verify ({} rejected so far)",
+                gnr.inner.public_key, self.rejected_record_count
+            ));
+            if GossipAcceptorReal::is_senders_own(gnr, sender_ip) {
+                self.penalize(sender_ip, Offense::ForgedRecord, now);
+            }
+        });
+        let (node_records, invalids): (Vec<GossipNodeRecord>, Vec<GossipNodeRecord>) =
+            signed.into_iter().partition(|gnr| self.is_not_invalid(gnr));
+        invalids
+            .iter()
+            .for_each(|_| self.penalize(sender_ip, Offense::InvalidRecord, now));
+        Gossip { node_records }
+    }
+
+    fn is_senders_own(gnr: &GossipNodeRecord, sender_ip: IpAddr) -> bool {
+        match gnr.inner.node_addr_opt {
+            Some(ref node_addr) => node_addr.ip_addr() == sender_ip,
+            None => false,
+        }
+    }
+
+    fn penalize(&mut self, sender_ip: IpAddr, offense: Offense, now: Instant) {
+        if self.reputation.penalize(&sender_ip, offense, now) {
+            self.logger.warning(format!(
+                "Banning {} for {} seconds after {:?}",
+                sender_ip, BAN_DURATION_SECS, offense
+            ));
         }
     }
 
@@ -58,41 +210,41 @@ impl GossipAcceptorReal {
         gossip_ref: &Gossip,
     ) -> bool {
         let mut changed = false;
-        gossip_ref
-            .node_records
-            .iter()
-            .filter(|gnr_ref_ref| self.is_not_invalid(&gnr_ref_ref))
-            .for_each(|gnr_ref| {
-                changed = if database.keys().contains(&gnr_ref.inner.public_key) {
-                    let node_record = database
-                        .node_by_key_mut(&gnr_ref.inner.public_key)
-                        .expect("Key magically disappeared");
-                    let node_addr_changed = self.update_node_addrs(gnr_ref, node_record);
-                    if node_record.version() < gnr_ref.inner.version {
-                        self.update_version(gnr_ref, node_record);
-
-                        let is_bootstrap_node_changed =
-                            self.update_is_bootstrap_node(gnr_ref, node_record);
-                        let neighbors_changed = self.update_neighbors(gnr_ref, node_record);
-                        let signatures_changed = self.update_signatures(gnr_ref, node_record);
-                        let wallet_changed = self.update_wallet(gnr_ref, node_record);
-
-                        node_addr_changed
-                            || is_bootstrap_node_changed
-                            || neighbors_changed
-                            || signatures_changed
-                            || wallet_changed
-                            || changed
-                    } else {
-                        node_addr_changed || changed
-                    }
+        gossip_ref.node_records.iter().for_each(|gnr_ref| {
+            changed = if database.keys().contains(&gnr_ref.inner.public_key) {
+                let node_record = database
+                    .node_by_key_mut(&gnr_ref.inner.public_key)
+                    .expect("Key magically disappeared");
+                let node_addr_changed = self.update_node_addrs(gnr_ref, node_record);
+                if node_record.version() < gnr_ref.inner.version {
+                    self.update_version(gnr_ref, node_record);
+
+                    let is_bootstrap_node_changed =
+                        self.update_is_bootstrap_node(gnr_ref, node_record);
+                    let neighbors_changed = self.update_neighbors(gnr_ref, node_record);
+                    let signatures_changed = self.update_signatures(gnr_ref, node_record);
+                    let wallet_changed = self.update_wallet(gnr_ref, node_record);
+                    let supports_cells_changed = self.update_supports_cells(gnr_ref, node_record);
+                    let rate_pack_changed = self.update_rate_pack(gnr_ref, node_record);
+
+                    node_addr_changed
+                        || is_bootstrap_node_changed
+                        || neighbors_changed
+                        || signatures_changed
+                        || wallet_changed
+                        || supports_cells_changed
+                        || rate_pack_changed
+                        || changed
                 } else {
-                    database
-                        .add_node(&gnr_ref.to_node_record())
-                        .expect("Key magically appeared");
-                    true
+                    node_addr_changed || changed
                 }
-            });
+            } else {
+                database
+                    .add_node(&gnr_ref.to_node_record())
+                    .expect("Key magically appeared");
+                true
+            }
+        });
         changed
     }
 
@@ -120,7 +272,7 @@ impl GossipAcceptorReal {
             }
         });
         if changed {
-            database.root_mut().increment_version();
+            database.root_changed(self.cryptde);
         }
         changed
     }
@@ -191,6 +343,18 @@ impl GossipAcceptorReal {
         node_record.set_wallet(gnr_ref.inner.wallet.clone())
     }
 
+    fn update_supports_cells(
+        &self,
+        gnr_ref: &GossipNodeRecord,
+        node_record: &mut NodeRecord,
+    ) -> bool {
+        node_record.set_supports_cells(gnr_ref.inner.supports_cells)
+    }
+
+    fn update_rate_pack(&self, gnr_ref: &GossipNodeRecord, node_record: &mut NodeRecord) -> bool {
+        node_record.set_rate_pack(gnr_ref.inner.rate_pack)
+    }
+
     fn update_version(&self, gnr_ref: &GossipNodeRecord, node_record: &mut NodeRecord) {
         node_record.set_version(gnr_ref.inner.version);
     }
@@ -214,12 +378,16 @@ mod tests {
     use neighborhood_database::NodeSignatures;
     use neighborhood_test_utils::make_node_record;
     use neighborhood_test_utils::*;
+    use peer_reputation::BAN_THRESHOLD;
+    use peer_reputation::GOSSIP_BURST;
     use std::io;
     use std::net::IpAddr;
     use std::net::Ipv4Addr;
     use std::str::FromStr;
     use sub_lib::cryptde::CryptData;
     use sub_lib::cryptde::Key;
+    use sub_lib::cryptde_real::CryptDEReal;
+    use sub_lib::neighborhood::RatePack;
     use sub_lib::node_addr::NodeAddr;
     use sub_lib::wallet::Wallet;
     use test_utils::logging::init_test_logging;
@@ -230,7 +398,7 @@ mod tests {
 
     impl GossipAcceptorReal {
         fn new_for_tests(count: u32) -> GossipAcceptorReal {
-            let mut result = GossipAcceptorReal::new();
+            let mut result = GossipAcceptorReal::new(cryptde());
             let mut factory = TcpStreamWrapperFactoryMock::new();
             for _ in 0..count {
                 factory =
@@ -241,9 +409,13 @@ mod tests {
         }
     }
 
+    fn sender_ip() -> IpAddr {
+        IpAddr::from_str("9.8.7.6").unwrap()
+    }
+
     #[test]
     fn gossip_does_not_add_neighbors_that_already_exist() {
-        let subject = GossipAcceptorReal::new_for_tests(1);
+        let mut subject = GossipAcceptorReal::new_for_tests(1);
 
         let this_addr = NodeAddr::new(&IpAddr::from_str("5.7.3.4").unwrap(), &vec![13]);
         let root_key = &Key::new(b"scrud");
@@ -259,14 +431,14 @@ mod tests {
             node_records: vec![other_node_gossip],
         };
 
-        let result = subject.handle(&mut db, gossip);
+        let result = subject.handle(&mut db, gossip, sender_ip());
 
-        assert!(!result);
+        assert!(!result.db_changed());
     }
 
     #[test]
     fn gossip_does_not_add_neighbors_without_ip() {
-        let subject = GossipAcceptorReal::new_for_tests(1);
+        let mut subject = GossipAcceptorReal::new_for_tests(1);
 
         let this_addr = NodeAddr::new(&IpAddr::from_str("5.7.3.4").unwrap(), &vec![13]);
         let mut db =
@@ -279,14 +451,14 @@ mod tests {
             node_records: vec![other_node_gossip],
         };
 
-        subject.handle(&mut db, gossip);
+        subject.handle(&mut db, gossip, sender_ip());
 
         assert!(!db.has_neighbor(db.root().public_key(), other_node.public_key()));
     }
 
     #[test]
     fn gossip_does_not_add_neighbors_it_cannot_establish_a_tcp_stream_with() {
-        let mut subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
         subject.tcp_stream_factory = Box::new(
             TcpStreamWrapperFactoryMock::new().tcp_stream_wrapper(
@@ -306,7 +478,7 @@ mod tests {
             node_records: vec![other_node_gossip],
         };
 
-        subject.handle(&mut db, gossip);
+        subject.handle(&mut db, gossip, sender_ip());
 
         assert!(!db.has_neighbor(db.root().public_key(), other_node.public_key()));
     }
@@ -369,9 +541,9 @@ mod tests {
             .node(&incoming_far_right, false)
             .node(&bad_record_with_blank_key, false)
             .build();
-        let subject = GossipAcceptorReal::new_for_tests(2);
+        let mut subject = GossipAcceptorReal::new_for_tests(2);
 
-        subject.handle(&mut database, gossip);
+        subject.handle(&mut database, gossip, sender_ip());
 
         assert_eq!(
             database.keys(),
@@ -429,9 +601,9 @@ mod tests {
             .node(&not_a_neighbor_one, false)
             .node(&not_a_neighbor_two, false)
             .build();
-        let subject = GossipAcceptorReal::new_for_tests(2);
+        let mut subject = GossipAcceptorReal::new_for_tests(2);
 
-        subject.handle(&mut database, gossip);
+        subject.handle(&mut database, gossip, sender_ip());
 
         assert_eq!(
             neighbor_keys_of(&database, &existing_node),
@@ -481,9 +653,9 @@ mod tests {
             false,
         );
         let gossip = GossipBuilder::new().node(&new_node, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        subject.handle(&mut database, gossip);
+        subject.handle(&mut database, gossip, sender_ip());
 
         let existing_node_ref = database.node_by_key(existing_node.public_key()).unwrap();
         let existing_node_addr = existing_node_ref.node_addr_opt().unwrap();
@@ -510,9 +682,9 @@ mod tests {
         database.add_node(&existing_node).unwrap();
 
         let gossip = GossipBuilder::new().node(&incoming_node, true).build();
-        let subject = GossipAcceptorReal::new_for_tests(1);
+        let mut subject = GossipAcceptorReal::new_for_tests(1);
 
-        subject.handle(&mut database, gossip);
+        subject.handle(&mut database, gossip, sender_ip());
 
         let incoming_node_ref = database.node_by_key(incoming_node.public_key()).unwrap();
         let incoming_node_addr = incoming_node_ref.node_addr_opt().unwrap();
@@ -558,9 +730,9 @@ mod tests {
         let gossip = Gossip {
             node_records: vec![GossipNodeRecord::from(&invalid_record, true)],
         };
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        subject.handle(&mut database, gossip);
+        subject.handle(&mut database, gossip, sender_ip());
 
         // existing_neighbor in the database is untouched by the invalid Gossip.
         assert_eq!(
@@ -606,13 +778,13 @@ mod tests {
         signed_neighbor.sign(cryptde());
 
         let gossip = GossipBuilder::new().node(&signed_neighbor, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         let neighbor_in_db = database.node_by_key(neighbor.public_key()).unwrap();
         assert!(
-            result,
+            result.db_changed(),
             "Gossip did not result in a change to the DB as expected"
         );
         assert_eq!(neighbor_in_db.signatures(), signed_neighbor.signatures());
@@ -630,15 +802,15 @@ mod tests {
             cryptde(),
         );
         let gossip = GossipBuilder::new().node(&incoming_node, false).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         let incoming_node_ref = database.node_by_key(incoming_node.public_key()).unwrap();
         let incoming_node_addr = incoming_node_ref.node_addr_opt();
         assert!(incoming_node_addr.is_none());
         assert!(
-            result,
+            result.db_changed(),
             "Gossip did not result in a change to the DB as expected"
         )
     }
@@ -664,9 +836,9 @@ mod tests {
             .node(&this_node, true)
             .node(&existing_node_with_ip, true)
             .build();
-        let subject = GossipAcceptorReal::new_for_tests(1);
+        let mut subject = GossipAcceptorReal::new_for_tests(1);
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert!(
             database.has_neighbor(
@@ -678,7 +850,7 @@ mod tests {
             existing_node_without_ip.public_key(),
         );
         assert!(
-            result,
+            result.db_changed(),
             "Gossip did not result in a change to the DB as expected"
         )
     }
@@ -698,16 +870,16 @@ mod tests {
         );
 
         let gossip = GossipBuilder::new().node(&incoming_node, true).build();
-        let subject = GossipAcceptorReal::new_for_tests(1);
+        let mut subject = GossipAcceptorReal::new_for_tests(1);
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert_eq!(
             database.has_neighbor(this_node.public_key(), incoming_node.public_key()),
             true
         );
         assert!(
-            result,
+            result.db_changed(),
             "Gossip did not result in a change to the DB as expected"
         )
     }
@@ -727,12 +899,12 @@ mod tests {
         database.add_node(&existing_node).unwrap();
 
         let gossip = GossipBuilder::new().node(&existing_node, false).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert!(
-            !result,
+            !result.db_changed(),
             "Gossip unexpectedly resulted in a change to the DB"
         );
     }
@@ -757,9 +929,9 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&existing_node, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert!(
             database.has_neighbor(this_node.public_key(), existing_node.public_key()),
@@ -768,7 +940,7 @@ mod tests {
             existing_node.public_key(),
         );
         assert!(
-            !result,
+            !result.db_changed(),
             "Gossip unexpectedly resulted in a change to the DB"
         );
     }
@@ -792,9 +964,9 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&neighbor, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        subject.handle(&mut database, gossip);
+        subject.handle(&mut database, gossip, sender_ip());
 
         TestLogHandler::new().exists_no_log_containing(&format!("ERROR: GossipAcceptorReal: Gossip tried to modify signatures of node CQgHBg from {:?} to {:?}", neighbor.signatures().clone().unwrap(), neighbor.signatures().clone().unwrap()));
     }
@@ -814,7 +986,7 @@ mod tests {
         );
 
         let gossip = GossipBuilder::new().node(&incoming_node, true).build();
-        let subject = GossipAcceptorReal::new_for_tests(1);
+        let mut subject = GossipAcceptorReal::new_for_tests(1);
 
         assert_eq!(
             database
@@ -825,7 +997,7 @@ mod tests {
             "Initial version should be zero. Failed to set up test"
         );
 
-        let _result = subject.handle(&mut database, gossip);
+        let _result = subject.handle(&mut database, gossip, sender_ip());
 
         assert_eq!(
             database
@@ -836,6 +1008,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_ip_neighbors_re_signs_root_node_record_when_it_adds_a_neighbor() {
+        let cryptde = real_cryptde();
+        let this_node_addr = NodeAddr::new(&IpAddr::from_str("1.2.3.4").unwrap(), &vec![1234]);
+        let mut database =
+            NeighborhoodDatabase::new(&cryptde.public_key(), &this_node_addr, None, false, cryptde);
+        let incoming_node = make_node_record(2345, true, false);
+        database.add_node(&incoming_node).unwrap();
+        let gossip = GossipBuilder::new().node(&incoming_node, true).build();
+        let mut subject = GossipAcceptorReal::new(cryptde);
+        subject.tcp_stream_factory = Box::new(
+            TcpStreamWrapperFactoryMock::new()
+                .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
+        );
+
+        subject.add_ip_neighbors(&mut database, &gossip);
+
+        let root = database.root();
+        assert_eq!(root.has_neighbor(incoming_node.public_key()), true);
+        assert_eq!(root.version(), 1);
+        assert_eq!(root.has_valid_signatures(cryptde), true);
+    }
+
     #[test]
     fn handle_ignores_node_records_for_which_we_have_a_newer_version() {
         let this_node = make_node_record(1234, true, false);
@@ -861,9 +1056,9 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&older_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert!(
             database.has_neighbor(existing_node.public_key(), this_node.public_key()),
@@ -871,7 +1066,7 @@ mod tests {
             existing_node.public_key(),
             this_node.public_key()
         );
-        assert!(!result, "Gossip unexpectedly changed DB")
+        assert!(!result.db_changed(), "Gossip unexpectedly changed DB")
     }
 
     #[test]
@@ -903,12 +1098,12 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&newer_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert!(
-            !result,
+            !result.db_changed(),
             "Gossip should not have resulted in a change report"
         );
         assert_eq!(
@@ -947,16 +1142,64 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&newer_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
-        assert!(result, "Gossip did not result in a change to the database");
+        assert!(
+            result.db_changed(),
+            "Gossip did not result in a change to the database"
+        );
         let node = database.node_by_key(existing_node.public_key()).unwrap();
         assert_eq!(node.version(), newer_version.version());
         assert_eq!(node.wallet(), newer_version.wallet());
     }
 
+    #[test]
+    fn handle_updates_rate_pack_when_a_newer_version_is_received_and_returns_true() {
+        let this_node = make_node_record(1234, true, false);
+        let existing_node = make_node_record(2345, true, false);
+        let mut newer_version = existing_node.clone();
+        newer_version.set_rate_pack(RatePack {
+            routing_service_rate: 1,
+            routing_byte_rate: 2,
+            exit_service_rate: 3,
+            exit_byte_rate: 4,
+        });
+        newer_version
+            .neighbors_mut()
+            .push(this_node.public_key().clone());
+        newer_version.increment_version();
+
+        let mut database = NeighborhoodDatabase::new(
+            this_node.public_key(),
+            this_node.node_addr_opt().as_ref().unwrap(),
+            this_node.wallet(),
+            this_node.is_bootstrap_node(),
+            cryptde(),
+        );
+        database.add_node(&existing_node).unwrap();
+        database
+            .add_neighbor(this_node.public_key(), existing_node.public_key())
+            .unwrap();
+        database
+            .add_neighbor(existing_node.public_key(), this_node.public_key())
+            .unwrap();
+
+        let gossip = GossipBuilder::new().node(&newer_version, true).build();
+        let mut subject = GossipAcceptorReal::new(cryptde());
+
+        let result = subject.handle(&mut database, gossip, sender_ip());
+
+        assert!(
+            result.db_changed(),
+            "Gossip did not result in a change to the database"
+        );
+        let node = database.node_by_key(existing_node.public_key()).unwrap();
+        assert_eq!(node.version(), newer_version.version());
+        assert_eq!(node.rate_pack(), newer_version.rate_pack());
+    }
+
     #[test]
     fn handle_returns_false_when_gossip_results_in_no_change_to_an_existing_node_wallet() {
         let this_node = make_node_record(1234, true, false);
@@ -984,11 +1227,14 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&newer_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
-        assert!(!result, "Gossip resulted in a change to the database");
+        assert!(
+            !result.db_changed(),
+            "Gossip resulted in a change to the database"
+        );
         let node = database.node_by_key(existing_node.public_key()).unwrap();
         assert_eq!(node.version(), newer_version.version());
         assert_eq!(node.wallet(), newer_version.wallet());
@@ -1024,11 +1270,14 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&newer_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
-        assert!(result, "Gossip should result in a change to the database");
+        assert!(
+            result.db_changed(),
+            "Gossip should result in a change to the database"
+        );
         let node = database.node_by_key(existing_node.public_key()).unwrap();
         assert_eq!(node.version(), newer_version.version());
         assert_eq!(node.is_bootstrap_node(), newer_version.is_bootstrap_node());
@@ -1062,12 +1311,12 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&newer_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
         assert!(
-            !result,
+            !result.db_changed(),
             "Gossip should not result in a change to the database"
         );
         let node = database.node_by_key(existing_node.public_key()).unwrap();
@@ -1106,14 +1355,326 @@ mod tests {
             .unwrap();
 
         let gossip = GossipBuilder::new().node(&newer_version, true).build();
-        let subject = GossipAcceptorReal::new();
+        let mut subject = GossipAcceptorReal::new(cryptde());
 
-        let result = subject.handle(&mut database, gossip);
+        let result = subject.handle(&mut database, gossip, sender_ip());
 
-        assert!(result, "Gossip should result in a change to the database");
+        assert!(
+            result.db_changed(),
+            "Gossip should result in a change to the database"
+        );
         let node = database.node_by_key(existing_node.public_key()).unwrap();
         assert_eq!(node.version(), newer_version.version());
         assert_eq!(node.is_bootstrap_node(), newer_version.is_bootstrap_node());
         assert_eq!(node.wallet(), newer_version.wallet());
     }
+
+    fn real_cryptde() -> &'static CryptDE {
+        Box::leak(Box::new(CryptDEReal::new()))
+    }
+
+    fn make_database() -> NeighborhoodDatabase {
+        let this_node = make_node_record(1234, true, false);
+        NeighborhoodDatabase::new(
+            this_node.public_key(),
+            this_node.node_addr_opt().as_ref().unwrap(),
+            this_node.wallet(),
+            this_node.is_bootstrap_node(),
+            cryptde(),
+        )
+    }
+
+    #[test]
+    fn genuinely_signed_gossip_is_accepted() {
+        let (genuine, _) = make_genuinely_signed_node_record(2345, true);
+        let (obscured, _) = make_genuinely_signed_node_record(3456, true);
+        let gossip = TamperingGossipBuilder::new()
+            .node(&genuine, true)
+            .node(&obscured, false)
+            .build();
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(real_cryptde());
+        subject.tcp_stream_factory = Box::new(
+            TcpStreamWrapperFactoryMock::new()
+                .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
+        );
+
+        let result = subject.handle(&mut database, gossip, sender_ip());
+
+        assert_eq!(
+            result,
+            GossipAcceptance::Accepted {
+                db_changed: true,
+                verified_keys: vec![genuine.public_key().clone(), obscured.public_key().clone()],
+            }
+        );
+        assert_eq!(database.node_by_key(genuine.public_key()), Some(&genuine));
+        assert_eq!(
+            database
+                .node_by_key(obscured.public_key())
+                .unwrap()
+                .version(),
+            obscured.version()
+        );
+        assert_eq!(subject.rejected_record_count(), 0);
+    }
+
+    #[test]
+    fn gossip_records_changed_after_signing_are_rejected_and_counted() {
+        init_test_logging();
+        let (genuine, _) = make_genuinely_signed_node_record(2345, false);
+        let (bumped_version, _) = make_genuinely_signed_node_record(3456, false);
+        let (new_neighbor, _) = make_genuinely_signed_node_record(4567, false);
+        let (new_wallet, _) = make_genuinely_signed_node_record(5678, false);
+        let gossip = TamperingGossipBuilder::new()
+            .node(&genuine, false)
+            .node(&bumped_version, false)
+            .node(&new_neighbor, false)
+            .node(&new_wallet, false)
+            .tamper(bumped_version.public_key(), |inner| inner.version += 1)
+            .tamper(new_neighbor.public_key(), |inner| {
+                inner.neighbors.push(Key::new(b"sneaky"))
+            })
+            .tamper(new_wallet.public_key(), |inner| {
+                inner.wallet = Some(Wallet::new("0xdeadbeef"))
+            })
+            .build();
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(real_cryptde());
+
+        subject.handle(&mut database, gossip, sender_ip());
+
+        assert_eq!(database.node_by_key(genuine.public_key()), Some(&genuine));
+        assert_eq!(database.node_by_key(bumped_version.public_key()), None);
+        assert_eq!(database.node_by_key(new_neighbor.public_key()), None);
+        assert_eq!(database.node_by_key(new_wallet.public_key()), None);
+        assert_eq!(subject.rejected_record_count(), 3);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: GossipAcceptorReal: Rejecting GossipNodeRecord for {} with signatures that don't verify (3 rejected so far)",
+            new_wallet.public_key()
+        ));
+    }
+
+    #[test]
+    fn gossip_records_signed_by_another_node_are_rejected() {
+        let (victim, _) = make_genuinely_signed_node_record(2345, true);
+        let (_, impostor) = make_genuinely_signed_node_record(3456, true);
+        let gossip = TamperingGossipBuilder::new()
+            .node(&victim, true)
+            .tamper(victim.public_key(), |inner| inner.version += 1)
+            .sign_with(victim.public_key(), &impostor)
+            .build();
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(real_cryptde());
+
+        let result = subject.handle(&mut database, gossip, sender_ip());
+
+        assert_eq!(
+            result,
+            GossipAcceptance::Accepted {
+                db_changed: false,
+                verified_keys: vec![],
+            }
+        );
+        assert_eq!(database.node_by_key(victim.public_key()), None);
+        assert_eq!(subject.rejected_record_count(), 1);
+    }
+
+    #[test]
+    fn forged_node_addrs_are_neither_recorded_nor_connected_to() {
+        let (existing, _) = make_genuinely_signed_node_record(2345, false);
+        let (stranger, _) = make_genuinely_signed_node_record(3456, false);
+        let forged_node_addr = NodeAddr::new(&IpAddr::from_str("6.6.6.6").unwrap(), &vec![666]);
+        let gossip = TamperingGossipBuilder::new()
+            .node(&existing, false)
+            .node(&stranger, false)
+            .tamper(existing.public_key(), |inner| {
+                inner.node_addr_opt = Some(forged_node_addr.clone())
+            })
+            .tamper(stranger.public_key(), |inner| {
+                inner.node_addr_opt = Some(forged_node_addr.clone())
+            })
+            .build();
+        let mut database = make_database();
+        database.add_node(&existing).unwrap();
+        let mut subject = GossipAcceptorReal::new(real_cryptde());
+        // No connections are expected, so the mock factory has no streams to hand out
+        subject.tcp_stream_factory = Box::new(TcpStreamWrapperFactoryMock::new());
+
+        let result = subject.handle(&mut database, gossip, sender_ip());
+
+        assert_eq!(
+            result,
+            GossipAcceptance::Accepted {
+                db_changed: false,
+                verified_keys: vec![],
+            }
+        );
+        assert_eq!(
+            database
+                .node_by_key(existing.public_key())
+                .unwrap()
+                .node_addr_opt(),
+            None
+        );
+        assert_eq!(database.node_by_key(stranger.public_key()), None);
+        assert_eq!(database.node_by_ip(&forged_node_addr.ip_addr()), None);
+        assert_eq!(database.root().neighbors().len(), 0);
+        assert_eq!(subject.rejected_record_count(), 2);
+    }
+
+    #[test]
+    fn gossip_beyond_the_rate_limit_is_ignored_and_counted_against_the_sender() {
+        init_test_logging();
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(cryptde());
+        (0..GOSSIP_BURST).for_each(|_| {
+            subject.handle(
+                &mut database,
+                Gossip {
+                    node_records: vec![],
+                },
+                sender_ip(),
+            );
+        });
+        let (latecomer, _) = make_genuinely_signed_node_record(2345, false);
+        let gossip = TamperingGossipBuilder::new()
+            .node(&latecomer, false)
+            .build();
+
+        let result = subject.handle(&mut database, gossip.clone(), sender_ip());
+        let other_result =
+            subject.handle(&mut database, gossip, IpAddr::from_str("1.2.3.4").unwrap());
+
+        assert_eq!(result, GossipAcceptance::Refused);
+        assert_eq!(
+            other_result,
+            GossipAcceptance::Accepted {
+                db_changed: true,
+                verified_keys: vec![latecomer.public_key().clone()],
+            }
+        );
+        assert_eq!(
+            subject.reputation.score(&sender_ip()),
+            Offense::RateLimitExceeded.penalty()
+        );
+        TestLogHandler::new().exists_log_containing(
+            "WARN: GossipAcceptorReal: Ignoring Gossip from 9.8.7.6: it is sending too much too fast",
+        );
+    }
+
+    #[test]
+    fn oversized_gossip_is_ignored_without_counting_against_the_sender() {
+        let node_record = make_node_record(2345, false, false);
+        let gossip = Gossip {
+            node_records: vec![
+                GossipNodeRecord::from(&node_record, false);
+                MAX_GOSSIP_NODE_RECORDS + 1
+            ],
+        };
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(cryptde());
+
+        let result = subject.handle(&mut database, gossip, sender_ip());
+
+        assert_eq!(result, GossipAcceptance::Refused);
+        assert_eq!(database.node_by_key(node_record.public_key()), None);
+        assert_eq!(subject.reputation.score(&sender_ip()), 0);
+    }
+
+    #[test]
+    fn invalid_records_are_counted_against_the_sender() {
+        let mut self_neighbor = make_node_record(2345, false, false);
+        let self_key = self_neighbor.public_key().clone();
+        self_neighbor.neighbors_mut().push(self_key);
+        let gossip = GossipBuilder::new().node(&self_neighbor, false).build();
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(cryptde());
+
+        let result = subject.handle(&mut database, gossip, sender_ip());
+
+        assert_eq!(
+            result,
+            GossipAcceptance::Accepted {
+                db_changed: false,
+                verified_keys: vec![],
+            }
+        );
+        assert_eq!(database.node_by_key(self_neighbor.public_key()), None);
+        assert_eq!(
+            subject.reputation.score(&sender_ip()),
+            Offense::InvalidRecord.penalty()
+        );
+    }
+
+    #[test]
+    fn sender_of_enough_forgeries_is_banned_and_then_ignored() {
+        init_test_logging();
+        let sender_ip = IpAddr::from_str("6.6.6.6").unwrap();
+        let forgeries_to_ban = (BAN_THRESHOLD / Offense::ForgedRecord.penalty()) as u16;
+        let mut builder = TamperingGossipBuilder::new();
+        for n in 0..forgeries_to_ban {
+            let (forged, _) = make_genuinely_signed_node_record(3000 + n, false);
+            builder = builder
+                .node(&forged, false)
+                .tamper(forged.public_key(), |inner| {
+                    inner.node_addr_opt = Some(NodeAddr::new(&sender_ip, &vec![3000 + n]))
+                });
+        }
+        let (genuine, _) = make_genuinely_signed_node_record(2345, false);
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(real_cryptde());
+
+        subject.handle(&mut database, builder.build(), sender_ip);
+        let first_bans = subject.take_new_bans();
+        let result = subject.handle(
+            &mut database,
+            TamperingGossipBuilder::new().node(&genuine, false).build(),
+            sender_ip,
+        );
+
+        assert_eq!(first_bans, vec![sender_ip]);
+        assert_eq!(subject.take_new_bans(), Vec::<IpAddr>::new());
+        assert_eq!(result, GossipAcceptance::Refused);
+        assert_eq!(database.node_by_key(genuine.public_key()), None);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: GossipAcceptorReal: Banning 6.6.6.6 for {} seconds after ForgedRecord",
+            BAN_DURATION_SECS
+        ));
+    }
+
+    #[test]
+    fn relayed_forgeries_of_other_nodes_are_dropped_without_counting_against_the_sender() {
+        let sender_ip = IpAddr::from_str("6.6.6.6").unwrap();
+        let forgeries_to_ban = (BAN_THRESHOLD / Offense::ForgedRecord.penalty()) as u16;
+        let mut builder = TamperingGossipBuilder::new();
+        for n in 0..forgeries_to_ban {
+            let (forged, _) = make_genuinely_signed_node_record(3000 + n, true);
+            builder = builder
+                .node(&forged, true)
+                .tamper(forged.public_key(), |inner| inner.version += 1);
+        }
+        let (genuine, _) = make_genuinely_signed_node_record(2345, false);
+        let mut database = make_database();
+        let mut subject = GossipAcceptorReal::new(real_cryptde());
+
+        subject.handle(&mut database, builder.build(), sender_ip);
+        let bans = subject.take_new_bans();
+        let result = subject.handle(
+            &mut database,
+            TamperingGossipBuilder::new().node(&genuine, false).build(),
+            sender_ip,
+        );
+
+        assert_eq!(bans, Vec::<IpAddr>::new());
+        assert_eq!(subject.rejected_record_count(), forgeries_to_ban as usize);
+        assert_eq!(
+            result,
+            GossipAcceptance::Accepted {
+                db_changed: true,
+                verified_keys: vec![genuine.public_key().clone()],
+            }
+        );
+        assert_eq!(database.node_by_key(genuine.public_key()), Some(&genuine));
+    }
 }
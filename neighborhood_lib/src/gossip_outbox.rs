@@ -0,0 +1,179 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use gossip::Gossip;
+use peer_reputation::GOSSIP_BURST;
+use peer_reputation::GOSSIP_REFILL_MILLIS;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+use sub_lib::cryptde::Key;
+
+// A target lets each sender have GOSSIP_BURST Gossip messages at once and one more every GOSSIP_REFILL_MILLIS, and
+// counts anything faster against it. We stay well inside that, so that packages that arrive closer together than
+// they were sent don't get us in trouble.
+pub const OUTBOX_BURST: u32 = GOSSIP_BURST / 2;
+pub const OUTBOX_INTERVAL_MILLIS: u64 = GOSSIP_REFILL_MILLIS * 2;
+
+struct TargetQueue {
+    pending: VecDeque<Gossip>,
+    allowance: u32,
+    last_refill: Instant,
+}
+
+impl TargetQueue {
+    fn new(now: Instant) -> TargetQueue {
+        TargetQueue {
+            pending: VecDeque::new(),
+            allowance: OUTBOX_BURST,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now <= self.last_refill {
+            return;
+        }
+        let elapsed = now.duration_since(self.last_refill);
+        let elapsed_millis = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64;
+        let earned = elapsed_millis / OUTBOX_INTERVAL_MILLIS;
+        if earned > 0 {
+            self.allowance = (self.allowance as u64 + earned).min(OUTBOX_BURST as u64) as u32;
+            self.last_refill += Duration::from_millis(earned * OUTBOX_INTERVAL_MILLIS);
+        }
+    }
+}
+
+// Holds Gossip for each target until it can be sent without going over the target's rate limit. Gossip about a big
+// neighborhood is split into many packages, and sending them all at once would get us banned. Methods take the
+// current time as a parameter so that they can be tested without waiting.
+pub struct GossipOutbox {
+    queues: HashMap<Key, TargetQueue>,
+}
+
+impl GossipOutbox {
+    pub fn new() -> GossipOutbox {
+        GossipOutbox {
+            queues: HashMap::new(),
+        }
+    }
+
+    pub fn post(&mut self, target: &Key, packages: Vec<Gossip>, now: Instant) {
+        self.queues
+            .entry(target.clone())
+            .or_insert_with(|| TargetQueue::new(now))
+            .pending
+            .extend(packages);
+    }
+
+    // Whatever can go now, in the order it was posted for each target
+    pub fn take_sendable(&mut self, now: Instant) -> Vec<(Key, Gossip)> {
+        let mut sendable = vec![];
+        self.queues.iter_mut().for_each(|(target, queue)| {
+            queue.refill(now);
+            while queue.allowance > 0 {
+                match queue.pending.pop_front() {
+                    Some(gossip) => {
+                        queue.allowance -= 1;
+                        sendable.push((target.clone(), gossip));
+                    }
+                    None => break,
+                }
+            }
+        });
+        // A target with nothing waiting is forgotten once it has its whole allowance back.
+        self.queues
+            .retain(|_, queue| !queue.pending.is_empty() || (queue.allowance < OUTBOX_BURST));
+        sendable
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.values().all(|queue| queue.pending.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gossip::GossipBuilder;
+    use neighborhood_test_utils::make_node_record;
+
+    fn package(n: u16) -> Gossip {
+        GossipBuilder::new()
+            .node(&make_node_record(n, false, false), false)
+            .build()
+    }
+
+    fn interval(count: u64) -> Duration {
+        Duration::from_millis(OUTBOX_INTERVAL_MILLIS * count)
+    }
+
+    #[test]
+    fn a_burst_goes_at_once_and_the_rest_one_interval_at_a_time() {
+        let target = Key::new(b"target");
+        let start = Instant::now();
+        let mut subject = GossipOutbox::new();
+        let packages: Vec<Gossip> = (0..(OUTBOX_BURST as u16 + 2)).map(package).collect();
+        subject.post(&target, packages.clone(), start);
+
+        let burst = subject.take_sendable(start);
+        let too_soon = subject.take_sendable(start + interval(1) - Duration::from_millis(1));
+        let first_straggler = subject.take_sendable(start + interval(1));
+        let second_straggler = subject.take_sendable(start + interval(2));
+
+        let sent = |range: ::std::ops::Range<usize>| -> Vec<(Key, Gossip)> {
+            packages[range]
+                .iter()
+                .map(|gossip| (target.clone(), gossip.clone()))
+                .collect()
+        };
+        assert_eq!(burst, sent(0..(OUTBOX_BURST as usize)));
+        assert_eq!(too_soon, vec![]);
+        assert_eq!(
+            first_straggler,
+            sent((OUTBOX_BURST as usize)..(OUTBOX_BURST as usize + 1))
+        );
+        assert_eq!(
+            second_straggler,
+            sent((OUTBOX_BURST as usize + 1)..(OUTBOX_BURST as usize + 2))
+        );
+        assert!(subject.is_empty());
+    }
+
+    #[test]
+    fn each_target_has_an_allowance_of_its_own() {
+        let first = Key::new(b"first");
+        let second = Key::new(b"second");
+        let now = Instant::now();
+        let mut subject = GossipOutbox::new();
+        subject.post(
+            &first,
+            (0..(OUTBOX_BURST as u16)).map(package).collect(),
+            now,
+        );
+        subject.post(&second, vec![package(1234)], now);
+
+        let result = subject.take_sendable(now);
+
+        assert_eq!(result.len(), OUTBOX_BURST as usize + 1);
+        assert!(subject.is_empty());
+    }
+
+    #[test]
+    fn allowance_is_not_regained_by_being_forgotten() {
+        let target = Key::new(b"target");
+        let start = Instant::now();
+        let mut subject = GossipOutbox::new();
+        subject.post(
+            &target,
+            (0..(OUTBOX_BURST as u16)).map(package).collect(),
+            start,
+        );
+        subject.take_sendable(start);
+        subject.post(&target, vec![package(1234)], start);
+
+        let result = subject.take_sendable(start);
+
+        assert_eq!(result, vec![]);
+        assert!(!subject.is_empty());
+    }
+}
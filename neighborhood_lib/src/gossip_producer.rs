@@ -2,19 +2,58 @@
 use gossip::to_dot_graph;
 use gossip::Gossip;
 use gossip::GossipBuilder;
+use gossip::GossipNodeRecord;
 use neighborhood_database::NeighborhoodDatabase;
 use neighborhood_database::NodeRecord;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
 use sub_lib::cryptde::Key;
 use sub_lib::logger::Logger;
 
 static MINIMUM_NEIGHBORS: usize = 3;
+// Deltas are marked told when they're produced, not when they arrive, so one that goes astray would leave a hole in
+// the target's picture of the neighborhood until the records in it change again. Every so often each target gets
+// full Gossip instead, which fills any such holes.
+pub const FULL_GOSSIP_REFRESH_SECS: u64 = 600;
 
 pub trait GossipProducer {
     fn produce(&self, database: &NeighborhoodDatabase, target: &Key) -> Gossip;
+    // Like produce, but leaves out records the target has already been told about, unless it's time for the target's
+    // periodic full Gossip.
+    fn produce_delta(&mut self, database: &NeighborhoodDatabase, target: &Key) -> Gossip;
+    // Gossip from a Node shows which versions of which records it has. Returns true if the sender turns out to
+    // be behind, and should be sent full Gossip.
+    fn note_gossip_from(&mut self, sender: &Key, gossip: &Gossip) -> bool;
+    // The next Gossip to this target will be full Gossip.
+    fn forget(&mut self, target: &Key);
+}
+
+// What a target has been told about a Node
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ToldVersion {
+    version: u32,
+    node_addr_revealed: bool,
+}
+
+// What a target has been told, and when it was last told everything
+struct ToldTarget {
+    versions: HashMap<Key, ToldVersion>,
+    full_gossip_at: Instant,
+}
+
+impl ToldVersion {
+    fn of(gnr: &GossipNodeRecord) -> ToldVersion {
+        ToldVersion {
+            version: gnr.inner.version,
+            node_addr_revealed: gnr.inner.node_addr_opt.is_some(),
+        }
+    }
 }
 
 pub struct GossipProducerReal {
     logger: Logger,
+    told: HashMap<Key, ToldTarget>,
 }
 
 impl GossipProducer for GossipProducerReal {
@@ -56,13 +95,111 @@ impl GossipProducer for GossipProducerReal {
         ));
         gossip
     }
+
+    /*
+        `produce_delta`
+            produces the same Gossip as `produce`, minus the records the target has already been sent in the same
+            version with the same NodeAddr visibility. The first Gossip to a target, the first after it has been
+            forgotten, and the first once FULL_GOSSIP_REFRESH_SECS have passed since it last got full Gossip, is full
+            Gossip.
+    */
+    fn produce_delta(&mut self, database: &NeighborhoodDatabase, target: &Key) -> Gossip {
+        self.produce_delta_at(database, target, Instant::now())
+    }
+
+    // If the sender has an older version of a record than it was sent, it has lost track of what it was told
+    // (perhaps it restarted, or some Gossip went astray), and deltas would leave it with holes. If it has a
+    // newer version, there's no need to tell it about that record until it changes again.
+    fn note_gossip_from(&mut self, sender: &Key, gossip: &Gossip) -> bool {
+        let diverged = match self.told.get_mut(sender) {
+            Some(told) => gossip.node_records.iter().any(|gnr| {
+                let key = &gnr.inner.public_key;
+                match told
+                    .versions
+                    .get(key)
+                    .map(|told_version| told_version.version)
+                {
+                    Some(version) if version > gnr.inner.version => true,
+                    Some(version) if version == gnr.inner.version => false,
+                    _ => {
+                        told.versions.insert(key.clone(), ToldVersion::of(gnr));
+                        false
+                    }
+                }
+            }),
+            None => false,
+        };
+        if diverged {
+            self.logger.info(format!(
+                "{} is behind on Gossip; it will get full Gossip next time",
+                sender
+            ));
+            self.forget(sender);
+        }
+        diverged
+    }
+
+    fn forget(&mut self, target: &Key) {
+        self.told.remove(target);
+    }
 }
 
 impl GossipProducerReal {
     pub fn new() -> GossipProducerReal {
         GossipProducerReal {
             logger: Logger::new("GossipProducerReal"),
+            told: HashMap::new(),
+        }
+    }
+
+    // produce_delta, as of the given time
+    pub fn produce_delta_at(
+        &mut self,
+        database: &NeighborhoodDatabase,
+        target: &Key,
+        now: Instant,
+    ) -> Gossip {
+        let full_gossip = self.produce(database, target);
+        let refresh_due = match self.told.get(target) {
+            Some(told) => {
+                now >= told.full_gossip_at + Duration::from_secs(FULL_GOSSIP_REFRESH_SECS)
+            }
+            None => true,
+        };
+        if refresh_due {
+            self.logger.debug(format!(
+                "Sending full Gossip about {} Nodes to {}",
+                full_gossip.node_records.len(),
+                target
+            ));
+            let versions = full_gossip
+                .node_records
+                .iter()
+                .map(|gnr| (gnr.inner.public_key.clone(), ToldVersion::of(gnr)))
+                .collect();
+            self.told.insert(
+                target.clone(),
+                ToldTarget {
+                    versions,
+                    full_gossip_at: now,
+                },
+            );
+            return full_gossip;
         }
+        let told = self
+            .told
+            .get_mut(target)
+            .expect("Target magically disappeared");
+        let node_records: Vec<GossipNodeRecord> = full_gossip
+            .node_records
+            .into_iter()
+            .filter(|gnr| told.versions.get(&gnr.inner.public_key) != Some(&ToldVersion::of(gnr)))
+            .collect();
+        node_records.iter().for_each(|gnr| {
+            told.versions
+                .insert(gnr.inner.public_key.clone(), ToldVersion::of(gnr));
+        });
+        Gossip { node_records }
     }
 
     pub fn choose_introductions<'a>(
@@ -75,7 +212,7 @@ impl GossipProducerReal {
             .iter()
             .filter(|key| match database.node_by_key(key) {
                 Some(node) => !node.is_bootstrap_node(),
-                None => unimplemented!(), // we don't know this node, so we should assume it is not a bootstrap node
+                None => true, // we don't know this node, so we should assume it is not a bootstrap node
             })
             .count();
 
@@ -1049,4 +1186,173 @@ mod tests {
         TestLogHandler::new().await_log_containing("\"AQIDBA\" -> \"AgMEBQ\" [style=dashed];", 500);
         TestLogHandler::new().await_log_containing("\"AQIDBA\" -> \"BAUGBw\" [style=dashed];", 500);
     }
+
+    fn make_delta_database() -> (NeighborhoodDatabase, NodeRecord, NodeRecord) {
+        let this_node = make_node_record(1234, true, false);
+        let target = make_node_record(2345, true, false);
+        let other = make_node_record(3456, true, false);
+        let mut database = NeighborhoodDatabase::new(
+            this_node.public_key(),
+            this_node.node_addr_opt().as_ref().unwrap(),
+            this_node.wallet(),
+            this_node.is_bootstrap_node(),
+            &CryptDENull::from(this_node.public_key()),
+        );
+        database.add_node(&target).unwrap();
+        database.add_node(&other).unwrap();
+        database
+            .add_neighbor(this_node.public_key(), target.public_key())
+            .unwrap();
+        database
+            .add_neighbor(target.public_key(), this_node.public_key())
+            .unwrap();
+        (database, target, other)
+    }
+
+    fn keys_of(gossip: &Gossip) -> Vec<Key> {
+        gossip
+            .node_records
+            .iter()
+            .map(|gnr| gnr.inner.public_key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn first_delta_is_full_gossip_and_then_only_changes_are_sent() {
+        let (mut database, target, other) = make_delta_database();
+        let mut subject = GossipProducerReal::new();
+        let full = subject.produce(&database, target.public_key());
+
+        let first = subject.produce_delta(&database, target.public_key());
+        let second = subject.produce_delta(&database, target.public_key());
+        database
+            .node_by_key_mut(other.public_key())
+            .unwrap()
+            .increment_version();
+        let third = subject.produce_delta(&database, target.public_key());
+
+        assert_eq!(
+            vec_to_set(first.node_records),
+            vec_to_set(full.node_records)
+        );
+        assert_eq!(second.node_records, vec![]);
+        assert_eq!(keys_of(&third), vec![other.public_key().clone()]);
+    }
+
+    #[test]
+    fn delta_includes_records_whose_node_addrs_are_newly_revealed() {
+        let (mut database, target, other) = make_delta_database();
+        let mut subject = GossipProducerReal::new();
+        subject.produce_delta(&database, target.public_key());
+        database
+            .add_neighbor(target.public_key(), other.public_key())
+            .unwrap();
+
+        let result = subject.produce_delta(&database, target.public_key());
+
+        let other_gnr = result
+            .node_records
+            .iter()
+            .find(|gnr| &gnr.inner.public_key == other.public_key())
+            .expect("Newly revealed Node missing from delta");
+        assert_eq!(other_gnr.inner.node_addr_opt, other.node_addr_opt().clone());
+    }
+
+    #[test]
+    fn gossip_showing_a_newer_version_than_was_told_suppresses_that_record() {
+        let (mut database, target, other) = make_delta_database();
+        let mut subject = GossipProducerReal::new();
+        subject.produce_delta(&database, target.public_key());
+        database
+            .node_by_key_mut(other.public_key())
+            .unwrap()
+            .increment_version();
+        let news = GossipBuilder::new()
+            .node(database.node_by_key(other.public_key()).unwrap(), false)
+            .build();
+
+        let behind = subject.note_gossip_from(target.public_key(), &news);
+        let result = subject.produce_delta(&database, target.public_key());
+
+        assert_eq!(behind, false);
+        assert_eq!(result.node_records, vec![]);
+    }
+
+    #[test]
+    fn gossip_showing_an_older_version_than_was_told_brings_on_full_gossip() {
+        init_test_logging();
+        let (mut database, target, other) = make_delta_database();
+        let mut subject = GossipProducerReal::new();
+        database
+            .node_by_key_mut(other.public_key())
+            .unwrap()
+            .increment_version();
+        let full = subject.produce_delta(&database, target.public_key());
+        let stale = GossipBuilder::new().node(&other, false).build();
+
+        let behind = subject.note_gossip_from(target.public_key(), &stale);
+        let result = subject.produce_delta(&database, target.public_key());
+
+        assert_eq!(behind, true);
+        assert_eq!(
+            vec_to_set(result.node_records),
+            vec_to_set(full.node_records)
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: GossipProducerReal: {} is behind on Gossip; it will get full Gossip next time",
+            target.public_key()
+        ));
+    }
+
+    #[test]
+    fn delta_that_went_astray_is_made_up_for_by_the_next_full_gossip() {
+        let (mut database, target, other) = make_delta_database();
+        let mut subject = GossipProducerReal::new();
+        let start = Instant::now();
+        subject.produce_delta_at(&database, target.public_key(), start);
+        database
+            .node_by_key_mut(other.public_key())
+            .unwrap()
+            .increment_version();
+        let lost = subject.produce_delta_at(&database, target.public_key(), start);
+        let refresh_time = start + Duration::from_secs(FULL_GOSSIP_REFRESH_SECS);
+
+        let too_soon = subject.produce_delta_at(
+            &database,
+            target.public_key(),
+            refresh_time - Duration::from_secs(1),
+        );
+        let refresh = subject.produce_delta_at(&database, target.public_key(), refresh_time);
+        let after_refresh = subject.produce_delta_at(&database, target.public_key(), refresh_time);
+
+        assert_eq!(keys_of(&lost), vec![other.public_key().clone()]);
+        assert_eq!(too_soon.node_records, vec![]);
+        let full = subject.produce(&database, target.public_key());
+        assert_eq!(
+            vec_to_set(refresh.node_records.clone()),
+            vec_to_set(full.node_records)
+        );
+        let refreshed_other = refresh
+            .node_records
+            .iter()
+            .find(|gnr| &gnr.inner.public_key == other.public_key())
+            .expect("Refresh left out the record whose delta went astray");
+        assert_eq!(refreshed_other.inner.version, other.version() + 1);
+        assert_eq!(after_refresh.node_records, vec![]);
+    }
+
+    #[test]
+    fn forgotten_target_gets_full_gossip() {
+        let (database, target, _) = make_delta_database();
+        let mut subject = GossipProducerReal::new();
+        let full = subject.produce_delta(&database, target.public_key());
+
+        subject.forget(target.public_key());
+        let result = subject.produce_delta(&database, target.public_key());
+
+        assert_eq!(
+            vec_to_set(result.node_records),
+            vec_to_set(full.node_records)
+        );
+    }
 }
@@ -13,11 +13,16 @@ extern crate sha1;
 #[cfg(test)]
 extern crate test_utils;
 
+pub mod database_snapshot;
 pub mod gossip;
 pub mod gossip_acceptor;
+pub mod gossip_outbox;
 pub mod gossip_producer;
+pub mod liveness;
 pub mod neighborhood;
 pub mod neighborhood_database;
+pub mod peer_reputation;
+pub mod route_scorer;
 
 #[cfg(test)]
 mod neighborhood_test_utils;
@@ -0,0 +1,153 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::time::Duration;
+use sub_lib::cryptde::Key;
+use sub_lib::node_addr::NodeAddr;
+
+pub const PROBE_INTERVAL_SECS: u64 = 60;
+pub const PROBE_TIMEOUT_SECS: u64 = 5;
+// A neighbor that misses this many probes in a row is considered dead
+pub const MAX_MISSED_PROBES: u32 = 3;
+
+pub trait NeighborProber: Send {
+    fn probe(&self, node_addr: &NodeAddr) -> bool;
+    fn dup(&self) -> Box<NeighborProber>;
+}
+
+// A neighbor is alive if it accepts a TCP connection on any of its clandestine ports.
+pub struct NeighborProberReal {
+    timeout: Duration,
+}
+
+impl NeighborProber for NeighborProberReal {
+    fn probe(&self, node_addr: &NodeAddr) -> bool {
+        let socket_addrs: Vec<SocketAddr> = node_addr.clone().into();
+        socket_addrs
+            .iter()
+            .any(|socket_addr| TcpStream::connect_timeout(socket_addr, self.timeout).is_ok())
+    }
+
+    fn dup(&self) -> Box<NeighborProber> {
+        Box::new(NeighborProberReal {
+            timeout: self.timeout,
+        })
+    }
+}
+
+impl NeighborProberReal {
+    pub fn new() -> NeighborProberReal {
+        NeighborProberReal {
+            timeout: Duration::from_secs(PROBE_TIMEOUT_SECS),
+        }
+    }
+}
+
+// Keeps track of how many probes in a row each neighbor has missed, and which neighbors have been given up for
+// dead. A dead neighbor comes back to life as soon as it's seen again.
+pub struct LivenessTracker {
+    missed_probes: HashMap<Key, u32>,
+    dead: HashSet<Key>,
+}
+
+impl LivenessTracker {
+    pub fn new() -> LivenessTracker {
+        LivenessTracker {
+            missed_probes: HashMap::new(),
+            dead: HashSet::new(),
+        }
+    }
+
+    pub fn seen(&mut self, key: &Key) {
+        self.missed_probes.remove(key);
+        self.dead.remove(key);
+    }
+
+    // Returns true if this miss is the one that makes the neighbor dead.
+    pub fn missed_probe(&mut self, key: &Key) -> bool {
+        if self.dead.contains(key) {
+            return false;
+        }
+        let missed_probes = self.missed_probes.entry(key.clone()).or_insert(0);
+        *missed_probes += 1;
+        if *missed_probes >= MAX_MISSED_PROBES {
+            self.missed_probes.remove(key);
+            self.dead.insert(key.clone());
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_dead(&self, key: &Key) -> bool {
+        self.dead.contains(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+    use std::net::Ipv4Addr;
+    use std::net::TcpListener;
+    use test_utils::test_utils::find_free_port;
+
+    #[test]
+    fn probe_succeeds_if_any_port_accepts_a_connection() {
+        let localhost = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let listener = TcpListener::bind(SocketAddr::new(localhost, 0)).unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let closed_port = find_free_port();
+        let subject = NeighborProberReal::new();
+
+        let result = subject.probe(&NodeAddr::new(&localhost, &vec![closed_port, open_port]));
+
+        assert_eq!(result, true);
+    }
+
+    #[test]
+    fn probe_fails_if_no_port_accepts_a_connection() {
+        let localhost = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let subject = NeighborProberReal::new();
+
+        let result = subject.probe(&NodeAddr::new(&localhost, &vec![find_free_port()]));
+
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn neighbor_dies_after_missing_enough_probes_in_a_row() {
+        let key = Key::new(b"neighbor");
+        let mut subject = LivenessTracker::new();
+
+        let results: Vec<bool> = (0..MAX_MISSED_PROBES)
+            .map(|_| subject.missed_probe(&key))
+            .collect();
+
+        let mut expected = vec![false; (MAX_MISSED_PROBES - 1) as usize];
+        expected.push(true);
+        assert_eq!(results, expected);
+        assert_eq!(subject.is_dead(&key), true);
+        assert_eq!(subject.missed_probe(&key), false);
+    }
+
+    #[test]
+    fn being_seen_resets_missed_probes_and_revives_the_dead() {
+        let (flaky, dead) = (Key::new(b"flaky"), Key::new(b"dead"));
+        let mut subject = LivenessTracker::new();
+        (0..(MAX_MISSED_PROBES - 1)).for_each(|_| {
+            subject.missed_probe(&flaky);
+        });
+        (0..MAX_MISSED_PROBES).for_each(|_| {
+            subject.missed_probe(&dead);
+        });
+
+        subject.seen(&flaky);
+        subject.seen(&dead);
+
+        assert_eq!(subject.missed_probe(&flaky), false);
+        assert_eq!(subject.is_dead(&dead), false);
+    }
+}
@@ -1,21 +1,41 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
+use actix::Message;
 use actix::MessageResult;
 use actix::Recipient;
 use actix::Syn;
+use database_snapshot::DatabaseSnapshot;
 use gossip::to_dot_graph;
 use gossip::Gossip;
+use gossip_acceptor::GossipAcceptance;
 use gossip_acceptor::GossipAcceptor;
 use gossip_acceptor::GossipAcceptorReal;
+use gossip_outbox::GossipOutbox;
+use gossip_outbox::OUTBOX_INTERVAL_MILLIS;
 use gossip_producer::GossipProducer;
 use gossip_producer::GossipProducerReal;
+use liveness::LivenessTracker;
+use liveness::NeighborProber;
+use liveness::NeighborProberReal;
+use liveness::MAX_MISSED_PROBES;
+use liveness::PROBE_INTERVAL_SECS;
 use neighborhood_database::NeighborhoodDatabase;
 use neighborhood_database::NodeRecord;
+use peer_reputation::BAN_DURATION_SECS;
+use route_scorer::RouteScorer;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::Key;
+use sub_lib::dispatcher::BanPeerMessage;
 use sub_lib::dispatcher::Component;
 use sub_lib::hopper::ExpiredCoresPackagePackage;
 use sub_lib::hopper::IncipientCoresPackage;
@@ -23,6 +43,7 @@ use sub_lib::logger::Logger;
 use sub_lib::neighborhood::sentinel_ip_addr;
 use sub_lib::neighborhood::BootstrapNeighborhoodNowMessage;
 use sub_lib::neighborhood::DispatcherNodeQueryMessage;
+use sub_lib::neighborhood::ExpectedService;
 use sub_lib::neighborhood::NeighborhoodConfig;
 use sub_lib::neighborhood::NeighborhoodSubs;
 use sub_lib::neighborhood::NodeDescriptor;
@@ -39,18 +60,89 @@ use sub_lib::route::RouteSegment;
 use sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
 use sub_lib::utils::plus;
 use sub_lib::utils::NODE_MAILBOX_CAPACITY;
+use sub_lib::wallet::Wallet;
+
+// Rates are compared as if for a package of about this many bytes of payload
+const RANKING_PAYLOAD_SIZE: u64 = 1000;
+// Bounds on the route search, so that it stays fast no matter how big the database gets
+const MAX_ROUTE_CANDIDATES: usize = 32;
+const MAX_ROUTE_SEARCH_STEPS: usize = 10000;
+// Gossip that changes nothing still tells us who's alive; save that at most this often
+const SNAPSHOT_INTERVAL_SECS: u64 = 300;
+// How often to see whether any neighbor is due for its periodic full Gossip
+const GOSSIP_REFRESH_CHECK_SECS: u64 = 60;
 
 pub struct Neighborhood {
     cryptde: &'static CryptDE,
     hopper: Option<Recipient<Syn, IncipientCoresPackage>>,
+    dispatcher_ban_peer: Option<Recipient<Syn, BanPeerMessage>>,
     gossip_acceptor: Box<GossipAcceptor>,
     gossip_producer: Box<GossipProducer>,
+    gossip_outbox: GossipOutbox,
     neighborhood_database: NeighborhoodDatabase,
+    cell_size_opt: Option<usize>,
+    max_gossip_size: usize,
+    route_scorer: RouteScorer,
+    data_directory_opt: Option<PathBuf>,
+    last_snapshot_opt: Option<Instant>,
+    neighbor_prober: Box<NeighborProber>,
+    liveness: LivenessTracker,
     logger: Logger,
 }
 
 impl Actor for Neighborhood {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.notify_later(
+            ProbeNeighborsMessage {},
+            Duration::from_secs(PROBE_INTERVAL_SECS),
+        );
+        ctx.notify_later(
+            SendOutboxMessage {},
+            Duration::from_millis(OUTBOX_INTERVAL_MILLIS),
+        );
+        ctx.notify_later(
+            RefreshGossipMessage {},
+            Duration::from_secs(GOSSIP_REFRESH_CHECK_SECS),
+        );
+    }
+}
+
+// The Neighborhood sends this to itself every PROBE_INTERVAL_SECS to find out which of its neighbors are still alive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeNeighborsMessage {}
+
+impl Message for ProbeNeighborsMessage {
+    type Result = ();
+}
+
+// The Neighborhood sends this to itself every OUTBOX_INTERVAL_MILLIS to send Gossip that had to wait its turn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SendOutboxMessage {}
+
+impl Message for SendOutboxMessage {
+    type Result = ();
+}
+
+// The Neighborhood sends this to itself every GOSSIP_REFRESH_CHECK_SECS so that neighbors get their periodic full
+// Gossip even when nothing has changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshGossipMessage {}
+
+impl Message for RefreshGossipMessage {
+    type Result = ();
+}
+
+// Probing happens on a separate thread, because a dead neighbor can take a while to time out; this comes back
+// with whether each neighbor answered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NeighborProbeResultsMessage {
+    pub results: Vec<(Key, bool)>,
+}
+
+impl Message for NeighborProbeResultsMessage {
+    type Result = ();
 }
 
 impl Handler<BindMessage> for Neighborhood {
@@ -59,6 +151,7 @@ impl Handler<BindMessage> for Neighborhood {
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.hopper = Some(msg.peer_actors.hopper.from_hopper_client);
+        self.dispatcher_ban_peer = Some(msg.peer_actors.dispatcher.ban_peer);
         ()
     }
 }
@@ -100,15 +193,13 @@ impl Handler<BootstrapNeighborhoodNowMessage> for Neighborhood {
                 .info(format!("Nothing to report to bootstrap Node(s)"));
             return ();
         }
+        let bootstrap_node_keys: Vec<Key> = bootstrap_node_keys.into_iter().cloned().collect();
         bootstrap_node_keys
             .into_iter()
             .for_each(|bootstrap_node_key| {
                 let gossip = self
                     .gossip_producer
-                    .produce(&self.neighborhood_database, &bootstrap_node_key);
-                let route = self.create_single_hop_route(&bootstrap_node_key);
-                let package =
-                    IncipientCoresPackage::new(route, gossip.clone(), &bootstrap_node_key);
+                    .produce_delta(&self.neighborhood_database, &bootstrap_node_key);
                 self.logger.info(format!(
                     "Sending initial Gossip about {} nodes to bootstrap Node at {}:{}",
                     gossip.node_records.len(),
@@ -120,11 +211,7 @@ impl Handler<BootstrapNeighborhoodNowMessage> for Neighborhood {
                         .as_ref()
                         .expect("internal error: must know NodeAddr of bootstrap Node")
                 ));
-                self.hopper
-                    .as_ref()
-                    .expect("unbound hopper")
-                    .try_send(package)
-                    .expect("hopper is dead");
+                self.send_gossip(gossip, &bootstrap_node_key);
             });
         ()
     }
@@ -223,43 +310,7 @@ impl Handler<ExpiredCoresPackagePackage> for Neighborhood {
         msg: ExpiredCoresPackagePackage,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        let incoming_gossip: Gossip = match msg.expired_cores_package.payload() {
-            Ok(p) => p,
-            Err(_) => {
-                self.logger
-                    .error(format!("Unintelligible Gossip message received: ignoring"));
-                return ();
-            }
-        };
-        self.logger.trace(format!(
-            "Received Gossip: {}",
-            to_dot_graph(
-                incoming_gossip.clone(),
-                self.neighborhood_database.root().public_key(),
-                match self.neighborhood_database.node_by_ip(&msg.sender_ip) {
-                    Some(node) => node.public_key().clone(),
-                    None => Key::new(&[]),
-                }
-            )
-        ));
-        let gossip_records = incoming_gossip.clone().node_records;
-        let num_nodes = gossip_records.len();
-        self.logger
-            .info(format!("Processing Gossip about {} Nodes", num_nodes));
-
-        let db_changed = self
-            .gossip_acceptor
-            .handle(&mut self.neighborhood_database, incoming_gossip);
-        if db_changed {
-            match gossip_records.as_slice() {
-                [only] => self.gossip_to(&vec![only.public_key()]),
-                _ => self.gossip_to_neighbors(),
-            };
-        }
-        self.logger.info(format!(
-            "Finished processing Gossip about {} Nodes",
-            num_nodes
-        ));
+        self.handle_gossip(msg);
         ()
     }
 }
@@ -268,18 +319,98 @@ impl Handler<RemoveNeighborMessage> for Neighborhood {
     type Result = ();
 
     fn handle(&mut self, msg: RemoveNeighborMessage, _ctx: &mut Self::Context) -> Self::Result {
-        let public_key = &msg.public_key;
-        match self.neighborhood_database.remove_neighbor(public_key) {
-            Err(s) => self.logger.error(s),
-            Ok(db_changed) => {
-                if db_changed {
-                    self.neighborhood_database.root_mut().increment_version();
-                    self.gossip_to_neighbors();
-                    self.logger
-                        .info(format!("removed neighbor by public key: {}", public_key))
-                }
-            }
+        self.remove_neighbor(&msg.public_key);
+        ()
+    }
+}
+
+impl Handler<SendOutboxMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, _msg: SendOutboxMessage, ctx: &mut Self::Context) -> Self::Result {
+        self.send_outbox(Instant::now());
+        ctx.notify_later(
+            SendOutboxMessage {},
+            Duration::from_millis(OUTBOX_INTERVAL_MILLIS),
+        );
+        ()
+    }
+}
+
+impl Handler<RefreshGossipMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, _msg: RefreshGossipMessage, ctx: &mut Self::Context) -> Self::Result {
+        self.gossip_to_neighbors();
+        ctx.notify_later(
+            RefreshGossipMessage {},
+            Duration::from_secs(GOSSIP_REFRESH_CHECK_SECS),
+        );
+        ()
+    }
+}
+
+impl Handler<ProbeNeighborsMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ProbeNeighborsMessage, ctx: &mut Self::Context) -> Self::Result {
+        let targets: Vec<(Key, NodeAddr)> = self
+            .neighborhood_database
+            .root()
+            .neighbors()
+            .iter()
+            .filter_map(|key| {
+                let node_addr = self
+                    .neighborhood_database
+                    .node_by_key(key)?
+                    .node_addr_opt()?;
+                Some((key.clone(), node_addr))
+            })
+            .collect();
+        if !targets.is_empty() {
+            let prober = self.neighbor_prober.dup();
+            let addr: Addr<Syn, Neighborhood> = ctx.address();
+            let recipient = addr.recipient::<NeighborProbeResultsMessage>();
+            thread::spawn(move || {
+                let results = targets
+                    .into_iter()
+                    .map(|(key, node_addr)| {
+                        let alive = prober.probe(&node_addr);
+                        (key, alive)
+                    })
+                    .collect();
+                recipient
+                    .try_send(NeighborProbeResultsMessage { results })
+                    .expect("Neighborhood is dead");
+            });
         }
+        ctx.notify_later(
+            ProbeNeighborsMessage {},
+            Duration::from_secs(PROBE_INTERVAL_SECS),
+        );
+        ()
+    }
+}
+
+impl Handler<NeighborProbeResultsMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: NeighborProbeResultsMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        msg.results.into_iter().for_each(|(key, alive)| {
+            if alive {
+                self.liveness.seen(&key);
+            } else if self.liveness.missed_probe(&key) {
+                self.logger.info(format!(
+                    "Neighbor {} missed {} probes in a row; evicting it",
+                    key, MAX_MISSED_PROBES
+                ));
+                self.remove_neighbor(&key);
+            }
+        });
         ()
     }
 }
@@ -301,7 +432,7 @@ impl Neighborhood {
         {
             panic! ("An --ip setting indicates that you want to decentralize, but you also need at least one --neighbor setting or --node_type bootstrap for that, and a --port_count greater than 0")
         }
-        let gossip_acceptor: Box<GossipAcceptor> = Box::new(GossipAcceptorReal::new());
+        let gossip_acceptor: Box<GossipAcceptor> = Box::new(GossipAcceptorReal::new(cryptde));
         let gossip_producer = Box::new(GossipProducerReal::new());
         let local_node_addr = NodeAddr::new(&config.local_ip_addr, &config.clandestine_port_list);
         let mut neighborhood_database = NeighborhoodDatabase::new(
@@ -311,6 +442,12 @@ impl Neighborhood {
             config.is_bootstrap_node,
             cryptde,
         );
+        neighborhood_database
+            .root_mut()
+            .set_supports_cells(config.cell_size_opt.is_some());
+        neighborhood_database
+            .root_mut()
+            .set_rate_pack(config.rate_pack);
 
         let add_node = |neighborhood_database: &mut NeighborhoodDatabase,
                         neighbor: &(Key, NodeAddr),
@@ -336,41 +473,293 @@ impl Neighborhood {
             .neighbor_configs
             .iter()
             .for_each(|neighbor| add_node(&mut neighborhood_database, neighbor, true));
+        neighborhood_database.root_mut().sign(cryptde);
+
+        let logger = Logger::new("Neighborhood");
+        if let Some(ref data_directory) = config.data_directory_opt {
+            Neighborhood::restore_snapshot(
+                &mut neighborhood_database,
+                data_directory,
+                cryptde,
+                config.max_node_age,
+                &logger,
+            );
+        }
 
         Neighborhood {
             cryptde,
             hopper: None,
+            dispatcher_ban_peer: None,
             gossip_acceptor,
             gossip_producer,
+            gossip_outbox: GossipOutbox::new(),
             neighborhood_database,
-            logger: Logger::new("Neighborhood"),
+            cell_size_opt: config.cell_size_opt,
+            max_gossip_size: config.max_gossip_size,
+            route_scorer: RouteScorer::new(config.route_randomness),
+            data_directory_opt: config.data_directory_opt,
+            last_snapshot_opt: None,
+            neighbor_prober: Box::new(NeighborProberReal::new()),
+            liveness: LivenessTracker::new(),
+            logger,
+        }
+    }
+
+    fn remove_neighbor(&mut self, public_key: &Key) {
+        self.route_scorer.record_failure(public_key);
+        self.gossip_producer.forget(public_key);
+        match self.neighborhood_database.remove_neighbor(public_key) {
+            Err(s) => self.logger.error(s),
+            Ok(db_changed) => {
+                if db_changed {
+                    self.neighborhood_database.root_changed(self.cryptde);
+                    self.save_snapshot();
+                    self.gossip_to_neighbors();
+                    self.logger
+                        .info(format!("removed neighbor by public key: {}", public_key))
+                }
+            }
+        }
+    }
+
+    // A missing or unusable snapshot isn't fatal: the Node just has to learn the network again from its neighbors.
+    fn restore_snapshot(
+        neighborhood_database: &mut NeighborhoodDatabase,
+        data_directory: &PathBuf,
+        cryptde: &CryptDE,
+        max_node_age: Duration,
+        logger: &Logger,
+    ) {
+        let restored =
+            DatabaseSnapshot::load(data_directory).and_then(|snapshot_opt| match snapshot_opt {
+                Some(snapshot) => snapshot
+                    .restore_into(
+                        neighborhood_database,
+                        cryptde,
+                        max_node_age,
+                        SystemTime::now(),
+                    )
+                    .map(Some),
+                None => Ok(None),
+            });
+        match restored {
+            Ok(Some(report)) => logger.info(format!(
+                "Restored {} Nodes from the saved database; rejected {} with bad signatures and pruned {} not heard from in {} hours",
+                report.restored,
+                report.rejected,
+                report.pruned,
+                max_node_age.as_secs() / 3600
+            )),
+            Ok(None) => logger.info(format!("No saved database; starting from scratch")),
+            Err(e) => logger.warning(format!(
+                "Could not restore saved database; starting from scratch: {:?}",
+                e
+            )),
+        }
+    }
+
+    // The GossipAcceptor can only ignore a banned peer's Gossip; the Dispatcher can keep its traffic from
+    // getting this far in the first place.
+    fn handle_gossip(&mut self, msg: ExpiredCoresPackagePackage) {
+        let gossip_size = msg.expired_cores_package.payload.data.len();
+        if gossip_size > self.max_gossip_size {
+            // The limit is only ours, so a sender that doesn't know it isn't held responsible for going over it.
+            self.logger.error(format!(
+                "Ignoring {}-byte Gossip from {}: no more than {} bytes are allowed",
+                gossip_size, msg.sender_ip, self.max_gossip_size
+            ));
+            return;
+        }
+        let incoming_gossip: Gossip = match msg.expired_cores_package.payload() {
+            Ok(p) => p,
+            Err(_) => {
+                self.logger
+                    .error(format!("Unintelligible Gossip message received: ignoring"));
+                return;
+            }
+        };
+        self.logger.trace(format!(
+            "Received Gossip: {}",
+            to_dot_graph(
+                incoming_gossip.clone(),
+                self.neighborhood_database.root().public_key(),
+                match self.neighborhood_database.node_by_ip(&msg.sender_ip) {
+                    Some(node) => node.public_key().clone(),
+                    None => Key::new(&[]),
+                }
+            )
+        ));
+        let gossip_records = incoming_gossip.clone().node_records;
+        let num_nodes = gossip_records.len();
+        self.logger
+            .info(format!("Processing Gossip about {} Nodes", num_nodes));
+        // Now that neighbors only send what's changed, Gossip about one Node can't be taken for an introduction
+        // unless it's about a Node we don't know.
+        let introducer_opt = match gossip_records.as_slice() {
+            [only]
+                if self
+                    .neighborhood_database
+                    .node_by_key(&only.inner.public_key)
+                    .is_none() =>
+            {
+                Some(only.public_key())
+            }
+            _ => None,
+        };
+
+        let acceptance = self.gossip_acceptor.handle(
+            &mut self.neighborhood_database,
+            incoming_gossip,
+            msg.sender_ip,
+        );
+        self.report_new_bans();
+        let (db_changed, verified_keys) = match acceptance {
+            GossipAcceptance::Refused => {
+                self.logger.info(format!(
+                    "Gossip about {} Nodes from {} was refused",
+                    num_nodes, msg.sender_ip
+                ));
+                return;
+            }
+            GossipAcceptance::Accepted {
+                db_changed,
+                verified_keys,
+            } => (db_changed, verified_keys),
+        };
+        let now = SystemTime::now();
+        let mut sender_behind_opt = None;
+        if let Some(sender_key) = self
+            .neighborhood_database
+            .node_by_ip(&msg.sender_ip)
+            .map(|node| node.public_key().clone())
+        {
+            self.liveness.seen(&sender_key);
+            self.neighborhood_database.heard_from(&sender_key, now);
+            let sender_gossip = Gossip {
+                node_records: gossip_records.clone(),
+            };
+            if self
+                .gossip_producer
+                .note_gossip_from(&sender_key, &sender_gossip)
+            {
+                sender_behind_opt = Some(sender_key);
+            }
+        }
+        // Anybody can mention a Node in Gossip; only a record it signed itself shows that it's still around.
+        verified_keys
+            .iter()
+            .for_each(|key| self.neighborhood_database.heard_from(key, now));
+        let snapshot_is_stale = match self.last_snapshot_opt {
+            Some(last_snapshot) => {
+                last_snapshot.elapsed() >= Duration::from_secs(SNAPSHOT_INTERVAL_SECS)
+            }
+            None => true,
+        };
+        if db_changed || snapshot_is_stale {
+            self.save_snapshot();
+        }
+        if db_changed {
+            match introducer_opt {
+                Some(introducer) => self.gossip_to(&vec![introducer]),
+                None => self.gossip_to_neighbors(),
+            };
+        }
+        // If the sender was among the neighbors just gossiped to, it's already caught up, and this sends nothing.
+        if let Some(sender_behind) = sender_behind_opt {
+            self.gossip_to(&vec![sender_behind]);
+        }
+        self.logger.info(format!(
+            "Finished processing Gossip about {} Nodes",
+            num_nodes
+        ));
+    }
+
+    fn report_new_bans(&mut self) {
+        let new_bans = self.gossip_acceptor.take_new_bans();
+        if new_bans.is_empty() {
+            return;
+        }
+        let dispatcher_ban_peer = self
+            .dispatcher_ban_peer
+            .as_ref()
+            .expect("Dispatcher unbound in Neighborhood");
+        new_bans.into_iter().for_each(|ip_addr| {
+            dispatcher_ban_peer
+                .try_send(BanPeerMessage {
+                    ip_addr,
+                    duration: Duration::from_secs(BAN_DURATION_SECS),
+                })
+                .expect("Dispatcher is dead")
+        });
+    }
+
+    fn save_snapshot(&mut self) {
+        if let Some(ref data_directory) = self.data_directory_opt {
+            match DatabaseSnapshot::take(&self.neighborhood_database).save(data_directory) {
+                Ok(()) => self.last_snapshot_opt = Some(Instant::now()),
+                Err(e) => self
+                    .logger
+                    .error(format!("Could not save database: {:?}", e)),
+            }
         }
     }
 
-    fn gossip_to_neighbors(&self) {
-        self.gossip_to(self.neighborhood_database.root().neighbors());
+    fn gossip_to_neighbors(&mut self) {
+        let neighbors = self.neighborhood_database.root().neighbors().clone();
+        self.gossip_to(&neighbors);
     }
 
-    fn gossip_to(&self, neighbors: &Vec<Key>) {
+    fn gossip_to(&mut self, neighbors: &Vec<Key>) {
         neighbors.iter().for_each(|neighbor| {
             let gossip = self
                 .gossip_producer
-                .produce(&self.neighborhood_database, neighbor);
+                .produce_delta(&self.neighborhood_database, neighbor);
             let gossip_len = gossip.node_records.len();
-            let route = self.create_single_hop_route(neighbor);
-            let package = IncipientCoresPackage::new(route, gossip, neighbor);
+            if gossip_len == 0 {
+                self.logger
+                    .debug(format!("{} is up to date; no Gossip needed", neighbor));
+                return;
+            }
             self.logger.info(format!(
                 "Relaying Gossip about {} nodes to {}",
                 gossip_len, neighbor
             ));
-            self.hopper
-                .as_ref()
-                .expect("unbound hopper")
-                .try_send(package)
-                .expect("hopper is dead");
+            self.send_gossip(gossip, neighbor);
         });
     }
 
+    // Gossip that would be too big for its target to accept is sent in as many packages as it takes, and packages
+    // beyond what the target will take at once wait in the outbox.
+    fn send_gossip(&mut self, gossip: Gossip, target: &Key) {
+        let chunks = gossip.chunks(self.max_gossip_size);
+        if chunks.len() > 1 {
+            self.logger.debug(format!(
+                "Splitting Gossip to {} into {} packages of no more than {} bytes",
+                target,
+                chunks.len(),
+                self.max_gossip_size
+            ));
+        }
+        let now = Instant::now();
+        self.gossip_outbox.post(target, chunks, now);
+        self.send_outbox(now);
+    }
+
+    fn send_outbox(&mut self, now: Instant) {
+        self.gossip_outbox
+            .take_sendable(now)
+            .into_iter()
+            .for_each(|(target, chunk)| {
+                let route = self.create_single_hop_route(&target);
+                let package = IncipientCoresPackage::new(route, chunk, &target);
+                self.hopper
+                    .as_ref()
+                    .expect("unbound hopper")
+                    .try_send(package)
+                    .expect("hopper is dead");
+            });
+    }
+
     pub fn make_subs_from(addr: &Addr<Syn, Neighborhood>) -> NeighborhoodSubs {
         NeighborhoodSubs {
             bind: addr.clone().recipient::<BindMessage>(),
@@ -391,12 +780,13 @@ impl Neighborhood {
                 Component::Neighborhood,
             )],
             self.cryptde,
+            None,
         )
         .expect("route creation error")
     }
 
     fn zero_hop_route_response(&self) -> RouteQueryResponse {
-        let route = Route::new(
+        let mut route = Route::new(
             vec![
                 RouteSegment::new(
                     vec![&self.cryptde.public_key(), &self.cryptde.public_key()],
@@ -408,11 +798,14 @@ impl Neighborhood {
                 ),
             ],
             self.cryptde,
+            self.consuming_wallet(),
         )
         .expect("Couldn't create route");
+        route.cell_size_opt = self.negotiate_cell_size(&[self.cryptde.public_key()]);
         RouteQueryResponse {
             route,
             segment_endpoints: vec![self.cryptde.public_key(), self.cryptde.public_key()],
+            expected_services: vec![],
         }
     }
 
@@ -426,9 +819,15 @@ impl Neighborhood {
         ) {
             Some(segment) => {
                 let segment_endpoint = segment.keys.last().expect("empty segment").clone();
+                let cell_size_opt = self.negotiate_cell_size(&segment.keys);
+                let expected_services = self.expected_services(&segment);
+                let mut route = Route::new(vec![segment], self.cryptde, self.consuming_wallet())
+                    .expect("bad route");
+                route.cell_size_opt = cell_size_opt;
                 Some(RouteQueryResponse {
-                    route: Route::new(vec![segment], self.cryptde).expect("bad route"),
+                    route,
                     segment_endpoints: vec![segment_endpoint],
+                    expected_services,
                 })
             }
             None => None,
@@ -460,15 +859,68 @@ impl Neighborhood {
             ) {
                 segment_endpoints.push(back.keys.last().expect("empty segment").clone());
                 self.logger.debug(format!("Route back: {:?}", back));
+                let cell_size_opt =
+                    self.negotiate_cell_size(&[&over.keys[..], &back.keys[..]].concat());
+                let expected_services = self.expected_services(&over);
+                let mut route = Route::new(vec![over, back], self.cryptde, self.consuming_wallet())
+                    .expect("Bad route");
+                route.cell_size_opt = cell_size_opt;
                 return Some(RouteQueryResponse {
-                    route: Route::new(vec![over, back], self.cryptde).expect("Bad route"),
+                    route,
                     segment_endpoints,
+                    expected_services,
                 });
             }
         }
         None
     }
 
+    fn consuming_wallet(&self) -> Option<Wallet> {
+        self.neighborhood_database.root().wallet()
+    }
+
+    // Services provided by Nodes without wallets, or by this Node itself, can't be paid for.
+    fn expected_services(&self, segment: &RouteSegment) -> Vec<ExpectedService> {
+        let last_index = segment.keys.len() - 1;
+        segment
+            .keys
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, key)| **key != self.cryptde.public_key())
+            .flat_map(|(index, key)| {
+                let wallet = self.neighborhood_database.node_by_key(key)?.wallet()?;
+                if index < last_index {
+                    Some(ExpectedService::Routing(wallet))
+                } else if segment.recipient == Component::ProxyClient {
+                    Some(ExpectedService::Exit(wallet))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Fixed-size cells are only used if every Node on the route knows how to handle them.
+    fn negotiate_cell_size(&self, keys: &[Key]) -> Option<usize> {
+        let cell_size = self.cell_size_opt?;
+        let all_support_cells =
+            keys.iter()
+                .all(|key| match self.neighborhood_database.node_by_key(key) {
+                    Some(node) => node.supports_cells(),
+                    None => false,
+                });
+        if all_support_cells {
+            Some(cell_size)
+        } else {
+            self.logger.debug(format!(
+                "Not every Node on route supports {}-byte cells; using variable-size packages",
+                cell_size
+            ));
+            None
+        }
+    }
+
     fn make_route_segment(
         &self,
         origin: &Key,
@@ -477,12 +929,21 @@ impl Neighborhood {
         minimum_hop_count: usize,
         target_component: Component,
     ) -> Option<RouteSegment> {
-        let mut node_seqs =
-            self.complete_routes(vec![origin], target, target_type, minimum_hop_count);
-        if node_seqs.is_empty() {
-            return None;
-        }
-        let chosen_node_seq = node_seqs.remove(0);
+        let candidates = self
+            .complete_routes(
+                vec![origin],
+                target,
+                target_type,
+                minimum_hop_count,
+                target_component,
+            )
+            .into_iter()
+            .map(|node_seq| {
+                let cost = self.route_cost(&node_seq, target_component);
+                (node_seq, cost)
+            })
+            .collect();
+        let chosen_node_seq = self.route_scorer.choose(candidates, self.cryptde)?;
         Some(RouteSegment::new(chosen_node_seq, target_component))
     }
 
@@ -505,56 +966,120 @@ impl Neighborhood {
         (target_type == TargetType::Bootstrap) == last_node_ref.is_bootstrap_node()
     }
 
-    // Main recursive routing engine. Supply origin key as single-element vector in prefix,
-    // target key, if any, in target, and minimum hop count in hops_remaining. Return value is
-    // a list of all the node sequences that will either go from the origin to the target in
-    // hops_remaining or more hops with no cycles, or from the origin hops_remaining hops out into
-    // the Substratum Network. No round trips; if you want a round trip, call this method twice.
-    // If the return value is empty, no qualifying route was found.
+    // Returns the routes find_routes keeps, cheapest first; routes that cost the same stay in the order
+    // find_routes found them.
     fn complete_routes<'a>(
         &'a self,
         prefix: Vec<&'a Key>,
         target: Option<&'a Key>,
         target_type: TargetType,
         hops_remaining: usize,
+        target_component: Component,
     ) -> Vec<Vec<&'a Key>> {
-        let last_node_ref = self
-            .neighborhood_database
-            .node_by_key(prefix.last().expect("Empty prefix"))
-            .expect("Node magically disappeared");
-        // Check to see if we're done. If we are, all three of these qualifications will pass.
-        if self.route_length_qualifies(hops_remaining)
-            && self.last_key_qualifies(last_node_ref, target)
-            && self.last_type_qualifies(last_node_ref, target_type)
-        {
-            vec![prefix]
-        }
-        // If we're not done, then last_node is for routing, and bootstrap Nodes don't route.
-        else if last_node_ref.is_bootstrap_node() {
-            vec![]
-        }
-        // Go through all the neighbors and compute shorter routes through all the ones we're not already using.
-        else {
-            last_node_ref
-                .neighbors()
-                .iter()
-                .filter(|neighbor_key_ref_ref| !prefix.contains(neighbor_key_ref_ref))
-                .flat_map(|neighbor_key_ref_ref| {
-                    let mut new_prefix = prefix.clone();
-                    new_prefix.push(neighbor_key_ref_ref);
-                    self.complete_routes(
-                        new_prefix.clone(),
-                        target,
-                        target_type,
-                        if hops_remaining == 0 {
-                            0
-                        } else {
-                            hops_remaining - 1
-                        },
-                    )
-                })
-                .collect()
+        self.find_routes(
+            prefix,
+            target,
+            target_type,
+            hops_remaining,
+            target_component,
+        )
+    }
+
+    // What this Node expects to pay to send a package of RANKING_PAYLOAD_SIZE bytes along the route. As in
+    // expected_services, the origin and this Node itself cost nothing, and the last Node charges for exit
+    // service only if the package is bound for the ProxyClient.
+    fn route_cost(&self, route: &[&Key], target_component: Component) -> u64 {
+        let last_index = route.len() - 1;
+        route
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, key)| ***key != self.cryptde.public_key())
+            .map(
+                |(index, key)| match self.neighborhood_database.node_by_key(key) {
+                    Some(node) if index < last_index => {
+                        node.rate_pack().routing_charge(RANKING_PAYLOAD_SIZE)
+                    }
+                    Some(node) if target_component == Component::ProxyClient => {
+                        node.rate_pack().exit_charge(RANKING_PAYLOAD_SIZE)
+                    }
+                    _ => 0,
+                },
+            )
+            .fold(0u64, |so_far, charge| so_far.saturating_add(charge))
+    }
+
+    // Main routing engine. Supply origin key as single-element vector in prefix, target key, if any,
+    // in target, and minimum hop count in hops_remaining. Return value is a list of node sequences
+    // that will either go from the origin to the target in hops_remaining or more hops with no
+    // cycles, or from the origin hops_remaining hops out into the Substratum Network. No round trips;
+    // if you want a round trip, call this method twice. If the return value is empty, no qualifying
+    // route was found.
+    //
+    // The number of cycle-free paths grows exponentially with the size of the database, so this
+    // doesn't look for all of them. It searches breadth-first, so shorter routes are found before
+    // longer ones, and stops once it has considered MAX_ROUTE_SEARCH_STEPS partial routes. Of the
+    // routes it finds, it keeps the MAX_ROUTE_CANDIDATES cheapest, cheapest first; routes that cost
+    // the same stay in the order they were found.
+    fn find_routes<'a>(
+        &'a self,
+        prefix: Vec<&'a Key>,
+        target: Option<&'a Key>,
+        target_type: TargetType,
+        hops_remaining: usize,
+        target_component: Component,
+    ) -> Vec<Vec<&'a Key>> {
+        let mut routes: Vec<(u64, Vec<&'a Key>)> = vec![];
+        let mut partial_routes: VecDeque<(Vec<&'a Key>, usize)> = VecDeque::new();
+        partial_routes.push_back((prefix, hops_remaining));
+        let mut steps = 0;
+        while let Some((route, hops_remaining)) = partial_routes.pop_front() {
+            if steps >= MAX_ROUTE_SEARCH_STEPS {
+                break;
+            }
+            steps += 1;
+            let last_node_ref = self
+                .neighborhood_database
+                .node_by_key(route.last().expect("Empty prefix"))
+                .expect("Node magically disappeared");
+            // Check to see if we're done. If we are, all three of these qualifications will pass.
+            if self.route_length_qualifies(hops_remaining)
+                && self.last_key_qualifies(last_node_ref, target)
+                && self.last_type_qualifies(last_node_ref, target_type)
+            {
+                let cost = self.route_cost(&route, target_component);
+                let position = routes
+                    .iter()
+                    .position(|(kept_cost, _)| *kept_cost > cost)
+                    .unwrap_or(routes.len());
+                if position < MAX_ROUTE_CANDIDATES {
+                    routes.insert(position, (cost, route));
+                    routes.truncate(MAX_ROUTE_CANDIDATES);
+                }
+            }
+            // If we're not done, then last_node is for routing, and bootstrap Nodes don't route.
+            else if !last_node_ref.is_bootstrap_node() {
+                // Extend the route through all the neighbors we're not already using.
+                last_node_ref
+                    .neighbors()
+                    .iter()
+                    .filter(|neighbor_key_ref| !route.contains(neighbor_key_ref))
+                    // A restored database may mention Nodes it no longer knows, and there's no point
+                    // routing through Nodes that have stopped answering
+                    .filter(|neighbor_key_ref| {
+                        self.neighborhood_database
+                            .node_by_key(neighbor_key_ref)
+                            .is_some()
+                            && !self.liveness.is_dead(neighbor_key_ref)
+                    })
+                    .for_each(|neighbor_key_ref| {
+                        let mut new_route = route.clone();
+                        new_route.push(neighbor_key_ref);
+                        partial_routes.push_back((new_route, hops_remaining.saturating_sub(1)));
+                    });
+            }
         }
+        routes.into_iter().map(|(_, route)| route).collect()
     }
 }
 
@@ -565,18 +1090,32 @@ mod tests {
     use actix::Arbiter;
     use actix::Recipient;
     use actix::System;
+    use database_snapshot::SNAPSHOT_FILENAME;
     use gossip::GossipBuilder;
     use gossip::GossipNodeRecord;
+    use gossip_outbox::GossipOutbox;
+    use neighborhood_test_utils::make_genuinely_signed_node_record;
     use neighborhood_test_utils::make_node_record;
+    use neighborhood_test_utils::TamperingGossipBuilder;
+    use peer_reputation::Offense;
+    use peer_reputation::BAN_THRESHOLD;
+    use peer_reputation::GOSSIP_BURST;
     use serde_cbor;
+    use std::io;
     use std::net::IpAddr;
     use std::str::FromStr;
     use std::thread;
+    use std::time::Duration;
+    use std::time::Instant;
+    use std::time::UNIX_EPOCH;
     use sub_lib::cryptde::PlainData;
     use sub_lib::cryptde_null::CryptDENull;
+    use sub_lib::cryptde_real::CryptDEReal;
     use sub_lib::dispatcher::Endpoint;
     use sub_lib::hopper::ExpiredCoresPackage;
     use sub_lib::neighborhood::sentinel_ip_addr;
+    use sub_lib::neighborhood::RatePack;
+    use sub_lib::neighborhood::DEFAULT_MAX_GOSSIP_SIZE;
     use sub_lib::stream_handler_pool::TransmitDataMsg;
     use test_utils::logging::init_test_logging;
     use test_utils::logging::TestLogHandler;
@@ -588,6 +1127,7 @@ mod tests {
     use test_utils::tcp_wrapper_mocks::TcpStreamWrapperMock;
     use test_utils::test_utils::assert_contains;
     use test_utils::test_utils::cryptde;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
     use test_utils::test_utils::make_meaningless_route;
     use tokio::prelude::Future;
 
@@ -610,6 +1150,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
     }
@@ -629,6 +1175,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![1234],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
     }
@@ -648,6 +1200,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
     }
@@ -671,6 +1229,12 @@ mod tests {
                 local_ip_addr: IpAddr::from_str("2.3.4.5").unwrap(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
     }
@@ -690,6 +1254,12 @@ mod tests {
                 local_ip_addr: IpAddr::from_str("2.3.4.5").unwrap(),
                 clandestine_port_list: vec![2345],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
     }
@@ -707,6 +1277,12 @@ mod tests {
                 local_ip_addr: this_node_addr.ip_addr(),
                 clandestine_port_list: this_node_addr.ports().clone(),
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
 
@@ -731,6 +1307,12 @@ mod tests {
                 local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                 clandestine_port_list: vec![5678],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -775,6 +1357,12 @@ mod tests {
                 local_ip_addr: this_node_addr.ip_addr(),
                 clandestine_port_list: this_node_addr.ports().clone(),
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
 
@@ -825,6 +1413,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -853,6 +1447,12 @@ mod tests {
                 local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                 clandestine_port_list: vec![5678],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -883,6 +1483,12 @@ mod tests {
                 local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                 clandestine_port_list: vec![5678],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -919,6 +1525,12 @@ mod tests {
                 local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                 clandestine_port_list: vec![5678],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -962,6 +1574,12 @@ mod tests {
                     .ports()
                     .clone(),
                 wallet: node_record.wallet(),
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -984,10 +1602,60 @@ mod tests {
     }
 
     #[test]
-    fn route_query_responds_with_none_when_asked_for_route_with_too_many_hops() {
+    fn ipv4_and_ipv6_neighbors_coexist_in_one_neighborhood() {
         let cryptde = cryptde();
-        let system = System::new("responds_with_none_when_asked_for_route_with_empty_database");
-        let subject = Neighborhood::new(
+        let system = System::new("ipv4_and_ipv6_neighbors_coexist_in_one_neighborhood");
+        let ipv4_neighbor = (
+            Key::new(b"ipv4_neighbor"),
+            NodeAddr::new(&IpAddr::from_str("1.2.3.4").unwrap(), &vec![1234]),
+        );
+        let ipv6_neighbor = (
+            Key::new(b"ipv6_neighbor"),
+            NodeAddr::new(&IpAddr::from_str("2001:db8::2").unwrap(), &vec![2345]),
+        );
+        let subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![ipv4_neighbor.clone(), ipv6_neighbor.clone()],
+                is_bootstrap_node: false,
+                local_ip_addr: IpAddr::from_str("2001:db8::1").unwrap(),
+                clandestine_port_list: vec![5678],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+        let addr: Addr<Syn, Neighborhood> = subject.start();
+        let sub: Recipient<Syn, NodeQueryMessage> = addr.recipient::<NodeQueryMessage>();
+
+        let ipv4_future = sub.send(NodeQueryMessage::IpAddress(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+        ));
+        let ipv6_future = sub.send(NodeQueryMessage::IpAddress(
+            IpAddr::from_str("2001:db8::2").unwrap(),
+        ));
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+        assert_eq!(
+            ipv4_future.wait().unwrap(),
+            Some(NodeDescriptor::new(ipv4_neighbor.0, Some(ipv4_neighbor.1)))
+        );
+        assert_eq!(
+            ipv6_future.wait().unwrap(),
+            Some(NodeDescriptor::new(ipv6_neighbor.0, Some(ipv6_neighbor.1)))
+        );
+    }
+
+    #[test]
+    fn route_query_responds_with_none_when_asked_for_route_with_too_many_hops() {
+        let cryptde = cryptde();
+        let system = System::new("responds_with_none_when_asked_for_route_with_empty_database");
+        let subject = Neighborhood::new(
             cryptde,
             NeighborhoodConfig {
                 neighbor_configs: vec![],
@@ -995,6 +1663,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1020,6 +1694,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1043,9 +1723,11 @@ mod tests {
                     ),
                 ],
                 cryptde,
+                None,
             )
             .unwrap(),
             segment_endpoints: vec![cryptde.public_key(), cryptde.public_key()],
+            expected_services: vec![],
         };
         assert_eq!(result, expected_response);
     }
@@ -1072,6 +1754,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let b = &make_node_record(1234, true, true);
@@ -1115,9 +1803,15 @@ mod tests {
             route: Route::new(
                 vec![segment(vec![p, q, r, s, b], Component::Neighborhood)],
                 cryptde,
+                None,
             )
             .unwrap(),
             segment_endpoints: vec![b.public_key().clone()],
+            expected_services: vec![
+                ExpectedService::Routing(q.wallet().unwrap()),
+                ExpectedService::Routing(r.wallet().unwrap()),
+                ExpectedService::Routing(s.wallet().unwrap()),
+            ],
         };
         assert_eq!(result, expected_response);
 
@@ -1129,9 +1823,14 @@ mod tests {
                     segment(vec![r, q, p], Component::ProxyServer),
                 ],
                 cryptde,
+                None,
             )
             .unwrap(),
             segment_endpoints: vec![r.public_key().clone(), p.public_key().clone()],
+            expected_services: vec![
+                ExpectedService::Routing(q.wallet().unwrap()),
+                ExpectedService::Exit(r.wallet().unwrap()),
+            ],
         };
         assert_eq!(result, expected_response);
     }
@@ -1158,6 +1857,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let b = &make_node_record(1234, true, true);
@@ -1190,7 +1895,13 @@ mod tests {
         };
 
         // At least two hops from P to anywhere standard
-        let routes = subject.complete_routes(vec![p.public_key()], None, TargetType::Standard, 2);
+        let routes = subject.complete_routes(
+            vec![p.public_key()],
+            None,
+            TargetType::Standard,
+            2,
+            Component::ProxyClient,
+        );
 
         contains(&routes, vec![p, s, t]);
         contains(&routes, vec![p, r, s]);
@@ -1203,6 +1914,7 @@ mod tests {
             Some(t.public_key()),
             TargetType::Standard,
             2,
+            Component::ProxyClient,
         );
 
         contains(&routes, vec![p, s, t]);
@@ -1215,6 +1927,7 @@ mod tests {
             Some(b.public_key()),
             TargetType::Bootstrap,
             2,
+            Component::ProxyClient,
         );
 
         contains(&routes, vec![p, r, b]);
@@ -1222,7 +1935,13 @@ mod tests {
         assert_eq!(routes.len(), 2);
 
         // At least two hops from P to anywhere bootstrap
-        let routes = subject.complete_routes(vec![p.public_key()], None, TargetType::Bootstrap, 2);
+        let routes = subject.complete_routes(
+            vec![p.public_key()],
+            None,
+            TargetType::Bootstrap,
+            2,
+            Component::ProxyClient,
+        );
 
         contains(&routes, vec![p, r, b]);
         contains(&routes, vec![p, s, r, b]);
@@ -1234,6 +1953,7 @@ mod tests {
             Some(s.public_key()),
             TargetType::Standard,
             2,
+            Component::ProxyClient,
         );
 
         contains(&routes, vec![p, r, s]);
@@ -1245,6 +1965,7 @@ mod tests {
             Some(q.public_key()),
             TargetType::Standard,
             2,
+            Component::ProxyClient,
         );
 
         assert_eq!(routes.len(), 0);
@@ -1255,6 +1976,7 @@ mod tests {
             Some(r.public_key()),
             TargetType::Bootstrap,
             2,
+            Component::ProxyClient,
         );
 
         assert_eq!(routes.len(), 0);
@@ -1280,6 +2002,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1323,6 +2051,12 @@ mod tests {
                     local_ip_addr: this_node_inside.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: this_node_inside.node_addr_opt().unwrap().ports(),
                     wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
 
@@ -1411,10 +2145,16 @@ mod tests {
                     local_ip_addr: this_node_inside.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: this_node_inside.node_addr_opt().unwrap().ports(),
                     wallet: this_node_inside.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
 
-            let mut gossip_acceptor = GossipAcceptorReal::new();
+            let mut gossip_acceptor = GossipAcceptorReal::new(cryptde);
             gossip_acceptor.tcp_stream_factory = Box::new(
                 TcpStreamWrapperFactoryMock::new()
                     .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
@@ -1460,6 +2200,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn neighbor_that_is_behind_on_gossip_gets_full_gossip_even_when_nothing_changed() {
+        let cryptde = cryptde();
+        let this_node_addr = NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![1234]);
+        let stale_neighbor = make_node_record(4567, true, false);
+        let stale_gossip = GossipBuilder::new().node(&stale_neighbor, true).build();
+        let cores_package = ExpiredCoresPackagePackage {
+            expired_cores_package: ExpiredCoresPackage::new(
+                make_meaningless_route(),
+                PlainData::new(&serde_cbor::ser::to_vec(&stale_gossip).unwrap()[..]),
+            ),
+            sender_ip: stale_neighbor.node_addr_opt().unwrap().ip_addr(),
+        };
+        let hopper = Recorder::new();
+        let hopper_awaiter = hopper.get_awaiter();
+        let hopper_recording = hopper.get_recording();
+        let stale_neighbor_inside = stale_neighbor.clone();
+        thread::spawn(move || {
+            let system = System::new(
+                "neighbor_that_is_behind_on_gossip_gets_full_gossip_even_when_nothing_changed",
+            );
+            let mut subject = Neighborhood::new(
+                cryptde,
+                NeighborhoodConfig {
+                    neighbor_configs: vec![],
+                    is_bootstrap_node: true,
+                    local_ip_addr: this_node_addr.ip_addr(),
+                    clandestine_port_list: this_node_addr.ports(),
+                    wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+                },
+            );
+            let neighbor_key = stale_neighbor_inside.public_key();
+            let mut newer_neighbor = stale_neighbor_inside.clone();
+            newer_neighbor.increment_version();
+            subject
+                .neighborhood_database
+                .add_node(&newer_neighbor)
+                .unwrap();
+            subject
+                .neighborhood_database
+                .add_neighbor(&cryptde.public_key(), neighbor_key)
+                .unwrap();
+            // The neighbor has been told about the newer version of its own record...
+            subject
+                .gossip_producer
+                .produce_delta(&subject.neighborhood_database, neighbor_key);
+            let addr: Addr<Syn, Neighborhood> = subject.start();
+            let peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            // ...but it Gossips as if it hadn't heard.
+            addr.try_send(cores_package).unwrap();
+
+            system.run();
+        });
+        hopper_awaiter.await_message_count(1);
+
+        let recording = hopper_recording.lock().unwrap();
+        let package: &IncipientCoresPackage = recording.get_record(0);
+        assert_eq!(
+            &package.payload_destination_key,
+            stale_neighbor.public_key()
+        );
+        let gossip: Gossip = serde_cbor::de::from_slice(&package.payload.data[..]).unwrap();
+        let mut keys: Vec<Key> = gossip
+            .node_records
+            .into_iter()
+            .map(|gnr| gnr.inner.public_key)
+            .collect();
+        keys.sort_by(|a, b| a.data.cmp(&b.data));
+        let mut expected_keys = vec![cryptde.public_key(), stale_neighbor.public_key().clone()];
+        expected_keys.sort_by(|a, b| a.data.cmp(&b.data));
+        assert_eq!(keys, expected_keys);
+    }
+
     #[test]
     fn standard_node_requests_bootstrap_properly() {
         let cryptde = cryptde();
@@ -1481,6 +2302,12 @@ mod tests {
                     local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                     clandestine_port_list: vec![1234],
                     wallet: Some(NodeRecord::wallet_from_key(&cryptde.public_key())),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1555,6 +2382,12 @@ mod tests {
                 local_ip_addr: sentinel_ip_addr(),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
         );
         let n = &subject.neighborhood_database.root().clone();
@@ -1658,6 +2491,12 @@ mod tests {
                     local_ip_addr: sentinel_ip_addr(),
                     clandestine_port_list: vec![],
                     wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1708,6 +2547,12 @@ mod tests {
                     local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                     clandestine_port_list: vec![5678],
                     wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1765,6 +2610,12 @@ mod tests {
                     local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                     clandestine_port_list: vec![5678],
                     wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1814,6 +2665,12 @@ mod tests {
                     local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
                     clandestine_port_list: vec![5678],
                     wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1884,6 +2741,12 @@ mod tests {
                         .ports()
                         .clone(),
                     wallet: node_record.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             let addr: Addr<Syn, Neighborhood> = subject.start();
@@ -1954,6 +2817,12 @@ mod tests {
                     local_ip_addr: this_node.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: this_node.node_addr_opt().unwrap().ports(),
                     wallet: this_node.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
             subject
@@ -2030,10 +2899,16 @@ mod tests {
                     local_ip_addr: this_node_inside.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: this_node_inside.node_addr_opt().unwrap().ports(),
                     wallet: this_node_inside.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
 
-            let mut gossip_acceptor = GossipAcceptorReal::new();
+            let mut gossip_acceptor = GossipAcceptorReal::new(cryptde);
             gossip_acceptor.tcp_stream_factory = Box::new(
                 TcpStreamWrapperFactoryMock::new()
                     .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
@@ -2103,10 +2978,16 @@ mod tests {
                     local_ip_addr: bootstrap_node_inside.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: bootstrap_node_inside.node_addr_opt().unwrap().ports(),
                     wallet: bootstrap_node_inside.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
 
-            let mut gossip_acceptor = GossipAcceptorReal::new();
+            let mut gossip_acceptor = GossipAcceptorReal::new(cryptde);
             gossip_acceptor.tcp_stream_factory = Box::new(
                 TcpStreamWrapperFactoryMock::new()
                     .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
@@ -2202,10 +3083,16 @@ mod tests {
                     local_ip_addr: this_node_inside.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: this_node_inside.node_addr_opt().unwrap().ports(),
                     wallet: this_node_inside.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
 
-            let mut gossip_acceptor = GossipAcceptorReal::new();
+            let mut gossip_acceptor = GossipAcceptorReal::new(cryptde);
             gossip_acceptor.tcp_stream_factory = Box::new(
                 TcpStreamWrapperFactoryMock::new()
                     .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
@@ -2264,6 +3151,12 @@ mod tests {
                     local_ip_addr: this_node_inside.node_addr_opt().unwrap().ip_addr(),
                     clandestine_port_list: this_node_inside.node_addr_opt().unwrap().ports(),
                     wallet: this_node_inside.wallet(),
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
                 },
             );
 
@@ -2310,4 +3203,1135 @@ mod tests {
             .expect("should have the node record");
         assert_eq!(the_node_record.inner.version, 1);
     }
+
+    #[test]
+    fn root_node_record_is_signed_after_construction_and_after_removing_a_neighbor() {
+        let cryptde: &'static CryptDE = Box::leak(Box::new(CryptDEReal::new()));
+        let neighbor = make_node_record(3456, true, true);
+        let mut subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![(
+                    neighbor.public_key().clone(),
+                    neighbor.node_addr_opt().unwrap().clone(),
+                )],
+                is_bootstrap_node: false,
+                local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                clandestine_port_list: vec![5678],
+                wallet: None,
+                cell_size_opt: Some(512),
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+        let constructed_root = subject.neighborhood_database.root().clone();
+
+        subject.remove_neighbor(neighbor.public_key());
+
+        let root = subject.neighborhood_database.root();
+        assert_eq!(constructed_root.has_neighbor(neighbor.public_key()), true);
+        assert_eq!(constructed_root.has_valid_signatures(cryptde), true);
+        assert_eq!(root.has_neighbor(neighbor.public_key()), false);
+        assert_eq!(root.version(), 1);
+        assert_eq!(root.has_valid_signatures(cryptde), true);
+    }
+
+    fn make_cell_subject(r_supports_cells: bool) -> Neighborhood {
+        let mut subject = Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: Some(512),
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &mut make_node_record(3456, true, false);
+        q.set_supports_cells(true);
+        let r = &mut make_node_record(4567, false, false);
+        r.set_supports_cells(r_supports_cells);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q).unwrap();
+            db.add_node(r).unwrap();
+            dual_edge_func(db, p, q);
+            dual_edge_func(db, q, r);
+        }
+        subject
+    }
+
+    #[test]
+    fn round_trip_route_uses_cells_when_every_node_on_it_supports_them() {
+        let subject = make_cell_subject(true);
+
+        let result = subject
+            .make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2))
+            .unwrap();
+
+        assert_eq!(result.route.cell_size_opt, Some(512));
+    }
+
+    #[test]
+    fn round_trip_route_does_not_use_cells_when_a_node_on_it_does_not_support_them() {
+        let subject = make_cell_subject(false);
+
+        let result = subject
+            .make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(2))
+            .unwrap();
+
+        assert_eq!(result.route.cell_size_opt, None);
+    }
+
+    #[test]
+    fn zero_hop_route_uses_configured_cell_size() {
+        let subject = make_cell_subject(false);
+
+        let result = subject.zero_hop_route_response();
+
+        assert_eq!(result.route.cell_size_opt, Some(512));
+    }
+
+    #[test]
+    fn node_advertises_cell_support_only_when_configured_with_cell_size() {
+        let with_cells = make_cell_subject(true);
+        let without_cells = Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+
+        assert_eq!(
+            with_cells.neighborhood_database.root().supports_cells(),
+            true
+        );
+        assert_eq!(
+            without_cells.neighborhood_database.root().supports_cells(),
+            false
+        );
+    }
+
+    #[test]
+    fn routes_do_not_use_cells_unless_configured_to() {
+        let subject = Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+
+        let result = subject.zero_hop_route_response();
+
+        assert_eq!(result.route.cell_size_opt, None);
+    }
+
+    fn make_rate_pack(routing_byte_rate: u64, exit_byte_rate: u64) -> RatePack {
+        RatePack {
+            routing_service_rate: 0,
+            routing_byte_rate,
+            exit_service_rate: 0,
+            exit_byte_rate,
+        }
+    }
+
+    /*
+            Q---S
+            |   |
+            P---R
+
+            Test is written from the standpoint of P
+    */
+    fn make_priced_subject(q_rate_pack: RatePack, r_rate_pack: RatePack) -> Neighborhood {
+        let mut subject = Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &mut make_node_record(3456, true, false);
+        q.set_rate_pack(q_rate_pack);
+        let r = &mut make_node_record(4567, true, false);
+        r.set_rate_pack(r_rate_pack);
+        let s = &make_node_record(5678, true, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q).unwrap();
+            db.add_node(r).unwrap();
+            db.add_node(s).unwrap();
+            dual_edge_func(db, p, q);
+            dual_edge_func(db, p, r);
+            dual_edge_func(db, q, s);
+            dual_edge_func(db, r, s);
+        }
+        subject
+    }
+
+    #[test]
+    fn complete_routes_ranks_routes_with_cheaper_routing_first() {
+        let p = cryptde().public_key();
+        let q = make_node_record(3456, true, false).public_key().clone();
+        let r = make_node_record(4567, true, false).public_key().clone();
+        let s = make_node_record(5678, true, false).public_key().clone();
+        let q_expensive = make_priced_subject(make_rate_pack(10, 0), make_rate_pack(5, 0));
+        let r_expensive = make_priced_subject(make_rate_pack(5, 0), make_rate_pack(10, 0));
+
+        let q_expensive_routes = q_expensive.complete_routes(
+            vec![&p],
+            Some(&s),
+            TargetType::Standard,
+            2,
+            Component::ProxyClient,
+        );
+        let r_expensive_routes = r_expensive.complete_routes(
+            vec![&p],
+            Some(&s),
+            TargetType::Standard,
+            2,
+            Component::ProxyClient,
+        );
+
+        assert_eq!(q_expensive_routes, vec![vec![&p, &r, &s], vec![&p, &q, &s]]);
+        assert_eq!(r_expensive_routes, vec![vec![&p, &q, &s], vec![&p, &r, &s]]);
+    }
+
+    #[test]
+    fn complete_routes_considers_exit_rates_only_for_routes_to_the_proxy_client() {
+        let p = cryptde().public_key();
+        let q = make_node_record(3456, true, false).public_key().clone();
+        let r = make_node_record(4567, true, false).public_key().clone();
+        let subject = make_priced_subject(make_rate_pack(0, 10), make_rate_pack(0, 5));
+
+        let exit_routes = subject.complete_routes(
+            vec![&p],
+            None,
+            TargetType::Standard,
+            1,
+            Component::ProxyClient,
+        );
+        let gossip_routes = subject.complete_routes(
+            vec![&p],
+            None,
+            TargetType::Standard,
+            1,
+            Component::Neighborhood,
+        );
+
+        assert_eq!(exit_routes, vec![vec![&p, &r], vec![&p, &q]]);
+        assert_eq!(gossip_routes, vec![vec![&p, &q], vec![&p, &r]]);
+    }
+
+    #[test]
+    fn data_route_goes_through_the_cheapest_nodes() {
+        let r = make_node_record(4567, true, false).public_key().clone();
+        let subject = make_priced_subject(make_rate_pack(10, 0), make_rate_pack(5, 0));
+
+        let result = subject
+            .make_one_way_route(RouteQueryMessage::data_indefinite_route_request(2))
+            .unwrap();
+
+        let next_key = result.route.next_hop(cryptde()).unwrap().public_key;
+        assert_eq!(next_key, r);
+        assert_eq!(
+            result.segment_endpoints,
+            vec![make_node_record(5678, true, false).public_key().clone()]
+        );
+    }
+
+    #[test]
+    fn node_advertises_configured_rate_pack() {
+        let rate_pack = RatePack {
+            routing_service_rate: 1,
+            routing_byte_rate: 2,
+            exit_service_rate: 3,
+            exit_byte_rate: 4,
+        };
+
+        let subject = Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack,
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+
+        let root = subject.neighborhood_database.root();
+        assert_eq!(root.rate_pack(), &rate_pack);
+        let gossip_record = GossipNodeRecord::from(root, true);
+        assert_eq!(gossip_record.inner.rate_pack, rate_pack);
+    }
+
+    #[test]
+    fn routes_avoid_nodes_that_have_been_removed_for_failing() {
+        let cryptde = cryptde();
+        let system = System::new("routes_avoid_nodes_that_have_been_removed_for_failing");
+        let hopper = Recorder::new();
+        let mut subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+        let n = &subject.neighborhood_database.root().clone();
+        let a = &make_node_record(3456, true, false);
+        let q = &make_node_record(4567, true, false);
+        let r = &make_node_record(5678, true, false);
+        let t = &make_node_record(6789, true, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(a).unwrap();
+            db.add_node(q).unwrap();
+            db.add_node(r).unwrap();
+            db.add_node(t).unwrap();
+            let mut edge = |a: &NodeRecord, b: &NodeRecord| single_edge_func(db, a, b);
+            edge(n, a);
+            edge(n, q);
+            edge(n, r);
+            edge(q, a);
+            edge(r, t);
+        }
+        let route_request = || RouteQueryMessage {
+            route_type: RouteType::OneWay,
+            target_type: TargetType::Standard,
+            target_key_opt: None,
+            target_component: Component::ProxyClient,
+            minimum_hop_count: 2,
+            return_component_opt: None,
+        };
+        let control_route = subject.make_one_way_route(route_request()).unwrap();
+
+        let addr: Addr<Syn, Neighborhood> = subject.start();
+        let peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+        addr.try_send(RemoveNeighborMessage {
+            public_key: a.public_key().clone(),
+        })
+        .unwrap();
+        let route_future = addr.send(route_request());
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+
+        assert_eq!(
+            control_route.segment_endpoints,
+            vec![a.public_key().clone()]
+        );
+        let result = route_future.wait().unwrap().unwrap();
+        assert_eq!(result.segment_endpoints, vec![t.public_key().clone()]);
+    }
+
+    fn make_plain_subject() -> Neighborhood {
+        Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        )
+    }
+
+    // Every node is connected to the root and to the nodes that come 1, 7, 31 and 97 after it, so
+    // there are far too many cycle-free paths through it to enumerate.
+    fn make_big_subject(node_count: u16) -> Neighborhood {
+        let mut subject = make_plain_subject();
+        let root = subject.neighborhood_database.root().clone();
+        let nodes: Vec<NodeRecord> = (0..node_count)
+            .map(|n| make_node_record(1000 + n, true, false))
+            .collect();
+        let db = &mut subject.neighborhood_database;
+        nodes.iter().for_each(|node| {
+            db.add_node(node).unwrap();
+        });
+        nodes.iter().enumerate().for_each(|(index, node)| {
+            if index < 4 {
+                dual_edge_func(db, &root, node);
+            }
+            [1, 7, 31, 97].iter().for_each(|offset| {
+                let neighbor = &nodes[(index + offset) % nodes.len()];
+                dual_edge_func(db, node, neighbor);
+            });
+        });
+        subject
+    }
+
+    #[test]
+    fn find_routes_keeps_no_more_than_max_route_candidates() {
+        let subject = make_big_subject(100);
+        let root_key = cryptde().public_key();
+
+        let result = subject.find_routes(
+            vec![&root_key],
+            None,
+            TargetType::Standard,
+            3,
+            Component::ProxyClient,
+        );
+
+        assert_eq!(result.len(), MAX_ROUTE_CANDIDATES);
+        result.iter().for_each(|route| assert_eq!(route.len(), 4));
+    }
+
+    #[test]
+    fn find_routes_keeps_the_cheapest_route_even_when_it_is_found_late() {
+        let mut subject = make_big_subject(100);
+        let root_key = cryptde().public_key();
+        // The search goes out through the root's neighbors 1000 to 1003 in that order, and there are far more
+        // than MAX_ROUTE_CANDIDATES routes through 1000 alone; only routes through 1003 are free.
+        let expensive = make_rate_pack(100, 100);
+        (1000..1003).for_each(|n| {
+            subject
+                .neighborhood_database
+                .node_by_key_mut(make_node_record(n, true, false).public_key())
+                .unwrap()
+                .set_rate_pack(expensive);
+        });
+        let cheap_router = make_node_record(1003, true, false).public_key().clone();
+
+        let result = subject.find_routes(
+            vec![&root_key],
+            None,
+            TargetType::Standard,
+            3,
+            Component::ProxyClient,
+        );
+
+        assert_eq!(result.len(), MAX_ROUTE_CANDIDATES);
+        assert_eq!(result[0][1], &cheap_router);
+        assert_eq!(
+            subject.route_cost(&result[0], Component::ProxyClient),
+            0,
+            "{:?}",
+            result[0]
+        );
+    }
+
+    #[test]
+    fn find_routes_finds_shorter_routes_before_longer_ones() {
+        let subject = make_big_subject(100);
+        let root_key = cryptde().public_key();
+        let target_key = make_node_record(1050, true, false).public_key().clone();
+
+        let result = subject.find_routes(
+            vec![&root_key],
+            Some(&target_key),
+            TargetType::Standard,
+            2,
+            Component::ProxyClient,
+        );
+
+        assert_eq!(result.is_empty(), false);
+        let lengths: Vec<usize> = result.iter().map(|route| route.len()).collect();
+        let mut sorted_lengths = lengths.clone();
+        sorted_lengths.sort();
+        assert_eq!(lengths, sorted_lengths);
+        result.iter().for_each(|route| {
+            assert_eq!(route.last(), Some(&&target_key));
+            assert!(route.len() >= 3, "{:?} is too short", route);
+        });
+    }
+
+    #[test]
+    fn find_routes_gives_up_on_unreachable_target_in_bounded_time() {
+        let mut subject = make_big_subject(1000);
+        let root_key = cryptde().public_key();
+        let stranger = make_node_record(9999, true, false);
+        subject.neighborhood_database.add_node(&stranger).unwrap();
+        let started_at = Instant::now();
+
+        let result = subject.find_routes(
+            vec![&root_key],
+            Some(stranger.public_key()),
+            TargetType::Standard,
+            2,
+            Component::ProxyClient,
+        );
+
+        assert_eq!(result.is_empty(), true);
+        let elapsed = started_at.elapsed();
+        assert!(
+            elapsed < Duration::from_millis(500),
+            "Search took {:?}",
+            elapsed
+        );
+    }
+
+    // On a 1,000-node database, round-trip route queries are expected to take a few milliseconds in an optimized
+    // build. The limit here is loose enough for a debug build.
+    #[test]
+    fn round_trip_route_queries_on_a_thousand_node_database_are_fast() {
+        let subject = make_big_subject(1000);
+        let query_count = 20;
+        let started_at = Instant::now();
+
+        (0..query_count).for_each(|_| {
+            subject
+                .make_round_trip_route(RouteQueryMessage::data_indefinite_route_request(3))
+                .expect("No route found");
+        });
+
+        let per_query = started_at.elapsed() / query_count;
+        assert!(
+            per_query < Duration::from_millis(100),
+            "Each route query took {:?}",
+            per_query
+        );
+    }
+
+    #[test]
+    fn database_is_saved_after_gossip_and_restored_after_a_restart() {
+        let cryptde = cryptde();
+        let data_directory = ensure_node_home_directory_exists(
+            "neighborhood",
+            "database_is_saved_after_gossip_and_restored_after_a_restart",
+        );
+        let this_node = NodeRecord::new_for_tests(
+            &cryptde.public_key(),
+            Some(&NodeAddr::new(
+                &IpAddr::from_str("5.4.3.2").unwrap(),
+                &vec![1234],
+            )),
+            true,
+        );
+        let mut gossip_neighbor = make_node_record(4567, true, false);
+        gossip_neighbor
+            .neighbors_mut()
+            .push(this_node.public_key().clone());
+        let gossip = GossipBuilder::new().node(&gossip_neighbor, true).build();
+        let serialized_gossip = PlainData::new(&serde_cbor::ser::to_vec(&gossip).unwrap()[..]);
+        let cores_package = ExpiredCoresPackagePackage {
+            expired_cores_package: ExpiredCoresPackage::new(
+                make_meaningless_route(),
+                serialized_gossip,
+            ),
+            sender_ip: IpAddr::from_str("4.5.6.7").unwrap(),
+        };
+        let config = NeighborhoodConfig {
+            neighbor_configs: vec![],
+            is_bootstrap_node: true,
+            local_ip_addr: this_node.node_addr_opt().unwrap().ip_addr(),
+            clandestine_port_list: this_node.node_addr_opt().unwrap().ports(),
+            wallet: this_node.wallet(),
+            cell_size_opt: None,
+            rate_pack: RatePack::default(),
+            route_randomness: 0,
+            data_directory_opt: Some(data_directory.clone()),
+            max_node_age: Duration::from_secs(3600),
+            max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+        };
+        let hopper = Recorder::new();
+        let hopper_awaiter = hopper.get_awaiter();
+        let config_inside = config.clone();
+        thread::spawn(move || {
+            let system = System::new("database_is_saved_after_gossip_and_restored_after_a_restart");
+            let mut subject = Neighborhood::new(cryptde, config_inside);
+            let mut gossip_acceptor = GossipAcceptorReal::new(cryptde);
+            gossip_acceptor.tcp_stream_factory = Box::new(
+                TcpStreamWrapperFactoryMock::new()
+                    .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Ok(()))),
+            );
+            subject.gossip_acceptor = Box::new(gossip_acceptor);
+            let addr: Addr<Syn, Neighborhood> = subject.start();
+            let peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(cores_package).unwrap();
+
+            system.run();
+        });
+        hopper_awaiter.await_message_count(1);
+
+        let restarted = Neighborhood::new(cryptde, config);
+
+        let database = &restarted.neighborhood_database;
+        assert_eq!(
+            database.node_by_key(gossip_neighbor.public_key()),
+            Some(&gossip_neighbor)
+        );
+        assert_eq!(
+            database.root().neighbors(),
+            &vec![gossip_neighbor.public_key().clone()]
+        );
+        // Once for the new neighbor, and once more for the restart
+        assert_eq!(database.root().version(), 2);
+    }
+
+    #[test]
+    fn unusable_saved_database_is_logged_and_ignored() {
+        init_test_logging();
+        let data_directory = ensure_node_home_directory_exists(
+            "neighborhood",
+            "unusable_saved_database_is_logged_and_ignored",
+        );
+        ::std::fs::write(
+            data_directory.join(SNAPSHOT_FILENAME),
+            b"this is not a snapshot",
+        )
+        .unwrap();
+
+        let subject = Neighborhood::new(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: Some(data_directory),
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+            },
+        );
+
+        assert_eq!(subject.neighborhood_database.keys().len(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Neighborhood: Could not restore saved database; starting from scratch: Corrupt(",
+        );
+    }
+
+    struct NeighborProberMock {
+        live_node_addrs: Vec<NodeAddr>,
+    }
+
+    impl NeighborProber for NeighborProberMock {
+        fn probe(&self, node_addr: &NodeAddr) -> bool {
+            self.live_node_addrs.contains(node_addr)
+        }
+
+        fn dup(&self) -> Box<NeighborProber> {
+            Box::new(NeighborProberMock {
+                live_node_addrs: self.live_node_addrs.clone(),
+            })
+        }
+    }
+
+    #[test]
+    fn neighbors_that_stop_answering_probes_are_evicted_and_gossiped_about() {
+        let cryptde = cryptde();
+        let dead_neighbor = make_node_record(2345, true, false);
+        let live_neighbor = make_node_record(3456, true, false);
+        let hopper = Recorder::new();
+        let hopper_awaiter = hopper.get_awaiter();
+        let hopper_recording = hopper.get_recording();
+        let dead_neighbor_inside = dead_neighbor.clone();
+        let live_neighbor_inside = live_neighbor.clone();
+        thread::spawn(move || {
+            let system =
+                System::new("neighbors_that_stop_answering_probes_are_evicted_and_gossiped_about");
+            let mut subject = Neighborhood::new(
+                cryptde,
+                NeighborhoodConfig {
+                    neighbor_configs: vec![],
+                    is_bootstrap_node: true,
+                    local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                    clandestine_port_list: vec![1234],
+                    wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+                },
+            );
+            {
+                let db = &mut subject.neighborhood_database;
+                let root = db.root().clone();
+                db.add_node(&dead_neighbor_inside).unwrap();
+                db.add_node(&live_neighbor_inside).unwrap();
+                dual_edge_func(db, &root, &dead_neighbor_inside);
+                dual_edge_func(db, &root, &live_neighbor_inside);
+            }
+            subject.neighbor_prober = Box::new(NeighborProberMock {
+                live_node_addrs: vec![live_neighbor_inside.node_addr_opt().unwrap()],
+            });
+            let addr: Addr<Syn, Neighborhood> = subject.start();
+            let peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            (0..MAX_MISSED_PROBES).for_each(|_| addr.try_send(ProbeNeighborsMessage {}).unwrap());
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let locked_recording = hopper_recording.lock().unwrap();
+        let package: &IncipientCoresPackage = locked_recording.get_record(0);
+        assert_eq!(&package.payload_destination_key, live_neighbor.public_key());
+        let gossip: Gossip = serde_cbor::de::from_slice(&package.payload.data[..]).unwrap();
+        let root_record = gossip
+            .node_records
+            .iter()
+            .find(|gnr| gnr.inner.public_key == cryptde.public_key())
+            .unwrap();
+        assert_eq!(
+            root_record.inner.neighbors,
+            vec![live_neighbor.public_key().clone()]
+        );
+    }
+
+    #[test]
+    fn refresh_check_gossips_to_neighbors_that_are_due_even_though_nothing_changed() {
+        let cryptde = cryptde();
+        let neighbor = make_node_record(2345, true, false);
+        let hopper = Recorder::new();
+        let hopper_awaiter = hopper.get_awaiter();
+        let hopper_recording = hopper.get_recording();
+        let neighbor_inside = neighbor.clone();
+        thread::spawn(move || {
+            let system = System::new(
+                "refresh_check_gossips_to_neighbors_that_are_due_even_though_nothing_changed",
+            );
+            let mut subject = Neighborhood::new(
+                cryptde,
+                NeighborhoodConfig {
+                    neighbor_configs: vec![],
+                    is_bootstrap_node: true,
+                    local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                    clandestine_port_list: vec![1234],
+                    wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+                },
+            );
+            {
+                let db = &mut subject.neighborhood_database;
+                let root = db.root().clone();
+                db.add_node(&neighbor_inside).unwrap();
+                dual_edge_func(db, &root, &neighbor_inside);
+            }
+            let addr: Addr<Syn, Neighborhood> = subject.start();
+            let peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(RefreshGossipMessage {}).unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let locked_recording = hopper_recording.lock().unwrap();
+        let package: &IncipientCoresPackage = locked_recording.get_record(0);
+        assert_eq!(&package.payload_destination_key, neighbor.public_key());
+        let gossip: Gossip = serde_cbor::de::from_slice(&package.payload.data[..]).unwrap();
+        assert_eq!(gossip.node_records.len(), 2);
+    }
+
+    #[test]
+    fn routes_do_not_go_through_nodes_that_have_stopped_answering() {
+        let q = make_node_record(3456, true, false).public_key().clone();
+        let r = make_node_record(4567, true, false).public_key().clone();
+        let mut subject = make_priced_subject(make_rate_pack(10, 0), make_rate_pack(5, 0));
+        (0..MAX_MISSED_PROBES).for_each(|_| {
+            subject.liveness.missed_probe(&r);
+        });
+
+        let result = subject
+            .make_one_way_route(RouteQueryMessage::data_indefinite_route_request(2))
+            .unwrap();
+
+        let next_key = result.route.next_hop(cryptde()).unwrap().public_key;
+        assert_eq!(next_key, q);
+    }
+
+    struct GossipAcceptorBanningMock {
+        new_bans: Vec<IpAddr>,
+    }
+
+    impl GossipAcceptor for GossipAcceptorBanningMock {
+        fn handle(
+            &mut self,
+            _database: &mut NeighborhoodDatabase,
+            _gossip: Gossip,
+            sender_ip: IpAddr,
+        ) -> GossipAcceptance {
+            self.new_bans.push(sender_ip);
+            GossipAcceptance::Refused
+        }
+
+        fn take_new_bans(&mut self) -> Vec<IpAddr> {
+            self.new_bans.drain(..).collect()
+        }
+    }
+
+    #[test]
+    fn peers_banned_by_the_gossip_acceptor_are_banned_at_the_dispatcher_too() {
+        let cryptde = cryptde();
+        let sender_ip = IpAddr::from_str("6.6.6.6").unwrap();
+        let gossip = GossipBuilder::new()
+            .node(&make_node_record(2345, true, false), true)
+            .build();
+        let cores_package = ExpiredCoresPackagePackage {
+            expired_cores_package: ExpiredCoresPackage::new(
+                make_meaningless_route(),
+                PlainData::new(&serde_cbor::ser::to_vec(&gossip).unwrap()[..]),
+            ),
+            sender_ip,
+        };
+        let dispatcher = Recorder::new();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let dispatcher_recording = dispatcher.get_recording();
+        thread::spawn(move || {
+            let system =
+                System::new("peers_banned_by_the_gossip_acceptor_are_banned_at_the_dispatcher_too");
+            let mut subject = Neighborhood::new(
+                cryptde,
+                NeighborhoodConfig {
+                    neighbor_configs: vec![],
+                    is_bootstrap_node: true,
+                    local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                    clandestine_port_list: vec![1234],
+                    wallet: None,
+                    cell_size_opt: None,
+                    rate_pack: RatePack::default(),
+                    route_randomness: 0,
+                    data_directory_opt: None,
+                    max_node_age: Duration::from_secs(3600),
+                    max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
+                },
+            );
+            subject.gossip_acceptor = Box::new(GossipAcceptorBanningMock { new_bans: vec![] });
+            let addr: Addr<Syn, Neighborhood> = subject.start();
+            let peer_actors = make_peer_actors_from(None, Some(dispatcher), None, None, None, None);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(cores_package).unwrap();
+
+            system.run();
+        });
+        dispatcher_awaiter.await_message_count(1);
+
+        let recording = dispatcher_recording.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<BanPeerMessage>(0),
+            &BanPeerMessage {
+                ip_addr: sender_ip,
+                duration: Duration::from_secs(BAN_DURATION_SECS),
+            }
+        );
+    }
+
+    #[test]
+    fn gossip_from_a_banned_sender_is_no_sign_of_life() {
+        let mut subject = make_plain_subject();
+        let sender = make_node_record(6666, true, false);
+        let sender_ip = sender.node_addr_opt().unwrap().ip_addr();
+        subject.neighborhood_database.add_node(&sender).unwrap();
+        let long_ago = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        subject
+            .neighborhood_database
+            .heard_from(sender.public_key(), long_ago);
+        (0..MAX_MISSED_PROBES).for_each(|_| {
+            subject.liveness.missed_probe(sender.public_key());
+        });
+        let mut gossip_acceptor = GossipAcceptorReal::new(cryptde());
+        let flood = GOSSIP_BURST + BAN_THRESHOLD / Offense::RateLimitExceeded.penalty();
+        (0..flood).for_each(|_| {
+            gossip_acceptor.handle(
+                &mut subject.neighborhood_database,
+                Gossip {
+                    node_records: vec![],
+                },
+                sender_ip,
+            );
+        });
+        assert_eq!(gossip_acceptor.take_new_bans(), vec![sender_ip]);
+        subject.gossip_acceptor = Box::new(gossip_acceptor);
+        let gossip = GossipBuilder::new().node(&sender, true).build();
+
+        subject.handle_gossip(ExpiredCoresPackagePackage {
+            expired_cores_package: ExpiredCoresPackage::new(
+                make_meaningless_route(),
+                PlainData::new(&serde_cbor::ser::to_vec(&gossip).unwrap()[..]),
+            ),
+            sender_ip,
+        });
+
+        assert!(subject.liveness.is_dead(sender.public_key()));
+        assert_eq!(
+            subject
+                .neighborhood_database
+                .last_heard(sender.public_key()),
+            Some(long_ago)
+        );
+    }
+
+    #[test]
+    fn only_records_that_verify_show_that_their_nodes_are_still_around() {
+        let mut subject = make_plain_subject();
+        let (victim, _) = make_genuinely_signed_node_record(2345, false);
+        let (genuine, _) = make_genuinely_signed_node_record(3456, false);
+        subject.neighborhood_database.add_node(&victim).unwrap();
+        subject.neighborhood_database.add_node(&genuine).unwrap();
+        let long_ago = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        subject
+            .neighborhood_database
+            .heard_from(victim.public_key(), long_ago);
+        subject
+            .neighborhood_database
+            .heard_from(genuine.public_key(), long_ago);
+        subject.gossip_acceptor = Box::new(GossipAcceptorReal::new(Box::leak(Box::new(
+            CryptDEReal::new(),
+        ))));
+        let gossip = TamperingGossipBuilder::new()
+            .node(&victim, false)
+            .node(&genuine, false)
+            .tamper(victim.public_key(), |inner| inner.version += 1)
+            .build();
+
+        subject.handle_gossip(ExpiredCoresPackagePackage {
+            expired_cores_package: ExpiredCoresPackage::new(
+                make_meaningless_route(),
+                PlainData::new(&serde_cbor::ser::to_vec(&gossip).unwrap()[..]),
+            ),
+            sender_ip: IpAddr::from_str("6.6.6.6").unwrap(),
+        });
+
+        assert_eq!(
+            subject
+                .neighborhood_database
+                .last_heard(victim.public_key()),
+            Some(long_ago)
+        );
+        assert!(
+            subject
+                .neighborhood_database
+                .last_heard(genuine.public_key())
+                .unwrap()
+                > long_ago
+        );
+    }
+
+    #[test]
+    fn gossip_about_a_very_large_neighborhood_is_sent_in_packages_that_fit() {
+        // Few enough packages to fit in the Recorder's mailbox before the System starts running
+        let max_gossip_size = 32768;
+        let target_key = make_node_record(1000, true, false).public_key().clone();
+        let hopper = Recorder::new();
+        let hopper_awaiter = hopper.get_awaiter();
+        let hopper_recording = hopper.get_recording();
+        let (expected_chunk_count_tx, expected_chunk_count_rx) = ::std::sync::mpsc::channel();
+        let target_key_inside = target_key.clone();
+        thread::spawn(move || {
+            let system =
+                System::new("gossip_about_a_very_large_neighborhood_is_sent_in_packages_that_fit");
+            let mut subject = make_big_subject(1000);
+            subject.max_gossip_size = max_gossip_size;
+            let full_gossip = subject
+                .gossip_producer
+                .produce(&subject.neighborhood_database, &target_key_inside);
+            expected_chunk_count_tx
+                .send((
+                    full_gossip.node_records.len(),
+                    full_gossip.chunks(max_gossip_size).len(),
+                ))
+                .unwrap();
+            let peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+            subject.hopper = Some(peer_actors.hopper.from_hopper_client);
+
+            subject.gossip_to(&vec![target_key_inside]);
+            let mut later = Instant::now();
+            while !subject.gossip_outbox.is_empty() {
+                later += Duration::from_millis(OUTBOX_INTERVAL_MILLIS);
+                subject.send_outbox(later);
+            }
+
+            system.run();
+        });
+        let (record_count, chunk_count) = expected_chunk_count_rx.recv().unwrap();
+        hopper_awaiter.await_message_count(chunk_count);
+
+        assert!(chunk_count > 1, "Gossip was not split");
+        let recording = hopper_recording.lock().unwrap();
+        let received_record_count: usize = (0..chunk_count)
+            .map(|index| {
+                let package: &IncipientCoresPackage = recording.get_record(index);
+                assert!(package.payload.data.len() <= max_gossip_size);
+                assert_eq!(&package.payload_destination_key, &target_key);
+                let gossip: Gossip = serde_cbor::de::from_slice(&package.payload.data[..]).unwrap();
+                gossip.node_records.len()
+            })
+            .sum();
+        assert_eq!(received_record_count, record_count);
+    }
+
+    #[test]
+    fn gossip_about_a_very_large_neighborhood_is_paced_so_that_its_target_takes_all_of_it() {
+        let max_gossip_size = 8192;
+        let sender = make_big_subject(1000);
+        let sender_ip = IpAddr::from_str("6.6.6.6").unwrap();
+        let target = make_node_record(1000, true, false);
+        let mut full_gossip = sender
+            .gossip_producer
+            .produce(&sender.neighborhood_database, target.public_key());
+        // The sender isn't decentralized, so its own record has a NodeAddr with no ports in it, which is no use to
+        // the target.
+        let sender_key = sender.neighborhood_database.root().public_key().clone();
+        full_gossip
+            .node_records
+            .retain(|gnr| gnr.inner.public_key != sender_key);
+        let record_keys: Vec<Key> = full_gossip
+            .node_records
+            .iter()
+            .map(|gnr| gnr.inner.public_key.clone())
+            .collect();
+        let revealed_count = full_gossip
+            .node_records
+            .iter()
+            .filter(|gnr| {
+                gnr.inner.node_addr_opt.is_some() && (&gnr.inner.public_key != target.public_key())
+            })
+            .count();
+        let chunks = full_gossip.chunks(max_gossip_size);
+        assert!(chunks.len() > GOSSIP_BURST as usize, "{}", chunks.len());
+        let make_target = || {
+            let mut database = NeighborhoodDatabase::new(
+                target.public_key(),
+                target.node_addr_opt().as_ref().unwrap(),
+                None,
+                false,
+                cryptde(),
+            );
+            let mut acceptor = GossipAcceptorReal::new(cryptde());
+            let mut tcp_stream_factory = TcpStreamWrapperFactoryMock::new();
+            for _ in 0..revealed_count {
+                tcp_stream_factory =
+                    tcp_stream_factory
+                        .tcp_stream_wrapper(TcpStreamWrapperMock::new().connect_result(Err(
+                            io::Error::from(io::ErrorKind::ConnectionRefused),
+                        )));
+            }
+            acceptor.tcp_stream_factory = Box::new(tcp_stream_factory);
+            (database, acceptor)
+        };
+        let start = Instant::now();
+        let (mut unpaced_database, mut unpaced_acceptor) = make_target();
+        let (mut database, mut acceptor) = make_target();
+        let mut outbox = GossipOutbox::new();
+        outbox.post(target.public_key(), chunks.clone(), start);
+
+        let unpaced_acceptances: Vec<GossipAcceptance> = chunks
+            .into_iter()
+            .map(|chunk| unpaced_acceptor.handle_at(&mut unpaced_database, chunk, sender_ip, start))
+            .collect();
+        let mut acceptances = vec![];
+        let mut now = start;
+        while !outbox.is_empty() {
+            outbox
+                .take_sendable(now)
+                .into_iter()
+                .for_each(|(_, chunk)| {
+                    acceptances.push(acceptor.handle_at(&mut database, chunk, sender_ip, now))
+                });
+            now += Duration::from_millis(OUTBOX_INTERVAL_MILLIS);
+        }
+
+        assert!(unpaced_acceptances.contains(&GossipAcceptance::Refused));
+        assert!(!acceptances.contains(&GossipAcceptance::Refused));
+        assert_eq!(acceptor.take_new_bans(), Vec::<IpAddr>::new());
+        record_keys
+            .iter()
+            .for_each(|key| assert!(database.node_by_key(key).is_some(), "{} is missing", key));
+    }
+
+    #[test]
+    fn oversized_incoming_gossip_is_ignored_without_being_deserialized_or_held_against_its_sender()
+    {
+        init_test_logging();
+        let sender_ip = IpAddr::from_str("6.6.6.7").unwrap();
+        let mut subject = make_plain_subject();
+        subject.max_gossip_size = 1000;
+        subject.gossip_acceptor = Box::new(GossipAcceptorBanningMock { new_bans: vec![] });
+
+        // Not CBOR at all, so any attempt to deserialize it would complain
+        subject.handle_gossip(ExpiredCoresPackagePackage {
+            expired_cores_package: ExpiredCoresPackage::new(
+                make_meaningless_route(),
+                PlainData::new(&[0xFF; 1001]),
+            ),
+            sender_ip,
+        });
+
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Neighborhood: Ignoring 1001-byte Gossip from 6.6.6.7: no more than 1000 bytes are allowed",
+        );
+        assert_eq!(
+            subject.gossip_acceptor.take_new_bans(),
+            Vec::<IpAddr>::new()
+        );
+    }
 }
@@ -8,10 +8,12 @@ use std::fmt::Debug;
 use std::fmt::Error;
 use std::fmt::Formatter;
 use std::net::IpAddr;
+use std::time::SystemTime;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::CryptData;
 use sub_lib::cryptde::Key;
 use sub_lib::cryptde::PlainData;
+use sub_lib::neighborhood::RatePack;
 use sub_lib::node_addr::NodeAddr;
 use sub_lib::wallet::Wallet;
 
@@ -23,6 +25,16 @@ pub struct NodeRecordInner {
     pub is_bootstrap_node: bool,
     pub neighbors: Vec<Key>,
     pub version: u32,
+    // Omitted when false so that records from Nodes that predate it still verify
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub supports_cells: bool,
+    // Omitted when free, for the same reason
+    #[serde(default, skip_serializing_if = "RatePack::is_free")]
+    pub rate_pack: RatePack,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 impl NodeRecordInner {
@@ -30,6 +42,21 @@ impl NodeRecordInner {
     // For now, this is only called at initialization time (NeighborhoodDatabase) and in tests, so panicking is OK.
     // When we start signing NodeRecords at other times, we should probably not panic
     pub fn generate_signature(&self, cryptde: &CryptDE) -> CryptData {
+        cryptde.sign(&self.signable()).expect(&format!(
+            "NodeRecord content {:?} could not be signed",
+            &self
+        ))
+    }
+
+    // What's left when the NodeAddr is hidden; this is what the obscured signature signs
+    pub fn obscured(&self) -> NodeRecordInner {
+        NodeRecordInner {
+            node_addr_opt: None,
+            ..self.clone()
+        }
+    }
+
+    fn signable(&self) -> PlainData {
         let serialized = match serde_cbor::ser::to_vec(&self) {
             Ok(inner) => inner,
             Err(_) => panic!("NodeRecord content {:?} could not be serialized", &self),
@@ -37,13 +64,7 @@ impl NodeRecordInner {
 
         let mut hash = sha1::Sha1::new();
         hash.update(&serialized[..]);
-
-        cryptde
-            .sign(&PlainData::new(&hash.digest().bytes()))
-            .expect(&format!(
-                "NodeRecord content {:?} could not be signed",
-                &self
-            ))
+        PlainData::new(&hash.digest().bytes())
     }
 }
 
@@ -61,14 +82,7 @@ impl NodeSignatures {
     pub fn from(cryptde: &CryptDE, node_record_inner: &NodeRecordInner) -> Self {
         let complete_signature = node_record_inner.generate_signature(cryptde);
 
-        let obscured_inner = NodeRecordInner {
-            public_key: node_record_inner.clone().public_key,
-            node_addr_opt: None,
-            wallet: node_record_inner.wallet.clone(),
-            is_bootstrap_node: node_record_inner.is_bootstrap_node,
-            neighbors: node_record_inner.neighbors.clone(),
-            version: node_record_inner.version,
-        };
+        let obscured_inner = node_record_inner.obscured();
         let obscured_signature = obscured_inner.generate_signature(cryptde);
 
         NodeSignatures::new(complete_signature, obscured_signature)
@@ -81,6 +95,17 @@ impl NodeSignatures {
     pub fn obscured(&self) -> &CryptData {
         &self.obscured
     }
+
+    // The complete signature covers the NodeAddr, so it can only be checked if we know the NodeAddr.
+    pub fn verify(&self, cryptde: &CryptDE, node_record_inner: &NodeRecordInner) -> bool {
+        let public_key = &node_record_inner.public_key;
+        let obscured_inner = node_record_inner.obscured();
+        let obscured_verifies =
+            cryptde.verify_signature(&obscured_inner.signable(), &self.obscured, public_key);
+        let complete_verifies = node_record_inner.node_addr_opt.is_none()
+            || cryptde.verify_signature(&node_record_inner.signable(), &self.complete, public_key);
+        obscured_verifies && complete_verifies
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -110,11 +135,21 @@ impl NodeRecord {
                 is_bootstrap_node,
                 neighbors: vec![],
                 version,
+                supports_cells: false,
+                rate_pack: RatePack::default(),
             },
             signatures,
         }
     }
 
+    pub fn from_inner(inner: NodeRecordInner, signatures: Option<NodeSignatures>) -> NodeRecord {
+        NodeRecord { inner, signatures }
+    }
+
+    pub fn inner(&self) -> &NodeRecordInner {
+        &self.inner
+    }
+
     pub fn public_key(&self) -> &Key {
         &self.inner.public_key
     }
@@ -187,6 +222,13 @@ impl NodeRecord {
         self.signatures.clone()
     }
 
+    pub fn has_valid_signatures(&self, cryptde: &CryptDE) -> bool {
+        match self.signatures {
+            Some(ref signatures) => signatures.verify(cryptde, &self.inner),
+            None => false,
+        }
+    }
+
     pub fn sign(&mut self, cryptde: &CryptDE) {
         self.signatures = Some(NodeSignatures::from(cryptde, &self.inner))
     }
@@ -224,12 +266,39 @@ impl NodeRecord {
             true
         }
     }
+
+    pub fn supports_cells(&self) -> bool {
+        self.inner.supports_cells
+    }
+
+    pub fn set_supports_cells(&mut self, supports_cells: bool) -> bool {
+        if self.inner.supports_cells == supports_cells {
+            false
+        } else {
+            self.inner.supports_cells = supports_cells;
+            true
+        }
+    }
+
+    pub fn rate_pack(&self) -> &RatePack {
+        &self.inner.rate_pack
+    }
+
+    pub fn set_rate_pack(&mut self, rate_pack: RatePack) -> bool {
+        if self.inner.rate_pack == rate_pack {
+            false
+        } else {
+            self.inner.rate_pack = rate_pack;
+            true
+        }
+    }
 }
 
 pub struct NeighborhoodDatabase {
     this_node: Key,
     by_public_key: HashMap<Key, NodeRecord>,
     by_ip_addr: HashMap<IpAddr, Key>,
+    last_heard: HashMap<Key, SystemTime>,
 }
 
 impl Debug for NeighborhoodDatabase {
@@ -250,6 +319,7 @@ impl NeighborhoodDatabase {
             this_node: public_key.clone(),
             by_public_key: HashMap::new(),
             by_ip_addr: HashMap::new(),
+            last_heard: HashMap::new(),
         };
 
         let mut node_record = NodeRecord::new(
@@ -276,6 +346,13 @@ impl NeighborhoodDatabase {
         self.node_by_key_mut(root_key).expect("Internal error")
     }
 
+    // Call after changing the root NodeRecord, so that its new version carries signatures that match it.
+    pub fn root_changed(&mut self, cryptde: &CryptDE) {
+        let root = self.root_mut();
+        root.increment_version();
+        root.sign(cryptde);
+    }
+
     pub fn keys(&self) -> HashSet<&Key> {
         self.by_public_key.keys().into_iter().collect()
     }
@@ -317,9 +394,42 @@ impl NeighborhoodDatabase {
             }
             None => (),
         }
+        self.last_heard
+            .insert(node_record.inner.public_key.clone(), SystemTime::now());
         Ok(())
     }
 
+    pub fn replace_node(
+        &mut self,
+        node_record: &NodeRecord,
+    ) -> Result<(), NeighborhoodDatabaseError> {
+        let public_key = &node_record.inner.public_key;
+        let old_ip_addr_opt = match self.node_by_key(public_key) {
+            Some(old_node_record) => old_node_record.node_addr_opt().map(|addr| addr.ip_addr()),
+            None => return Err(NodeKeyNotFound(public_key.clone())),
+        };
+        if let Some(old_ip_addr) = old_ip_addr_opt {
+            self.by_ip_addr.remove(&old_ip_addr);
+        }
+        if let Some(ref node_addr) = node_record.inner.node_addr_opt {
+            self.by_ip_addr
+                .insert(node_addr.ip_addr(), public_key.clone());
+        }
+        self.by_public_key
+            .insert(public_key.clone(), node_record.clone());
+        Ok(())
+    }
+
+    pub fn heard_from(&mut self, public_key: &Key, when: SystemTime) {
+        if let Some(last_heard) = self.last_heard.get_mut(public_key) {
+            *last_heard = when;
+        }
+    }
+
+    pub fn last_heard(&self, public_key: &Key) -> Option<SystemTime> {
+        self.last_heard.get(public_key).cloned()
+    }
+
     pub fn remove_neighbor(&mut self, node_key: &Key) -> Result<bool, String> {
         let ip_addr: Option<IpAddr>;
         {
@@ -419,10 +529,13 @@ pub enum NeighborhoodDatabaseError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use neighborhood_test_utils::make_genuinely_signed_node_record;
     use neighborhood_test_utils::make_node_record;
     use std::iter::FromIterator;
     use std::str::FromStr;
+    use std::time::Duration;
     use sub_lib::cryptde_null::CryptDENull;
+    use sub_lib::cryptde_real::CryptDEReal;
 
     #[test]
     fn a_brand_new_database_has_the_expected_contents() {
@@ -818,6 +931,8 @@ mod tests {
             wallet: Some(Wallet::new("0x1234")),
             neighbors: Vec::new(),
             version: 0,
+            supports_cells: false,
+            rate_pack: RatePack::default(),
         };
         let cryptde = CryptDENull::from(&to_be_signed.public_key);
 
@@ -835,6 +950,150 @@ mod tests {
         )
     }
 
+    #[test]
+    fn genuine_signatures_verify_with_and_without_the_node_addr() {
+        let (node_record, _) = make_genuinely_signed_node_record(1234, true);
+        let mut without_node_addr = node_record.clone();
+        without_node_addr.unset_node_addr();
+        let cryptde = CryptDEReal::new();
+
+        assert_eq!(node_record.has_valid_signatures(&cryptde), true);
+        assert_eq!(without_node_addr.has_valid_signatures(&cryptde), true);
+    }
+
+    #[test]
+    fn tampered_records_do_not_verify() {
+        let (node_record, _) = make_genuinely_signed_node_record(1234, true);
+        let mut new_version = node_record.clone();
+        new_version.increment_version();
+        let mut new_node_addr = node_record.clone();
+        new_node_addr.unset_node_addr();
+        new_node_addr
+            .set_node_addr(&NodeAddr::new(
+                &IpAddr::from_str("4.3.2.1").unwrap(),
+                &vec![4321],
+            ))
+            .unwrap();
+        let mut new_neighbor = node_record.clone();
+        new_neighbor.neighbors_mut().push(Key::new(b"sneaky"));
+        let (_, impostor) = make_genuinely_signed_node_record(2345, true);
+        let mut signed_by_impostor = node_record.clone();
+        signed_by_impostor.sign(&impostor);
+        let cryptde = CryptDEReal::new();
+
+        assert_eq!(new_version.has_valid_signatures(&cryptde), false);
+        assert_eq!(new_node_addr.has_valid_signatures(&cryptde), false);
+        assert_eq!(new_neighbor.has_valid_signatures(&cryptde), false);
+        assert_eq!(signed_by_impostor.has_valid_signatures(&cryptde), false);
+    }
+
+    #[test]
+    fn root_changed_increments_the_root_version_and_re_signs_it() {
+        let cryptde = CryptDEReal::new();
+        let this_node_addr = NodeAddr::new(&IpAddr::from_str("1.2.3.4").unwrap(), &vec![1234]);
+        let mut subject = NeighborhoodDatabase::new(
+            &cryptde.public_key(),
+            &this_node_addr,
+            None,
+            false,
+            &cryptde,
+        );
+        subject.root_mut().set_supports_cells(true);
+        let changed_but_unsigned = subject.root().clone();
+
+        subject.root_changed(&cryptde);
+
+        assert_eq!(changed_but_unsigned.has_valid_signatures(&cryptde), false);
+        assert_eq!(subject.root().version(), 1);
+        assert_eq!(subject.root().has_valid_signatures(&cryptde), true);
+    }
+
+    #[test]
+    fn unsigned_records_do_not_verify() {
+        let subject = NodeRecord::new(&Key::new(b"unsigned"), None, None, false, None, 0);
+
+        assert_eq!(subject.has_valid_signatures(&CryptDENull::new()), false);
+    }
+
+    #[test]
+    fn replace_node_updates_the_record_and_its_ip_address() {
+        let this_node = make_node_record(1234, true, false);
+        let old_node = make_node_record(2345, true, false);
+        let mut new_node = old_node.clone();
+        new_node.unset_node_addr();
+        new_node
+            .set_node_addr(&NodeAddr::new(
+                &IpAddr::from_str("5.4.3.2").unwrap(),
+                &vec![5432],
+            ))
+            .unwrap();
+        new_node.increment_version();
+        let mut subject = NeighborhoodDatabase::new(
+            this_node.public_key(),
+            this_node.node_addr_opt().as_ref().unwrap(),
+            this_node.wallet(),
+            this_node.is_bootstrap_node(),
+            &CryptDENull::from(this_node.public_key()),
+        );
+        subject.add_node(&old_node).unwrap();
+
+        let result = subject.replace_node(&new_node);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(subject.node_by_key(old_node.public_key()), Some(&new_node));
+        assert_eq!(
+            subject.node_by_ip(&IpAddr::from_str("5.4.3.2").unwrap()),
+            Some(&new_node)
+        );
+        assert_eq!(
+            subject.node_by_ip(&old_node.node_addr_opt().unwrap().ip_addr()),
+            None
+        );
+    }
+
+    #[test]
+    fn replace_node_complains_about_unknown_nodes() {
+        let this_node = make_node_record(1234, true, false);
+        let stranger = make_node_record(2345, true, false);
+        let mut subject = NeighborhoodDatabase::new(
+            this_node.public_key(),
+            this_node.node_addr_opt().as_ref().unwrap(),
+            this_node.wallet(),
+            this_node.is_bootstrap_node(),
+            &CryptDENull::from(this_node.public_key()),
+        );
+
+        let result = subject.replace_node(&stranger);
+
+        assert_eq!(result, Err(NodeKeyNotFound(stranger.public_key().clone())));
+    }
+
+    #[test]
+    fn heard_from_records_when_known_nodes_were_last_heard_from() {
+        let this_node = make_node_record(1234, true, false);
+        let other_node = make_node_record(2345, true, false);
+        let stranger = make_node_record(3456, true, false);
+        let mut subject = NeighborhoodDatabase::new(
+            this_node.public_key(),
+            this_node.node_addr_opt().as_ref().unwrap(),
+            this_node.wallet(),
+            this_node.is_bootstrap_node(),
+            &CryptDENull::from(this_node.public_key()),
+        );
+        let before_adding = SystemTime::now();
+        subject.add_node(&other_node).unwrap();
+        let after_adding = SystemTime::now();
+        let long_ago = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+
+        let when_added = subject.last_heard(other_node.public_key()).unwrap();
+        subject.heard_from(other_node.public_key(), long_ago);
+        subject.heard_from(stranger.public_key(), long_ago);
+
+        assert!(when_added >= before_adding && when_added <= after_adding);
+        assert_eq!(subject.last_heard(other_node.public_key()), Some(long_ago));
+        assert_eq!(subject.last_heard(stranger.public_key()), None);
+    }
+
     #[test]
     fn node_record_partial_eq() {
         let exemplar = NodeRecord::new(
@@ -1,13 +1,19 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use gossip::Gossip;
+use gossip::GossipNodeRecord;
 use neighborhood_database::NeighborhoodDatabase;
 use neighborhood_database::NodeRecord;
+use neighborhood_database::NodeRecordInner;
+use neighborhood_database::NodeSignatures;
 use std::collections::HashSet;
 use std::hash::Hash;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
+use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::Key;
 use sub_lib::cryptde_null::CryptDENull;
+use sub_lib::cryptde_real::CryptDEReal;
 use sub_lib::node_addr::NodeAddr;
 use sub_lib::wallet::Wallet;
 
@@ -27,6 +33,73 @@ pub fn make_node_record(n: u16, has_ip: bool, is_bootstrap_node: bool) -> NodeRe
     )
 }
 
+// Like make_node_record, but signed with a real key pair, so that tampering with it can be detected
+pub fn make_genuinely_signed_node_record(n: u16, has_ip: bool) -> (NodeRecord, CryptDEReal) {
+    let cryptde = CryptDEReal::new();
+    let template = make_node_record(n, has_ip, false);
+    let mut node_record = NodeRecord::new(
+        &cryptde.public_key(),
+        template.node_addr_opt().as_ref(),
+        template.wallet(),
+        false,
+        None,
+        0,
+    );
+    node_record.sign(&cryptde);
+    (node_record, cryptde)
+}
+
+// Builds Gossip the way a malicious Node might: records can be changed after they're signed, or signed by the
+// wrong Node.
+pub struct TamperingGossipBuilder {
+    node_records: Vec<GossipNodeRecord>,
+}
+
+impl TamperingGossipBuilder {
+    pub fn new() -> TamperingGossipBuilder {
+        TamperingGossipBuilder {
+            node_records: vec![],
+        }
+    }
+
+    pub fn node(
+        mut self,
+        node_record: &NodeRecord,
+        reveal_node_addr: bool,
+    ) -> TamperingGossipBuilder {
+        self.node_records
+            .push(GossipNodeRecord::from(node_record, reveal_node_addr));
+        self
+    }
+
+    pub fn tamper<F>(mut self, public_key: &Key, change: F) -> TamperingGossipBuilder
+    where
+        F: FnOnce(&mut NodeRecordInner),
+    {
+        change(&mut self.record_mut(public_key).inner);
+        self
+    }
+
+    pub fn sign_with(mut self, public_key: &Key, cryptde: &CryptDE) -> TamperingGossipBuilder {
+        let gnr = self.record_mut(public_key);
+        gnr.signatures = NodeSignatures::from(cryptde, &gnr.inner);
+        self
+    }
+
+    pub fn build(self) -> Gossip {
+        Gossip {
+            node_records: self.node_records,
+        }
+    }
+
+    fn record_mut(&mut self, public_key: &Key) -> &mut GossipNodeRecord {
+        self.node_records
+            .iter_mut()
+            .find(|gnr| &gnr.inner.public_key == public_key)
+            .expect("TamperingGossipBuilder can't tamper with a Node it doesn't have")
+    }
+}
+
 pub fn vec_to_set<T>(vec: Vec<T>) -> HashSet<T>
 where
     T: Eq + Hash,
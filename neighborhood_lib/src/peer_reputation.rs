@@ -0,0 +1,267 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use std::time::Instant;
+
+// Each sender may send this many Gossip messages in a burst...
+pub const GOSSIP_BURST: u32 = 10;
+// ...and earns permission to send one more every this many milliseconds.
+pub const GOSSIP_REFILL_MILLIS: u64 = 1000;
+// A sender whose score reaches this is banned for BAN_DURATION_SECS.
+pub const BAN_THRESHOLD: u32 = 100;
+pub const BAN_DURATION_SECS: u64 = 3600;
+// Scores are forgiven a point at a time, so that an occasional mistake doesn't add up to a ban.
+const SCORE_DECAY_SECS: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Offense {
+    RateLimitExceeded,
+    InvalidRecord,
+    ForgedRecord,
+}
+
+impl Offense {
+    pub fn penalty(&self) -> u32 {
+        match self {
+            Offense::RateLimitExceeded => 5,
+            Offense::InvalidRecord => 10,
+            Offense::ForgedRecord => 25,
+        }
+    }
+}
+
+struct PeerStanding {
+    tokens: u32,
+    last_refill: Instant,
+    score: u32,
+    last_decay: Instant,
+    banned_until_opt: Option<Instant>,
+}
+
+impl PeerStanding {
+    fn new(now: Instant) -> PeerStanding {
+        PeerStanding {
+            tokens: GOSSIP_BURST,
+            last_refill: now,
+            score: 0,
+            last_decay: now,
+            banned_until_opt: None,
+        }
+    }
+
+    fn is_banned(&self, now: Instant) -> bool {
+        match self.banned_until_opt {
+            Some(banned_until) => banned_until > now,
+            None => false,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill);
+        let elapsed_millis = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64;
+        let earned = elapsed_millis / GOSSIP_REFILL_MILLIS;
+        if earned > 0 {
+            self.tokens = (self.tokens as u64 + earned).min(GOSSIP_BURST as u64) as u32;
+            self.last_refill += Duration::from_millis(earned * GOSSIP_REFILL_MILLIS);
+        }
+    }
+
+    fn decay(&mut self, now: Instant) {
+        let forgiven = now.duration_since(self.last_decay).as_secs() / SCORE_DECAY_SECS;
+        if forgiven > 0 {
+            self.score = self
+                .score
+                .saturating_sub(forgiven.min(u32::max_value() as u64) as u32);
+            self.last_decay += Duration::from_secs(forgiven * SCORE_DECAY_SECS);
+        }
+    }
+}
+
+// Keeps track of how well each sender of Gossip has behaved. Senders are identified by IP address, since that's
+// the only thing about them we didn't learn from the Gossip itself. Methods take the current time as a parameter
+// so that they can be tested without waiting.
+pub struct PeerReputation {
+    standings: HashMap<IpAddr, PeerStanding>,
+    new_bans: Vec<IpAddr>,
+}
+
+impl PeerReputation {
+    pub fn new() -> PeerReputation {
+        PeerReputation {
+            standings: HashMap::new(),
+            new_bans: vec![],
+        }
+    }
+
+    pub fn is_banned(&self, ip_addr: &IpAddr, now: Instant) -> bool {
+        match self.standings.get(ip_addr) {
+            Some(standing) => standing.is_banned(now),
+            None => false,
+        }
+    }
+
+    // Uses up one of the sender's Gossip allowance. Returns false if there was none left.
+    pub fn allow_gossip(&mut self, ip_addr: &IpAddr, now: Instant) -> bool {
+        let standing = self.standing_mut(ip_addr, now);
+        standing.refill(now);
+        if standing.tokens == 0 {
+            false
+        } else {
+            standing.tokens -= 1;
+            true
+        }
+    }
+
+    // Returns true if this offense is the one that gets the sender banned.
+    pub fn penalize(&mut self, ip_addr: &IpAddr, offense: Offense, now: Instant) -> bool {
+        let newly_banned = {
+            let standing = self.standing_mut(ip_addr, now);
+            if standing.is_banned(now) {
+                return false;
+            }
+            standing.decay(now);
+            standing.score = standing.score.saturating_add(offense.penalty());
+            if standing.score >= BAN_THRESHOLD {
+                standing.banned_until_opt = Some(now + Duration::from_secs(BAN_DURATION_SECS));
+                true
+            } else {
+                false
+            }
+        };
+        if newly_banned {
+            self.new_bans.push(*ip_addr);
+        }
+        newly_banned
+    }
+
+    pub fn score(&self, ip_addr: &IpAddr) -> u32 {
+        match self.standings.get(ip_addr) {
+            Some(standing) => standing.score,
+            None => 0,
+        }
+    }
+
+    // Bans that haven't been reported yet, so that they can be enforced elsewhere too.
+    pub fn take_new_bans(&mut self) -> Vec<IpAddr> {
+        self.new_bans.drain(..).collect()
+    }
+
+    // A sender whose ban has run out starts over with a clean slate.
+    fn standing_mut(&mut self, ip_addr: &IpAddr, now: Instant) -> &mut PeerStanding {
+        let standing = self
+            .standings
+            .entry(*ip_addr)
+            .or_insert_with(|| PeerStanding::new(now));
+        if standing.banned_until_opt.is_some() && !standing.is_banned(now) {
+            *standing = PeerStanding::new(now);
+        }
+        standing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn gossip_is_allowed_up_to_the_burst_and_then_refused() {
+        let sender = ip("1.2.3.4");
+        let now = Instant::now();
+        let mut subject = PeerReputation::new();
+
+        let results: Vec<bool> = (0..(GOSSIP_BURST + 1))
+            .map(|_| subject.allow_gossip(&sender, now))
+            .collect();
+
+        let mut expected = vec![true; GOSSIP_BURST as usize];
+        expected.push(false);
+        assert_eq!(results, expected);
+        assert_eq!(subject.allow_gossip(&ip("4.3.2.1"), now), true);
+    }
+
+    #[test]
+    fn gossip_allowance_refills_over_time_but_not_past_the_burst() {
+        let sender = ip("1.2.3.4");
+        let start = Instant::now();
+        let mut subject = PeerReputation::new();
+        (0..GOSSIP_BURST).for_each(|_| {
+            subject.allow_gossip(&sender, start);
+        });
+
+        let later = start + Duration::from_millis(2 * GOSSIP_REFILL_MILLIS + 500);
+        let refilled: Vec<bool> = (0..3)
+            .map(|_| subject.allow_gossip(&sender, later))
+            .collect();
+        let much_later = later + Duration::from_secs(3600);
+        let allowed = (0..(GOSSIP_BURST * 2))
+            .filter(|_| subject.allow_gossip(&sender, much_later))
+            .count();
+
+        assert_eq!(refilled, vec![true, true, false]);
+        assert_eq!(allowed, GOSSIP_BURST as usize);
+    }
+
+    #[test]
+    fn offenses_add_up_to_a_ban_that_is_reported_once() {
+        let sender = ip("1.2.3.4");
+        let now = Instant::now();
+        let mut subject = PeerReputation::new();
+
+        let results: Vec<bool> = (0..4)
+            .map(|_| subject.penalize(&sender, Offense::ForgedRecord, now))
+            .collect();
+
+        assert_eq!(results, vec![false, false, false, true]);
+        assert_eq!(subject.score(&sender), 4 * Offense::ForgedRecord.penalty());
+        assert_eq!(subject.is_banned(&sender, now), true);
+        assert_eq!(subject.is_banned(&ip("4.3.2.1"), now), false);
+        assert_eq!(subject.take_new_bans(), vec![sender]);
+        assert_eq!(subject.penalize(&sender, Offense::ForgedRecord, now), false);
+        assert_eq!(subject.take_new_bans(), Vec::<IpAddr>::new());
+    }
+
+    #[test]
+    fn scores_decay_over_time() {
+        let sender = ip("1.2.3.4");
+        let start = Instant::now();
+        let mut subject = PeerReputation::new();
+        subject.penalize(&sender, Offense::ForgedRecord, start);
+
+        subject.penalize(
+            &sender,
+            Offense::InvalidRecord,
+            start + Duration::from_secs(SCORE_DECAY_SECS * 20),
+        );
+
+        assert_eq!(
+            subject.score(&sender),
+            Offense::ForgedRecord.penalty() - 20 + Offense::InvalidRecord.penalty()
+        );
+    }
+
+    #[test]
+    fn bans_expire_and_leave_a_clean_slate() {
+        let sender = ip("1.2.3.4");
+        let start = Instant::now();
+        let mut subject = PeerReputation::new();
+        (0..(BAN_THRESHOLD / Offense::ForgedRecord.penalty())).for_each(|_| {
+            subject.penalize(&sender, Offense::ForgedRecord, start);
+        });
+        let expiration = start + Duration::from_secs(BAN_DURATION_SECS);
+
+        let banned_just_before = subject.is_banned(&sender, expiration - Duration::from_secs(1));
+        let banned_at_expiration = subject.is_banned(&sender, expiration);
+        let allowed = subject.allow_gossip(&sender, expiration);
+
+        assert_eq!(banned_just_before, true);
+        assert_eq!(banned_at_expiration, false);
+        assert_eq!(allowed, true);
+        assert_eq!(subject.score(&sender), 0);
+    }
+}
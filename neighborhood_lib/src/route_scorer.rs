@@ -0,0 +1,253 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+use sub_lib::cryptde::CryptDE;
+use sub_lib::cryptde::Key;
+
+// Scores are in the same units as route costs: what the route would charge for a typical package.
+pub const HOP_PENALTY: u64 = 100;
+pub const FAILURE_PENALTY: u64 = 10000;
+// A Node that keeps failing can't be made infinitely unattractive, or it could never be used again.
+const MAX_COUNTED_FAILURES: u32 = 10;
+// Nodes recover from whatever made them fail, so a failure stops counting against a Node after this long.
+pub const FAILURE_MEMORY_SECS: u64 = 1800;
+
+// Chooses one of several candidate routes. Lower scores are better. Each route's score is its cost, plus
+// HOP_PENALTY for each hop, plus FAILURE_PENALTY for each failure observed in the last FAILURE_MEMORY_SECS at
+// any Node on it other than the origin. The route is chosen at random from those whose scores are within randomness percent of the
+// best score, so that traffic spreads out over routes that are nearly as good as the best one.
+pub struct RouteScorer {
+    randomness: u8,
+    failures: HashMap<Key, VecDeque<Instant>>,
+}
+
+impl RouteScorer {
+    pub fn new(randomness: u8) -> RouteScorer {
+        RouteScorer {
+            randomness,
+            failures: HashMap::new(),
+        }
+    }
+
+    pub fn record_failure(&mut self, key: &Key) {
+        self.record_failure_at(key, Instant::now())
+    }
+
+    // Nodes whose failures have all been forgotten are forgotten too.
+    pub fn record_failure_at(&mut self, key: &Key, now: Instant) {
+        self.failures
+            .values_mut()
+            .for_each(|times| times.retain(|time| RouteScorer::remembered(*time, now)));
+        self.failures.retain(|_, times| !times.is_empty());
+        let times = self
+            .failures
+            .entry(key.clone())
+            .or_insert_with(VecDeque::new);
+        times.push_back(now);
+        if times.len() > (MAX_COUNTED_FAILURES as usize) {
+            times.pop_front();
+        }
+    }
+
+    pub fn failures(&self, key: &Key) -> u32 {
+        self.failures_at(key, Instant::now())
+    }
+
+    pub fn failures_at(&self, key: &Key, now: Instant) -> u32 {
+        match self.failures.get(key) {
+            Some(times) => times
+                .iter()
+                .filter(|time| RouteScorer::remembered(**time, now))
+                .count() as u32,
+            None => 0,
+        }
+    }
+
+    pub fn score(&self, route: &[&Key], cost: u64) -> u64 {
+        let hop_count = route.len().saturating_sub(1) as u64;
+        let failures: u64 = route
+            .iter()
+            .skip(1)
+            .map(|key| self.failures(key) as u64)
+            .sum();
+        cost.saturating_add(HOP_PENALTY.saturating_mul(hop_count))
+            .saturating_add(FAILURE_PENALTY.saturating_mul(failures))
+    }
+
+    // Candidates are routes paired with their costs. Among routes with the same score, the earlier one is
+    // preferred when randomness is 0.
+    pub fn choose<'a>(
+        &self,
+        candidates: Vec<(Vec<&'a Key>, u64)>,
+        cryptde: &CryptDE,
+    ) -> Option<Vec<&'a Key>> {
+        let scored: Vec<(Vec<&'a Key>, u64)> = candidates
+            .into_iter()
+            .map(|(route, cost)| {
+                let score = self.score(&route, cost);
+                (route, score)
+            })
+            .collect();
+        let best_score = scored.iter().map(|(_, score)| *score).min()?;
+        let tolerance = best_score.saturating_mul(self.randomness as u64) / 100;
+        let limit = best_score.saturating_add(tolerance);
+        let mut eligible: Vec<Vec<&'a Key>> = scored
+            .into_iter()
+            .filter(|(_, score)| *score <= limit)
+            .map(|(route, _)| route)
+            .collect();
+        let index = if self.randomness == 0 {
+            0
+        } else {
+            RouteScorer::random_index(eligible.len(), cryptde)
+        };
+        Some(eligible.remove(index))
+    }
+
+    fn remembered(time: Instant, now: Instant) -> bool {
+        (now <= time) || (now.duration_since(time) < Duration::from_secs(FAILURE_MEMORY_SECS))
+    }
+
+    fn random_index(limit: usize, cryptde: &CryptDE) -> usize {
+        let mut bytes = [0u8; 4];
+        cryptde.random(&mut bytes);
+        let random = bytes
+            .iter()
+            .fold(0usize, |so_far, byte| (so_far << 8) | (*byte as usize));
+        random % limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sub_lib::cryptde_null::CryptDENull;
+
+    fn key(n: u8) -> Key {
+        Key::new(&[n])
+    }
+
+    #[test]
+    fn score_counts_cost_hops_and_failures_after_the_origin() {
+        let (origin, a, b) = (key(1), key(2), key(3));
+        let mut subject = RouteScorer::new(0);
+        subject.record_failure(&origin);
+        subject.record_failure(&b);
+        subject.record_failure(&b);
+
+        let result = subject.score(&[&origin, &a, &b], 1234);
+
+        assert_eq!(result, 1234 + (2 * HOP_PENALTY) + (2 * FAILURE_PENALTY));
+    }
+
+    #[test]
+    fn failures_stop_counting_after_a_while() {
+        let a = key(1);
+        let mut subject = RouteScorer::new(0);
+
+        (0..(MAX_COUNTED_FAILURES + 5)).for_each(|_| subject.record_failure(&a));
+
+        assert_eq!(subject.failures(&a), MAX_COUNTED_FAILURES);
+        assert_eq!(subject.failures(&key(2)), 0);
+    }
+
+    #[test]
+    fn failures_are_forgotten_after_a_while() {
+        let (a, b) = (key(1), key(2));
+        let start = Instant::now();
+        let memory = Duration::from_secs(FAILURE_MEMORY_SECS);
+        let mut subject = RouteScorer::new(0);
+        subject.record_failure_at(&a, start);
+        subject.record_failure_at(&a, start + Duration::from_secs(60));
+
+        let before_forgetting = subject.failures_at(&a, start + memory - Duration::from_secs(1));
+        let after_forgetting_one = subject.failures_at(&a, start + memory);
+        let after_forgetting_both =
+            subject.failures_at(&a, start + memory + Duration::from_secs(60));
+        subject.record_failure_at(&b, start + memory + Duration::from_secs(60));
+
+        assert_eq!(before_forgetting, 2);
+        assert_eq!(after_forgetting_one, 1);
+        assert_eq!(after_forgetting_both, 0);
+        assert_eq!(subject.failures.contains_key(&a), false);
+        assert_eq!(
+            subject.score(&[&key(0), &a], 0),
+            HOP_PENALTY,
+            "A Node whose failures are forgotten is as good as new"
+        );
+    }
+
+    #[test]
+    fn choose_returns_none_when_there_are_no_candidates() {
+        let subject = RouteScorer::new(50);
+
+        let result = subject.choose(vec![], &CryptDENull::new());
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn choose_without_randomness_takes_the_first_of_the_best() {
+        let (origin, a, b, c) = (key(1), key(2), key(3), key(4));
+        let subject = RouteScorer::new(0);
+
+        let result = subject.choose(
+            vec![
+                (vec![&origin, &a], 10),
+                (vec![&origin, &b], 5),
+                (vec![&origin, &c], 5),
+            ],
+            &CryptDENull::new(),
+        );
+
+        assert_eq!(result, Some(vec![&origin, &b]));
+    }
+
+    #[test]
+    fn choose_avoids_routes_through_nodes_that_have_failed() {
+        let (origin, a, b) = (key(1), key(2), key(3));
+        let mut subject = RouteScorer::new(0);
+        subject.record_failure(&a);
+
+        let result = subject.choose(
+            vec![(vec![&origin, &a], 0), (vec![&origin, &b], 5000)],
+            &CryptDENull::new(),
+        );
+
+        assert_eq!(result, Some(vec![&origin, &b]));
+    }
+
+    #[test]
+    fn choose_prefers_fewer_hops_when_costs_are_equal() {
+        let (origin, a, b, c) = (key(1), key(2), key(3), key(4));
+        let subject = RouteScorer::new(0);
+
+        let result = subject.choose(
+            vec![(vec![&origin, &a, &b], 0), (vec![&origin, &c], 0)],
+            &CryptDENull::new(),
+        );
+
+        assert_eq!(result, Some(vec![&origin, &c]));
+    }
+
+    #[test]
+    fn choose_with_randomness_picks_among_routes_close_to_the_best() {
+        let keys: Vec<Key> = (0..5).map(key).collect();
+        let origin = &keys[0];
+        let subject = RouteScorer::new(10);
+        let candidates = vec![
+            (vec![origin, &keys[1]], 900),
+            (vec![origin, &keys[2]], 950),
+            (vec![origin, &keys[3]], 1000),
+            (vec![origin, &keys[4]], 1001),
+        ];
+        // CryptDENull's random bytes are all 0x34
+        let expected_index = 0x34343434 % 3;
+
+        let result = subject.choose(candidates.clone(), &CryptDENull::new());
+
+        assert_eq!(result, Some(candidates[expected_index].0.clone()));
+    }
+}
@@ -23,7 +23,6 @@ use stream_messages::PoolBindMessage;
 use sub_lib::accountant::AccountantConfig;
 use sub_lib::accountant::AccountantSubs;
 use sub_lib::cryptde::CryptDE;
-use sub_lib::cryptde_null::CryptDENull;
 use sub_lib::dispatcher::DispatcherSubs;
 use sub_lib::hopper::HopperSubs;
 use sub_lib::neighborhood::BootstrapNeighborhoodNowMessage;
@@ -50,8 +49,12 @@ impl ActorSystemFactory for ActorSystemFactoryReal {
         config: BootstrapperConfig,
         actor_factory: Box<ActorFactory>,
     ) -> StreamHandlerPoolSubs {
-        let cryptde: &'static CryptDENull =
-            unsafe { bootstrapper::CRYPT_DE_OPT.as_ref().expect("Internal error") };
+        let cryptde: &'static CryptDE = unsafe {
+            bootstrapper::CRYPT_DE_OPT
+                .as_ref()
+                .expect("Internal error")
+                .as_ref()
+        };
         let (tx, rx) = mpsc::channel();
 
         // TODO: this thread::spawn goes away with actix 0.7
@@ -78,8 +81,12 @@ impl ActorSystemFactoryReal {
     ) {
         // make all the actors
         let (dispatcher_subs, pool_bind_sub) = actor_factory.make_and_start_dispatcher();
-        let proxy_server_subs = actor_factory
-            .make_and_start_proxy_server(cryptde, config.neighborhood_config.is_decentralized());
+        let proxy_server_subs = actor_factory.make_and_start_proxy_server(
+            cryptde,
+            config.neighborhood_config.is_decentralized(),
+            config.http_proxy_port_opt,
+            config.socks_port_opt,
+        );
         let proxy_client_subs =
             actor_factory.make_and_start_proxy_client(cryptde, config.dns_servers);
         let hopper_subs = actor_factory
@@ -175,6 +182,8 @@ pub trait ActorFactory: Send {
         &self,
         cryptde: &'static CryptDE,
         is_decentralized: bool,
+        http_proxy_port_opt: Option<u16>,
+        socks_port_opt: Option<u16>,
     ) -> ProxyServerSubs;
     fn make_and_start_hopper(
         &self,
@@ -214,8 +223,15 @@ impl ActorFactory for ActorFactoryReal {
         &self,
         cryptde: &'static CryptDE,
         is_decentralized: bool,
+        http_proxy_port_opt: Option<u16>,
+        socks_port_opt: Option<u16>,
     ) -> ProxyServerSubs {
-        let proxy_server = ProxyServer::new(cryptde, is_decentralized);
+        let proxy_server = ProxyServer::new(
+            cryptde,
+            is_decentralized,
+            http_proxy_port_opt,
+            socks_port_opt,
+        );
         let addr: Addr<Syn, ProxyServer> = proxy_server.start();
         ProxyServer::make_subs_from(&addr)
     }
@@ -275,21 +291,31 @@ mod tests {
     use std::cell::RefCell;
     use std::net::IpAddr;
     use std::net::Ipv4Addr;
+    use std::path::PathBuf;
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::time::Duration;
     use stream_messages::AddStreamMsg;
     use stream_messages::RemoveStreamMsg;
+    use sub_lib::accountant::LedgerQueryMessage;
+    use sub_lib::accountant::ReportExitServiceConsumedMessage;
+    use sub_lib::accountant::ReportExitServiceMessage;
+    use sub_lib::accountant::ReportRoutingServiceConsumedMessage;
+    use sub_lib::accountant::ReportRoutingServiceMessage;
     use sub_lib::crash_point::CrashPoint;
     use sub_lib::cryptde::PlainData;
+    use sub_lib::cryptde_null::CryptDENull;
+    use sub_lib::dispatcher::BanPeerMessage;
     use sub_lib::dispatcher::InboundClientData;
     use sub_lib::hopper::ExpiredCoresPackage;
     use sub_lib::hopper::ExpiredCoresPackagePackage;
     use sub_lib::hopper::IncipientCoresPackage;
     use sub_lib::neighborhood::DispatcherNodeQueryMessage;
     use sub_lib::neighborhood::NodeQueryMessage;
+    use sub_lib::neighborhood::RatePack;
     use sub_lib::neighborhood::RemoveNeighborMessage;
     use sub_lib::neighborhood::RouteQueryMessage;
+    use sub_lib::neighborhood::DEFAULT_MAX_GOSSIP_SIZE;
     use sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
     use sub_lib::stream_handler_pool::TransmitDataMsg;
     use test_utils::recorder::Recorder;
@@ -315,6 +341,7 @@ mod tests {
                 ibcd_sub: addr.clone().recipient::<InboundClientData>(),
                 bind: addr.clone().recipient::<BindMessage>(),
                 from_dispatcher_client: addr.clone().recipient::<TransmitDataMsg>(),
+                ban_peer: addr.clone().recipient::<BanPeerMessage>(),
             };
             (dispatcher_subs, addr.recipient::<PoolBindMessage>())
         }
@@ -323,12 +350,19 @@ mod tests {
             &self,
             cryptde: &'a CryptDE,
             is_decentralized: bool,
+            http_proxy_port_opt: Option<u16>,
+            socks_port_opt: Option<u16>,
         ) -> ProxyServerSubs {
             self.parameters
                 .proxy_server_params
                 .lock()
                 .unwrap()
-                .get_or_insert((cryptde, is_decentralized));
+                .get_or_insert((
+                    cryptde,
+                    is_decentralized,
+                    http_proxy_port_opt,
+                    socks_port_opt,
+                ));
             let addr: Addr<Syn, Recorder> = ActorFactoryMock::start_recorder(&self.proxy_server);
             ProxyServerSubs {
                 bind: addr.clone().recipient::<BindMessage>(),
@@ -386,6 +420,15 @@ mod tests {
             let addr: Addr<Syn, Recorder> = ActorFactoryMock::start_recorder(&self.accountant);
             AccountantSubs {
                 bind: addr.clone().recipient::<BindMessage>(),
+                report_routing_service: addr.clone().recipient::<ReportRoutingServiceMessage>(),
+                report_exit_service: addr.clone().recipient::<ReportExitServiceMessage>(),
+                report_routing_service_consumed: addr
+                    .clone()
+                    .recipient::<ReportRoutingServiceConsumedMessage>(),
+                report_exit_service_consumed: addr
+                    .clone()
+                    .recipient::<ReportExitServiceConsumedMessage>(),
+                ledger_query: addr.clone().recipient::<LedgerQueryMessage>(),
             }
         }
 
@@ -435,7 +478,7 @@ mod tests {
     #[derive(Clone)]
     struct Parameters<'a> {
         proxy_client_params: Arc<Mutex<Option<(&'a CryptDE, Vec<SocketAddr>)>>>,
-        proxy_server_params: Arc<Mutex<Option<(&'a CryptDE, bool)>>>,
+        proxy_server_params: Arc<Mutex<Option<(&'a CryptDE, bool, Option<u16>, Option<u16>)>>>,
         hopper_params: Arc<Mutex<Option<(&'a CryptDE, bool)>>>,
         neighborhood_params: Arc<Mutex<Option<(&'a CryptDE, NeighborhoodConfig)>>>,
         accountant_params: Arc<Mutex<Option<AccountantConfig>>>,
@@ -512,15 +555,25 @@ mod tests {
                 local_ip_addr: IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
             accountant_config: AccountantConfig {
-                replace_me: String::new(),
+                wallet: None,
+                data_directory_opt: None,
             },
             clandestine_discriminator_factories: Vec::new(),
+            http_proxy_port_opt: None,
+            socks_port_opt: None,
+            data_directory: PathBuf::new(),
         };
         let subject = ActorSystemFactoryReal {};
         unsafe {
-            CRYPT_DE_OPT = Some(CryptDENull::new());
+            CRYPT_DE_OPT = Some(Box::new(CryptDENull::new()));
         }
 
         subject.make_and_start_actors(config, Box::new(actor_factory));
@@ -550,11 +603,21 @@ mod tests {
                 local_ip_addr: IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: 0,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
             accountant_config: AccountantConfig {
-                replace_me: String::new(),
+                wallet: None,
+                data_directory_opt: None,
             },
             clandestine_discriminator_factories: Vec::new(),
+            http_proxy_port_opt: Some(8080),
+            socks_port_opt: Some(1080),
+            data_directory: PathBuf::new(),
         };
         let (tx, rx) = mpsc::channel();
         let system = System::new("SubstratumNode");
@@ -579,10 +642,16 @@ mod tests {
         let (cryptde, dns_servers) = Parameters::get(parameters.proxy_client_params);
         check_cryptde(cryptde);
         assert_eq!(dns_servers, config.dns_servers);
-        let (actual_cryptde, actual_is_decentralized) =
-            Parameters::get(parameters.proxy_server_params);
+        let (
+            actual_cryptde,
+            actual_is_decentralized,
+            actual_http_proxy_port_opt,
+            actual_socks_port_opt,
+        ) = Parameters::get(parameters.proxy_server_params);
         check_cryptde(actual_cryptde);
         assert_eq!(actual_is_decentralized, false);
+        assert_eq!(actual_http_proxy_port_opt, Some(8080));
+        assert_eq!(actual_socks_port_opt, Some(1080));
         let (cryptde, neighborhood_config) = Parameters::get(parameters.neighborhood_params);
         check_cryptde(cryptde);
         assert_eq!(neighborhood_config, config.neighborhood_config);
@@ -5,25 +5,39 @@ use actor_system_factory::ActorSystemFactoryReal;
 use base64;
 use configuration::Configuration;
 use crash_test_dummy::CrashTestDummy;
+use dirs::data_local_dir;
 use discriminator::DiscriminatorFactory;
+use hopper_lib::payload_cell::MAX_CELL_SIZE;
+use hopper_lib::payload_cell::MIN_CELL_SIZE;
+use keystore::Keystore;
 use listener_handler::ListenerHandler;
 use listener_handler::ListenerHandlerFactory;
 use listener_handler::ListenerHandlerFactoryReal;
+use privilege_drop::EnvironmentWrapper;
+use privilege_drop::EnvironmentWrapperReal;
 use regex::Regex;
+use std::io::Read;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 use std::vec::Vec;
 use sub_lib::accountant::AccountantConfig;
 use sub_lib::crash_point::CrashPoint;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::Key;
 use sub_lib::cryptde_null::CryptDENull;
+use sub_lib::cryptde_real::CryptDEReal;
 use sub_lib::logger::Logger;
 use sub_lib::main_tools::StdStreams;
 use sub_lib::neighborhood::sentinel_ip_addr;
 use sub_lib::neighborhood::NeighborhoodConfig;
+use sub_lib::neighborhood::RatePack;
+use sub_lib::neighborhood::DEFAULT_MAX_GOSSIP_SIZE;
+use sub_lib::neighborhood::DEFAULT_MAX_NODE_AGE_HOURS;
+use sub_lib::neighborhood::DEFAULT_ROUTE_RANDOMNESS;
 use sub_lib::node_addr::NodeAddr;
 use sub_lib::parameter_finder::ParameterFinder;
 use sub_lib::socket_server::SocketServer;
@@ -33,7 +47,10 @@ use tokio::prelude::Async;
 use tokio::prelude::Future;
 use tokio::prelude::Stream;
 
-pub static mut CRYPT_DE_OPT: Option<CryptDENull> = None;
+pub const KEYSTORE_PASSPHRASE_VAR: &str = "SUBSTRATUM_KEYSTORE_PASSPHRASE";
+pub const PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG: &str = "--prompt_for_keystore_passphrase";
+
+pub static mut CRYPT_DE_OPT: Option<Box<CryptDE>> = None;
 
 #[derive(Clone)]
 pub struct BootstrapperConfig {
@@ -42,6 +59,9 @@ pub struct BootstrapperConfig {
     pub accountant_config: AccountantConfig,
     pub crash_point: CrashPoint,
     pub clandestine_discriminator_factories: Vec<Box<DiscriminatorFactory>>,
+    pub http_proxy_port_opt: Option<u16>,
+    pub socks_port_opt: Option<u16>,
+    pub data_directory: PathBuf,
 }
 
 impl BootstrapperConfig {
@@ -54,12 +74,22 @@ impl BootstrapperConfig {
                 local_ip_addr: IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
                 clandestine_port_list: vec![],
                 wallet: None,
+                cell_size_opt: None,
+                rate_pack: RatePack::default(),
+                route_randomness: DEFAULT_ROUTE_RANDOMNESS,
+                data_directory_opt: None,
+                max_node_age: Duration::from_secs(DEFAULT_MAX_NODE_AGE_HOURS * 3600),
+                max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
             },
             accountant_config: AccountantConfig {
-                replace_me: String::new(),
+                wallet: None,
+                data_directory_opt: None,
             },
             crash_point: CrashPoint::None,
             clandestine_discriminator_factories: vec![],
+            http_proxy_port_opt: None,
+            socks_port_opt: None,
+            data_directory: PathBuf::new(),
         }
     }
 }
@@ -94,9 +124,20 @@ impl SocketServer for Bootstrapper {
     fn initialize_as_privileged(&mut self, args: &Vec<String>, streams: &mut StdStreams) {
         let mut configuration = Configuration::new();
         configuration.establish(args);
-        let cryptde_ref = Bootstrapper::initialize_cryptde();
         let mut config = BootstrapperConfig::new();
         Bootstrapper::parse_args(args, &mut config);
+        let finder = ParameterFinder::new(args.clone());
+        let keystore = Keystore::new(
+            &config.data_directory,
+            &Bootstrapper::parse_keystore_passphrase(
+                args,
+                &finder,
+                &EnvironmentWrapperReal,
+                streams,
+            ),
+        );
+        let cryptde_ref =
+            Bootstrapper::initialize_cryptde(Bootstrapper::parse_cryptde(&finder), &keystore);
         Bootstrapper::add_clandestine_port_info(&configuration, &mut config);
         Bootstrapper::report_local_descriptor(
             cryptde_ref,
@@ -104,6 +145,7 @@ impl SocketServer for Bootstrapper {
             config.neighborhood_config.clandestine_port_list.clone(),
             streams,
         );
+        let local_ip_addr = config.neighborhood_config.local_ip_addr;
         self.config = Some(config);
         self.listener_handlers =
             FuturesUnordered::<Box<ListenerHandler<Item = (), Error = ()>>>::new();
@@ -113,9 +155,11 @@ impl SocketServer for Bootstrapper {
             .iter()
             .for_each(|(port, port_configuration)| {
                 let mut listener_handler = self.listener_handler_factory.make();
-                match listener_handler
-                    .bind_port_and_configuration(*port, port_configuration.clone())
-                {
+                match listener_handler.bind_port_and_configuration(
+                    *port,
+                    port_configuration.clone(),
+                    local_ip_addr,
+                ) {
                     Ok(()) => (),
                     Err(e) => panic!("Could not listen on port {}: {}", port, e.to_string()),
                 }
@@ -162,6 +206,17 @@ impl Bootstrapper {
         config.neighborhood_config.is_bootstrap_node = Bootstrapper::parse_node_type(&finder);
         config.neighborhood_config.local_ip_addr = local_ip_addr;
         config.neighborhood_config.wallet = Bootstrapper::parse_wallet_address(&finder);
+        config.accountant_config.wallet = config.neighborhood_config.wallet.clone();
+        config.neighborhood_config.cell_size_opt = Bootstrapper::parse_cell_size(&finder);
+        config.neighborhood_config.rate_pack = Bootstrapper::parse_rate_pack(&finder);
+        config.neighborhood_config.route_randomness = Bootstrapper::parse_route_randomness(&finder);
+        config.neighborhood_config.max_node_age = Bootstrapper::parse_max_node_age(&finder);
+        config.neighborhood_config.max_gossip_size = Bootstrapper::parse_max_gossip_size(&finder);
+        config.http_proxy_port_opt = Configuration::parse_http_proxy_port(&finder);
+        config.socks_port_opt = Configuration::parse_socks_port(&finder);
+        config.data_directory = Bootstrapper::parse_data_directory(&finder);
+        config.neighborhood_config.data_directory_opt = Some(config.data_directory.clone());
+        config.accountant_config.data_directory_opt = Some(config.data_directory.clone());
     }
 
     fn parse_crash_point(finder: &ParameterFinder) -> CrashPoint {
@@ -178,6 +233,75 @@ impl Bootstrapper {
         }
     }
 
+    pub fn parse_data_directory(finder: &ParameterFinder) -> PathBuf {
+        let usage = "--data_directory <directory where the Node keeps its identity and other persistent data>";
+        match finder.find_value_for("--data_directory", usage) {
+            Some(directory) => PathBuf::from(directory),
+            None => match data_local_dir() {
+                Some(directory) => directory.join("Substratum"),
+                None => panic!("Cannot find a default data directory; specify {}", usage),
+            },
+        }
+    }
+
+    // A passphrase on the command line can be seen by anyone who can list processes, so it can come from the
+    // environment or be typed in instead. An empty passphrase is allowed, but not without a warning.
+    pub fn parse_keystore_passphrase(
+        args: &Vec<String>,
+        finder: &ParameterFinder,
+        environment: &EnvironmentWrapper,
+        streams: &mut StdStreams,
+    ) -> String {
+        let usage = "--keystore_passphrase <passphrase protecting the Node's private key>";
+        let passphrase = match finder.find_value_for("--keystore_passphrase", usage) {
+            Some(passphrase) => passphrase,
+            None => match environment.var(KEYSTORE_PASSPHRASE_VAR) {
+                Some(passphrase) => passphrase,
+                None if args.contains(&String::from(PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG)) => {
+                    Bootstrapper::prompt_for_keystore_passphrase(streams)
+                }
+                None => String::new(),
+            },
+        };
+        if passphrase.is_empty() {
+            writeln!(
+                streams.stderr,
+                "Warning: the Node's private key is protected by an empty passphrase; use --keystore_passphrase, {} or {} to protect it",
+                KEYSTORE_PASSPHRASE_VAR, PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG
+            )
+            .expect("Internal error");
+        }
+        passphrase
+    }
+
+    // Reads one line, and no more, so that nothing after it is taken from whoever reads stdin next.
+    fn prompt_for_keystore_passphrase(streams: &mut StdStreams) -> String {
+        write!(streams.stdout, "Keystore passphrase: ").expect("Internal error");
+        streams.stdout.flush().expect("Internal error");
+        let mut line = vec![];
+        let mut byte = [0u8; 1];
+        while let Ok(1) = streams.stdin.read(&mut byte) {
+            if byte[0] == b'\n' {
+                break;
+            }
+            line.push(byte[0]);
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).expect("Keystore passphrase must be valid UTF-8")
+    }
+
+    pub fn parse_cryptde(finder: &ParameterFinder) -> Box<CryptDE> {
+        let usage = "--cryptde real|null";
+        match finder.find_value_for("--cryptde", usage) {
+            None => Box::new(CryptDEReal::new()),
+            Some(ref cryptde) if cryptde == "real" => Box::new(CryptDEReal::new()),
+            Some(ref cryptde) if cryptde == "null" => Box::new(CryptDENull::new()),
+            Some(ref cryptde) => panic!("--cryptde must be either real or null, not {}", cryptde),
+        }
+    }
+
     fn is_valid_ethereum_address(address: &str) -> bool {
         Regex::new("^0x[0-9a-fA-F]{40}$")
             .expect("Failed to compile regular expression")
@@ -197,6 +321,116 @@ impl Bootstrapper {
         }
     }
 
+    fn parse_cell_size(finder: &ParameterFinder) -> Option<usize> {
+        let usage = format!(
+            "--cell_size <bytes of payload per fixed-size cell, from {} to {}>",
+            MIN_CELL_SIZE, MAX_CELL_SIZE
+        );
+        match finder.find_value_for("--cell_size", &usage) {
+            None => None,
+            Some(ref cell_size_str) => match cell_size_str.parse::<usize>() {
+                Ok(cell_size) if (cell_size >= MIN_CELL_SIZE) && (cell_size <= MAX_CELL_SIZE) => {
+                    Some(cell_size)
+                }
+                _ => panic!(
+                    "--cell_size must be a number from {} to {}, not '{}'",
+                    MIN_CELL_SIZE, MAX_CELL_SIZE, cell_size_str
+                ),
+            },
+        }
+    }
+
+    fn parse_rate_pack(finder: &ParameterFinder) -> RatePack {
+        RatePack {
+            routing_service_rate: Bootstrapper::parse_rate(
+                finder,
+                "--routing_service_rate",
+                "for each CORES package routed",
+            ),
+            routing_byte_rate: Bootstrapper::parse_rate(
+                finder,
+                "--routing_byte_rate",
+                "for each byte of payload routed",
+            ),
+            exit_service_rate: Bootstrapper::parse_rate(
+                finder,
+                "--exit_service_rate",
+                "for each CORES package exited",
+            ),
+            exit_byte_rate: Bootstrapper::parse_rate(
+                finder,
+                "--exit_byte_rate",
+                "for each byte of payload exited",
+            ),
+        }
+    }
+
+    fn parse_rate(finder: &ParameterFinder, parameter: &str, charged_for: &str) -> u64 {
+        let usage = format!(
+            "{} <charge {}, in the smallest unit of currency; default 0>",
+            parameter, charged_for
+        );
+        match finder.find_value_for(parameter, &usage) {
+            None => 0,
+            Some(ref rate_str) => match rate_str.parse::<u64>() {
+                Ok(rate) => rate,
+                Err(_) => panic!("{} must be a whole number, not '{}'", parameter, rate_str),
+            },
+        }
+    }
+
+    fn parse_route_randomness(finder: &ParameterFinder) -> u8 {
+        let usage = format!(
+            "--route_randomness <percent by which a route may be worse than the best and still be chosen, default {}>",
+            DEFAULT_ROUTE_RANDOMNESS
+        );
+        match finder.find_value_for("--route_randomness", &usage) {
+            None => DEFAULT_ROUTE_RANDOMNESS,
+            Some(ref randomness_str) => match randomness_str.parse::<u8>() {
+                Ok(randomness) if randomness <= 100 => randomness,
+                _ => panic!(
+                    "--route_randomness must be a number from 0 to 100, not '{}'",
+                    randomness_str
+                ),
+            },
+        }
+    }
+
+    fn parse_max_node_age(finder: &ParameterFinder) -> Duration {
+        let usage = format!(
+            "--max_node_age <hours a Node may go unheard from before it is forgotten, default {}>",
+            DEFAULT_MAX_NODE_AGE_HOURS
+        );
+        let hours = match finder.find_value_for("--max_node_age", &usage) {
+            None => DEFAULT_MAX_NODE_AGE_HOURS,
+            Some(ref hours_str) => match hours_str.parse::<u64>() {
+                Ok(hours) if hours > 0 => hours,
+                _ => panic!(
+                    "--max_node_age must be a positive number of hours, not '{}'",
+                    hours_str
+                ),
+            },
+        };
+        Duration::from_secs(hours * 3600)
+    }
+
+    fn parse_max_gossip_size(finder: &ParameterFinder) -> usize {
+        let usage = format!(
+            "--max_gossip_size <largest Gossip message in bytes to send or accept, default {}>",
+            DEFAULT_MAX_GOSSIP_SIZE
+        );
+        match finder.find_value_for("--max_gossip_size", &usage) {
+            None => DEFAULT_MAX_GOSSIP_SIZE,
+            Some(ref size_str) => match size_str.parse::<usize>() {
+                Ok(size) if size > 0 => size,
+                _ => panic!(
+                    "--max_gossip_size must be a positive number of bytes, not '{}'",
+                    size_str
+                ),
+            },
+        }
+    }
+
     fn parse_ip(finder: &ParameterFinder) -> IpAddr {
         let usage = "--ip <public IP address>";
         match finder.find_value_for("--ip", usage) {
@@ -309,12 +543,28 @@ impl Bootstrapper {
         config.neighborhood_config.clandestine_port_list = clandestine_ports;
     }
 
-    fn initialize_cryptde() -> &'static CryptDE {
-        let mut exemplar = CryptDENull::new();
-        exemplar.generate_key_pair();
-        let cryptde: &'static CryptDENull = unsafe {
+    fn initialize_cryptde(mut exemplar: Box<CryptDE>, keystore: &Keystore) -> &'static CryptDE {
+        if keystore.exists() {
+            if let Err(e) = keystore.load(exemplar.as_mut()) {
+                panic!(
+                    "Could not load Node identity from {}: {:?}",
+                    keystore.path().display(),
+                    e
+                )
+            }
+        } else {
+            exemplar.generate_key_pair();
+            if let Err(e) = keystore.save(exemplar.as_ref()) {
+                panic!(
+                    "Could not save Node identity to {}: {:?}",
+                    keystore.path().display(),
+                    e
+                )
+            }
+        }
+        let cryptde: &'static CryptDE = unsafe {
             CRYPT_DE_OPT = Some(exemplar);
-            CRYPT_DE_OPT.as_ref().expect("Internal error")
+            CRYPT_DE_OPT.as_ref().expect("Internal error").as_ref()
         };
         cryptde
     }
@@ -325,21 +575,19 @@ impl Bootstrapper {
         ports: Vec<u16>,
         streams: &mut StdStreams,
     ) {
-        let port_strings: Vec<String> = ports.iter().map(|n| format!("{}", n)).collect();
-        let port_list = port_strings.join(",");
+        // NodeAddr puts IPv6 addresses in brackets, so that the descriptor can be pasted into --neighbor as is
+        let node_addr = NodeAddr::new(&ip_addr, &ports);
         writeln!(
             streams.stdout,
-            "SubstratumNode local descriptor: {}:{}:{}",
+            "SubstratumNode local descriptor: {}:{}",
             base64::encode_config(&cryptde.public_key().data, base64::STANDARD_NO_PAD),
-            ip_addr,
-            port_list
+            node_addr
         )
         .expect("Internal error");
         Logger::new("Bootstrapper").log(format!(
-            "SubstratumNode local descriptor: {}:{}:{}",
+            "SubstratumNode local descriptor: {}:{}",
             base64::encode_config(&cryptde.public_key().data, base64::STANDARD_NO_PAD),
-            ip_addr,
-            port_list
+            node_addr
         ));
     }
 }
@@ -374,6 +622,7 @@ mod tests {
     use stream_handler_pool::StreamHandlerPoolSubs;
     use stream_messages::AddStreamMsg;
     use sub_lib::cryptde::PlainData;
+    use sub_lib::cryptde_real::PUBLIC_KEY_LEN;
     use sub_lib::parameter_finder::ParameterFinder;
     use sub_lib::stream_connector::ConnectionInfo;
     use test_utils::logging::init_test_logging;
@@ -383,6 +632,8 @@ mod tests {
     use test_utils::recorder::RecordAwaiter;
     use test_utils::recorder::Recording;
     use test_utils::test_utils::assert_contains;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
+    use test_utils::test_utils::ByteArrayReader;
     use test_utils::test_utils::FakeStreamHolder;
     use test_utils::tokio_wrapper_mocks::ReadHalfWrapperMock;
     use test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
@@ -430,6 +681,7 @@ mod tests {
             &mut self,
             port: u16,
             discriminator_factories: PortConfiguration,
+            _local_ip_addr: IpAddr,
         ) -> io::Result<()> {
             self.log
                 .lock()
@@ -491,15 +743,24 @@ mod tests {
         }
     }
 
-    fn make_default_cli_params() -> Vec<String> {
+    fn make_default_cli_params(test_name: &str) -> Vec<String> {
         vec![
             String::from("--dns_servers"),
             String::from("222.222.222.222"),
             String::from("--port_count"),
             String::from("0"),
+            String::from("--data_directory"),
+            data_directory_for(test_name),
         ]
     }
 
+    fn data_directory_for(test_name: &str) -> String {
+        ensure_node_home_directory_exists("bootstrapper", test_name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
     #[test]
     fn knows_its_name() {
         let subject = BootstrapperBuilder::new().build();
@@ -509,6 +770,188 @@ mod tests {
         assert_eq!(result, String::from("Dispatcher"));
     }
 
+    #[test]
+    fn parse_cryptde_defaults_to_real() {
+        let finder = ParameterFinder::new(vec![]);
+
+        let result = Bootstrapper::parse_cryptde(&finder);
+
+        assert_eq!(result.public_key().data.len(), PUBLIC_KEY_LEN);
+    }
+
+    #[test]
+    fn parse_cryptde_handles_real() {
+        let finder = ParameterFinder::new(vec![String::from("--cryptde"), String::from("real")]);
+
+        let result = Bootstrapper::parse_cryptde(&finder);
+
+        assert_eq!(result.public_key().data.len(), PUBLIC_KEY_LEN);
+    }
+
+    #[test]
+    fn parse_cryptde_handles_null() {
+        let finder = ParameterFinder::new(vec![String::from("--cryptde"), String::from("null")]);
+
+        let result = Bootstrapper::parse_cryptde(&finder);
+
+        assert_eq!(result.private_key(), Key::new(b"uninitialized"));
+    }
+
+    #[test]
+    fn parse_data_directory_uses_specified_directory() {
+        let finder = ParameterFinder::new(vec![
+            String::from("--data_directory"),
+            String::from("/home/booga/substratum"),
+        ]);
+
+        let result = Bootstrapper::parse_data_directory(&finder);
+
+        assert_eq!(result, PathBuf::from("/home/booga/substratum"));
+    }
+
+    #[test]
+    fn parse_data_directory_defaults_to_local_data_directory() {
+        let finder = ParameterFinder::new(vec![]);
+
+        let result = Bootstrapper::parse_data_directory(&finder);
+
+        assert_eq!(result, data_local_dir().unwrap().join("Substratum"));
+    }
+
+    struct EnvironmentWrapperMock {
+        passphrase_opt: Option<String>,
+    }
+
+    impl EnvironmentWrapper for EnvironmentWrapperMock {
+        fn var(&self, key: &str) -> Option<String> {
+            match key {
+                KEYSTORE_PASSPHRASE_VAR => self.passphrase_opt.clone(),
+                _ => None,
+            }
+        }
+    }
+
+    fn parse_keystore_passphrase_from(
+        args: Vec<&str>,
+        env_passphrase_opt: Option<&str>,
+        holder: &mut FakeStreamHolder,
+    ) -> String {
+        let args: Vec<String> = args.into_iter().map(String::from).collect();
+        Bootstrapper::parse_keystore_passphrase(
+            &args,
+            &ParameterFinder::new(args.clone()),
+            &EnvironmentWrapperMock {
+                passphrase_opt: env_passphrase_opt.map(String::from),
+            },
+            &mut holder.streams(),
+        )
+    }
+
+    #[test]
+    fn parse_keystore_passphrase_defaults_to_empty_with_a_warning() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = parse_keystore_passphrase_from(vec![], None, &mut holder);
+
+        assert_eq!(result, String::new());
+        assert_eq!(
+            holder.stderr.get_string(),
+            format!(
+                "Warning: the Node's private key is protected by an empty passphrase; use --keystore_passphrase, {} or {} to protect it\n",
+                KEYSTORE_PASSPHRASE_VAR, PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG
+            )
+        );
+    }
+
+    #[test]
+    fn parse_keystore_passphrase_uses_specified_passphrase() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = parse_keystore_passphrase_from(
+            vec![
+                "--keystore_passphrase",
+                "booga",
+                PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG,
+            ],
+            Some("agoob"),
+            &mut holder,
+        );
+
+        assert_eq!(result, String::from("booga"));
+        assert_eq!(holder.stdout.get_string(), String::new());
+        assert_eq!(holder.stderr.get_string(), String::new());
+    }
+
+    #[test]
+    fn parse_keystore_passphrase_takes_passphrase_from_environment_if_not_specified() {
+        let mut holder = FakeStreamHolder::new();
+
+        let result = parse_keystore_passphrase_from(
+            vec![PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG],
+            Some("agoob"),
+            &mut holder,
+        );
+
+        assert_eq!(result, String::from("agoob"));
+        assert_eq!(holder.stdout.get_string(), String::new());
+        assert_eq!(holder.stderr.get_string(), String::new());
+    }
+
+    #[test]
+    fn parse_keystore_passphrase_prompts_for_passphrase_if_asked_to() {
+        let mut holder = FakeStreamHolder::new();
+        holder.stdin = ByteArrayReader::new(b"booga agoob\r\nnext line");
+
+        let result = parse_keystore_passphrase_from(
+            vec![
+                "--data_directory",
+                "dir",
+                PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG,
+            ],
+            None,
+            &mut holder,
+        );
+
+        assert_eq!(result, String::from("booga agoob"));
+        assert_eq!(
+            holder.stdout.get_string(),
+            String::from("Keystore passphrase: ")
+        );
+        assert_eq!(holder.stderr.get_string(), String::new());
+        let mut rest = String::new();
+        holder.stdin.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, String::from("next line"));
+    }
+
+    #[test]
+    fn parse_keystore_passphrase_warns_about_empty_passphrase_typed_in() {
+        let mut holder = FakeStreamHolder::new();
+        holder.stdin = ByteArrayReader::new(b"\n");
+
+        let result = parse_keystore_passphrase_from(
+            vec![PROMPT_FOR_KEYSTORE_PASSPHRASE_FLAG],
+            None,
+            &mut holder,
+        );
+
+        assert_eq!(result, String::new());
+        assert_eq!(
+            holder
+                .stderr
+                .get_string()
+                .starts_with("Warning: the Node's private key is protected by an empty passphrase"),
+            true
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "--cryptde must be either real or null, not booga")]
+    fn parse_cryptde_rejects_unknown_implementations() {
+        let finder = ParameterFinder::new(vec![String::from("--cryptde"), String::from("booga")]);
+
+        Bootstrapper::parse_cryptde(&finder);
+    }
+
     #[test]
     #[should_panic(
         expected = "Missing value for --wallet_address: --wallet_address <address> where 'address' is an Ethereum wallet address"
@@ -519,6 +962,167 @@ mod tests {
         Bootstrapper::parse_wallet_address(&finder);
     }
 
+    #[test]
+    fn parse_cell_size_defaults_to_none() {
+        let finder = ParameterFinder::new(vec![]);
+
+        assert_eq!(Bootstrapper::parse_cell_size(&finder), None);
+    }
+
+    #[test]
+    fn parse_cell_size_accepts_size_in_range() {
+        let finder = ParameterFinder::new(vec![String::from("--cell_size"), String::from("512")]);
+
+        assert_eq!(Bootstrapper::parse_cell_size(&finder), Some(512));
+    }
+
+    #[test]
+    #[should_panic(expected = "--cell_size must be a number from 64 to 65535, not '63'")]
+    fn parse_cell_size_rejects_size_that_is_too_small() {
+        let finder = ParameterFinder::new(vec![String::from("--cell_size"), String::from("63")]);
+
+        Bootstrapper::parse_cell_size(&finder);
+    }
+
+    #[test]
+    #[should_panic(expected = "--cell_size must be a number from 64 to 65535, not 'booga'")]
+    fn parse_cell_size_rejects_garbage() {
+        let finder = ParameterFinder::new(vec![String::from("--cell_size"), String::from("booga")]);
+
+        Bootstrapper::parse_cell_size(&finder);
+    }
+
+    #[test]
+    fn parse_rate_pack_defaults_to_free() {
+        let finder = ParameterFinder::new(vec![]);
+
+        assert_eq!(Bootstrapper::parse_rate_pack(&finder), RatePack::default());
+    }
+
+    #[test]
+    fn parse_rate_pack_reads_every_rate() {
+        let finder = ParameterFinder::new(
+            vec![
+                "--routing_service_rate",
+                "1",
+                "--routing_byte_rate",
+                "2",
+                "--exit_service_rate",
+                "3",
+                "--exit_byte_rate",
+                "4",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        );
+
+        assert_eq!(
+            Bootstrapper::parse_rate_pack(&finder),
+            RatePack {
+                routing_service_rate: 1,
+                routing_byte_rate: 2,
+                exit_service_rate: 3,
+                exit_byte_rate: 4,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "--exit_byte_rate must be a whole number, not '-1'")]
+    fn parse_rate_pack_rejects_negative_rate() {
+        let finder =
+            ParameterFinder::new(vec![String::from("--exit_byte_rate"), String::from("-1")]);
+
+        Bootstrapper::parse_rate_pack(&finder);
+    }
+
+    #[test]
+    fn parse_route_randomness_has_a_default() {
+        let finder = ParameterFinder::new(vec![]);
+
+        assert_eq!(
+            Bootstrapper::parse_route_randomness(&finder),
+            DEFAULT_ROUTE_RANDOMNESS
+        );
+    }
+
+    #[test]
+    fn parse_route_randomness_accepts_percentage() {
+        let finder =
+            ParameterFinder::new(vec![String::from("--route_randomness"), String::from("0")]);
+
+        assert_eq!(Bootstrapper::parse_route_randomness(&finder), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "--route_randomness must be a number from 0 to 100, not '101'")]
+    fn parse_route_randomness_rejects_more_than_100_percent() {
+        let finder = ParameterFinder::new(vec![
+            String::from("--route_randomness"),
+            String::from("101"),
+        ]);
+
+        Bootstrapper::parse_route_randomness(&finder);
+    }
+
+    #[test]
+    fn parse_max_node_age_has_a_default() {
+        let finder = ParameterFinder::new(vec![]);
+
+        assert_eq!(
+            Bootstrapper::parse_max_node_age(&finder),
+            Duration::from_secs(DEFAULT_MAX_NODE_AGE_HOURS * 3600)
+        );
+    }
+
+    #[test]
+    fn parse_max_node_age_accepts_hours() {
+        let finder = ParameterFinder::new(vec![String::from("--max_node_age"), String::from("2")]);
+
+        assert_eq!(
+            Bootstrapper::parse_max_node_age(&finder),
+            Duration::from_secs(7200)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "--max_node_age must be a positive number of hours, not '0'")]
+    fn parse_max_node_age_rejects_zero() {
+        let finder = ParameterFinder::new(vec![String::from("--max_node_age"), String::from("0")]);
+
+        Bootstrapper::parse_max_node_age(&finder);
+    }
+
+    #[test]
+    fn parse_max_gossip_size_has_a_default() {
+        let finder = ParameterFinder::new(vec![]);
+
+        assert_eq!(
+            Bootstrapper::parse_max_gossip_size(&finder),
+            DEFAULT_MAX_GOSSIP_SIZE
+        );
+    }
+
+    #[test]
+    fn parse_max_gossip_size_accepts_bytes() {
+        let finder = ParameterFinder::new(vec![
+            String::from("--max_gossip_size"),
+            String::from("65536"),
+        ]);
+
+        assert_eq!(Bootstrapper::parse_max_gossip_size(&finder), 65536);
+    }
+
+    #[test]
+    #[should_panic(expected = "--max_gossip_size must be a positive number of bytes, not 'big'")]
+    fn parse_max_gossip_size_rejects_nonsense() {
+        let finder =
+            ParameterFinder::new(vec![String::from("--max_gossip_size"), String::from("big")]);
+
+        Bootstrapper::parse_max_gossip_size(&finder);
+    }
+
     #[test]
     #[should_panic(expected = "--wallet_address requires a valid Ethereum wallet address")]
     fn parse_wallet_address_requires_an_address_that_is_42_characters_long() {
@@ -712,6 +1316,37 @@ mod tests {
         )
     }
 
+    #[test]
+    fn parse_neighbor_configs_accepts_ipv4_and_ipv6_neighbors_together() {
+        let finder = ParameterFinder::new(
+            vec![
+                "--booga",
+                "R29vZEtleQ:1.2.3.4:1234,2345",
+                "--booga",
+                "QW5vdGhlckdvb2RLZXk:[2001:db8::1]:4567,5678",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        );
+
+        let result = Bootstrapper::parse_neighbor_configs(&finder, "--booga");
+
+        assert_eq!(
+            result,
+            vec!(
+                (
+                    Key::new(b"GoodKey"),
+                    NodeAddr::new(&IpAddr::from_str("1.2.3.4").unwrap(), &vec!(1234, 2345))
+                ),
+                (
+                    Key::new(b"AnotherGoodKey"),
+                    NodeAddr::new(&IpAddr::from_str("2001:db8::1").unwrap(), &vec!(4567, 5678))
+                )
+            )
+        )
+    }
+
     #[test]
     fn parse_node_type_handles_standard() {
         let finder = ParameterFinder::new(
@@ -817,6 +1452,14 @@ mod tests {
             "irrelevant",
             "--wallet_address",
             "0xbDfeFf9A1f4A1bdF483d680046344316019C58CF",
+            "--data_directory",
+            "booga/data",
+            "--routing_byte_rate",
+            "5",
+            "--http_proxy_port",
+            "8080",
+            "--socks_port",
+            "1080",
         ]
         .into_iter()
         .map(String::from)
@@ -856,6 +1499,21 @@ mod tests {
             config.neighborhood_config.wallet,
             Some(Wallet::new("0xbDfeFf9A1f4A1bdF483d680046344316019C58CF"))
         );
+        assert_eq!(
+            config.accountant_config.wallet,
+            Some(Wallet::new("0xbDfeFf9A1f4A1bdF483d680046344316019C58CF"))
+        );
+        assert_eq!(
+            config.accountant_config.data_directory_opt,
+            Some(PathBuf::from("booga/data"))
+        );
+        assert_eq!(
+            config.neighborhood_config.data_directory_opt,
+            Some(PathBuf::from("booga/data"))
+        );
+        assert_eq!(config.neighborhood_config.rate_pack.routing_byte_rate, 5);
+        assert_eq!(config.http_proxy_port_opt, Some(8080));
+        assert_eq!(config.socks_port_opt, Some(1080));
     }
 
     #[test]
@@ -886,7 +1544,7 @@ mod tests {
             .build();
 
         subject.initialize_as_privileged(
-            &make_default_cli_params(),
+            &make_default_cli_params("initialize_as_root_with_no_args_binds_port_80_and_443"),
             &mut FakeStreamHolder::new().streams(),
         );
 
@@ -917,7 +1575,7 @@ mod tests {
             .build();
 
         subject.initialize_as_privileged(
-            &make_default_cli_params(),
+            &make_default_cli_params("initialize_as_root_with_no_args_produces_empty_clandestine_discriminator_factories_vector"),
             &mut FakeStreamHolder::new().streams(),
         );
 
@@ -947,6 +1605,8 @@ mod tests {
                 String::from("222.222.222.222"),
                 String::from("--port_count"),
                 String::from("1"),
+                String::from("--data_directory"),
+                data_directory_for("initialize_as_root_with_one_clandestine_port_produces_expected_clandestine_discriminator_factories_vector"),
             ],
             &mut FakeStreamHolder::new().streams(),
         );
@@ -995,6 +1655,8 @@ mod tests {
                 String::from("1.2.3.4,2.3.4.5"),
                 String::from("--port_count"),
                 String::from("0"),
+                String::from("--data_directory"),
+                data_directory_for("initialize_as_root_stores_dns_servers_and_passes_them_to_actor_system_factory_for_proxy_client_in_initialize_as_unprivileged"),
             ],
             &mut FakeStreamHolder::new().streams(),
         );
@@ -1029,6 +1691,8 @@ mod tests {
                 String::from("booga,booga"),
                 String::from("--port_count"),
                 String::from("0"),
+                String::from("--data_directory"),
+                data_directory_for("initialize_as_root_complains_about_dns_servers_syntax_errors"),
             ],
             &mut FakeStreamHolder::new().streams(),
         );
@@ -1053,6 +1717,8 @@ mod tests {
                 String::from("1.1.1.1"),
                 String::from("--port_count"),
                 String::from("0"),
+                String::from("--data_directory"),
+                data_directory_for("initialize_as_root_panics_if_tcp_listener_doesnt_bind"),
             ],
             &mut FakeStreamHolder::new().streams(),
         );
@@ -1066,7 +1732,15 @@ mod tests {
         let cryptde_ref = {
             let mut streams = holder.streams();
 
-            let cryptde_ref = Bootstrapper::initialize_cryptde();
+            let keystore = Keystore::new(
+                &ensure_node_home_directory_exists(
+                    "bootstrapper",
+                    "initialize_cryptde_and_report_local_descriptor",
+                ),
+                "",
+            );
+            let cryptde_ref =
+                Bootstrapper::initialize_cryptde(Box::new(CryptDENull::new()), &keystore);
             Bootstrapper::report_local_descriptor(cryptde_ref, ip_addr, ports, &mut streams);
 
             cryptde_ref
@@ -1094,6 +1768,89 @@ mod tests {
         assert_eq!(decrypted_data, expected_data)
     }
 
+    #[test]
+    fn report_local_descriptor_puts_ipv6_addresses_in_brackets() {
+        let mut holder = FakeStreamHolder::new();
+        let cryptde = CryptDENull::new();
+
+        Bootstrapper::report_local_descriptor(
+            &cryptde,
+            IpAddr::from_str("2001:db8::1").unwrap(),
+            vec![4567, 3456],
+            &mut holder.streams(),
+        );
+
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!(
+                "SubstratumNode local descriptor: {}:[2001:db8::1]:3456,4567\n",
+                base64::encode_config(&cryptde.public_key().data, base64::STANDARD_NO_PAD)
+            )
+        );
+    }
+
+    #[test]
+    fn initialize_cryptde_restores_identity_from_existing_keystore() {
+        let data_directory = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_cryptde_restores_identity_from_existing_keystore",
+        );
+        let keystore = Keystore::new(&data_directory, "passphrase");
+        let original = CryptDEReal::new();
+        keystore.save(&original).unwrap();
+
+        let cryptde_ref = Bootstrapper::initialize_cryptde(Box::new(CryptDEReal::new()), &keystore);
+
+        let public_key = cryptde_ref.public_key();
+        assert_eq!(public_key, original.public_key());
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not load Node identity from")]
+    fn initialize_cryptde_complains_about_wrong_keystore_passphrase() {
+        let data_directory = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_cryptde_complains_about_wrong_keystore_passphrase",
+        );
+        Keystore::new(&data_directory, "passphrase")
+            .save(&CryptDEReal::new())
+            .unwrap();
+
+        Bootstrapper::initialize_cryptde(
+            Box::new(CryptDEReal::new()),
+            &Keystore::new(&data_directory, "booga"),
+        );
+    }
+
+    #[test]
+    fn initialize_as_privileged_reports_the_same_descriptor_across_restarts() {
+        let args = make_default_cli_params(
+            "initialize_as_privileged_reports_the_same_descriptor_across_restarts",
+        );
+        let run_once = |args: &Vec<String>| {
+            let mut holder = FakeStreamHolder::new();
+            let mut subject = BootstrapperBuilder::new()
+                .add_listener_handler(Box::new(
+                    ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())),
+                ))
+                .add_listener_handler(Box::new(
+                    ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())),
+                ))
+                .build();
+            subject.initialize_as_privileged(args, &mut holder.streams());
+            holder.stdout.get_string()
+        };
+
+        let first_run = run_once(&args);
+        let second_run = run_once(&args);
+
+        assert_eq!(
+            first_run.starts_with("SubstratumNode local descriptor: "),
+            true
+        );
+        assert_eq!(second_run, first_run);
+    }
+
     #[test]
     fn initialize_as_unprivileged_moves_streams_from_listener_handlers_to_stream_handler_pool() {
         init_test_logging();
@@ -1106,6 +1863,8 @@ mod tests {
             String::from("222.222.222.222"),
             String::from("--port_count"),
             String::from("1"),
+            String::from("--data_directory"),
+            data_directory_for("initialize_as_unprivileged_moves_streams_from_listener_handlers_to_stream_handler_pool"),
         ];
         let actor_system_factory = ActorSystemFactoryMock::new();
         let mut subject = BootstrapperBuilder::new()
@@ -1184,7 +1943,7 @@ mod tests {
             .build();
 
         subject.initialize_as_privileged(
-            &make_default_cli_params(),
+            &make_default_cli_params("bootstrapper_as_future_polls_listener_handler_futures"),
             &mut FakeStreamHolder::new().streams(),
         );
         subject.initialize_as_unprivileged();
@@ -1224,7 +1983,7 @@ mod tests {
 
     #[test]
     fn no_parameters_produces_configuration_for_crash_point() {
-        let args = make_default_cli_params();
+        let args = make_default_cli_params("no_parameters_produces_configuration_for_crash_point");
         let mut subject = BootstrapperConfig::new();
         Bootstrapper::parse_args(&args, &mut subject);
 
@@ -1233,7 +1992,8 @@ mod tests {
 
     #[test]
     fn with_parameters_produces_configuration_for_crash_point() {
-        let mut args = make_default_cli_params();
+        let mut args =
+            make_default_cli_params("with_parameters_produces_configuration_for_crash_point");
         let crash_args = vec![String::from("--crash_point"), String::from("1")];
         let mut subject = BootstrapperConfig::new();
 
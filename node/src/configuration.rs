@@ -1,12 +1,15 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use discriminator::DiscriminatorFactory;
+use http_connect_discriminator_factory::HttpConnectDiscriminatorFactory;
 use http_request_start_finder::HttpRequestDiscriminatorFactory;
 use json_discriminator_factory::JsonDiscriminatorFactory;
+use socks5_discriminator_factory::Socks5DiscriminatorFactory;
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::net::UdpSocket;
+use std::str::FromStr;
 use sub_lib::parameter_finder::ParameterFinder;
 use tls_discriminator_factory::TlsDiscriminatorFactory;
 
@@ -35,7 +38,34 @@ impl Configuration {
             PortConfiguration::new(vec![Box::new(TlsDiscriminatorFactory::new())], false),
         );
 
-        let port_count = Configuration::parse_port_count(&ParameterFinder::new(args.clone()));
+        let finder = ParameterFinder::new(args.clone());
+        let http_proxy_port_opt = Configuration::parse_http_proxy_port(&finder);
+        let proxy_listen_address = Configuration::parse_proxy_listen_address(&finder);
+        if let Some(http_proxy_port) = http_proxy_port_opt {
+            self.port_configurations.insert(
+                http_proxy_port,
+                PortConfiguration::new(
+                    vec![Box::new(HttpConnectDiscriminatorFactory::new())],
+                    false,
+                )
+                .listening_on(proxy_listen_address),
+            );
+        }
+        if let Some(socks_port) = Configuration::parse_socks_port(&finder) {
+            if http_proxy_port_opt == Some(socks_port) {
+                panic!(
+                    "--socks_port and --http_proxy_port can't both be {}",
+                    socks_port
+                );
+            }
+            self.port_configurations.insert(
+                socks_port,
+                PortConfiguration::new(vec![Box::new(Socks5DiscriminatorFactory::new())], false)
+                    .listening_on(proxy_listen_address),
+            );
+        }
+
+        let port_count = Configuration::parse_port_count(&finder);
         for _ in 0..port_count {
             let port = Configuration::find_free_port();
             self.port_configurations.insert(
@@ -53,12 +83,57 @@ impl Configuration {
     }
 
     pub fn clandestine_ports(&self) -> Vec<u16> {
-        self.all_ports()
-            .into_iter()
-            .filter(|port| (*port != 80) && (*port != 443))
+        self.port_configurations
+            .iter()
+            .filter(|(_, port_configuration)| port_configuration.is_clandestine)
+            .map(|(port, _)| *port)
             .collect()
     }
 
+    pub fn parse_http_proxy_port(finder: &ParameterFinder) -> Option<u16> {
+        let usage = "--http_proxy_port <port for clients that use the Node as an HTTP proxy>";
+        match finder.find_value_for("--http_proxy_port", usage) {
+            None => None,
+            Some(ref port_str) => match port_str.parse::<u16>() {
+                Ok(port) if (port != 0) && (port != 80) && (port != 443) => Some(port),
+                _ => panic!(
+                    "--http_proxy_port needs a port number other than 80 or 443, not '{}'",
+                    port_str
+                ),
+            },
+        }
+    }
+
+    pub fn parse_socks_port(finder: &ParameterFinder) -> Option<u16> {
+        let usage = "--socks_port <port for clients that use the Node as a SOCKS5 proxy>";
+        match finder.find_value_for("--socks_port", usage) {
+            None => None,
+            Some(ref port_str) => match port_str.parse::<u16>() {
+                Ok(port) if (port != 0) && (port != 80) && (port != 443) => Some(port),
+                _ => panic!(
+                    "--socks_port needs a port number other than 80 or 443, not '{}'",
+                    port_str
+                ),
+            },
+        }
+    }
+
+    // Whoever can reach an explicit proxy port can spend this Node's money, so only the local machine can unless the
+    // user says otherwise.
+    pub fn parse_proxy_listen_address(finder: &ParameterFinder) -> IpAddr {
+        let usage = "--proxy_listen_address <IP address the explicit proxy ports listen on, default = 127.0.0.1>";
+        match finder.find_value_for("--proxy_listen_address", usage) {
+            None => IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            Some(ref ip_addr_str) => match IpAddr::from_str(ip_addr_str) {
+                Ok(ip_addr) => ip_addr,
+                Err(_) => panic!(
+                    "--proxy_listen_address needs an IP address, not '{}'",
+                    ip_addr_str
+                ),
+            },
+        }
+    }
+
     fn find_free_port() -> u16 {
         let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0))
             .expect("Not enough free ports");
@@ -84,6 +159,7 @@ impl Configuration {
 pub struct PortConfiguration {
     pub discriminator_factories: Vec<Box<DiscriminatorFactory>>,
     pub is_clandestine: bool,
+    pub listen_ip_addr_opt: Option<IpAddr>,
 }
 
 impl PortConfiguration {
@@ -94,8 +170,14 @@ impl PortConfiguration {
         PortConfiguration {
             discriminator_factories,
             is_clandestine,
+            listen_ip_addr_opt: None,
         }
     }
+
+    pub fn listening_on(mut self, listen_ip_addr: IpAddr) -> PortConfiguration {
+        self.listen_ip_addr_opt = Some(listen_ip_addr);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -167,12 +249,12 @@ mod tests {
         assert!(!port_443_configuration.is_clandestine);
         let tls_factory = port_443_configuration.discriminator_factories.remove(0);
         let mut tls_discriminator = tls_factory.make();
-        tls_discriminator.add_data(&vec![0x16, 0x03, 0x01, 0x00, 0x03, 0x01, 0x02, 0x03][..]);
+        tls_discriminator.add_data(&vec![0x17, 0x03, 0x01, 0x00, 0x03, 0x01, 0x02, 0x03][..]);
         let tls_chunk = tls_discriminator.take_chunk().unwrap();
         assert_eq!(
             tls_chunk,
             UnmaskedChunk::new(
-                vec!(0x16, 0x03, 0x01, 0x00, 0x03, 0x01, 0x02, 0x03),
+                vec!(0x17, 0x03, 0x01, 0x00, 0x03, 0x01, 0x02, 0x03),
                 true,
                 true
             )
@@ -231,6 +313,173 @@ mod tests {
         Configuration::parse_port_count(&finder);
     }
 
+    #[test]
+    fn http_proxy_port_produces_configuration_that_hears_connect_requests() {
+        let args = vec![
+            String::from("command"),
+            String::from("--http_proxy_port"),
+            String::from("8080"),
+        ];
+        let mut subject = Configuration::new();
+
+        subject.establish(&args);
+
+        let mut proxy_port_configuration = subject.port_configurations.remove(&8080).unwrap();
+        assert_eq!(proxy_port_configuration.discriminator_factories.len(), 1);
+        assert!(!proxy_port_configuration.is_clandestine);
+        let connect_factory = proxy_port_configuration.discriminator_factories.remove(0);
+        let mut connect_discriminator = connect_factory.make();
+        connect_discriminator.add_data(&b"CONNECT server.com:443 HTTP/1.1\r\n\r\n"[..]);
+        let connect_chunk = connect_discriminator.take_chunk().unwrap();
+        assert_eq!(
+            connect_chunk,
+            UnmaskedChunk::new(
+                b"CONNECT server.com:443 HTTP/1.1\r\n\r\n".to_vec(),
+                true,
+                true
+            )
+        );
+        assert_eq!(
+            proxy_port_configuration.listen_ip_addr_opt,
+            Some(IpAddr::from_str("127.0.0.1").unwrap())
+        );
+        assert_eq!(subject.clandestine_ports().len(), 0);
+    }
+
+    #[test]
+    fn http_proxy_port_listens_on_the_proxy_listen_address_if_one_is_given() {
+        let args = vec![
+            String::from("command"),
+            String::from("--http_proxy_port"),
+            String::from("8080"),
+            String::from("--proxy_listen_address"),
+            String::from("0.0.0.0"),
+        ];
+        let mut subject = Configuration::new();
+
+        subject.establish(&args);
+
+        let proxy_port_configuration = subject.port_configurations.remove(&8080).unwrap();
+        assert_eq!(
+            proxy_port_configuration.listen_ip_addr_opt,
+            Some(IpAddr::from_str("0.0.0.0").unwrap())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "--proxy_listen_address needs an IP address, not 'booga'")]
+    fn parse_proxy_listen_address_rejects_badly_formatted_address() {
+        let finder = ParameterFinder::new(vec![
+            String::from("command"),
+            String::from("--proxy_listen_address"),
+            String::from("booga"),
+        ]);
+
+        Configuration::parse_proxy_listen_address(&finder);
+    }
+
+    #[test]
+    fn no_parameters_produces_no_http_proxy_port() {
+        let finder = ParameterFinder::new(vec![String::from("command")]);
+
+        let result = Configuration::parse_http_proxy_port(&finder);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "--http_proxy_port needs a port number other than 80 or 443, not '443'"
+    )]
+    fn parse_http_proxy_port_rejects_port_the_node_already_uses() {
+        let finder = ParameterFinder::new(vec![
+            String::from("command"),
+            String::from("--http_proxy_port"),
+            String::from("443"),
+        ]);
+
+        Configuration::parse_http_proxy_port(&finder);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "--http_proxy_port needs a port number other than 80 or 443, not 'booga'"
+    )]
+    fn parse_http_proxy_port_rejects_badly_formatted_port() {
+        let finder = ParameterFinder::new(vec![
+            String::from("command"),
+            String::from("--http_proxy_port"),
+            String::from("booga"),
+        ]);
+
+        Configuration::parse_http_proxy_port(&finder);
+    }
+
+    #[test]
+    fn socks_port_produces_configuration_that_hears_socks5_greetings() {
+        let args = vec![
+            String::from("command"),
+            String::from("--socks_port"),
+            String::from("1080"),
+        ];
+        let mut subject = Configuration::new();
+
+        subject.establish(&args);
+
+        let mut socks_port_configuration = subject.port_configurations.remove(&1080).unwrap();
+        assert_eq!(socks_port_configuration.discriminator_factories.len(), 1);
+        assert!(!socks_port_configuration.is_clandestine);
+        let socks5_factory = socks_port_configuration.discriminator_factories.remove(0);
+        let mut socks5_discriminator = socks5_factory.make();
+        socks5_discriminator.add_data(&[0x05, 0x02, 0x00, 0x02]);
+        let greeting_chunk = socks5_discriminator.take_chunk().unwrap();
+        assert_eq!(
+            greeting_chunk,
+            UnmaskedChunk::new(vec![0x05, 0x02, 0x00, 0x02], true, true)
+        );
+        assert_eq!(
+            socks_port_configuration.listen_ip_addr_opt,
+            Some(IpAddr::from_str("127.0.0.1").unwrap())
+        );
+        assert_eq!(subject.clandestine_ports().len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "--socks_port and --http_proxy_port can't both be 8080")]
+    fn establish_rejects_socks_port_that_is_also_the_http_proxy_port() {
+        let args = vec![
+            String::from("command"),
+            String::from("--http_proxy_port"),
+            String::from("8080"),
+            String::from("--socks_port"),
+            String::from("8080"),
+        ];
+        let mut subject = Configuration::new();
+
+        subject.establish(&args);
+    }
+
+    #[test]
+    fn no_parameters_produces_no_socks_port() {
+        let finder = ParameterFinder::new(vec![String::from("command")]);
+
+        let result = Configuration::parse_socks_port(&finder);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "--socks_port needs a port number other than 80 or 443, not '80'")]
+    fn parse_socks_port_rejects_port_the_node_already_uses() {
+        let finder = ParameterFinder::new(vec![
+            String::from("command"),
+            String::from("--socks_port"),
+            String::from("80"),
+        ]);
+
+        Configuration::parse_socks_port(&finder);
+    }
+
     #[test]
     fn all_ports_returns_list_of_all_ports() {
         let mut subject = Configuration::new();
@@ -5,18 +5,24 @@ use actix::Context;
 use actix::Handler;
 use actix::Recipient;
 use actix::Syn;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Instant;
 use stream_messages::PoolBindMessage;
+use sub_lib::dispatcher::BanPeerMessage;
 use sub_lib::dispatcher::DispatcherSubs;
 use sub_lib::dispatcher::InboundClientData;
 use sub_lib::logger::Logger;
 use sub_lib::peer_actors::BindMessage;
 use sub_lib::stream_handler_pool::TransmitDataMsg;
+use sub_lib::utils::unmapped;
 use sub_lib::utils::NODE_MAILBOX_CAPACITY;
 
 pub struct Dispatcher {
     to_proxy_server: Option<Recipient<Syn, InboundClientData>>,
     to_hopper: Option<Recipient<Syn, InboundClientData>>,
     to_stream: Option<Recipient<Syn, TransmitDataMsg>>,
+    banned_peers: HashMap<IpAddr, Instant>,
     logger: Logger,
 }
 
@@ -47,6 +53,14 @@ impl Handler<InboundClientData> for Dispatcher {
 
     fn handle(&mut self, msg: InboundClientData, _ctx: &mut Self::Context) {
         if msg.is_clandestine {
+            if self.is_banned(&unmapped(msg.peer_addr.ip())) {
+                self.logger.debug(format!(
+                    "Dropping {} bytes from banned peer {}",
+                    msg.data.len(),
+                    msg.peer_addr
+                ));
+                return;
+            }
             self.to_hopper
                 .as_ref()
                 .expect("Hopper unbound in Dispatcher")
@@ -62,6 +76,20 @@ impl Handler<InboundClientData> for Dispatcher {
     }
 }
 
+impl Handler<BanPeerMessage> for Dispatcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: BanPeerMessage, _ctx: &mut Self::Context) {
+        self.logger.info(format!(
+            "Dropping clandestine traffic from {} for {} seconds",
+            msg.ip_addr,
+            msg.duration.as_secs()
+        ));
+        self.banned_peers
+            .insert(unmapped(msg.ip_addr), Instant::now() + msg.duration);
+    }
+}
+
 impl Handler<TransmitDataMsg> for Dispatcher {
     type Result = ();
 
@@ -85,6 +113,7 @@ impl Dispatcher {
             to_proxy_server: None,
             to_stream: None,
             to_hopper: None,
+            banned_peers: HashMap::new(),
             logger: Logger::new("Dispatcher"),
         }
     }
@@ -94,8 +123,21 @@ impl Dispatcher {
             ibcd_sub: addr.clone().recipient::<InboundClientData>(),
             bind: addr.clone().recipient::<BindMessage>(),
             from_dispatcher_client: addr.clone().recipient::<TransmitDataMsg>(),
+            ban_peer: addr.clone().recipient::<BanPeerMessage>(),
         }
     }
+
+    // Bans are forgotten once they expire.
+    fn is_banned(&mut self, ip_addr: &IpAddr) -> bool {
+        let expired = match self.banned_peers.get(ip_addr) {
+            Some(banned_until) => *banned_until <= Instant::now(),
+            None => return false,
+        };
+        if expired {
+            self.banned_peers.remove(ip_addr);
+        }
+        !expired
+    }
 }
 
 #[cfg(test)]
@@ -108,6 +150,7 @@ mod tests {
     use node_test_utils::make_stream_handler_pool_subs_from;
     use std::net::SocketAddr;
     use std::str::FromStr;
+    use std::time::Duration;
     use sub_lib::dispatcher::Endpoint;
     use test_utils::recorder::make_peer_actors_from;
     use test_utils::recorder::Recorder;
@@ -201,6 +244,66 @@ mod tests {
         assert_eq!(recording.len(), 1);
     }
 
+    #[test]
+    fn drops_clandestine_data_from_banned_peers_until_the_ban_expires() {
+        let system = System::new("test");
+        let subject = Dispatcher::new();
+        let subject_addr: Addr<Syn, Dispatcher> = subject.start();
+        let subject_ibcd = subject_addr.clone().recipient::<InboundClientData>();
+        let hopper = Recorder::new();
+        let recording_arc = hopper.get_recording();
+        let awaiter = hopper.get_awaiter();
+        let banned_addr = SocketAddr::from_str("6.6.6.6:666").unwrap();
+        let mapped_banned_addr = SocketAddr::from_str("[::ffff:6.6.6.6]:667").unwrap();
+        let briefly_banned_addr = SocketAddr::from_str("7.7.7.7:777").unwrap();
+        let ibcd = |peer_addr: SocketAddr, data: Vec<u8>| InboundClientData {
+            peer_addr,
+            reception_port: Some(8080),
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data,
+        };
+        let mut peer_actors = make_peer_actors_from(None, None, Some(hopper), None, None, None);
+        peer_actors.dispatcher = Dispatcher::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        subject_addr
+            .try_send(BanPeerMessage {
+                ip_addr: banned_addr.ip(),
+                duration: Duration::from_secs(3600),
+            })
+            .unwrap();
+        subject_addr
+            .try_send(BanPeerMessage {
+                ip_addr: briefly_banned_addr.ip(),
+                duration: Duration::from_secs(0),
+            })
+            .unwrap();
+
+        subject_ibcd.try_send(ibcd(banned_addr, vec![1])).unwrap();
+        subject_ibcd
+            .try_send(ibcd(mapped_banned_addr, vec![3]))
+            .unwrap();
+        subject_ibcd
+            .try_send(ibcd(briefly_banned_addr, vec![2]))
+            .unwrap();
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+
+        awaiter.await_message_count(1);
+        let recording = recording_arc.lock().unwrap();
+        let message = &recording.get_record::<InboundClientData>(0) as *const _;
+        let (actual_socket_addr, actual_data) = unsafe {
+            let tptr = message as *const Box<InboundClientData>;
+            let message = &*tptr;
+            (message.peer_addr, message.data.clone())
+        };
+        assert_eq!(actual_socket_addr, briefly_banned_addr);
+        assert_eq!(actual_data, vec![2]);
+        assert_eq!(recording.len(), 1);
+    }
+
     #[test]
     #[should_panic(expected = "ProxyServer unbound in Dispatcher")]
     fn inbound_client_data_handler_panics_when_proxy_server_is_unbound() {
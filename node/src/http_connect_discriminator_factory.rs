@@ -0,0 +1,166 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use discriminator::Discriminator;
+use discriminator::DiscriminatorFactory;
+use null_masquerader::NullMasquerader;
+use sub_lib::framer::FramedChunk;
+use sub_lib::framer::Framer;
+use sub_lib::utils::index_of;
+
+// A client that goes on sending without ever finishing its request head isn't going to send a CONNECT request.
+const MAX_REQUEST_HEAD_LENGTH: usize = 8192;
+
+// Frames the CONNECT request that opens a tunnel by itself, and then frames whatever comes through the tunnel as
+// soon as it arrives.
+pub struct HttpConnectFramer {
+    data_so_far: Vec<u8>,
+    tunnelling: bool,
+}
+
+impl Framer for HttpConnectFramer {
+    fn add_data(&mut self, data: &[u8]) {
+        self.data_so_far.extend(data);
+    }
+
+    fn take_frame(&mut self) -> Option<FramedChunk> {
+        if self.tunnelling {
+            if self.data_so_far.is_empty() {
+                return None;
+            }
+            return Some(FramedChunk {
+                chunk: self.data_so_far.drain(..).collect(),
+                last_chunk: false,
+            });
+        }
+        let head_length = match index_of(&self.data_so_far[..], &b"\r\n\r\n"[..]) {
+            Some(index) => index + 4,
+            None if self.data_so_far.len() > MAX_REQUEST_HEAD_LENGTH => self.data_so_far.len(),
+            None => return None,
+        };
+        self.tunnelling = true;
+        Some(FramedChunk {
+            chunk: self.data_so_far.drain(0..head_length).collect(),
+            last_chunk: false,
+        })
+    }
+}
+
+impl HttpConnectFramer {
+    pub fn new() -> HttpConnectFramer {
+        HttpConnectFramer {
+            data_so_far: Vec::new(),
+            tunnelling: false,
+        }
+    }
+}
+
+pub struct HttpConnectDiscriminatorFactory {}
+
+impl DiscriminatorFactory for HttpConnectDiscriminatorFactory {
+    fn make(&self) -> Discriminator {
+        Discriminator::new(
+            Box::new(HttpConnectFramer::new()),
+            vec![Box::new(NullMasquerader::new())],
+        )
+    }
+
+    fn duplicate(&self) -> Box<DiscriminatorFactory> {
+        Box::new(HttpConnectDiscriminatorFactory {})
+    }
+}
+
+impl HttpConnectDiscriminatorFactory {
+    pub fn new() -> HttpConnectDiscriminatorFactory {
+        HttpConnectDiscriminatorFactory {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use discriminator::UnmaskedChunk;
+
+    #[test]
+    fn discriminator_factory_duplicate_works() {
+        let subject = HttpConnectDiscriminatorFactory::new();
+
+        subject.duplicate();
+
+        // no panic; test passes
+    }
+
+    #[test]
+    fn waits_for_the_end_of_the_connect_request() {
+        let mut subject = HttpConnectFramer::new();
+        subject.add_data(&b"CONNECT server.com:443 HTTP/1.1\r\nHost: server.com:443\r\n"[..]);
+
+        let result = subject.take_frame();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn frames_connect_request_by_itself_and_then_everything_after_it_as_it_comes() {
+        let mut subject = HttpConnectFramer::new();
+        subject.add_data(&b"CONNECT server.com:443 HTTP/1.1\r\n\r\n\x16\x03\x01"[..]);
+
+        let connect_request = subject.take_frame();
+        let tunnelled_data = subject.take_frame();
+        let nothing = subject.take_frame();
+        subject.add_data(&b"GET / HTTP/1.1\r\n\r\n"[..]);
+        let more_tunnelled_data = subject.take_frame();
+
+        assert_eq!(
+            connect_request,
+            Some(FramedChunk {
+                chunk: b"CONNECT server.com:443 HTTP/1.1\r\n\r\n".to_vec(),
+                last_chunk: false,
+            })
+        );
+        assert_eq!(
+            tunnelled_data,
+            Some(FramedChunk {
+                chunk: vec![0x16, 0x03, 0x01],
+                last_chunk: false,
+            })
+        );
+        assert_eq!(nothing, None);
+        assert_eq!(
+            more_tunnelled_data,
+            Some(FramedChunk {
+                chunk: b"GET / HTTP/1.1\r\n\r\n".to_vec(),
+                last_chunk: false,
+            })
+        );
+    }
+
+    #[test]
+    fn gives_up_waiting_for_a_request_head_that_is_too_long() {
+        let mut subject = HttpConnectFramer::new();
+        let garbage = vec![b'x'; MAX_REQUEST_HEAD_LENGTH + 1];
+        subject.add_data(&garbage[..]);
+
+        let result = subject.take_frame();
+
+        assert_eq!(
+            result,
+            Some(FramedChunk {
+                chunk: garbage,
+                last_chunk: false,
+            })
+        );
+    }
+
+    #[test]
+    fn factory_makes_discriminator_that_passes_connect_request_to_proxy_server() {
+        let data = &b"CONNECT server.com:443 HTTP/1.1\r\n\r\n"[..];
+        let subject = HttpConnectDiscriminatorFactory::new();
+        let mut discriminator = subject.make();
+
+        discriminator.add_data(data);
+
+        assert_eq!(
+            discriminator.take_chunk(),
+            Some(UnmaskedChunk::new(Vec::from(data), true, true))
+        );
+    }
+}
@@ -242,4 +242,32 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn factory_makes_discriminator_that_frames_each_request_on_a_kept_alive_connection() {
+        let requests: Vec<&[u8]> = vec![
+            b"POST /form HTTP/1.1\r\nHost: one.com\r\ncontent-length: 12\r\n\r\nGET /nothing",
+            b"PUT /upload HTTP/1.1\r\nHost: one.com\r\nTransfer-Encoding: chunked\r\n\r\n",
+            b"4\r\nGET \r\n",
+            b"0\r\n\r\n",
+            b"GET /index.html HTTP/1.1\r\nHost: two.com\r\n\r\n",
+        ];
+        let subject = HttpRequestDiscriminatorFactory::new();
+        let mut http_discriminator = subject.make();
+        http_discriminator.add_data(&requests.concat()[..]);
+
+        let chunks: Vec<Vec<u8>> = (0..requests.len())
+            .map(|_| http_discriminator.take_chunk().unwrap().chunk)
+            .collect();
+        let nothing = http_discriminator.take_chunk();
+
+        assert_eq!(
+            chunks,
+            requests
+                .iter()
+                .map(|request| request.to_vec())
+                .collect::<Vec<Vec<u8>>>()
+        );
+        assert_eq!(nothing, None);
+    }
 }
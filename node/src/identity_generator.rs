@@ -0,0 +1,163 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use base64;
+use bootstrapper::Bootstrapper;
+use keystore::Keystore;
+use privilege_drop::EnvironmentWrapperReal;
+use sub_lib::main_tools::Command;
+use sub_lib::main_tools::StdStreams;
+use sub_lib::parameter_finder::ParameterFinder;
+
+pub const GENERATE_IDENTITY_FLAG: &str = "--generate_identity";
+
+pub struct IdentityGenerator {}
+
+impl Command for IdentityGenerator {
+    fn go(&mut self, streams: &mut StdStreams, args: &Vec<String>) -> u8 {
+        let finder = ParameterFinder::new(args.clone());
+        let keystore = Keystore::new(
+            &Bootstrapper::parse_data_directory(&finder),
+            &Bootstrapper::parse_keystore_passphrase(
+                args,
+                &finder,
+                &EnvironmentWrapperReal,
+                streams,
+            ),
+        );
+        if keystore.exists() {
+            writeln!(
+                streams.stderr,
+                "A Node identity already exists in {}; remove it first if you really want to replace it",
+                keystore.path().display()
+            )
+            .expect("Internal error");
+            return 1;
+        }
+        let mut cryptde = Bootstrapper::parse_cryptde(&finder);
+        cryptde.generate_key_pair();
+        match keystore.save(cryptde.as_ref()) {
+            Ok(()) => {
+                writeln!(
+                    streams.stdout,
+                    "SubstratumNode public key: {}\nSaved in {}",
+                    base64::encode_config(&cryptde.public_key().data, base64::STANDARD_NO_PAD),
+                    keystore.path().display()
+                )
+                .expect("Internal error");
+                0
+            }
+            Err(e) => {
+                writeln!(streams.stderr, "Could not save Node identity: {:?}", e)
+                    .expect("Internal error");
+                1
+            }
+        }
+    }
+}
+
+impl IdentityGenerator {
+    pub fn new() -> IdentityGenerator {
+        IdentityGenerator {}
+    }
+
+    pub fn is_requested(args: &Vec<String>) -> bool {
+        args.contains(&String::from(GENERATE_IDENTITY_FLAG))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sub_lib::cryptde::CryptDE;
+    use sub_lib::cryptde_real::CryptDEReal;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
+    use test_utils::test_utils::FakeStreamHolder;
+
+    fn make_args(data_directory: &str) -> Vec<String> {
+        vec![
+            GENERATE_IDENTITY_FLAG,
+            "--data_directory",
+            data_directory,
+            "--keystore_passphrase",
+            "passphrase",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect()
+    }
+
+    #[test]
+    fn is_requested_recognizes_the_flag() {
+        assert_eq!(
+            IdentityGenerator::is_requested(&vec![
+                String::from("--dns_servers"),
+                String::from("1.1.1.1"),
+                String::from(GENERATE_IDENTITY_FLAG),
+            ]),
+            true
+        );
+        assert_eq!(
+            IdentityGenerator::is_requested(&vec![
+                String::from("--dns_servers"),
+                String::from("1.1.1.1"),
+            ]),
+            false
+        );
+    }
+
+    #[test]
+    fn generates_and_saves_a_new_identity() {
+        let data_directory =
+            ensure_node_home_directory_exists("identity_generator", "generates_and_saves");
+        let mut holder = FakeStreamHolder::new();
+        let mut subject = IdentityGenerator::new();
+
+        let result = subject.go(
+            &mut holder.streams(),
+            &make_args(data_directory.to_str().unwrap()),
+        );
+
+        assert_eq!(result, 0);
+        let keystore = Keystore::new(&data_directory, "passphrase");
+        let mut cryptde = CryptDEReal::new();
+        keystore.load(&mut cryptde).unwrap();
+        assert_eq!(
+            holder.stdout.get_string(),
+            format!(
+                "SubstratumNode public key: {}\nSaved in {}\n",
+                base64::encode_config(&cryptde.public_key().data, base64::STANDARD_NO_PAD),
+                keystore.path().display()
+            )
+        );
+    }
+
+    #[test]
+    fn refuses_to_replace_an_existing_identity() {
+        let data_directory =
+            ensure_node_home_directory_exists("identity_generator", "refuses_to_replace");
+        let original = CryptDEReal::new();
+        Keystore::new(&data_directory, "passphrase")
+            .save(&original)
+            .unwrap();
+        let mut holder = FakeStreamHolder::new();
+        let mut subject = IdentityGenerator::new();
+
+        let result = subject.go(
+            &mut holder.streams(),
+            &make_args(data_directory.to_str().unwrap()),
+        );
+
+        assert_eq!(result, 1);
+        assert_eq!(
+            holder
+                .stderr
+                .get_string()
+                .starts_with("A Node identity already exists in "),
+            true
+        );
+        let mut cryptde = CryptDEReal::new();
+        Keystore::new(&data_directory, "passphrase")
+            .load(&mut cryptde)
+            .unwrap();
+        assert_eq!(cryptde.public_key(), original.public_key());
+    }
+}
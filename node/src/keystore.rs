@@ -0,0 +1,298 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use serde_cbor;
+use sodiumoxide;
+use sodiumoxide::crypto::pwhash::argon2id13;
+use sodiumoxide::crypto::secretbox;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use sub_lib::cryptde::CryptDE;
+use sub_lib::cryptde::Key;
+
+pub const KEYSTORE_FILENAME: &str = "node_identity.keystore";
+const KEYSTORE_VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum KeystoreError {
+    IoError(String),
+    Corrupt(String),
+    WrongPassphrase,
+    InvalidKeyPair(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct KeystoreFile {
+    version: u8,
+    public_key: Key,
+    salt: Vec<u8>,
+    opslimit: usize,
+    memlimit: usize,
+    nonce: Vec<u8>,
+    encrypted_private_key: Vec<u8>,
+}
+
+pub struct Keystore {
+    path: PathBuf,
+    passphrase: String,
+}
+
+impl Keystore {
+    pub fn new(data_directory: &Path, passphrase: &str) -> Keystore {
+        Keystore {
+            path: data_directory.join(KEYSTORE_FILENAME),
+            passphrase: String::from(passphrase),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn load(&self, cryptde: &mut CryptDE) -> Result<(), KeystoreError> {
+        let bytes = fs::read(&self.path).map_err(|e| {
+            KeystoreError::IoError(format!("Could not read {}: {}", self.path.display(), e))
+        })?;
+        let file: KeystoreFile = serde_cbor::de::from_slice(&bytes[..]).map_err(|e| {
+            KeystoreError::Corrupt(format!("Could not parse {}: {}", self.path.display(), e))
+        })?;
+        if file.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::Corrupt(format!(
+                "Unsupported keystore version {} in {}",
+                file.version,
+                self.path.display()
+            )));
+        }
+        let salt = argon2id13::Salt::from_slice(&file.salt[..])
+            .ok_or_else(|| KeystoreError::Corrupt(String::from("Malformed salt")))?;
+        let nonce = secretbox::Nonce::from_slice(&file.nonce[..])
+            .ok_or_else(|| KeystoreError::Corrupt(String::from("Malformed nonce")))?;
+        let key = self.derive_key(
+            &salt,
+            argon2id13::OpsLimit(file.opslimit),
+            argon2id13::MemLimit(file.memlimit),
+        )?;
+        let private_key_data = secretbox::open(&file.encrypted_private_key[..], &nonce, &key)
+            .map_err(|_| KeystoreError::WrongPassphrase)?;
+        cryptde
+            .restore_key_pair(&Key::new(&private_key_data[..]), &file.public_key)
+            .map_err(|e| KeystoreError::InvalidKeyPair(format!("{:?}", e)))
+    }
+
+    pub fn save(&self, cryptde: &CryptDE) -> Result<(), KeystoreError> {
+        let salt = argon2id13::gen_salt();
+        let nonce = secretbox::gen_nonce();
+        let key = self.derive_key(
+            &salt,
+            argon2id13::OPSLIMIT_INTERACTIVE,
+            argon2id13::MEMLIMIT_INTERACTIVE,
+        )?;
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            public_key: cryptde.public_key(),
+            salt: salt.0.to_vec(),
+            opslimit: argon2id13::OPSLIMIT_INTERACTIVE.0,
+            memlimit: argon2id13::MEMLIMIT_INTERACTIVE.0,
+            nonce: nonce.0.to_vec(),
+            encrypted_private_key: secretbox::seal(&cryptde.private_key().data[..], &nonce, &key),
+        };
+        let bytes = serde_cbor::ser::to_vec(&file).expect("Internal error");
+        self.write_atomically(&bytes[..])
+    }
+
+    // Writing to a scratch file and renaming it means a crash can't leave a half-written keystore.
+    fn write_atomically(&self, bytes: &[u8]) -> Result<(), KeystoreError> {
+        let io_error = |e: ::std::io::Error| {
+            KeystoreError::IoError(format!("Could not write {}: {}", self.path.display(), e))
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let scratch_path = self.path.with_extension("tmp");
+        {
+            let mut file = Keystore::open_private(&scratch_path).map_err(io_error)?;
+            file.write_all(bytes).map_err(io_error)?;
+            file.sync_all().map_err(io_error)?;
+        }
+        fs::rename(&scratch_path, &self.path).map_err(io_error)
+    }
+
+    #[cfg(unix)]
+    fn open_private(path: &Path) -> ::std::io::Result<fs::File> {
+        use std::os::unix::fs::OpenOptionsExt;
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+    }
+
+    #[cfg(not(unix))]
+    fn open_private(path: &Path) -> ::std::io::Result<fs::File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    fn derive_key(
+        &self,
+        salt: &argon2id13::Salt,
+        opslimit: argon2id13::OpsLimit,
+        memlimit: argon2id13::MemLimit,
+    ) -> Result<secretbox::Key, KeystoreError> {
+        sodiumoxide::init().expect("Could not initialize libsodium");
+        let mut key = secretbox::Key([0; secretbox::KEYBYTES]);
+        {
+            let secretbox::Key(ref mut key_bytes) = key;
+            argon2id13::derive_key(
+                key_bytes,
+                self.passphrase.as_bytes(),
+                salt,
+                opslimit,
+                memlimit,
+            )
+            .map_err(|_| {
+                KeystoreError::IoError(String::from(
+                    "Could not derive keystore key from passphrase",
+                ))
+            })?;
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sub_lib::cryptde::CryptDE;
+    use sub_lib::cryptde_null::CryptDENull;
+    use sub_lib::cryptde_real::CryptDEReal;
+    use test_utils::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn keystore_does_not_exist_before_it_is_saved() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "does_not_exist");
+
+        let subject = Keystore::new(&data_directory, "passphrase");
+
+        assert_eq!(subject.exists(), false);
+        assert_eq!(
+            subject.path(),
+            data_directory.join(KEYSTORE_FILENAME).as_path()
+        );
+    }
+
+    #[test]
+    fn saved_key_pair_can_be_loaded() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "save_and_load");
+        let original = CryptDEReal::new();
+        let subject = Keystore::new(&data_directory, "passphrase");
+        subject.save(&original).unwrap();
+        let mut restored = CryptDEReal::new();
+
+        let result = subject.load(&mut restored);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(subject.exists(), true);
+        assert_eq!(restored.public_key(), original.public_key());
+        assert_eq!(restored.private_key(), original.private_key());
+    }
+
+    #[test]
+    fn private_key_is_not_stored_in_the_clear() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "not_in_the_clear");
+        let original = CryptDEReal::new();
+        let subject = Keystore::new(&data_directory, "passphrase");
+
+        subject.save(&original).unwrap();
+
+        let contents = fs::read(subject.path()).unwrap();
+        let private_key = original.private_key().data;
+        assert_eq!(
+            contents
+                .windows(private_key.len())
+                .any(|window| window == &private_key[..]),
+            false
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keystore_is_readable_only_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let data_directory = ensure_node_home_directory_exists("keystore", "permissions");
+        let subject = Keystore::new(&data_directory, "passphrase");
+
+        subject.save(&CryptDEReal::new()).unwrap();
+
+        let mode = fs::metadata(subject.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "wrong_passphrase");
+        let original = CryptDEReal::new();
+        Keystore::new(&data_directory, "passphrase")
+            .save(&original)
+            .unwrap();
+        let mut restored = CryptDEReal::new();
+        let before = restored.private_key();
+
+        let result = Keystore::new(&data_directory, "booga").load(&mut restored);
+
+        assert_eq!(result, Err(KeystoreError::WrongPassphrase));
+        assert_eq!(restored.private_key(), before);
+    }
+
+    #[test]
+    fn garbage_keystore_is_reported_as_corrupt() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "garbage");
+        let subject = Keystore::new(&data_directory, "passphrase");
+        fs::write(subject.path(), b"booga").unwrap();
+
+        let result = subject.load(&mut CryptDEReal::new());
+
+        match result {
+            Err(KeystoreError::Corrupt(_)) => (),
+            x => panic!("Expected Corrupt, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn missing_keystore_is_reported_as_io_error() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "missing");
+        let subject = Keystore::new(&data_directory, "passphrase");
+
+        let result = subject.load(&mut CryptDEReal::new());
+
+        match result {
+            Err(KeystoreError::IoError(_)) => (),
+            x => panic!("Expected IoError, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn key_pair_from_a_different_cryptde_is_rejected() {
+        let data_directory = ensure_node_home_directory_exists("keystore", "different_cryptde");
+        let mut original = CryptDENull::new();
+        original.generate_key_pair();
+        let subject = Keystore::new(&data_directory, "passphrase");
+        subject.save(&original).unwrap();
+
+        let result = subject.load(&mut CryptDEReal::new());
+
+        match result {
+            Err(KeystoreError::InvalidKeyPair(_)) => (),
+            x => panic!("Expected InvalidKeyPair, got {:?}", x),
+        }
+    }
+}
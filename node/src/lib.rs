@@ -3,6 +3,7 @@
 extern crate actix;
 extern crate base64;
 extern crate chrono;
+extern crate dirs;
 extern crate entry_dns_lib;
 extern crate flexi_logger;
 #[macro_use]
@@ -17,6 +18,7 @@ extern crate serde_cbor;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate sodiumoxide;
 extern crate sub_lib;
 extern crate tokio;
 
@@ -33,15 +35,19 @@ mod configuration;
 mod crash_test_dummy;
 pub mod discriminator;
 mod dispatcher;
+pub mod http_connect_discriminator_factory;
 pub mod http_request_start_finder;
+pub mod identity_generator;
 pub mod json_discriminator_factory;
 pub mod json_framer;
 pub mod json_masquerader;
+mod keystore;
 mod listener_handler;
 pub mod masquerader;
 mod null_masquerader;
 mod privilege_drop;
 pub mod server_initializer;
+pub mod socks5_discriminator_factory;
 mod stream_handler_pool;
 mod stream_messages;
 mod stream_reader;
@@ -6,6 +6,7 @@ use std::io;
 use std::marker::Send;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::net::SocketAddr;
 use stream_messages::AddStreamMsg;
 use sub_lib::logger::Logger;
@@ -13,6 +14,7 @@ use sub_lib::stream_connector::StreamConnector;
 use sub_lib::stream_connector::StreamConnectorReal;
 use sub_lib::tokio_wrappers::TokioListenerWrapper;
 use sub_lib::tokio_wrappers::TokioListenerWrapperReal;
+use sub_lib::utils::unmapped;
 use tokio::prelude::Async;
 use tokio::prelude::Future;
 
@@ -21,6 +23,7 @@ pub trait ListenerHandler: Send + Future {
         &mut self,
         port: u16,
         port_configuration: PortConfiguration,
+        local_ip_addr: IpAddr,
     ) -> io::Result<()>;
     fn bind_subs(&mut self, add_stream_sub: Recipient<Syn, AddStreamMsg>);
 }
@@ -42,12 +45,23 @@ impl ListenerHandler for ListenerHandlerReal {
         &mut self,
         port: u16,
         port_configuration: PortConfiguration,
+        local_ip_addr: IpAddr,
     ) -> io::Result<()> {
+        // Other Nodes reach clandestine ports at the local IP address, so they must listen in its address family.
+        // Everything else is reached over IPv4, unless the port was told exactly where to listen.
+        let listen_ip_addr = match (
+            port_configuration.listen_ip_addr_opt,
+            port_configuration.is_clandestine,
+            local_ip_addr,
+        ) {
+            (Some(listen_ip_addr), _, _) => listen_ip_addr,
+            (None, true, IpAddr::V6(_)) => IpAddr::V6(Ipv6Addr::from(0)),
+            (None, _, _) => IpAddr::V4(Ipv4Addr::from(0)),
+        };
         self.port = Some(port);
         self.port_configuration = Some(port_configuration);
         self.logger = Logger::new(&format!("ListenerHandler {}", port));
-        self.listener
-            .bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(0)), port))
+        self.listener.bind(SocketAddr::new(listen_ip_addr, port))
     }
 
     fn bind_subs(&mut self, add_stream_sub: Recipient<Syn, AddStreamMsg>) {
@@ -64,11 +78,23 @@ impl Future for ListenerHandlerReal {
             let result = self.listener.poll_accept();
             match result {
                 Ok(Async::Ready((stream, _socket_addr))) => {
+                    let mut connection_info =
+                        StreamConnectorReal {}.split_stream(stream, &self.logger);
+                    // A clandestine port listening at [::] takes IPv4 connections too; they must be attributed
+                    // (and banned) by their IPv4 addresses.
+                    connection_info.peer_addr = SocketAddr::new(
+                        unmapped(connection_info.peer_addr.ip()),
+                        connection_info.peer_addr.port(),
+                    );
+                    connection_info.local_addr = SocketAddr::new(
+                        unmapped(connection_info.local_addr.ip()),
+                        connection_info.local_addr.port(),
+                    );
                     self.add_stream_sub
                         .as_ref()
                         .expect("Internal error: StreamHandlerPool unbound")
                         .try_send(AddStreamMsg::new(
-                            StreamConnectorReal {}.split_stream(stream, &self.logger),
+                            connection_info,
                             self.port,
                             self.port_configuration
                                 .as_ref()
@@ -121,6 +147,7 @@ mod tests {
     use actix::Actor;
     use actix::Addr;
     use actix::System;
+    use configuration::Configuration;
     use configuration::PortConfiguration;
     use node_test_utils::NullDiscriminatorFactory;
     use std::cell::RefCell;
@@ -141,6 +168,7 @@ mod tests {
     use test_utils::test_utils::find_free_port;
     use tokio;
     use tokio::net::TcpStream;
+    use tokio::reactor::Handle;
 
     struct TokioListenerWrapperMock {
         log: Arc<TestLog>,
@@ -204,6 +232,7 @@ mod tests {
         let result = subject.bind_port_and_configuration(
             1234,
             PortConfiguration::new(vec![Box::new(discriminator_factory)], false),
+            IpAddr::from_str("1.2.3.4").unwrap(),
         );
 
         assert_eq!(result.err().unwrap().kind(), ErrorKind::AddrNotAvailable);
@@ -221,6 +250,7 @@ mod tests {
         let result = subject.bind_port_and_configuration(
             2345,
             PortConfiguration::new(vec![Box::new(discriminator_factory)], true),
+            IpAddr::from_str("1.2.3.4").unwrap(),
         );
 
         assert_eq!(result.unwrap(), ());
@@ -237,6 +267,72 @@ mod tests {
         assert!(port_configuration.is_clandestine);
     }
 
+    #[test]
+    fn clandestine_ports_listen_on_ipv6_if_the_local_ip_address_is_ipv6() {
+        let local_ip_addr = IpAddr::from_str("2001:db8::1").unwrap();
+        let clandestine_listener = TokioListenerWrapperMock::new().bind_result(Ok(()));
+        let clandestine_listener_log = clandestine_listener.log.clone();
+        let mut clandestine_subject = ListenerHandlerReal::new();
+        clandestine_subject.listener = Box::new(clandestine_listener);
+        let browser_listener = TokioListenerWrapperMock::new().bind_result(Ok(()));
+        let browser_listener_log = browser_listener.log.clone();
+        let mut browser_subject = ListenerHandlerReal::new();
+        browser_subject.listener = Box::new(browser_listener);
+
+        clandestine_subject
+            .bind_port_and_configuration(2345, PortConfiguration::new(vec![], true), local_ip_addr)
+            .unwrap();
+        browser_subject
+            .bind_port_and_configuration(80, PortConfiguration::new(vec![], false), local_ip_addr)
+            .unwrap();
+
+        assert_eq!(
+            clandestine_listener_log.dump(),
+            vec!(format!(
+                "bind ({:?})",
+                SocketAddr::from_str("[::]:2345").unwrap()
+            ))
+        );
+        assert_eq!(
+            browser_listener_log.dump(),
+            vec!(format!(
+                "bind ({:?})",
+                SocketAddr::from_str("0.0.0.0:80").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn http_proxy_port_listens_only_on_the_loopback_address_by_default() {
+        let listener = TokioListenerWrapperMock::new().bind_result(Ok(()));
+        let listener_log = listener.log.clone();
+        let mut subject = ListenerHandlerReal::new();
+        subject.listener = Box::new(listener);
+        let mut configuration = Configuration::new();
+        configuration.establish(&vec![
+            String::from("command"),
+            String::from("--http_proxy_port"),
+            String::from("8080"),
+        ]);
+        let port_configuration = configuration.port_configurations.remove(&8080).unwrap();
+
+        subject
+            .bind_port_and_configuration(
+                8080,
+                port_configuration,
+                IpAddr::from_str("1.2.3.4").unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            listener_log.dump(),
+            vec!(format!(
+                "bind ({:?})",
+                SocketAddr::from_str("127.0.0.1:8080").unwrap()
+            ))
+        );
+    }
+
     #[test]
     fn handles_connection_errors() {
         init_test_logging();
@@ -264,7 +360,11 @@ mod tests {
             subject.listener = Box::new(tokio_listener_wrapper);
             subject.bind_subs(add_stream_sub);
             subject
-                .bind_port_and_configuration(port, PortConfiguration::new(vec![], false))
+                .bind_port_and_configuration(
+                    port,
+                    PortConfiguration::new(vec![], false),
+                    IpAddr::from_str("1.2.3.4").unwrap(),
+                )
                 .unwrap();
             tokio::run(subject)
         });
@@ -302,7 +402,11 @@ mod tests {
             let mut subject = ListenerHandlerReal::new();
             subject.bind_subs(add_stream_sub);
             subject
-                .bind_port_and_configuration(port, PortConfiguration::new(vec![], false))
+                .bind_port_and_configuration(
+                    port,
+                    PortConfiguration::new(vec![], false),
+                    IpAddr::from_str("1.2.3.4").unwrap(),
+                )
                 .unwrap();
             tokio::run(subject)
         });
@@ -349,6 +453,63 @@ mod tests {
         assert_eq!(recording.len(), 3);
     }
 
+    #[test]
+    fn ipv4_peers_of_a_dual_stack_listener_are_known_by_their_ipv4_addresses() {
+        let (stream_handler_pool, awaiter, recording_arc) = make_recorder();
+        let dual_stack_listener = net::TcpListener::bind("[::]:0").unwrap();
+        let listener_port = dual_stack_listener.local_addr().unwrap().port();
+        let ipv4_peer = net::TcpStream::connect(SocketAddr::new(
+            IpAddr::from_str("127.0.0.1").unwrap(),
+            listener_port,
+        ))
+        .unwrap();
+        let (accepted_stream, mapped_peer_addr) = dual_stack_listener.accept().unwrap();
+        assert_eq!(
+            mapped_peer_addr.ip(),
+            IpAddr::from_str("::ffff:127.0.0.1").unwrap()
+        );
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = System::new(
+                "ipv4_peers_of_a_dual_stack_listener_are_known_by_their_ipv4_addresses",
+            );
+            let add_stream_sub = start_recorder(stream_handler_pool);
+            tx.send(add_stream_sub).is_ok();
+            system.run();
+        });
+        thread::spawn(move || {
+            let add_stream_sub = rx.recv().unwrap();
+            let accepted_stream = TcpStream::from_std(accepted_stream, &Handle::default()).unwrap();
+            let tokio_listener_wrapper = TokioListenerWrapperMock::new()
+                .bind_result(Ok(()))
+                .poll_accept_results(vec![
+                    Ok(Async::Ready((accepted_stream, mapped_peer_addr))),
+                    Ok(Async::NotReady),
+                ]);
+            let mut subject = ListenerHandlerReal::new();
+            subject.listener = Box::new(tokio_listener_wrapper);
+            subject.bind_subs(add_stream_sub);
+            subject
+                .bind_port_and_configuration(
+                    listener_port,
+                    PortConfiguration::new(vec![], true),
+                    IpAddr::from_str("::1").unwrap(),
+                )
+                .unwrap();
+            tokio::run(subject)
+        });
+
+        awaiter.await_message_count(1);
+        let recording = recording_arc.lock().unwrap();
+        let connection_info = &recording.get_record::<AddStreamMsg>(0).connection_info;
+        assert_eq!(connection_info.peer_addr, ipv4_peer.local_addr().unwrap());
+        assert_eq!(
+            connection_info.local_addr,
+            SocketAddr::new(IpAddr::from_str("127.0.0.1").unwrap(), listener_port)
+        );
+    }
+
     fn start_recorder(recorder: Recorder) -> Recipient<Syn, AddStreamMsg> {
         let recorder_addr: Addr<Syn, Recorder> = recorder.start();
         recorder_addr.recipient::<AddStreamMsg>()
@@ -6,12 +6,24 @@ extern crate sub_lib;
 extern crate tokio;
 
 use futures::future::lazy;
+use node_lib::identity_generator::IdentityGenerator;
 use node_lib::server_initializer::ServerInitializer;
 use std::io;
 use sub_lib::main_tools::Command;
 use sub_lib::main_tools::StdStreams;
 
 pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if IdentityGenerator::is_requested(&args) {
+        let mut streams: StdStreams = StdStreams {
+            stdin: &mut io::stdin(),
+            stdout: &mut io::stdout(),
+            stderr: &mut io::stderr(),
+        };
+        let exit_code = IdentityGenerator::new().go(&mut streams, &args);
+        ::std::process::exit(exit_code as i32);
+    }
+
     let main_fn = move || {
         let mut streams: StdStreams = StdStreams {
             stdin: &mut io::stdin(),
@@ -21,7 +33,7 @@ pub fn main() {
 
         let mut command = ServerInitializer::new();
         let streams_ref: &mut StdStreams = &mut streams;
-        command.go(streams_ref, &std::env::args().collect());
+        command.go(streams_ref, &args);
 
         tokio::spawn(command);
         Ok(())
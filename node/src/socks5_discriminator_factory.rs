@@ -0,0 +1,213 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use discriminator::Discriminator;
+use discriminator::DiscriminatorFactory;
+use null_masquerader::NullMasquerader;
+use sub_lib::framer::FramedChunk;
+use sub_lib::framer::Framer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Socks5FramerState {
+    Greeting,
+    Requesting,
+    Tunnelling,
+}
+
+// Frames each message of a SOCKS5 handshake by itself, so that the ProxyServer can answer it, and then frames
+// whatever comes through the tunnel as soon as it arrives. The ProxyServer never asks for authentication, so the
+// greeting is followed directly by the CONNECT request that ends the handshake.
+pub struct Socks5Framer {
+    data_so_far: Vec<u8>,
+    state: Socks5FramerState,
+}
+
+impl Framer for Socks5Framer {
+    fn add_data(&mut self, data: &[u8]) {
+        self.data_so_far.extend(data);
+    }
+
+    fn take_frame(&mut self) -> Option<FramedChunk> {
+        if self.data_so_far.is_empty() {
+            return None;
+        }
+        let (frame_length, next_state) = match self.state {
+            Socks5FramerState::Greeting => (
+                self.length_at(1).map(|n| 2 + n),
+                Socks5FramerState::Requesting,
+            ),
+            Socks5FramerState::Requesting => match self.data_so_far[0] {
+                0x05 => (self.request_length(), Socks5FramerState::Tunnelling),
+                // Not a handshake message; the ProxyServer will refuse it
+                _ => (Some(self.data_so_far.len()), Socks5FramerState::Tunnelling),
+            },
+            Socks5FramerState::Tunnelling => {
+                (Some(self.data_so_far.len()), Socks5FramerState::Tunnelling)
+            }
+        };
+        match frame_length {
+            Some(frame_length) if frame_length <= self.data_so_far.len() => {
+                self.state = next_state;
+                Some(FramedChunk {
+                    chunk: self.data_so_far.drain(0..frame_length).collect(),
+                    last_chunk: false,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Socks5Framer {
+    pub fn new() -> Socks5Framer {
+        Socks5Framer {
+            data_so_far: Vec::new(),
+            state: Socks5FramerState::Greeting,
+        }
+    }
+
+    fn length_at(&self, index: usize) -> Option<usize> {
+        self.data_so_far.get(index).map(|n| *n as usize)
+    }
+
+    // VER CMD RSV ATYP DST.ADDR DST.PORT
+    fn request_length(&self) -> Option<usize> {
+        match self.length_at(3)? {
+            0x01 => Some(4 + 4 + 2),
+            0x03 => Some(4 + 1 + self.length_at(4)? + 2),
+            0x04 => Some(4 + 16 + 2),
+            // Not an address type we know; the ProxyServer will refuse it
+            _ => Some(self.data_so_far.len()),
+        }
+    }
+}
+
+pub struct Socks5DiscriminatorFactory {}
+
+impl DiscriminatorFactory for Socks5DiscriminatorFactory {
+    fn make(&self) -> Discriminator {
+        Discriminator::new(
+            Box::new(Socks5Framer::new()),
+            vec![Box::new(NullMasquerader::new())],
+        )
+    }
+
+    fn duplicate(&self) -> Box<DiscriminatorFactory> {
+        Box::new(Socks5DiscriminatorFactory {})
+    }
+}
+
+impl Socks5DiscriminatorFactory {
+    pub fn new() -> Socks5DiscriminatorFactory {
+        Socks5DiscriminatorFactory {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use discriminator::UnmaskedChunk;
+
+    fn frame(chunk: &[u8]) -> Option<FramedChunk> {
+        Some(FramedChunk {
+            chunk: chunk.to_vec(),
+            last_chunk: false,
+        })
+    }
+
+    #[test]
+    fn discriminator_factory_duplicate_works() {
+        let subject = Socks5DiscriminatorFactory::new();
+
+        subject.duplicate();
+
+        // no panic; test passes
+    }
+
+    #[test]
+    fn waits_for_the_whole_greeting() {
+        let mut subject = Socks5Framer::new();
+        subject.add_data(&[0x05]);
+
+        let first_result = subject.take_frame();
+        subject.add_data(&[0x02, 0x00]);
+        let second_result = subject.take_frame();
+        subject.add_data(&[0x02]);
+        let third_result = subject.take_frame();
+
+        assert_eq!(first_result, None);
+        assert_eq!(second_result, None);
+        assert_eq!(third_result, frame(&[0x05, 0x02, 0x00, 0x02]));
+    }
+
+    #[test]
+    fn frames_each_handshake_message_by_itself_and_then_everything_after_them_as_it_comes() {
+        let mut subject = Socks5Framer::new();
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, 10];
+        request.extend_from_slice(b"server.com");
+        request.extend_from_slice(&[0x00, 0x16]);
+        subject.add_data(&[0x05, 0x01, 0x00]);
+        subject.add_data(&request[..]);
+        subject.add_data(&b"SSH-2.0"[..]);
+
+        let greeting = subject.take_frame();
+        let connect_request = subject.take_frame();
+        let tunnelled_data = subject.take_frame();
+        let nothing = subject.take_frame();
+
+        assert_eq!(greeting, frame(&[0x05, 0x01, 0x00]));
+        assert_eq!(connect_request, frame(&request[..]));
+        assert_eq!(tunnelled_data, frame(&b"SSH-2.0"[..]));
+        assert_eq!(nothing, None);
+    }
+
+    #[test]
+    fn frames_connect_requests_with_ip_addresses() {
+        let ipv4_request = [0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0x01, 0xBB];
+        let mut ipv6_request = vec![0x05, 0x01, 0x00, 0x04];
+        ipv6_request.extend_from_slice(&[0x20, 0x01, 0x0D, 0xB8]);
+        ipv6_request.extend_from_slice(&[0; 11]);
+        ipv6_request.extend_from_slice(&[0x01, 0x01, 0xBB]);
+        vec![&ipv4_request[..], &ipv6_request[..]]
+            .into_iter()
+            .for_each(|request| {
+                let mut subject = Socks5Framer::new();
+                subject.add_data(&[0x05, 0x01, 0x00]);
+                subject.take_frame();
+                subject.add_data(&request[..(request.len() - 1)]);
+                let partial_result = subject.take_frame();
+                subject.add_data(&request[(request.len() - 1)..]);
+                subject.add_data(&[0x16, 0x03, 0x01]);
+
+                let request_result = subject.take_frame();
+                let tunnelled_result = subject.take_frame();
+
+                assert_eq!(partial_result, None, "{:?}", request);
+                assert_eq!(request_result, frame(request));
+                assert_eq!(tunnelled_result, frame(&[0x16, 0x03, 0x01]));
+            });
+    }
+
+    #[test]
+    fn passes_along_whatever_is_not_a_handshake_message_for_the_proxy_server_to_refuse() {
+        let mut subject = Socks5Framer::new();
+        subject.add_data(&[0x05, 0x01, 0x00]);
+        subject.take_frame();
+        subject.add_data(&b"GET / HTTP/1.1\r\n\r\n"[..]);
+
+        let result = subject.take_frame();
+
+        assert_eq!(result, frame(&b"GET / HTTP/1.1\r\n\r\n"[..]));
+    }
+
+    #[test]
+    fn factory_makes_discriminator_that_passes_greeting_to_proxy_server() {
+        let subject = Socks5DiscriminatorFactory::new();
+        let mut discriminator = subject.make();
+
+        discriminator.add_data(&[0x05, 0x01, 0x00]);
+
+        assert_eq!(
+            discriminator.take_chunk(),
+            Some(UnmaskedChunk::new(vec![0x05, 0x01, 0x00], true, true))
+        );
+    }
+}
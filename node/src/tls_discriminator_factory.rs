@@ -2,14 +2,63 @@
 use discriminator::Discriminator;
 use discriminator::DiscriminatorFactory;
 use null_masquerader::NullMasquerader;
+use proxy_server_lib::tls_protocol_pack::TlsProtocolPack;
+use sub_lib::framer::FramedChunk;
+use sub_lib::framer::Framer;
 use sub_lib::tls_framer::TlsFramer;
 
+// A ClientHello can't be bigger than this, and a client that sends more isn't sending one.
+const MAX_CLIENT_HELLO_LENGTH: usize = 65536;
+
+// Frames TLS records the way TlsFramer does, except that the records a ClientHello is split across are held back and
+// framed together, so that the ProxyServer sees the whole ClientHello--and the server name in it--at once.
+pub struct ClientHelloFramer {
+    tls_framer: TlsFramer,
+    client_hello_so_far: Vec<u8>,
+    client_hello_done: bool,
+}
+
+impl Framer for ClientHelloFramer {
+    fn add_data(&mut self, data: &[u8]) {
+        self.tls_framer.add_data(data);
+    }
+
+    fn take_frame(&mut self) -> Option<FramedChunk> {
+        if self.client_hello_done {
+            return self.tls_framer.take_frame();
+        }
+        while let Some(frame) = self.tls_framer.take_frame() {
+            self.client_hello_so_far.extend(frame.chunk);
+            if !TlsProtocolPack::needs_more_records(&self.client_hello_so_far[..])
+                || self.client_hello_so_far.len() >= MAX_CLIENT_HELLO_LENGTH
+            {
+                self.client_hello_done = true;
+                return Some(FramedChunk {
+                    chunk: self.client_hello_so_far.drain(..).collect(),
+                    last_chunk: false,
+                });
+            }
+        }
+        None
+    }
+}
+
+impl ClientHelloFramer {
+    pub fn new() -> ClientHelloFramer {
+        ClientHelloFramer {
+            tls_framer: TlsFramer::new(),
+            client_hello_so_far: Vec::new(),
+            client_hello_done: false,
+        }
+    }
+}
+
 pub struct TlsDiscriminatorFactory {}
 
 impl DiscriminatorFactory for TlsDiscriminatorFactory {
     fn make(&self) -> Discriminator {
         Discriminator::new(
-            Box::new(TlsFramer::new()),
+            Box::new(ClientHelloFramer::new()),
             vec![Box::new(NullMasquerader::new())],
         )
     }
@@ -30,6 +79,104 @@ mod tests {
     use super::*;
     use discriminator::UnmaskedChunk;
 
+    fn record(fragment: &[u8]) -> Vec<u8> {
+        let mut record = vec![0x16, 0x03, 0x01, 0x00, fragment.len() as u8];
+        record.extend_from_slice(fragment);
+        record
+    }
+
+    // Handshake header, then client_version, random, and empty session ID, cipher suites, compression methods, and
+    // extensions
+    fn client_hello() -> Vec<u8> {
+        let mut client_hello = vec![0x01, 0x00, 0x00, 0x28, 0x03, 0x03];
+        client_hello.extend_from_slice(&[0xAA; 32]);
+        client_hello.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        client_hello
+    }
+
+    fn frame(chunk: Vec<u8>) -> Option<FramedChunk> {
+        Some(FramedChunk {
+            chunk,
+            last_chunk: false,
+        })
+    }
+
+    #[test]
+    fn frames_client_hello_in_one_record_by_itself() {
+        let first_record = record(&client_hello()[..]);
+        let second_record = record(&[0x14, 0x00, 0x00, 0x00]);
+        let mut subject = ClientHelloFramer::new();
+        subject.add_data(&first_record[..]);
+        subject.add_data(&second_record[..]);
+
+        let first_result = subject.take_frame();
+        let second_result = subject.take_frame();
+        let third_result = subject.take_frame();
+
+        assert_eq!(first_result, frame(first_record));
+        assert_eq!(second_result, frame(second_record));
+        assert_eq!(third_result, None);
+    }
+
+    #[test]
+    fn holds_back_records_until_the_client_hello_in_them_is_all_there() {
+        let client_hello = client_hello();
+        let first_record = record(&client_hello[0..10]);
+        let second_record = record(&client_hello[10..30]);
+        let third_record = record(&client_hello[30..]);
+        let fourth_record = record(&[0x14, 0x00, 0x00, 0x00]);
+        let mut subject = ClientHelloFramer::new();
+
+        subject.add_data(&first_record[..]);
+        let first_result = subject.take_frame();
+        subject.add_data(&second_record[..]);
+        subject.add_data(&third_record[0..5]);
+        let second_result = subject.take_frame();
+        subject.add_data(&third_record[5..]);
+        subject.add_data(&fourth_record[..]);
+        let third_result = subject.take_frame();
+        let fourth_result = subject.take_frame();
+
+        assert_eq!(first_result, None);
+        assert_eq!(second_result, None);
+        let mut expected = first_record.clone();
+        expected.extend(second_record);
+        expected.extend(third_record);
+        assert_eq!(third_result, frame(expected));
+        assert_eq!(fourth_result, frame(fourth_record));
+    }
+
+    #[test]
+    fn does_not_hold_back_records_that_are_not_client_hello() {
+        let first_record = vec![0x17, 0x03, 0x03, 0x00, 0x01, 0xCA];
+        let second_record = vec![0x17, 0x03, 0x03, 0x00, 0x01, 0xFE];
+        let mut subject = ClientHelloFramer::new();
+        subject.add_data(&first_record[..]);
+        subject.add_data(&second_record[..]);
+
+        let first_result = subject.take_frame();
+        let second_result = subject.take_frame();
+
+        assert_eq!(first_result, frame(first_record));
+        assert_eq!(second_result, frame(second_record));
+    }
+
+    #[test]
+    fn gives_up_holding_back_a_client_hello_that_is_too_long() {
+        let mut header = vec![0x01, 0xFF, 0xFF, 0xFF];
+        header.extend_from_slice(&[0x00; 251]);
+        let mut subject = ClientHelloFramer::new();
+        subject.add_data(&record(&header[..]));
+        (0..(MAX_CLIENT_HELLO_LENGTH / 260)).for_each(|_| subject.add_data(&record(&[0x00; 255])));
+
+        let result = subject.take_frame();
+
+        assert_eq!(
+            result.map(|frame| frame.chunk.len()),
+            Some(MAX_CLIENT_HELLO_LENGTH / 260 * 260 + 260)
+        );
+    }
+
     #[test]
     fn discriminator_factory_duplicate_works() {
         let subject = TlsDiscriminatorFactory::new();
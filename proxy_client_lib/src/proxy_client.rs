@@ -11,6 +11,7 @@ use std::net::SocketAddr;
 use stream_handler_pool::StreamHandlerPool;
 use stream_handler_pool::StreamHandlerPoolFactory;
 use stream_handler_pool::StreamHandlerPoolFactoryReal;
+use sub_lib::accountant::ReportExitServiceMessage;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::hopper::ExpiredCoresPackage;
 use sub_lib::hopper::IncipientCoresPackage;
@@ -30,6 +31,7 @@ pub struct ProxyClient {
     stream_handler_pool_factory: Box<StreamHandlerPoolFactory>,
     _cryptde: &'static CryptDE, // This is not used now, but a version of it may be used in the future when ser/de and en/decrypt are combined.
     to_hopper: Option<Recipient<Syn, IncipientCoresPackage>>,
+    to_accountant: Option<Recipient<Syn, ReportExitServiceMessage>>,
     pool: Option<Box<StreamHandlerPool>>,
     logger: Logger,
 }
@@ -45,6 +47,7 @@ impl Handler<BindMessage> for ProxyClient {
         self.logger.debug(format!("Handling BindMessage"));
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.to_hopper = Some(msg.peer_actors.hopper.from_hopper_client.clone());
+        self.to_accountant = Some(msg.peer_actors.accountant.report_exit_service.clone());
         let mut config = ResolverConfig::new();
         for dns_server_ref in &self.dns_servers {
             self.logger
@@ -81,6 +84,16 @@ impl Handler<ExpiredCoresPackage> for ProxyClient {
                 return ();
             }
         };
+        if let Some(consuming_wallet) = msg.consuming_wallet {
+            self.to_accountant
+                .as_ref()
+                .expect("Accountant unbound")
+                .try_send(ReportExitServiceMessage {
+                    consuming_wallet,
+                    payload_size: payload.sequenced_packet.data.len(),
+                })
+                .expect("Accountant is dead");
+        }
         let return_route = msg.remaining_route;
         let pool = self.pool.as_mut().expect("StreamHandlerPool unbound");
         pool.process_package(payload, return_route);
@@ -100,6 +113,7 @@ impl ProxyClient {
             stream_handler_pool_factory: Box::new(StreamHandlerPoolFactoryReal {}),
             _cryptde: cryptde,
             to_hopper: None,
+            to_accountant: None,
             pool: None,
             logger: Logger::new("Proxy Client"),
         }
@@ -130,6 +144,7 @@ mod tests {
     use std::str::FromStr;
     use std::sync::Arc;
     use std::sync::Mutex;
+    use std::thread;
     use stream_handler_pool::StreamHandlerPool;
     use stream_handler_pool::StreamHandlerPoolFactory;
     use sub_lib::cryptde::Key;
@@ -138,6 +153,7 @@ mod tests {
     use sub_lib::proxy_server::ProxyProtocol;
     use sub_lib::route::Route;
     use sub_lib::sequence_buffer::SequencedPacket;
+    use sub_lib::wallet::Wallet;
     use test_utils::logging::init_test_logging;
     use test_utils::logging::TestLogHandler;
     use test_utils::recorder::make_peer_actors;
@@ -394,4 +410,98 @@ mod tests {
         let parameter = process_package_parameters.lock().unwrap().remove(0);
         assert_eq!(parameter, (request, test_utils::make_meaningless_route()));
     }
+
+    #[test]
+    fn exit_service_is_reported_to_accountant_for_package_with_consuming_wallet() {
+        let request = ClientRequestPayload {
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: b"inbound data".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: Key::new(&b"originator"[..]),
+        };
+        let mut package = ExpiredCoresPackage::new(
+            test_utils::make_meaningless_route(),
+            PlainData::new(&serde_cbor::ser::to_vec(&request.clone()).unwrap()[..]),
+        );
+        package.consuming_wallet = Some(Wallet::new("consumer"));
+        let accountant = Recorder::new();
+        let accountant_awaiter = accountant.get_awaiter();
+        let accountant_recording_arc = accountant.get_recording();
+        thread::spawn(move || {
+            let system = System::new(
+                "exit_service_is_reported_to_accountant_for_package_with_consuming_wallet",
+            );
+            let peer_actors = make_peer_actors_from(None, None, None, None, None, Some(accountant));
+            let pool = Box::new(StreamHandlerPoolMock::new());
+            let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(pool);
+            let resolver_factory =
+                ResolverWrapperFactoryMock::new().new_result(Box::new(ResolverWrapperMock::new()));
+            let mut subject = ProxyClient::new(cryptde(), dnss());
+            subject.resolver_wrapper_factory = Box::new(resolver_factory);
+            subject.stream_handler_pool_factory = Box::new(pool_factory);
+            let subject_addr: Addr<Syn, ProxyClient> = subject.start();
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(package).unwrap();
+
+            system.run();
+        });
+        accountant_awaiter.await_message_count(1);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ReportExitServiceMessage>(0),
+            &ReportExitServiceMessage {
+                consuming_wallet: Wallet::new("consumer"),
+                payload_size: 12,
+            }
+        );
+    }
+
+    #[test]
+    fn exit_service_is_not_reported_for_package_without_consuming_wallet() {
+        let request = ClientRequestPayload {
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: b"inbound data".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: Key::new(&b"originator"[..]),
+        };
+        let package = ExpiredCoresPackage::new(
+            test_utils::make_meaningless_route(),
+            PlainData::new(&serde_cbor::ser::to_vec(&request.clone()).unwrap()[..]),
+        );
+        let accountant = Recorder::new();
+        let accountant_recording_arc = accountant.get_recording();
+
+        let system =
+            System::new("exit_service_is_not_reported_for_package_without_consuming_wallet");
+        let peer_actors = make_peer_actors_from(None, None, None, None, None, Some(accountant));
+        let pool = Box::new(StreamHandlerPoolMock::new());
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(pool);
+        let resolver_factory =
+            ResolverWrapperFactoryMock::new().new_result(Box::new(ResolverWrapperMock::new()));
+        let mut subject = ProxyClient::new(cryptde(), dnss());
+        subject.resolver_wrapper_factory = Box::new(resolver_factory);
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<Syn, ProxyClient> = subject.start();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package).unwrap();
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(accountant_recording.len(), 0);
+    }
 }
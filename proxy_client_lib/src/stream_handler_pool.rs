@@ -29,6 +29,7 @@ use sub_lib::route::Route;
 use sub_lib::sequence_buffer::SequencedPacket;
 use sub_lib::stream_key::StreamKey;
 use sub_lib::tls_framer::TlsFramer;
+use sub_lib::tunnel_framer::TunnelFramer;
 
 pub trait StreamHandlerPool {
     fn process_package(&mut self, payload: ClientRequestPayload, route: Route);
@@ -236,6 +237,7 @@ impl StreamHandlerPoolReal {
                 Box::new(HttpPacketFramer::new(Box::new(HttpResponseStartFinder {})))
             }
             ProxyProtocol::TLS => Box::new(TlsFramer::new()),
+            ProxyProtocol::Tunnel => Box::new(TunnelFramer::new()),
         }
     }
 
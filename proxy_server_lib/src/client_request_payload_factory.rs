@@ -1,12 +1,15 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use http_connect_protocol_pack::HttpConnectProtocolPack;
 use http_protocol_pack::HttpProtocolPack;
 use protocol_pack::ProtocolPack;
+use socks5_protocol_pack::Socks5ProtocolPack;
 use std::collections::HashMap;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::PlainData;
 use sub_lib::dispatcher::InboundClientData;
 use sub_lib::logger::Logger;
 use sub_lib::proxy_server::ClientRequestPayload;
+use sub_lib::proxy_server::ProxyProtocol;
 use sub_lib::sequence_buffer::SequencedPacket;
 use sub_lib::stream_key::StreamKey;
 use tls_protocol_pack::TlsProtocolPack;
@@ -16,10 +19,19 @@ pub struct ClientRequestPayloadFactory {
 }
 
 impl ClientRequestPayloadFactory {
-    pub fn new() -> ClientRequestPayloadFactory {
+    pub fn new(
+        http_proxy_port_opt: Option<u16>,
+        socks_port_opt: Option<u16>,
+    ) -> ClientRequestPayloadFactory {
         let mut protocol_packs: HashMap<u16, Box<ProtocolPack>> = HashMap::new();
         protocol_packs.insert(80, Box::new(HttpProtocolPack {}));
         protocol_packs.insert(443, Box::new(TlsProtocolPack {}));
+        if let Some(http_proxy_port) = http_proxy_port_opt {
+            protocol_packs.insert(http_proxy_port, Box::new(HttpConnectProtocolPack {}));
+        }
+        if let Some(socks_port) = socks_port_opt {
+            protocol_packs.insert(socks_port, Box::new(Socks5ProtocolPack {}));
+        }
         ClientRequestPayloadFactory { protocol_packs }
     }
 
@@ -63,16 +75,26 @@ impl ClientRequestPayloadFactory {
                 return None;
             }
         };
-        let host_name = protocol_pack.find_host_name(&PlainData::new(&ibcd.data));
+        let plain_data = PlainData::new(&ibcd.data);
+        let host_name = protocol_pack.find_host_name(&plain_data);
+        let target_port = protocol_pack.find_port(&plain_data).unwrap_or(origin_port);
+        // The request that opens a tunnel is meant for us, not for the target
+        let data = if (protocol_pack.proxy_protocol() == ProxyProtocol::Tunnel)
+            && (sequence_number == 0)
+        {
+            vec![]
+        } else {
+            ibcd.data.clone()
+        };
         Some(ClientRequestPayload {
             stream_key,
             sequenced_packet: SequencedPacket {
-                data: ibcd.data.clone(),
+                data,
                 sequence_number,
                 last_data: ibcd.last_data,
             },
             target_hostname: host_name,
-            target_port: origin_port,
+            target_port,
             protocol: protocol_pack.proxy_protocol(),
             originator_public_key: cryptde.public_key().clone(),
         })
@@ -85,7 +107,6 @@ mod tests {
     use std::net::SocketAddr;
     use std::str::FromStr;
     use sub_lib::cryptde_null::CryptDENull;
-    use sub_lib::proxy_server::ProxyProtocol;
     use test_utils::logging::init_test_logging;
     use test_utils::logging::TestLogHandler;
     use test_utils::test_utils::make_meaningless_stream_key;
@@ -103,7 +124,7 @@ mod tests {
         };
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
 
@@ -128,9 +149,9 @@ mod tests {
     fn handles_tls_with_hostname() {
         let data = PlainData::new(&[
             0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
+            0x03, 0x01, 0x00, 0x3F, // version: TLS 1.0, length
             0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+            0x00, 0x00, 0x3B, 0x03, 0x03, // length, version: TLS 1.2
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -157,7 +178,7 @@ mod tests {
         };
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
 
@@ -204,7 +225,7 @@ mod tests {
         };
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
 
@@ -225,6 +246,108 @@ mod tests {
         );
     }
 
+    #[test]
+    fn handles_connect_request_that_opens_tunnel() {
+        let ibcd = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(8080),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: b"CONNECT server.com:8443 HTTP/1.1\r\nHost: server.com:8443\r\n\r\n".to_vec(),
+        };
+        let cryptde = CryptDENull::new();
+        let logger = Logger::new("test");
+        let subject = ClientRequestPayloadFactory::new(Some(8080), None);
+
+        let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
+
+        assert_eq!(
+            result,
+            Some(ClientRequestPayload {
+                stream_key: make_meaningless_stream_key(),
+                sequenced_packet: SequencedPacket {
+                    data: vec![],
+                    sequence_number: 0,
+                    last_data: false
+                },
+                target_hostname: Some(String::from("server.com")),
+                target_port: 8443,
+                protocol: ProxyProtocol::Tunnel,
+                originator_public_key: cryptde.public_key(),
+            })
+        );
+    }
+
+    #[test]
+    fn handles_data_in_tunnel_as_is() {
+        let ibcd = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(8080),
+            sequence_number: Some(1),
+            last_data: false,
+            is_clandestine: false,
+            data: vec![0x16, 0x03, 0x01, 0x00, 0x01, 0x01],
+        };
+        let cryptde = CryptDENull::new();
+        let logger = Logger::new("test");
+        let subject = ClientRequestPayloadFactory::new(Some(8080), None);
+
+        let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
+
+        assert_eq!(
+            result,
+            Some(ClientRequestPayload {
+                stream_key: make_meaningless_stream_key(),
+                sequenced_packet: SequencedPacket {
+                    data: vec![0x16, 0x03, 0x01, 0x00, 0x01, 0x01],
+                    sequence_number: 1,
+                    last_data: false
+                },
+                target_hostname: None,
+                target_port: 8080,
+                protocol: ProxyProtocol::Tunnel,
+                originator_public_key: cryptde.public_key(),
+            })
+        );
+    }
+
+    #[test]
+    fn handles_socks5_connect_request_that_opens_tunnel() {
+        let mut data = vec![0x05, 0x01, 0x00, 0x03, 10];
+        data.extend_from_slice(b"server.com");
+        data.extend_from_slice(&[0x00, 0x16]);
+        let ibcd = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(1080),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data,
+        };
+        let cryptde = CryptDENull::new();
+        let logger = Logger::new("test");
+        let subject = ClientRequestPayloadFactory::new(Some(8080), Some(1080));
+
+        let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
+
+        assert_eq!(
+            result,
+            Some(ClientRequestPayload {
+                stream_key: make_meaningless_stream_key(),
+                sequenced_packet: SequencedPacket {
+                    data: vec![],
+                    sequence_number: 0,
+                    last_data: false
+                },
+                target_hostname: Some(String::from("server.com")),
+                target_port: 22,
+                protocol: ProxyProtocol::Tunnel,
+                originator_public_key: cryptde.public_key(),
+            })
+        );
+    }
+
     #[test]
     fn makes_no_payload_if_origin_port_is_not_specified() {
         init_test_logging();
@@ -238,7 +361,7 @@ mod tests {
         };
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
 
@@ -261,7 +384,7 @@ mod tests {
         };
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
 
@@ -282,7 +405,7 @@ mod tests {
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
 
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject
             .make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger)
@@ -305,7 +428,7 @@ mod tests {
         let cryptde = CryptDENull::new();
         let logger = Logger::new("test");
 
-        let subject = ClientRequestPayloadFactory::new();
+        let subject = ClientRequestPayloadFactory::new(None, None);
 
         let result = subject.make(&ibcd, make_meaningless_stream_key(), &cryptde, &logger);
 
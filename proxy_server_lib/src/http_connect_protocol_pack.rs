@@ -0,0 +1,126 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use protocol_pack::ProtocolPack;
+use sub_lib::cryptde::PlainData;
+use sub_lib::proxy_server::ProxyProtocol;
+use sub_lib::utils::index_of;
+
+pub const CONNECTION_ESTABLISHED_RESPONSE: &[u8] = b"HTTP/1.1 200 Connection established\r\n\r\n";
+
+// Speaks just enough HTTP to hear a CONNECT request from a client that has been told to use us as its proxy;
+// everything after the CONNECT request goes through the tunnel untouched.
+pub struct HttpConnectProtocolPack {}
+
+impl ProtocolPack for HttpConnectProtocolPack {
+    fn proxy_protocol(&self) -> ProxyProtocol {
+        ProxyProtocol::Tunnel
+    }
+
+    fn find_host_name(&self, data: &PlainData) -> Option<String> {
+        HttpConnectProtocolPack::find_target(&data.data[..]).map(|(host_name, _)| host_name)
+    }
+
+    fn find_port(&self, data: &PlainData) -> Option<u16> {
+        HttpConnectProtocolPack::find_target(&data.data[..]).map(|(_, port)| port)
+    }
+}
+
+impl HttpConnectProtocolPack {
+    // The request line looks like "CONNECT <host>:<port> HTTP/1.1", with IPv6 hosts in brackets.
+    fn find_target(data: &[u8]) -> Option<(String, u16)> {
+        let idx = index_of(data, &b"\r\n"[..])?;
+        let request_line = match String::from_utf8(Vec::from(&data[0..idx])) {
+            Ok(request_line) => request_line,
+            Err(_) => return None,
+        };
+        let pieces: Vec<&str> = request_line.split(' ').collect();
+        if (pieces.len() != 3) || (pieces[0] != "CONNECT") || !pieces[2].starts_with("HTTP/") {
+            return None;
+        }
+        let authority = pieces[1];
+        let colon_index = authority.rfind(':')?;
+        let port = match authority[(colon_index + 1)..].parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => return None,
+        };
+        let host = &authority[0..colon_index];
+        let host = if host.starts_with('[') && host.ends_with(']') {
+            &host[1..(host.len() - 1)]
+        } else {
+            host
+        };
+        if host.is_empty() {
+            None
+        } else {
+            Some((String::from(host), port))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_its_protocol() {
+        let result = HttpConnectProtocolPack {}.proxy_protocol();
+
+        assert_eq!(result, ProxyProtocol::Tunnel);
+    }
+
+    #[test]
+    fn finds_host_name_and_port_in_connect_request() {
+        let data = PlainData::new(
+            b"CONNECT server.com:8443 HTTP/1.1\r\nHost: server.com:8443\r\nProxy-Connection: keep-alive\r\n\r\n",
+        );
+
+        let host_name = HttpConnectProtocolPack {}.find_host_name(&data);
+        let port = HttpConnectProtocolPack {}.find_port(&data);
+
+        assert_eq!(host_name, Some(String::from("server.com")));
+        assert_eq!(port, Some(8443));
+    }
+
+    #[test]
+    fn finds_ipv6_host_without_its_brackets() {
+        let data = PlainData::new(b"CONNECT [2001:db8::1]:443 HTTP/1.1\r\n\r\n");
+
+        let host_name = HttpConnectProtocolPack {}.find_host_name(&data);
+        let port = HttpConnectProtocolPack {}.find_port(&data);
+
+        assert_eq!(host_name, Some(String::from("2001:db8::1")));
+        assert_eq!(port, Some(443));
+    }
+
+    #[test]
+    fn finds_nothing_in_requests_that_are_not_connect() {
+        let data = PlainData::new(b"GET http://server.com:8443/ HTTP/1.1\r\n\r\n");
+
+        let host_name = HttpConnectProtocolPack {}.find_host_name(&data);
+        let port = HttpConnectProtocolPack {}.find_port(&data);
+
+        assert_eq!(host_name, None);
+        assert_eq!(port, None);
+    }
+
+    #[test]
+    fn finds_nothing_in_connect_requests_that_are_malformed() {
+        vec![
+            &b"CONNECT server.com:8443 HTTP/1.1"[..],
+            &b"CONNECT server.com HTTP/1.1\r\n\r\n"[..],
+            &b"CONNECT server.com:booga HTTP/1.1\r\n\r\n"[..],
+            &b"CONNECT server.com:65536 HTTP/1.1\r\n\r\n"[..],
+            &b"CONNECT :443 HTTP/1.1\r\n\r\n"[..],
+            &b"CONNECT server.com:443\r\n\r\n"[..],
+            &b"CONNECT server.com:443 HTTP/1.1 extra\r\n\r\n"[..],
+            &[0x43, 0x4F, 0x4E, 0xFF, 0x0D, 0x0A][..],
+        ]
+        .into_iter()
+        .for_each(|request| {
+            let data = PlainData::new(request);
+
+            let port = HttpConnectProtocolPack {}.find_port(&data);
+
+            assert_eq!(port, None, "{:?}", request);
+        });
+    }
+}
@@ -0,0 +1,695 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use http_protocol_pack::HttpProtocolPack;
+use std::collections::VecDeque;
+use sub_lib::sequence_buffer::SequenceBuffer;
+use sub_lib::sequence_buffer::SequencedPacket;
+use sub_lib::stream_key::StreamKey;
+use sub_lib::utils::index_of;
+
+// More hosts than this on one client connection, and the client is more likely misbehaving than browsing
+pub const MAX_STREAMS: usize = 16;
+
+// A response head or chunk-size line longer than this isn't one we can make sense of
+const MAX_HEAD_LENGTH: usize = 65536;
+
+struct HttpStream {
+    stream_key: StreamKey,
+    host_name_opt: Option<String>,
+    request_count: u64,
+    response_buffer: SequenceBuffer,
+    received: Vec<u8>,
+    finished: bool,
+    told_last_data: bool,
+}
+
+impl HttpStream {
+    fn new(stream_key: StreamKey, host_name_opt: Option<String>) -> HttpStream {
+        HttpStream {
+            stream_key,
+            host_name_opt,
+            request_count: 0,
+            response_buffer: SequenceBuffer::new(),
+            received: vec![],
+            finished: false,
+            told_last_data: false,
+        }
+    }
+
+    fn next_request_sequence_number(&mut self) -> u64 {
+        let sequence_number = self.request_count;
+        self.request_count += 1;
+        sequence_number
+    }
+
+    fn route(&mut self) -> (StreamKey, Option<String>, u64) {
+        (
+            self.stream_key,
+            self.host_name_opt.clone(),
+            self.next_request_sequence_number(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ResponseState {
+    Head,
+    Body(usize),
+    ChunkSize,
+    ChunkData(usize),
+    Trailers,
+    UntilClose,
+    Done,
+}
+
+// Follows one response through its head and body, to find where it ends and the next one on its stream begins
+struct ResponseTracker {
+    head_request: bool,
+    state: ResponseState,
+    line: Vec<u8>,
+}
+
+impl ResponseTracker {
+    fn new(head_request: bool) -> ResponseTracker {
+        ResponseTracker {
+            head_request,
+            state: ResponseState::Head,
+            line: vec![],
+        }
+    }
+
+    // Returns how much of the data finishes the response, or None if it's all part of the response and there's more
+    // to come.
+    fn consume(&mut self, data: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+        loop {
+            match self.state {
+                ResponseState::Done => return Some(offset),
+                ResponseState::UntilClose => return None,
+                ResponseState::Body(remaining) | ResponseState::ChunkData(remaining) => {
+                    let available = data.len() - offset;
+                    if available < remaining {
+                        self.state = match self.state {
+                            ResponseState::Body(_) => ResponseState::Body(remaining - available),
+                            _ => ResponseState::ChunkData(remaining - available),
+                        };
+                        return None;
+                    }
+                    offset += remaining;
+                    self.state = match self.state {
+                        ResponseState::Body(_) => ResponseState::Done,
+                        _ => ResponseState::ChunkSize,
+                    };
+                }
+                ResponseState::Head | ResponseState::ChunkSize | ResponseState::Trailers => {
+                    if offset == data.len() {
+                        return None;
+                    }
+                    self.line.push(data[offset]);
+                    offset += 1;
+                    self.take_line();
+                }
+            }
+        }
+    }
+
+    fn take_line(&mut self) {
+        if self.line.len() > MAX_HEAD_LENGTH {
+            self.state = ResponseState::UntilClose;
+            return;
+        }
+        let terminator: &[u8] = match self.state {
+            ResponseState::Head => b"\r\n\r\n",
+            _ => b"\r\n",
+        };
+        if !self.line.ends_with(terminator) {
+            return;
+        }
+        let line = self.line.split_off(0);
+        self.state = match self.state {
+            ResponseState::Head => self.state_after_head(&line),
+            ResponseState::ChunkSize => ResponseTracker::state_after_chunk_size(&line),
+            _ if line.len() == 2 => ResponseState::Done,
+            state => state,
+        }
+    }
+
+    fn state_after_head(&self, head: &[u8]) -> ResponseState {
+        let status = match ResponseTracker::status_code(head) {
+            Some(status) => status,
+            None => return ResponseState::UntilClose,
+        };
+        if status == 101 {
+            return ResponseState::UntilClose;
+        }
+        // An interim response comes before the real one, in the same place
+        if status >= 100 && status < 200 {
+            return ResponseState::Head;
+        }
+        if self.head_request || (status == 204) || (status == 304) {
+            return ResponseState::Done;
+        }
+        if let Some(value) = ResponseTracker::header_value(head, b"transfer-encoding:") {
+            if index_of(&value.to_ascii_lowercase()[..], &b"chunked"[..]).is_some() {
+                return ResponseState::ChunkSize;
+            }
+        }
+        match ResponseTracker::header_value(head, b"content-length:")
+            .and_then(|value| String::from_utf8(value).ok())
+            .and_then(|value| value.trim().parse::<usize>().ok())
+        {
+            Some(0) => ResponseState::Done,
+            Some(length) => ResponseState::Body(length),
+            None => ResponseState::UntilClose,
+        }
+    }
+
+    fn state_after_chunk_size(line: &[u8]) -> ResponseState {
+        let size_text = line[0..(line.len() - 2)]
+            .split(|byte| *byte == b';')
+            .next()
+            .and_then(|size| String::from_utf8(size.to_vec()).ok());
+        match size_text.and_then(|size| usize::from_str_radix(size.trim(), 16).ok()) {
+            Some(0) => ResponseState::Trailers,
+            Some(size) => match size.checked_add(2) {
+                Some(length) => ResponseState::ChunkData(length),
+                None => ResponseState::UntilClose,
+            },
+            None => ResponseState::UntilClose,
+        }
+    }
+
+    // HTTP-version SP status-code SP reason-phrase CRLF
+    fn status_code(head: &[u8]) -> Option<u16> {
+        let status_line = &head[0..index_of(head, &b"\r\n"[..])?];
+        let status = status_line.split(|byte| *byte == b' ').nth(1)?;
+        String::from_utf8(status.to_vec()).ok()?.parse::<u16>().ok()
+    }
+
+    fn header_value(head: &[u8], name: &[u8]) -> Option<Vec<u8>> {
+        head.split(|byte| *byte == b'\n')
+            .skip(1)
+            .find(|line| {
+                (line.len() >= name.len()) && line[0..name.len()].eq_ignore_ascii_case(name)
+            })
+            .map(|line| {
+                line[name.len()..]
+                    .iter()
+                    .cloned()
+                    .filter(|byte| *byte != b'\r')
+                    .collect()
+            })
+    }
+}
+
+struct PendingResponse {
+    stream_index: usize,
+    tracker: ResponseTracker,
+}
+
+// A client's kept-alive HTTP connection, over which it may send requests for more than one host. Each host gets a
+// stream of its own, and so a connection of its own from the exit Node; each stream numbers its requests and its
+// responses from zero, and the responses from all of them go back to the client as one sequence.
+//
+// The client expects its responses in the order it made its requests, so whatever a stream sends back waits until
+// the responses to every earlier request are complete. A request is counted when a packet starts with a request
+// line; requests pipelined together into one packet are counted as one, and whatever responses beyond the first come
+// back for them go out once nothing else is waiting.
+pub struct HttpConnection {
+    streams: Vec<HttpStream>,
+    current_stream_index: usize,
+    response_count: u64,
+    pending_responses: VecDeque<PendingResponse>,
+    closing: bool,
+    closed: bool,
+}
+
+impl HttpConnection {
+    pub fn new(stream_key: StreamKey) -> HttpConnection {
+        HttpConnection {
+            streams: vec![HttpStream::new(stream_key, None)],
+            current_stream_index: 0,
+            response_count: 0,
+            pending_responses: VecDeque::new(),
+            closing: false,
+            closed: false,
+        }
+    }
+
+    // Picks the stream for a request, or for the rest of one, along with the host it's for and its sequence number
+    // on that stream. Whatever names no host of its own goes with the latest request that did. A connection that's
+    // closing, or a request that would take it past MAX_STREAMS hosts, gets an error instead.
+    pub fn route_request(
+        &mut self,
+        host_name_opt: Option<String>,
+        data: &[u8],
+    ) -> Result<(StreamKey, Option<String>, u64), String> {
+        if self.closing {
+            return Err(String::from("Connection is closing"));
+        }
+        if let Some(host_name) = host_name_opt {
+            self.current_stream_index = self.find_or_add_stream(host_name)?;
+        }
+        if HttpProtocolPack::is_request_start(data) {
+            self.pending_responses.push_back(PendingResponse {
+                stream_index: self.current_stream_index,
+                tracker: ResponseTracker::new(data.starts_with(b"HEAD ")),
+            });
+        }
+        Ok(self.streams[self.current_stream_index].route())
+    }
+
+    // When the client goes away, every stream but the one its last data went to has to be told separately.
+    pub fn route_last_data_to_other_streams(
+        &mut self,
+        stream_key: &StreamKey,
+    ) -> Vec<(StreamKey, Option<String>, u64)> {
+        self.streams
+            .iter_mut()
+            .filter(|stream| stream.stream_key == *stream_key)
+            .for_each(|stream| stream.told_last_data = true);
+        self.close()
+    }
+
+    // Tells every stream that hasn't heard already that the client is done with it. Once they've all finished, the
+    // client hears the same.
+    pub fn close(&mut self) -> Vec<(StreamKey, Option<String>, u64)> {
+        self.closing = true;
+        self.streams
+            .iter_mut()
+            .filter(|stream| !stream.told_last_data)
+            .map(|stream| {
+                stream.told_last_data = true;
+                stream.route()
+            })
+            .collect()
+    }
+
+    pub fn stream_keys(&self) -> Vec<StreamKey> {
+        self.streams
+            .iter()
+            .map(|stream| stream.stream_key)
+            .collect()
+    }
+
+    pub fn has_stream(&self, stream_key: &StreamKey) -> bool {
+        self.streams
+            .iter()
+            .any(|stream| stream.stream_key == *stream_key)
+    }
+
+    // Takes a response packet from one of the connection's streams, and gives back whatever can go to the client
+    // now, renumbered into the client's sequence. The client's last data comes only once every stream has finished.
+    pub fn take_response(
+        &mut self,
+        stream_key: &StreamKey,
+        packet: SequencedPacket,
+    ) -> Vec<SequencedPacket> {
+        if self.closed {
+            return vec![];
+        }
+        let stream = match self
+            .streams
+            .iter_mut()
+            .find(|stream| stream.stream_key == *stream_key)
+        {
+            Some(stream) => stream,
+            None => return vec![],
+        };
+        stream.response_buffer.push(packet);
+        while let Some(packet) = stream.response_buffer.poll() {
+            // Nothing a stream sends after its last data is part of any response
+            if !stream.finished {
+                stream.received.extend(packet.data);
+                stream.finished = packet.last_data;
+            }
+        }
+        let mut ready = vec![];
+        self.release_responses(&mut ready);
+        if !self.closed && self.streams.iter().all(|stream| stream.finished) {
+            self.closed = true;
+            ready.push(SequencedPacket::new(vec![], self.response_count, true));
+            self.response_count += 1;
+        }
+        ready
+    }
+
+    fn release_responses(&mut self, ready: &mut Vec<SequencedPacket>) {
+        loop {
+            let data = match self.pending_responses.front_mut() {
+                Some(pending) => {
+                    let stream = &mut self.streams[pending.stream_index];
+                    if stream.received.is_empty() {
+                        if !stream.finished {
+                            return;
+                        }
+                        // The response ended when its stream did, or never came at all
+                        self.pending_responses.pop_front();
+                        continue;
+                    }
+                    match pending.tracker.consume(&stream.received) {
+                        Some(length) => {
+                            let data = stream.received.drain(0..length).collect();
+                            self.pending_responses.pop_front();
+                            data
+                        }
+                        None => stream.received.drain(..).collect(),
+                    }
+                }
+                None => {
+                    // Nothing is waiting for a response, so whatever comes can go as it comes
+                    let unsolicited: Vec<Vec<u8>> = self
+                        .streams
+                        .iter_mut()
+                        .filter(|stream| !stream.received.is_empty())
+                        .map(|stream| stream.received.drain(..).collect())
+                        .collect();
+                    unsolicited
+                        .into_iter()
+                        .for_each(|data| self.release(ready, data));
+                    return;
+                }
+            };
+            self.release(ready, data);
+        }
+    }
+
+    fn release(&mut self, ready: &mut Vec<SequencedPacket>, data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        ready.push(SequencedPacket::new(data, self.response_count, false));
+        self.response_count += 1;
+    }
+
+    fn find_or_add_stream(&mut self, host_name: String) -> Result<usize, String> {
+        if let Some(index) = self
+            .streams
+            .iter()
+            .position(|stream| match stream.host_name_opt {
+                Some(ref stream_host_name) => stream_host_name.eq_ignore_ascii_case(&host_name),
+                None => false,
+            })
+        {
+            return Ok(index);
+        }
+        // Requests that named no host before this one have gone wherever they could; this host can have their stream
+        if self.streams[self.current_stream_index]
+            .host_name_opt
+            .is_none()
+        {
+            self.streams[self.current_stream_index].host_name_opt = Some(host_name);
+            return Ok(self.current_stream_index);
+        }
+        if self.streams.len() >= MAX_STREAMS {
+            return Err(format!(
+                "Refusing request for {}: connection already has streams for {} hosts",
+                host_name, MAX_STREAMS
+            ));
+        }
+        let stream_key = self.streams[0].stream_key.for_host(&host_name);
+        self.streams
+            .push(HttpStream::new(stream_key, Some(host_name)));
+        Ok(self.streams.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::test_utils::make_meaningless_stream_key;
+
+    fn host(host_name: &str) -> Option<String> {
+        Some(String::from(host_name))
+    }
+
+    fn request(method: &str, host_name: &str) -> Vec<u8> {
+        format!("{} / HTTP/1.1\r\nHost: {}\r\n\r\n", method, host_name).into_bytes()
+    }
+
+    fn route(subject: &mut HttpConnection, host_name: &str) -> (StreamKey, Option<String>, u64) {
+        subject
+            .route_request(host(host_name), &request("GET", host_name))
+            .unwrap()
+    }
+
+    fn packet(data: &[u8], sequence_number: u64, last_data: bool) -> SequencedPacket {
+        SequencedPacket::new(data.to_vec(), sequence_number, last_data)
+    }
+
+    #[test]
+    fn requests_for_one_host_stay_on_the_original_stream_and_in_order() {
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = HttpConnection::new(stream_key);
+
+        let first = subject.route_request(host("one.com"), &request("POST", "one.com"));
+        let second = subject.route_request(None, b"body");
+        let third = subject.route_request(host("ONE.com"), &request("GET", "ONE.com"));
+
+        assert_eq!(first, Ok((stream_key, host("one.com"), 0)));
+        assert_eq!(second, Ok((stream_key, host("one.com"), 1)));
+        assert_eq!(third, Ok((stream_key, host("one.com"), 2)));
+        assert_eq!(subject.stream_keys(), vec![stream_key]);
+    }
+
+    #[test]
+    fn requests_for_another_host_get_their_own_stream_and_numbering() {
+        let stream_key = make_meaningless_stream_key();
+        let other_stream_key = stream_key.for_host("two.com");
+        let mut subject = HttpConnection::new(stream_key);
+
+        let first = route(&mut subject, "one.com");
+        let second = route(&mut subject, "two.com");
+        let third = subject.route_request(None, b"body").unwrap();
+        let fourth = route(&mut subject, "one.com");
+        let fifth = route(&mut subject, "two.com");
+
+        assert_eq!(first, (stream_key, host("one.com"), 0));
+        assert_eq!(second, (other_stream_key, host("two.com"), 0));
+        assert_eq!(third, (other_stream_key, host("two.com"), 1));
+        assert_eq!(fourth, (stream_key, host("one.com"), 1));
+        assert_eq!(fifth, (other_stream_key, host("two.com"), 2));
+        assert_eq!(subject.stream_keys(), vec![stream_key, other_stream_key]);
+        assert!(subject.has_stream(&other_stream_key));
+        assert!(!subject.has_stream(&stream_key.for_host("three.com")));
+    }
+
+    #[test]
+    fn first_host_named_takes_the_stream_of_requests_that_named_none() {
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = HttpConnection::new(stream_key);
+
+        let first = subject.route_request(None, b"GET / HTTP/1.0\r\n\r\n");
+        let second = route(&mut subject, "one.com");
+
+        assert_eq!(first, Ok((stream_key, None, 0)));
+        assert_eq!(second, (stream_key, host("one.com"), 1));
+    }
+
+    #[test]
+    fn requests_for_too_many_hosts_are_refused() {
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = HttpConnection::new(stream_key);
+        (0..MAX_STREAMS).for_each(|index| {
+            route(&mut subject, &format!("host{}.com", index));
+        });
+
+        let result = subject.route_request(host("one-too-many.com"), &request("GET", "x"));
+
+        assert_eq!(
+            result,
+            Err(format!(
+                "Refusing request for one-too-many.com: connection already has streams for {} hosts",
+                MAX_STREAMS
+            ))
+        );
+        assert_eq!(subject.stream_keys().len(), MAX_STREAMS);
+        assert!(subject
+            .route_request(host("host0.com"), &request("GET", "host0.com"))
+            .is_ok());
+    }
+
+    #[test]
+    fn closing_tells_every_stream_once_and_refuses_further_requests() {
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = HttpConnection::new(stream_key);
+        route(&mut subject, "one.com");
+        route(&mut subject, "two.com");
+
+        let first = subject.close();
+        let second = subject.close();
+        let request_result = subject.route_request(host("one.com"), &request("GET", "one.com"));
+
+        assert_eq!(
+            first,
+            vec![
+                (stream_key, host("one.com"), 1),
+                (stream_key.for_host("two.com"), host("two.com"), 1),
+            ]
+        );
+        assert_eq!(second, vec![]);
+        assert_eq!(request_result, Err(String::from("Connection is closing")));
+    }
+
+    #[test]
+    fn last_data_goes_to_every_other_stream_in_sequence() {
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = HttpConnection::new(stream_key);
+        route(&mut subject, "one.com");
+        route(&mut subject, "two.com");
+        route(&mut subject, "three.com");
+        route(&mut subject, "two.com");
+
+        let result = subject.route_last_data_to_other_streams(&stream_key.for_host("three.com"));
+
+        assert_eq!(
+            result,
+            vec![
+                (stream_key, host("one.com"), 1),
+                (stream_key.for_host("two.com"), host("two.com"), 2),
+            ]
+        );
+        assert_eq!(subject.close(), vec![]);
+    }
+
+    #[test]
+    fn responses_go_back_whole_and_in_the_order_of_their_requests() {
+        let stream_key = make_meaningless_stream_key();
+        let other_stream_key = stream_key.for_host("two.com");
+        let mut subject = HttpConnection::new(stream_key);
+        route(&mut subject, "one.com");
+        route(&mut subject, "two.com");
+        route(&mut subject, "one.com");
+
+        let first = subject.take_response(
+            &other_stream_key,
+            packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB0", 0, false),
+        );
+        let second =
+            subject.take_response(&stream_key, packet(b"llo\r\n0\r\n\r\nHTTP/1.1 ", 1, false));
+        let third = subject.take_response(
+            &stream_key,
+            packet(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhe",
+                0,
+                false,
+            ),
+        );
+        let fourth =
+            subject.take_response(&stream_key, packet(b"204 No Content\r\n\r\n", 2, false));
+
+        assert_eq!(first, vec![]);
+        assert_eq!(second, vec![]);
+        assert_eq!(
+            third,
+            vec![
+                packet(
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+                    0,
+                    false
+                ),
+                packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB0", 1, false),
+                packet(b"HTTP/1.1 ", 2, false),
+            ]
+        );
+        assert_eq!(fourth, vec![packet(b"204 No Content\r\n\r\n", 3, false)]);
+    }
+
+    #[test]
+    fn head_responses_and_interim_responses_are_followed_to_their_ends() {
+        let stream_key = make_meaningless_stream_key();
+        let other_stream_key = stream_key.for_host("two.com");
+        let mut subject = HttpConnection::new(stream_key);
+        subject
+            .route_request(host("one.com"), &request("HEAD", "one.com"))
+            .unwrap();
+        route(&mut subject, "two.com");
+        route(&mut subject, "one.com");
+
+        let first = subject.take_response(
+            &stream_key,
+            packet(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nHTTP/1.1 100 Continue\r\n\r\n",
+                0,
+                false,
+            ),
+        );
+        let second = subject.take_response(
+            &other_stream_key,
+            packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB0", 0, false),
+        );
+        let third = subject.take_response(
+            &stream_key,
+            packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nA1", 1, false),
+        );
+
+        assert_eq!(
+            first,
+            vec![packet(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n",
+                0,
+                false
+            )]
+        );
+        assert_eq!(
+            second,
+            vec![
+                packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB0", 1, false),
+                packet(b"HTTP/1.1 100 Continue\r\n\r\n", 2, false),
+            ]
+        );
+        assert_eq!(
+            third,
+            vec![packet(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nA1",
+                3,
+                false
+            )]
+        );
+    }
+
+    #[test]
+    fn response_without_a_length_ends_with_its_stream_and_the_client_hears_last_data_only_after_every_stream(
+    ) {
+        let stream_key = make_meaningless_stream_key();
+        let other_stream_key = stream_key.for_host("two.com");
+        let mut subject = HttpConnection::new(stream_key);
+        route(&mut subject, "one.com");
+        route(&mut subject, "two.com");
+
+        let first = subject.take_response(
+            &other_stream_key,
+            packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB0", 0, true),
+        );
+        let second =
+            subject.take_response(&stream_key, packet(b"HTTP/1.0 200 OK\r\n\r\nA0", 0, false));
+        let third = subject.take_response(&stream_key, packet(b"A1", 1, false));
+        let fourth = subject.take_response(&stream_key, packet(b"", 2, true));
+        let fifth = subject.take_response(&stream_key, packet(b"late", 3, true));
+
+        assert_eq!(first, vec![]);
+        assert_eq!(second, vec![packet(b"HTTP/1.0 200 OK\r\n\r\nA0", 0, false)]);
+        assert_eq!(third, vec![packet(b"A1", 1, false)]);
+        assert_eq!(
+            fourth,
+            vec![
+                packet(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB0", 2, false),
+                packet(b"", 3, true),
+            ]
+        );
+        assert_eq!(fifth, vec![]);
+    }
+
+    #[test]
+    fn response_to_a_stream_the_connection_does_not_have_goes_nowhere() {
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = HttpConnection::new(stream_key);
+        route(&mut subject, "one.com");
+
+        let result = subject.take_response(
+            &stream_key.for_host("three.com"),
+            packet(b"HTTP/1.1 204 No Content\r\n\r\n", 0, false),
+        );
+
+        assert_eq!(result, vec![]);
+    }
+}
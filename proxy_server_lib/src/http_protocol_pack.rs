@@ -11,29 +11,73 @@ impl ProtocolPack for HttpProtocolPack {
         ProxyProtocol::HTTP
     }
 
+    // On a kept-alive connection, whatever doesn't start with a request line is the rest of an earlier request's
+    // body, and any host name in it means nothing.
     fn find_host_name(&self, data: &PlainData) -> Option<String> {
+        if !HttpProtocolPack::is_request_start(&data.data[..]) {
+            return None;
+        }
         match HttpProtocolPack::find_header_host_name(&data.data[..]) {
             Some(string) => return Some(string),
             None => (),
         }
         HttpProtocolPack::find_url_host_name(&data.data[..])
     }
+
+    fn find_port(&self, _data: &PlainData) -> Option<u16> {
+        None
+    }
 }
 
 impl HttpProtocolPack {
+    // Method SP request-target SP HTTP-version CRLF
+    pub fn is_request_start(data: &[u8]) -> bool {
+        let first_line = match index_of(data, &b"\r\n"[..]) {
+            Some(idx) => &data[0..idx],
+            None => return false,
+        };
+        let mut words = first_line.split(|byte| *byte == b' ');
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (Some(method), Some(target), Some(version), None) => {
+                !method.is_empty() && !target.is_empty() && version.starts_with(b"HTTP/")
+            }
+            _ => false,
+        }
+    }
+
+    // Header names are case-insensitive, and there may be whitespace around the value.
     fn find_header_host_name(data: &[u8]) -> Option<String> {
         let idx = index_of(data, &b"\r\n\r\n"[..])?;
-        let headers = &data[0..idx + 2];
-        let needle = b"\r\nHost: ";
-        let begin = index_of(&headers, &needle[..])? + needle.len();
-        let end = index_of(&headers[begin..], &b"\r\n"[..])? + begin;
-        let hostname_u8s = &headers[begin..end];
+        let headers = &data[0..idx];
+        let hostname_u8s = headers
+            .split(|byte| *byte == b'\n')
+            .skip(1)
+            .map(|line| match line.last() {
+                Some(b'\r') => &line[0..(line.len() - 1)],
+                _ => line,
+            })
+            .find(|line| (line.len() >= 5) && line[0..5].eq_ignore_ascii_case(b"host:"))
+            .map(|line| HttpProtocolPack::trim(&line[5..]))?;
         match String::from_utf8(Vec::from(hostname_u8s)) {
             Ok(result) => Some(result),
             Err(_) => None,
         }
     }
 
+    fn trim(data: &[u8]) -> &[u8] {
+        let is_whitespace = |byte: &u8| (*byte == b' ') || (*byte == b'\t');
+        let begin = data
+            .iter()
+            .position(|byte| !is_whitespace(byte))
+            .unwrap_or(data.len());
+        let end = data
+            .iter()
+            .rposition(|byte| !is_whitespace(byte))
+            .map(|index| index + 1)
+            .unwrap_or(begin);
+        &data[begin..end]
+    }
+
     fn find_url_host_name(data: &[u8]) -> Option<String> {
         let idx = index_of(data, &b"\r\n"[..])?;
         let first_line = &data[0..idx];
@@ -164,6 +208,36 @@ mod tests {
         assert_eq!(result, Some(String::from("top.host.com")));
     }
 
+    #[test]
+    fn returns_host_name_from_header_in_any_case_with_any_whitespace() {
+        let data =
+            PlainData::new(b"GET / HTTP/1.1\r\nAccept: */*\r\nhOST:\t header.host.com \r\n\r\n");
+
+        let result = HttpProtocolPack {}.find_host_name(&data);
+
+        assert_eq!(result, Some(String::from("header.host.com")));
+    }
+
+    #[test]
+    fn returns_none_for_host_header_in_body_of_earlier_request() {
+        let data = PlainData::new(b"1A\r\nHost: body.host.com\r\n\r\n\r\n");
+
+        let result = HttpProtocolPack {}.find_host_name(&data);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn returns_host_name_from_first_of_several_requests() {
+        let data = PlainData::new(
+            b"GET / HTTP/1.1\r\nHost: first.host.com\r\n\r\nGET / HTTP/1.1\r\nHost: second.host.com\r\n\r\n",
+        );
+
+        let result = HttpProtocolPack {}.find_host_name(&data);
+
+        assert_eq!(result, Some(String::from("first.host.com")));
+    }
+
     #[test]
     fn from_integration_test() {
         let data = PlainData::new(b"GET / HTTP/1.1\r\nHost: www.example.com\r\n\r\n");
@@ -10,7 +10,10 @@ extern crate neighborhood_lib;
 extern crate test_utils;
 
 pub mod client_request_payload_factory;
+pub mod http_connect_protocol_pack;
+pub mod http_connection;
 pub mod http_protocol_pack;
 pub mod protocol_pack;
 pub mod proxy_server;
+pub mod socks5_protocol_pack;
 pub mod tls_protocol_pack;
@@ -5,4 +5,6 @@ use sub_lib::proxy_server::ProxyProtocol;
 pub trait ProtocolPack: Send + Sync {
     fn proxy_protocol(&self) -> ProxyProtocol;
     fn find_host_name(&self, data: &PlainData) -> Option<String>;
+    // Only protocols that say where they're going, rather than relying on the port they arrived at, will find one.
+    fn find_port(&self, data: &PlainData) -> Option<u16>;
 }
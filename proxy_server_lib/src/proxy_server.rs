@@ -7,7 +7,18 @@ use actix::MailboxError;
 use actix::Recipient;
 use actix::Syn;
 use client_request_payload_factory::ClientRequestPayloadFactory;
+use http_connect_protocol_pack::CONNECTION_ESTABLISHED_RESPONSE;
+use http_connection::HttpConnection;
+use socks5_protocol_pack::make_reply;
+use socks5_protocol_pack::Socks5Handshake;
+use socks5_protocol_pack::Socks5Step;
+use socks5_protocol_pack::NETWORK_UNREACHABLE;
+use socks5_protocol_pack::SUCCEEDED;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use sub_lib::accountant::AccountantSubs;
+use sub_lib::accountant::ReportExitServiceConsumedMessage;
+use sub_lib::accountant::ReportRoutingServiceConsumedMessage;
 use sub_lib::bidi_hashmap::BidiHashMap;
 use sub_lib::cryptde::CryptDE;
 use sub_lib::cryptde::Key;
@@ -17,6 +28,7 @@ use sub_lib::hopper::ExpiredCoresPackage;
 use sub_lib::hopper::IncipientCoresPackage;
 use sub_lib::http_server_impersonator;
 use sub_lib::logger::Logger;
+use sub_lib::neighborhood::ExpectedService;
 use sub_lib::neighborhood::RouteQueryMessage;
 use sub_lib::neighborhood::RouteQueryResponse;
 use sub_lib::peer_actors::BindMessage;
@@ -24,8 +36,10 @@ use sub_lib::proxy_client::ClientResponsePayload;
 use sub_lib::proxy_server::ClientRequestPayload;
 use sub_lib::proxy_server::ProxyProtocol;
 use sub_lib::proxy_server::ProxyServerSubs;
+use sub_lib::sequence_buffer::SequencedPacket;
 use sub_lib::stream_handler_pool::TransmitDataMsg;
 use sub_lib::stream_key::StreamKey;
+use sub_lib::tls_server_impersonator;
 use sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use tokio;
 use tokio::prelude::Future;
@@ -34,9 +48,16 @@ pub struct ProxyServer {
     dispatcher: Option<Recipient<Syn, TransmitDataMsg>>,
     hopper: Option<Recipient<Syn, IncipientCoresPackage>>,
     route_source: Option<Recipient<Syn, RouteQueryMessage>>,
+    accountant: Option<AccountantSubs>,
     client_request_payload_factory: ClientRequestPayloadFactory,
     stream_key_factory: Box<StreamKeyFactory>,
     keys_and_addrs: BidiHashMap<StreamKey, SocketAddr>,
+    tunnelled_streams: HashMap<StreamKey, Tunnel>,
+    socks_port_opt: Option<u16>,
+    socks5_handshakes: HashMap<SocketAddr, Socks5Handshake>,
+    http_connections: HashMap<SocketAddr, HttpConnection>,
+    // Streams for hosts other than the first one a client's HTTP connection asked for
+    http_stream_addrs: HashMap<StreamKey, SocketAddr>,
     is_decentralized: bool, // TODO: This should be replaced by something more general and configurable.
     cryptde: &'static CryptDE,
     logger: Logger,
@@ -54,6 +75,7 @@ impl Handler<BindMessage> for ProxyServer {
         self.dispatcher = Some(msg.peer_actors.dispatcher.from_dispatcher_client);
         self.hopper = Some(msg.peer_actors.hopper.from_hopper_client);
         self.route_source = Some(msg.peer_actors.neighborhood.route_query);
+        self.accountant = Some(msg.peer_actors.accountant);
         ()
     }
 }
@@ -77,29 +99,67 @@ impl Handler<InboundClientData> for ProxyServer {
             .as_ref()
             .expect("Dispatcher unbound in ProxyServer")
             .clone();
+        let accountant = self
+            .accountant
+            .as_ref()
+            .expect("Accountant unbound in ProxyServer")
+            .clone();
         let source_addr = msg.peer_addr;
+        let (msg, tunnel) =
+            if self.socks_port_opt.is_some() && (msg.reception_port == self.socks_port_opt) {
+                match self.negotiate_socks5(msg, &dispatcher) {
+                    Some(msg_and_tunnel) => msg_and_tunnel,
+                    None => return (),
+                }
+            } else {
+                (msg, Tunnel::HttpConnect)
+            };
         let payload = match self.make_payload(msg) {
             Ok(payload) => payload,
             Err(_) => return (),
         };
-        let logger = self.logger.clone();
+        let opened_tunnel_opt = if ProxyServer::is_tunnel_opener(&payload) {
+            if payload.target_hostname.is_none() {
+                self.refuse_tunnel(source_addr, &dispatcher);
+                return ();
+            }
+            self.tunnelled_streams.insert(payload.stream_key, tunnel);
+            Some(tunnel)
+        } else {
+            None
+        };
+        let payloads = if payload.protocol == ProxyProtocol::HTTP {
+            self.route_http_request(source_addr, payload)
+        } else {
+            vec![payload]
+        };
         let minimum_hop_count = if self.is_decentralized { 2 } else { 0 };
-        tokio::spawn(
-            route_source
-                .send(RouteQueryMessage::data_indefinite_route_request(
-                    minimum_hop_count,
-                ))
-                .then(move |route_result| {
-                    ProxyServer::try_transmit_to_hopper(
-                        hopper,
-                        route_result,
-                        payload,
-                        logger,
-                        source_addr,
-                        dispatcher,
-                    )
-                }),
-        );
+        payloads.into_iter().for_each(|payload| {
+            let (hopper, logger, dispatcher, accountant) = (
+                hopper.clone(),
+                self.logger.clone(),
+                dispatcher.clone(),
+                accountant.clone(),
+            );
+            tokio::spawn(
+                route_source
+                    .send(RouteQueryMessage::data_indefinite_route_request(
+                        minimum_hop_count,
+                    ))
+                    .then(move |route_result| {
+                        ProxyServer::try_transmit_to_hopper(
+                            hopper,
+                            route_result,
+                            payload,
+                            opened_tunnel_opt,
+                            logger,
+                            source_addr,
+                            dispatcher,
+                            accountant,
+                        )
+                    }),
+            );
+        });
         ()
     }
 }
@@ -114,21 +174,35 @@ impl Handler<ExpiredCoresPackage> for ProxyServer {
                     "Relaying {}-byte ExpiredCoresPackage payload from Hopper to Dispatcher",
                     payload.sequenced_packet.data.len()
                 ));
-                match self.keys_and_addrs.a_to_b(&payload.stream_key) {
+                let socket_addr_opt = match self.keys_and_addrs.a_to_b(&payload.stream_key) {
+                    Some(socket_addr) => Some(socket_addr),
+                    None => self.http_stream_addrs.get(&payload.stream_key).cloned(),
+                };
+                match socket_addr_opt {
                     Some(socket_addr) => {
-                        let last_data = payload.sequenced_packet.last_data;
-                        self.dispatcher
-                            .as_ref()
-                            .expect("Dispatcher unbound in ProxyServer")
-                            .try_send(TransmitDataMsg {
-                                endpoint: Endpoint::Socket(socket_addr),
-                                last_data,
-                                sequence_number: Some(payload.sequenced_packet.sequence_number),
-                                data: payload.sequenced_packet.data.clone(),
-                            })
-                            .expect("Dispatcher is dead");
+                        let packets = self.sequence_response(socket_addr, &payload);
+                        let last_data = packets.iter().any(|packet| packet.last_data);
+                        packets.into_iter().for_each(|packet| {
+                            self.dispatcher
+                                .as_ref()
+                                .expect("Dispatcher unbound in ProxyServer")
+                                .try_send(TransmitDataMsg {
+                                    endpoint: Endpoint::Socket(socket_addr),
+                                    last_data: packet.last_data,
+                                    sequence_number: Some(packet.sequence_number),
+                                    data: packet.data,
+                                })
+                                .expect("Dispatcher is dead");
+                        });
                         if last_data {
                             self.keys_and_addrs.remove_b(&socket_addr);
+                            self.tunnelled_streams.remove(&payload.stream_key);
+                            self.socks5_handshakes.remove(&socket_addr);
+                            if let Some(connection) = self.http_connections.remove(&socket_addr) {
+                                connection.stream_keys().iter().for_each(|stream_key| {
+                                    self.http_stream_addrs.remove(stream_key);
+                                });
+                            }
                         }
                     }
                     None => self.logger.error(format!(
@@ -151,14 +225,28 @@ impl Handler<ExpiredCoresPackage> for ProxyServer {
 }
 
 impl ProxyServer {
-    pub fn new(cryptde: &'static CryptDE, is_decentralized: bool) -> ProxyServer {
+    pub fn new(
+        cryptde: &'static CryptDE,
+        is_decentralized: bool,
+        http_proxy_port_opt: Option<u16>,
+        socks_port_opt: Option<u16>,
+    ) -> ProxyServer {
         ProxyServer {
             dispatcher: None,
             hopper: None,
             route_source: None,
-            client_request_payload_factory: ClientRequestPayloadFactory::new(),
+            accountant: None,
+            client_request_payload_factory: ClientRequestPayloadFactory::new(
+                http_proxy_port_opt,
+                socks_port_opt,
+            ),
             stream_key_factory: Box::new(StreamKeyFactoryReal {}),
             keys_and_addrs: BidiHashMap::new(),
+            tunnelled_streams: HashMap::new(),
+            socks_port_opt,
+            socks5_handshakes: HashMap::new(),
+            http_connections: HashMap::new(),
+            http_stream_addrs: HashMap::new(),
             is_decentralized,
             cryptde,
             logger: Logger::new("Proxy Server"),
@@ -202,9 +290,11 @@ impl ProxyServer {
         hopper: Recipient<Syn, IncipientCoresPackage>,
         route_result: Result<Option<RouteQueryResponse>, MailboxError>,
         payload: ClientRequestPayload,
+        opened_tunnel_opt: Option<Tunnel>,
         logger: Logger,
         source_addr: SocketAddr,
         dispatcher: Recipient<Syn, TransmitDataMsg>,
+        accountant: AccountantSubs,
     ) -> Result<(), ()> {
         match route_result {
             Ok(Some(response)) => {
@@ -212,13 +302,35 @@ impl ProxyServer {
                     .segment_endpoints
                     .first()
                     .expect("no segment endpoints");
+                let payload_size = payload.sequenced_packet.data.len();
                 let pkg =
                     IncipientCoresPackage::new(response.route, payload, &payload_destination_key);
                 hopper.try_send(pkg).expect("Hopper is dead");
+                if let Some(tunnel) = opened_tunnel_opt {
+                    dispatcher
+                        .try_send(TransmitDataMsg {
+                            endpoint: Endpoint::Socket(source_addr),
+                            last_data: false,
+                            sequence_number: Some(tunnel.reply_sequence_number()),
+                            data: tunnel.established_reply(),
+                        })
+                        .expect("Dispatcher is dead");
+                }
+                ProxyServer::report_services_consumed(
+                    &accountant,
+                    response.expected_services,
+                    payload_size,
+                );
             }
             Ok(None) => {
                 let target_hostname = ProxyServer::hostname(&payload);
-                ProxyServer::send_route_failure(payload, source_addr, dispatcher);
+                ProxyServer::send_route_failure(
+                    payload,
+                    opened_tunnel_opt,
+                    source_addr,
+                    dispatcher,
+                    &logger,
+                );
                 logger.error(format!("Failed to find route to {}", target_hostname));
             }
             Err(e) => {
@@ -229,34 +341,267 @@ impl ProxyServer {
         Ok(())
     }
 
+    fn report_services_consumed(
+        accountant: &AccountantSubs,
+        expected_services: Vec<ExpectedService>,
+        payload_size: usize,
+    ) {
+        expected_services
+            .into_iter()
+            .for_each(|service| match service {
+                ExpectedService::Routing(earning_wallet) => accountant
+                    .report_routing_service_consumed
+                    .try_send(ReportRoutingServiceConsumedMessage {
+                        earning_wallet,
+                        payload_size,
+                    })
+                    .expect("Accountant is dead"),
+                ExpectedService::Exit(earning_wallet) => accountant
+                    .report_exit_service_consumed
+                    .try_send(ReportExitServiceConsumedMessage {
+                        earning_wallet,
+                        payload_size,
+                    })
+                    .expect("Accountant is dead"),
+            });
+    }
+
     fn send_route_failure(
         payload: ClientRequestPayload,
+        opened_tunnel_opt: Option<Tunnel>,
         source_addr: SocketAddr,
         dispatcher: Recipient<Syn, TransmitDataMsg>,
+        logger: &Logger,
     ) {
-        let data = match payload.protocol {
-            ProxyProtocol::HTTP => {
-                let target_hostname = ProxyServer::hostname(&payload);
-                http_server_impersonator::make_error_response (
-                    503,
-                   "Routing Problem",
-                    format! ("Can't find a route to {}", target_hostname).as_str (),
-                    format! ("Substratum can't find a route through the Network yet to a Node that knows \
-                    where to find {}. Maybe later enough will be known about the Network to \
-                    find that Node, but we can't guarantee it. We're sorry.", target_hostname).as_str ()
-                )
-            }
-            ProxyProtocol::TLS => vec![],
+        let (data, sequence_number) = match (payload.protocol, opened_tunnel_opt) {
+            (ProxyProtocol::HTTP, _) => (ProxyServer::make_route_failure_response(&payload), 0),
+            (ProxyProtocol::TLS, _) => (
+                ProxyServer::make_route_failure_alert(&payload, source_addr, logger),
+                0,
+            ),
+            // Until its tunnel is open, the client is still waiting to hear whether it will be
+            (ProxyProtocol::Tunnel, Some(tunnel)) => (
+                tunnel.route_failure_reply(&payload),
+                tunnel.reply_sequence_number(),
+            ),
+            (ProxyProtocol::Tunnel, None) => (vec![], 0),
         };
         let msg = TransmitDataMsg {
             endpoint: Endpoint::Socket(source_addr),
             last_data: true,
-            sequence_number: Some(0),
+            sequence_number: Some(sequence_number),
             data,
         };
         dispatcher.try_send(msg).expect("Dispatcher is dead");
     }
 
+    fn make_route_failure_response(payload: &ClientRequestPayload) -> Vec<u8> {
+        let target_hostname = ProxyServer::hostname(payload);
+        http_server_impersonator::make_error_response(
+            503,
+            "Routing Problem",
+            format!("Can't find a route to {}", target_hostname).as_str(),
+            format!(
+                "Substratum can't find a route through the Network yet to a Node that knows \
+            where to find {}. Maybe later enough will be known about the Network to \
+            find that Node, but we can't guarantee it. We're sorry.",
+                target_hostname
+            )
+            .as_str(),
+        )
+    }
+
+    // A client that's still shaking hands can understand a plaintext alert; one that's past its handshake can't, so
+    // it just gets hung up on. A client that named the server it wants (SNI) is told that name can't be served
+    // (RFC 6066 section 3); one that didn't name a server can only be told the handshake failed.
+    fn make_route_failure_alert(
+        payload: &ClientRequestPayload,
+        source_addr: SocketAddr,
+        logger: &Logger,
+    ) -> Vec<u8> {
+        let client_record = &payload.sequenced_packet.data[..];
+        let description_opt = match (client_record.first(), &payload.target_hostname) {
+            (Some(0x16), Some(_)) => Some(tls_server_impersonator::UNRECOGNIZED_NAME),
+            (Some(0x16), None) => Some(tls_server_impersonator::HANDSHAKE_FAILURE),
+            _ => None,
+        };
+        logger.warning(format!(
+            "TLS route failure: hostname={} client={} alert={}",
+            ProxyServer::hostname(payload),
+            source_addr,
+            match description_opt {
+                Some(description) => tls_server_impersonator::alert_name(description),
+                None => "none",
+            }
+        ));
+        match description_opt {
+            Some(description) => tls_server_impersonator::make_alert(client_record, description),
+            None => vec![],
+        }
+    }
+
+    fn is_tunnel_opener(payload: &ClientRequestPayload) -> bool {
+        (payload.protocol == ProxyProtocol::Tunnel)
+            && (payload.sequenced_packet.sequence_number == 0)
+    }
+
+    fn refuse_tunnel(&self, source_addr: SocketAddr, dispatcher: &Recipient<Syn, TransmitDataMsg>) {
+        self.logger.error(format!(
+            "Client at {} asked for a tunnel without a CONNECT request naming its target",
+            source_addr
+        ));
+        let data = http_server_impersonator::make_error_response(
+            400,
+            "Bad Request",
+            "Expected a CONNECT request",
+            "Substratum can only tunnel to a target named in a CONNECT request, such as \
+             'CONNECT example.com:443 HTTP/1.1'.",
+        );
+        dispatcher
+            .try_send(TransmitDataMsg {
+                endpoint: Endpoint::Socket(source_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data,
+            })
+            .expect("Dispatcher is dead");
+    }
+
+    // Answers the SOCKS5 handshake messages that come before a client's CONNECT request. Once the request arrives,
+    // it and everything after it are renumbered as if the handshake had never happened, so that the ProxyClient sees
+    // the same kind of tunnel that an HTTP CONNECT request would open.
+    fn negotiate_socks5(
+        &mut self,
+        mut msg: InboundClientData,
+        dispatcher: &Recipient<Syn, TransmitDataMsg>,
+    ) -> Option<(InboundClientData, Tunnel)> {
+        let source_addr = msg.peer_addr;
+        let handshake_complete = {
+            let handshake = self
+                .socks5_handshakes
+                .entry(source_addr)
+                .or_insert_with(Socks5Handshake::new);
+            if !handshake.is_complete() && !msg.last_data {
+                let sequence_number = handshake.message_count();
+                let (data, last_data) = match handshake.step(&msg.data[..]) {
+                    Socks5Step::Reply(data) => (data, false),
+                    Socks5Step::Refuse(data) => {
+                        self.logger.error(format!(
+                            "Refusing SOCKS5 client at {} after {} handshake message(s)",
+                            source_addr,
+                            sequence_number + 1
+                        ));
+                        (data, true)
+                    }
+                    Socks5Step::Connect => (vec![], false),
+                };
+                if !data.is_empty() {
+                    dispatcher
+                        .try_send(TransmitDataMsg {
+                            endpoint: Endpoint::Socket(source_addr),
+                            last_data,
+                            sequence_number: Some(sequence_number),
+                            data,
+                        })
+                        .expect("Dispatcher is dead");
+                }
+            }
+            handshake.is_complete()
+        };
+        if !handshake_complete {
+            if msg.last_data {
+                self.socks5_handshakes.remove(&source_addr);
+            }
+            return None;
+        }
+        let message_count = match self.socks5_handshakes.get(&source_addr) {
+            Some(handshake) => handshake.message_count(),
+            None => 0,
+        };
+        if msg.last_data {
+            self.socks5_handshakes.remove(&source_addr);
+        }
+        msg.sequence_number = msg.sequence_number.map(|n| n - message_count);
+        Some((msg, Tunnel::Socks5(message_count)))
+    }
+
+    // A client may send requests for several hosts over one connection. Each host gets a stream of its own, and each
+    // stream sees only its own requests, numbered from zero; and when the client goes away, they all hear about it.
+    // A client that asks for too many hosts has its connection closed instead.
+    fn route_http_request(
+        &mut self,
+        source_addr: SocketAddr,
+        mut payload: ClientRequestPayload,
+    ) -> Vec<ClientRequestPayload> {
+        let connection = self
+            .http_connections
+            .entry(source_addr)
+            .or_insert_with(|| HttpConnection::new(payload.stream_key));
+        let (target_port, protocol, originator_public_key) = (
+            payload.target_port,
+            payload.protocol,
+            payload.originator_public_key.clone(),
+        );
+        let (mut payloads, last_data_routes) = match connection.route_request(
+            payload.target_hostname.clone(),
+            &payload.sequenced_packet.data[..],
+        ) {
+            Ok((stream_key, target_hostname, sequence_number)) => {
+                if stream_key != payload.stream_key {
+                    self.http_stream_addrs.insert(stream_key, source_addr);
+                }
+                payload.stream_key = stream_key;
+                payload.target_hostname = target_hostname;
+                payload.sequenced_packet.sequence_number = sequence_number;
+                let last_data_routes = if payload.sequenced_packet.last_data {
+                    connection.route_last_data_to_other_streams(&stream_key)
+                } else {
+                    vec![]
+                };
+                (vec![payload], last_data_routes)
+            }
+            Err(e) => {
+                self.logger.warning(format!(
+                    "Closing HTTP connection from {}: {}",
+                    source_addr, e
+                ));
+                (vec![], connection.close())
+            }
+        };
+        payloads.extend(last_data_routes.into_iter().map(
+            |(stream_key, target_hostname, sequence_number)| ClientRequestPayload {
+                stream_key,
+                sequenced_packet: SequencedPacket::new(vec![], sequence_number, true),
+                target_hostname,
+                target_port,
+                protocol,
+                originator_public_key: originator_public_key.clone(),
+            },
+        ));
+        payloads
+    }
+
+    // Responses on an HTTP connection come from as many streams as it has hosts, and are put into one sequence for
+    // the client; a tunnel's come after the replies that opened it.
+    fn sequence_response(
+        &mut self,
+        socket_addr: SocketAddr,
+        payload: &ClientResponsePayload,
+    ) -> Vec<SequencedPacket> {
+        if let Some(connection) = self.http_connections.get_mut(&socket_addr) {
+            if connection.has_stream(&payload.stream_key) {
+                return connection
+                    .take_response(&payload.stream_key, payload.sequenced_packet.clone());
+            }
+        }
+        let mut packet = payload.sequenced_packet.clone();
+        if let Some(tunnel) = self.tunnelled_streams.get(&payload.stream_key) {
+            // The client has already had the replies that opened its tunnel
+            packet.sequence_number += tunnel.reply_sequence_number() + 1;
+        }
+        vec![packet]
+    }
+
     fn hostname(payload: &ClientRequestPayload) -> String {
         match payload.target_hostname {
             Some(ref thn) => thn.clone(),
@@ -265,6 +610,37 @@ impl ProxyServer {
     }
 }
 
+// How a client asked for its tunnel, and so how it expects to hear whether the tunnel is open
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tunnel {
+    HttpConnect,
+    // Carries the number of SOCKS5 handshake messages we answered before the CONNECT request
+    Socks5(u64),
+}
+
+impl Tunnel {
+    fn reply_sequence_number(&self) -> u64 {
+        match self {
+            Tunnel::HttpConnect => 0,
+            Tunnel::Socks5(message_count) => *message_count,
+        }
+    }
+
+    fn established_reply(&self) -> Vec<u8> {
+        match self {
+            Tunnel::HttpConnect => CONNECTION_ESTABLISHED_RESPONSE.to_vec(),
+            Tunnel::Socks5(_) => make_reply(SUCCEEDED),
+        }
+    }
+
+    fn route_failure_reply(&self, payload: &ClientRequestPayload) -> Vec<u8> {
+        match self {
+            Tunnel::HttpConnect => ProxyServer::make_route_failure_response(payload),
+            Tunnel::Socks5(_) => make_reply(NETWORK_UNREACHABLE),
+        }
+    }
+}
+
 trait StreamKeyFactory: Send {
     fn make(&self, public_key: &Key, peer_addr: SocketAddr) -> StreamKey;
 }
@@ -301,6 +677,7 @@ mod tests {
     use sub_lib::route::Route;
     use sub_lib::route::RouteSegment;
     use sub_lib::sequence_buffer::SequencedPacket;
+    use sub_lib::wallet::Wallet;
     use test_utils::logging::init_test_logging;
     use test_utils::logging::TestLogHandler;
     use test_utils::recorder::make_peer_actors_from;
@@ -394,7 +771,7 @@ mod tests {
                 .make_parameters(&make_parameters_arc)
                 .make_result(stream_key);
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, None, None);
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<Syn, ProxyServer> = subject.start();
             let mut peer_actors = make_peer_actors_from(
@@ -468,7 +845,7 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, None, None);
             subject.stream_key_factory = Box::new(stream_key_factory);
             subject.keys_and_addrs.insert(stream_key, socket_addr);
             let subject_addr: Addr<Syn, ProxyServer> = subject.start();
@@ -525,12 +902,14 @@ mod tests {
                 ),
             ],
             cryptde,
+            None,
         )
         .unwrap();
         let (neighborhood_mock, _, neighborhood_recording_arc) = make_recorder();
         let neighborhood_mock = neighborhood_mock.route_query_response(Some(RouteQueryResponse {
             route: route.clone(),
             segment_endpoints: vec![Key::new(&[3]), cryptde.public_key()],
+            expected_services: vec![],
         }));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
@@ -562,7 +941,7 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, None, None);
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<Syn, ProxyServer> = subject.start();
             let mut peer_actors = make_peer_actors_from(
@@ -590,6 +969,77 @@ mod tests {
         assert_eq!(record, &RouteQueryMessage::data_indefinite_route_request(2));
     }
 
+    #[test]
+    fn proxy_server_reports_services_it_expects_to_consume_to_accountant() {
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let accountant = Recorder::new();
+        let accountant_recording_arc = accountant.get_recording();
+        let accountant_awaiter = accountant.get_awaiter();
+        let route = Route::new(
+            vec![RouteSegment::new(
+                vec![&cryptde.public_key(), &Key::new(&[1]), &Key::new(&[2])],
+                Component::ProxyClient,
+            )],
+            cryptde,
+            None,
+        )
+        .unwrap();
+        let neighborhood_mock = Recorder::new().route_query_response(Some(RouteQueryResponse {
+            route,
+            segment_endpoints: vec![Key::new(&[2])],
+            expected_services: vec![
+                ExpectedService::Routing(Wallet::new("router")),
+                ExpectedService::Exit(Wallet::new("exit")),
+            ],
+        }));
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(80),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: http_request.to_vec(),
+        };
+        thread::spawn(move || {
+            let system =
+                System::new("proxy_server_reports_services_it_expects_to_consume_to_accountant");
+            let subject = ProxyServer::new(cryptde, true, None, None);
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors = make_peer_actors_from(
+                None,
+                None,
+                None,
+                None,
+                Some(neighborhood_mock),
+                Some(accountant),
+            );
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        accountant_awaiter.await_message_count(2);
+        let recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<ReportRoutingServiceConsumedMessage>(0),
+            &ReportRoutingServiceConsumedMessage {
+                earning_wallet: Wallet::new("router"),
+                payload_size: http_request.len(),
+            }
+        );
+        assert_eq!(
+            recording.get_record::<ReportExitServiceConsumedMessage>(1),
+            &ReportExitServiceConsumedMessage {
+                earning_wallet: Wallet::new("exit"),
+                payload_size: http_request.len(),
+            }
+        );
+    }
+
     #[test]
     fn proxy_server_receives_http_request_from_dispatcher_but_neighborhood_cant_make_route() {
         init_test_logging();
@@ -612,7 +1062,7 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, true);
+            let subject = ProxyServer::new(cryptde, true, None, None);
             let subject_addr: Addr<Syn, ProxyServer> = subject.start();
             let mut peer_actors = make_peer_actors_from(
                 None,
@@ -658,9 +1108,9 @@ mod tests {
     fn proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper() {
         let tls_request = &[
             0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
+            0x03, 0x01, 0x00, 0x3F, // version: TLS 1.0, length
             0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+            0x00, 0x00, 0x3B, 0x03, 0x03, // length, version: TLS 1.2
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -685,7 +1135,7 @@ mod tests {
             zero_hop_route_response(&cryptde.public_key(), cryptde),
         ));
         let stream_key = make_meaningless_stream_key();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
         subject.stream_key_factory =
             Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -756,7 +1206,7 @@ mod tests {
             zero_hop_route_response(&cryptde.public_key(), cryptde),
         ));
         let stream_key = make_meaningless_stream_key();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
         subject.stream_key_factory =
             Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -825,7 +1275,7 @@ mod tests {
             zero_hop_route_response(&cryptde.public_key(), cryptde),
         ));
         let stream_key = make_meaningless_stream_key();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
         subject.stream_key_factory =
             Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -885,9 +1335,10 @@ mod tests {
         let cryptde = cryptde();
         let tls_request = [
             0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
+            0x03, 0x03, // version: TLS 1.2
+            0x00, 0x3F, // length
             0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+            0x00, 0x00, 0x3B, 0x03, 0x03, // length, version: TLS 1.2
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -920,7 +1371,7 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, false);
+            let subject = ProxyServer::new(cryptde, false, None, None);
             let subject_addr: Addr<Syn, ProxyServer> = subject.start();
             let mut peer_actors =
                 make_peer_actors_from(None, Some(dispatcher), None, None, Some(neighborhood), None);
@@ -938,12 +1389,764 @@ mod tests {
             endpoint: Endpoint::Socket(SocketAddr::from_str("1.2.3.4:5678").unwrap()),
             last_data: true,
             sequence_number: Some(0),
-            data: vec![],
+            data: vec![0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 112],
         };
         assert_eq!(record, &expected_msg);
 
         TestLogHandler::new()
             .exists_log_containing("ERROR: Proxy Server: Failed to find route to server.com");
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Proxy Server: TLS route failure: hostname=server.com client=1.2.3.4:5678 alert=unrecognized_name",
+        );
+    }
+
+    #[test]
+    fn route_failure_for_a_client_hello_that_names_no_server_is_a_handshake_failure() {
+        init_test_logging();
+        let client_hello = [
+            0x16, // content_type: Handshake
+            0x03, 0x03, // version: TLS 1.2
+            0x00, 0x2A, // length
+            0x01, // handshake_type: ClientHello
+            0x00, 0x00, 0x26, 0x03, 0x03, // length, version: TLS 1.2
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, // random: don't care
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, // random: don't care
+            0x00, // session_id_length
+            0x00, 0x00, // cipher_suites_length
+            0x00, // compression_methods_length
+        ]
+        .to_vec();
+        let payload = ClientRequestPayload {
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: client_hello,
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 443,
+            protocol: ProxyProtocol::TLS,
+            originator_public_key: cryptde().public_key(),
+        };
+
+        let result = ProxyServer::make_route_failure_alert(
+            &payload,
+            SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            &Logger::new("Proxy Server"),
+        );
+
+        assert_eq!(result, vec![0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 40]);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Proxy Server: TLS route failure: hostname=<unknown> client=1.2.3.4:5678 alert=handshake_failure",
+        );
+    }
+
+    #[test]
+    fn proxy_server_receives_connect_request_then_opens_tunnel_and_tells_client() {
+        let cryptde = cryptde();
+        let connect_request = b"CONNECT server.com:8443 HTTP/1.1\r\nHost: server.com:8443\r\n\r\n";
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let dispatcher_recording_arc = dispatcher_mock.get_recording();
+        let hopper_mock = Recorder::new();
+        let hopper_awaiter = hopper_mock.get_awaiter();
+        let hopper_recording_arc = hopper_mock.get_recording();
+        let neighborhood_mock = Recorder::new().route_query_response(Some(
+            zero_hop_route_response(&cryptde.public_key(), cryptde),
+        ));
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(8080),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: connect_request.to_vec(),
+        };
+        let key = cryptde.public_key();
+        let route = zero_hop_route_response(&key, cryptde).route;
+        let expected_payload = ClientRequestPayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: vec![],
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: Some(String::from("server.com")),
+            target_port: 8443,
+            protocol: ProxyProtocol::Tunnel,
+            originator_public_key: key.clone(),
+        };
+        let expected_pkg = IncipientCoresPackage::new(route.clone(), expected_payload, &key);
+        let mut subject = ProxyServer::new(cryptde, false, Some(8080), None);
+        subject.stream_key_factory =
+            Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
+        thread::spawn(move || {
+            let system = System::new(
+                "proxy_server_receives_connect_request_then_opens_tunnel_and_tells_client",
+            );
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors = make_peer_actors_from(
+                None,
+                Some(dispatcher_mock),
+                Some(hopper_mock),
+                None,
+                Some(neighborhood_mock),
+                None,
+            );
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<IncipientCoresPackage>(0),
+            &expected_pkg
+        );
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(0),
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: false,
+                sequence_number: Some(0),
+                data: b"HTTP/1.1 200 Connection established\r\n\r\n".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn proxy_server_refuses_tunnel_without_connect_request() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let dispatcher_recording_arc = dispatcher_mock.get_recording();
+        let hopper_mock = Recorder::new();
+        let hopper_recording_arc = hopper_mock.get_recording();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(8080),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: b"GET http://server.com/ HTTP/1.1\r\nHost: server.com\r\n\r\n".to_vec(),
+        };
+        let subject = ProxyServer::new(cryptde, false, Some(8080), None);
+        thread::spawn(move || {
+            let system = System::new("proxy_server_refuses_tunnel_without_connect_request");
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors = make_peer_actors_from(
+                None,
+                Some(dispatcher_mock),
+                Some(hopper_mock),
+                None,
+                None,
+                None,
+            );
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(record.endpoint, Endpoint::Socket(socket_addr));
+        assert_eq!(record.last_data, true);
+        assert_eq!(&record.data[0..12], &b"HTTP/1.1 400"[..]);
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Proxy Server: Client at 1.2.3.4:5678 asked for a tunnel without a CONNECT request naming its target",
+        );
+    }
+
+    #[test]
+    fn proxy_server_receives_connect_request_but_neighborhood_cant_make_route() {
+        let cryptde = cryptde();
+        let dispatcher = Recorder::new();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let dispatcher_recording_arc = dispatcher.get_recording();
+        let neighborhood = Recorder::new().route_query_response(None);
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(8080),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: b"CONNECT server.com:443 HTTP/1.1\r\n\r\n".to_vec(),
+        };
+        thread::spawn(move || {
+            let system = System::new(
+                "proxy_server_receives_connect_request_but_neighborhood_cant_make_route",
+            );
+            let subject = ProxyServer::new(cryptde, false, Some(8080), None);
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors =
+                make_peer_actors_from(None, Some(dispatcher), None, None, Some(neighborhood), None);
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        let expected_msg = TransmitDataMsg {
+            endpoint: Endpoint::Socket(socket_addr),
+            last_data: true,
+            sequence_number: Some(0),
+            data: http_server_impersonator::make_error_response(
+                503,
+                "Routing Problem",
+                "Can't find a route to server.com",
+                "Substratum can't find a route through the Network yet to a Node that knows \
+                 where to find server.com. Maybe later enough will be known about the Network to \
+                 find that Node, but we can't guarantee it. We're sorry.",
+            ),
+        };
+        assert_eq!(record, &expected_msg);
+    }
+
+    #[test]
+    fn proxy_server_puts_tunnelled_responses_after_the_one_that_opened_the_tunnel() {
+        let system = System::new(
+            "proxy_server_puts_tunnelled_responses_after_the_one_that_opened_the_tunnel",
+        );
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_log_arc = dispatcher_mock.get_recording();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, false, Some(8080), None);
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject
+            .tunnelled_streams
+            .insert(stream_key.clone(), Tunnel::HttpConnect);
+        let key = cryptde.public_key();
+        let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+        let remaining_route = route_to_proxy_server(&key, cryptde);
+        let client_response_payload = ClientResponsePayload {
+            stream_key: stream_key,
+            sequenced_packet: SequencedPacket {
+                data: b"data".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+        };
+        let incipient_cores_package =
+            IncipientCoresPackage::new(remaining_route.clone(), client_response_payload, &key);
+        let expired_cores_package =
+            ExpiredCoresPackage::new(remaining_route, incipient_cores_package.payload);
+        let mut peer_actors =
+            make_peer_actors_from(None, Some(dispatcher_mock), None, None, None, None);
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_log_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(record.sequence_number, Some(1));
+        assert_eq!(record.data, b"data".to_vec());
+    }
+
+    fn make_socks5_msg(sequence_number: u64, data: &[u8]) -> InboundClientData {
+        InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(1080),
+            sequence_number: Some(sequence_number),
+            last_data: false,
+            is_clandestine: false,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn proxy_server_answers_socks5_handshake_then_opens_tunnel_and_tells_client() {
+        let cryptde = cryptde();
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let dispatcher_recording_arc = dispatcher_mock.get_recording();
+        let hopper_mock = Recorder::new();
+        let hopper_awaiter = hopper_mock.get_awaiter();
+        let hopper_recording_arc = hopper_mock.get_recording();
+        let neighborhood_mock = Recorder::new()
+            .route_query_response(Some(zero_hop_route_response(
+                &cryptde.public_key(),
+                cryptde,
+            )))
+            .route_query_response(Some(zero_hop_route_response(
+                &cryptde.public_key(),
+                cryptde,
+            )));
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let mut connect_request = vec![0x05, 0x01, 0x00, 0x03, 10];
+        connect_request.extend_from_slice(b"server.com");
+        connect_request.extend_from_slice(&[0x00, 0x16]);
+        let key = cryptde.public_key();
+        let route = zero_hop_route_response(&key, cryptde).route;
+        let expected_payload = |data: Vec<u8>, sequence_number: u64| ClientRequestPayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data,
+                sequence_number,
+                last_data: false,
+            },
+            target_hostname: if sequence_number == 0 {
+                Some(String::from("server.com"))
+            } else {
+                None
+            },
+            target_port: if sequence_number == 0 { 22 } else { 1080 },
+            protocol: ProxyProtocol::Tunnel,
+            originator_public_key: key.clone(),
+        };
+        let expected_opening_pkg =
+            IncipientCoresPackage::new(route.clone(), expected_payload(vec![], 0), &key);
+        let expected_data_pkg = IncipientCoresPackage::new(
+            route.clone(),
+            expected_payload(b"SSH-2.0".to_vec(), 1),
+            &key,
+        );
+        let mut subject = ProxyServer::new(cryptde, false, None, Some(1080));
+        subject.stream_key_factory =
+            Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
+        thread::spawn(move || {
+            let system = System::new(
+                "proxy_server_answers_socks5_handshake_then_opens_tunnel_and_tells_client",
+            );
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors = make_peer_actors_from(
+                None,
+                Some(dispatcher_mock),
+                Some(hopper_mock),
+                None,
+                Some(neighborhood_mock),
+                None,
+            );
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr
+                .try_send(make_socks5_msg(0, &[0x05, 0x01, 0x00]))
+                .unwrap();
+            subject_addr
+                .try_send(make_socks5_msg(1, &connect_request[..]))
+                .unwrap();
+            subject_addr
+                .try_send(make_socks5_msg(2, &b"SSH-2.0"[..]))
+                .unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(2);
+        let recording = hopper_recording_arc.lock().unwrap();
+        // Each package waits for its own route, so they can reach the Hopper in either order
+        let pkgs = vec![
+            recording.get_record::<IncipientCoresPackage>(0),
+            recording.get_record::<IncipientCoresPackage>(1),
+        ];
+        assert!(pkgs.contains(&&expected_opening_pkg));
+        assert!(pkgs.contains(&&expected_data_pkg));
+        dispatcher_awaiter.await_message_count(2);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        let expected_msg = |sequence_number: u64, data: Vec<u8>| TransmitDataMsg {
+            endpoint: Endpoint::Socket(socket_addr),
+            last_data: false,
+            sequence_number: Some(sequence_number),
+            data,
+        };
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(0),
+            &expected_msg(0, vec![0x05, 0x00])
+        );
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(1),
+            &expected_msg(1, vec![0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn proxy_server_receives_socks5_connect_request_but_neighborhood_cant_make_route() {
+        let cryptde = cryptde();
+        let dispatcher = Recorder::new();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let dispatcher_recording_arc = dispatcher.get_recording();
+        let neighborhood = Recorder::new().route_query_response(None);
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        thread::spawn(move || {
+            let system = System::new(
+                "proxy_server_receives_socks5_connect_request_but_neighborhood_cant_make_route",
+            );
+            let subject = ProxyServer::new(cryptde, false, None, Some(1080));
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors =
+                make_peer_actors_from(None, Some(dispatcher), None, None, Some(neighborhood), None);
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr
+                .try_send(make_socks5_msg(0, &[0x05, 0x01, 0x00]))
+                .unwrap();
+            subject_addr
+                .try_send(make_socks5_msg(
+                    1,
+                    &[0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0x01, 0xBB],
+                ))
+                .unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(2);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(1),
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: true,
+                sequence_number: Some(1),
+                data: vec![0x05, 0x03, 0x00, 0x01, 0, 0, 0, 0, 0, 0],
+            }
+        );
+    }
+
+    #[test]
+    fn proxy_server_refuses_socks5_client_without_a_method_it_supports() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let dispatcher_recording_arc = dispatcher_mock.get_recording();
+        let hopper_mock = Recorder::new();
+        let hopper_recording_arc = hopper_mock.get_recording();
+        let socket_addr = SocketAddr::from_str("1.2.3.5:5678").unwrap();
+        let mut msg_from_dispatcher = make_socks5_msg(0, &[0x05, 0x01, 0x02]);
+        msg_from_dispatcher.peer_addr = socket_addr;
+        let subject = ProxyServer::new(cryptde, false, None, Some(1080));
+        thread::spawn(move || {
+            let system =
+                System::new("proxy_server_refuses_socks5_client_without_a_method_it_supports");
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors = make_peer_actors_from(
+                None,
+                Some(dispatcher_mock),
+                Some(hopper_mock),
+                None,
+                None,
+                None,
+            );
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(0),
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data: vec![0x05, 0xFF],
+            }
+        );
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Proxy Server: Refusing SOCKS5 client at 1.2.3.5:5678 after 1 handshake message(s)",
+        );
+    }
+
+    #[test]
+    fn proxy_server_puts_tunnelled_responses_after_the_socks5_replies_that_opened_the_tunnel() {
+        let system = System::new(
+            "proxy_server_puts_tunnelled_responses_after_the_socks5_replies_that_opened_the_tunnel",
+        );
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_log_arc = dispatcher_mock.get_recording();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, false, None, Some(1080));
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject
+            .tunnelled_streams
+            .insert(stream_key.clone(), Tunnel::Socks5(2));
+        let key = cryptde.public_key();
+        let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+        let remaining_route = route_to_proxy_server(&key, cryptde);
+        let client_response_payload = ClientResponsePayload {
+            stream_key: stream_key,
+            sequenced_packet: SequencedPacket {
+                data: b"data".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+        };
+        let incipient_cores_package =
+            IncipientCoresPackage::new(remaining_route.clone(), client_response_payload, &key);
+        let expired_cores_package =
+            ExpiredCoresPackage::new(remaining_route, incipient_cores_package.payload);
+        let mut peer_actors =
+            make_peer_actors_from(None, Some(dispatcher_mock), None, None, None, None);
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_log_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(record.sequence_number, Some(3));
+        assert_eq!(record.data, b"data".to_vec());
+    }
+
+    #[test]
+    fn proxy_server_sends_requests_for_each_host_on_a_kept_alive_connection_to_its_own_stream() {
+        let cryptde = cryptde();
+        let hopper_mock = Recorder::new();
+        let hopper_awaiter = hopper_mock.get_awaiter();
+        let hopper_recording_arc = hopper_mock.get_recording();
+        let neighborhood_mock = (0..5).fold(Recorder::new(), |recorder, _| {
+            recorder.route_query_response(Some(zero_hop_route_response(
+                &cryptde.public_key(),
+                cryptde,
+            )))
+        });
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let other_stream_key = stream_key.for_host("two.com");
+        let requests: Vec<(&[u8], bool)> = vec![
+            (b"GET / HTTP/1.1\r\nHost: one.com\r\n\r\n", false),
+            (
+                b"POST / HTTP/1.1\r\nHost: two.com\r\nTransfer-Encoding: chunked\r\n\r\n",
+                false,
+            ),
+            (b"5\r\nbooga\r\n", false),
+            (b"", true),
+        ];
+        let key = cryptde.public_key();
+        let route = zero_hop_route_response(&key, cryptde).route;
+        let expected_pkg = |stream_key: StreamKey,
+                            host_name: &str,
+                            sequence_number: u64,
+                            data: &[u8],
+                            last_data: bool| {
+            IncipientCoresPackage::new(
+                route.clone(),
+                ClientRequestPayload {
+                    stream_key,
+                    sequenced_packet: SequencedPacket::new(
+                        data.to_vec(),
+                        sequence_number,
+                        last_data,
+                    ),
+                    target_hostname: Some(String::from(host_name)),
+                    target_port: 80,
+                    protocol: ProxyProtocol::HTTP,
+                    originator_public_key: key.clone(),
+                },
+                &key,
+            )
+        };
+        let expected_pkgs = vec![
+            expected_pkg(stream_key, "one.com", 0, requests[0].0, false),
+            expected_pkg(other_stream_key, "two.com", 0, requests[1].0, false),
+            expected_pkg(other_stream_key, "two.com", 1, requests[2].0, false),
+            expected_pkg(other_stream_key, "two.com", 2, b"", true),
+            expected_pkg(stream_key, "one.com", 1, b"", true),
+        ];
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
+        subject.stream_key_factory =
+            Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
+        thread::spawn(move || {
+            let system = System::new(
+                "proxy_server_sends_requests_for_each_host_on_a_kept_alive_connection_to_its_own_stream",
+            );
+            let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+            let mut peer_actors = make_peer_actors_from(
+                None,
+                None,
+                Some(hopper_mock),
+                None,
+                Some(neighborhood_mock),
+                None,
+            );
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            requests
+                .into_iter()
+                .enumerate()
+                .for_each(|(sequence_number, (data, last_data))| {
+                    subject_addr
+                        .try_send(InboundClientData {
+                            peer_addr: socket_addr,
+                            reception_port: Some(80),
+                            sequence_number: Some(sequence_number as u64),
+                            last_data,
+                            is_clandestine: false,
+                            data: data.to_vec(),
+                        })
+                        .unwrap();
+                });
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(5);
+        let recording = hopper_recording_arc.lock().unwrap();
+        // Each package waits for its own route, so they can reach the Hopper in any order
+        let pkgs: Vec<&IncipientCoresPackage> = (0..5)
+            .map(|index| recording.get_record::<IncipientCoresPackage>(index))
+            .collect();
+        expected_pkgs
+            .iter()
+            .for_each(|expected_pkg| assert!(pkgs.contains(&expected_pkg)));
+    }
+
+    #[test]
+    fn proxy_server_sends_responses_from_each_host_on_a_kept_alive_connection_in_request_order() {
+        let system = System::new(
+            "proxy_server_sends_responses_from_each_host_on_a_kept_alive_connection_in_request_order",
+        );
+        let dispatcher_mock = Recorder::new();
+        let dispatcher_log_arc = dispatcher_mock.get_recording();
+        let dispatcher_awaiter = dispatcher_mock.get_awaiter();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let other_stream_key = stream_key.for_host("two.com");
+        let mut connection = HttpConnection::new(stream_key);
+        connection
+            .route_request(
+                Some(String::from("one.com")),
+                b"GET / HTTP/1.1\r\nHost: one.com\r\n\r\n",
+            )
+            .unwrap();
+        connection
+            .route_request(
+                Some(String::from("two.com")),
+                b"GET / HTTP/1.1\r\nHost: two.com\r\n\r\n",
+            )
+            .unwrap();
+        subject.http_connections.insert(socket_addr, connection);
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject
+            .http_stream_addrs
+            .insert(other_stream_key, socket_addr);
+        let key = cryptde.public_key();
+        let subject_addr: Addr<Syn, ProxyServer> = subject.start();
+        let remaining_route = route_to_proxy_server(&key, cryptde);
+        let expired_cores_package = |stream_key: StreamKey,
+                                     data: &[u8],
+                                     sequence_number: u64,
+                                     last_data: bool| {
+            let client_response_payload = ClientResponsePayload {
+                stream_key,
+                sequenced_packet: SequencedPacket::new(data.to_vec(), sequence_number, last_data),
+            };
+            let incipient_cores_package =
+                IncipientCoresPackage::new(remaining_route.clone(), client_response_payload, &key);
+            ExpiredCoresPackage::new(remaining_route.clone(), incipient_cores_package.payload)
+        };
+        let mut peer_actors =
+            make_peer_actors_from(None, Some(dispatcher_mock), None, None, None, None);
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        let response_a0 = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n";
+        let response_b0 = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nB";
+        subject_addr
+            .try_send(expired_cores_package(
+                other_stream_key,
+                response_b0,
+                0,
+                false,
+            ))
+            .unwrap();
+        subject_addr
+            .try_send(expired_cores_package(stream_key, b"A1", 1, false))
+            .unwrap();
+        subject_addr
+            .try_send(expired_cores_package(stream_key, response_a0, 0, false))
+            .unwrap();
+        subject_addr
+            .try_send(expired_cores_package(other_stream_key, b"1", 1, true))
+            .unwrap();
+        subject_addr
+            .try_send(expired_cores_package(stream_key, b"", 2, true))
+            .unwrap();
+
+        Arbiter::system().try_send(msgs::SystemExit(0)).unwrap();
+        system.run();
+
+        dispatcher_awaiter.await_message_count(4);
+        let recording = dispatcher_log_arc.lock().unwrap();
+        let expected_msg = |sequence_number: u64, data: &[u8], last_data: bool| TransmitDataMsg {
+            endpoint: Endpoint::Socket(socket_addr),
+            last_data,
+            sequence_number: Some(sequence_number),
+            data: data.to_vec(),
+        };
+        let mut response_a = response_a0.to_vec();
+        response_a.extend_from_slice(b"A1");
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(0),
+            &expected_msg(0, &response_a[..], false)
+        );
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(1),
+            &expected_msg(1, response_b0, false)
+        );
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(2),
+            &expected_msg(2, b"1", false)
+        );
+        assert_eq!(
+            recording.get_record::<TransmitDataMsg>(3),
+            &expected_msg(3, b"", true)
+        );
     }
 
     #[test]
@@ -954,7 +2157,7 @@ mod tests {
         let dispatcher_log_arc = dispatcher_mock.get_recording();
         let dispatcher_awaiter = dispatcher_mock.get_awaiter();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         subject
@@ -1004,7 +2207,7 @@ mod tests {
         let dispatcher_log_arc = dispatcher_mock.get_recording();
         let dispatcher_awaiter = dispatcher_mock.get_awaiter();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         subject
@@ -1057,7 +2260,7 @@ mod tests {
         let cryptde = cryptde();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, None, None);
         subject
             .keys_and_addrs
             .insert(stream_key.clone(), socket_addr.clone());
@@ -1088,7 +2291,7 @@ mod tests {
     fn panics_if_hopper_is_unbound() {
         let system = System::new("panics_if_hopper_is_unbound");
         let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
-        let subject = ProxyServer::new(cryptde(), false);
+        let subject = ProxyServer::new(cryptde(), false, None, None);
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let expected_data = http_request.to_vec();
         let msg_from_dispatcher = InboundClientData {
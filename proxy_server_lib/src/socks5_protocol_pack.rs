@@ -0,0 +1,322 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use protocol_pack::ProtocolPack;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use sub_lib::cryptde::PlainData;
+use sub_lib::proxy_server::ProxyProtocol;
+
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTHENTICATION_REQUIRED: u8 = 0x00;
+const NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+const CONNECT: u8 = 0x01;
+const IPV4: u8 = 0x01;
+const DOMAIN_NAME: u8 = 0x03;
+const IPV6: u8 = 0x04;
+
+pub const SUCCEEDED: u8 = 0x00;
+pub const GENERAL_FAILURE: u8 = 0x01;
+pub const NETWORK_UNREACHABLE: u8 = 0x03;
+pub const COMMAND_NOT_SUPPORTED: u8 = 0x07;
+pub const ADDRESS_TYPE_NOT_SUPPORTED: u8 = 0x08;
+
+// Hears the CONNECT request at the end of a SOCKS5 handshake (RFC 1928); everything after it goes through the tunnel
+// untouched. The messages before it are answered by a Socks5Handshake.
+pub struct Socks5ProtocolPack {}
+
+impl ProtocolPack for Socks5ProtocolPack {
+    fn proxy_protocol(&self) -> ProxyProtocol {
+        ProxyProtocol::Tunnel
+    }
+
+    fn find_host_name(&self, data: &PlainData) -> Option<String> {
+        Socks5ProtocolPack::find_target(&data.data[..])
+            .ok()
+            .map(|(host_name, _)| host_name)
+    }
+
+    fn find_port(&self, data: &PlainData) -> Option<u16> {
+        Socks5ProtocolPack::find_target(&data.data[..])
+            .ok()
+            .map(|(_, port)| port)
+    }
+}
+
+impl Socks5ProtocolPack {
+    // The request looks like VER CMD RSV ATYP DST.ADDR DST.PORT; if it's no good, the error is the reply code that
+    // says why.
+    fn find_target(data: &[u8]) -> Result<(String, u16), u8> {
+        if (data.len() < 4) || (data[0] != SOCKS_VERSION) {
+            return Err(GENERAL_FAILURE);
+        }
+        if data[1] != CONNECT {
+            return Err(COMMAND_NOT_SUPPORTED);
+        }
+        let (host_name, port_offset) = match data[3] {
+            IPV4 if data.len() == 10 => {
+                let ip_addr = Ipv4Addr::new(data[4], data[5], data[6], data[7]);
+                (format!("{}", ip_addr), 8)
+            }
+            IPV6 if data.len() == 22 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&data[4..20]);
+                (format!("{}", Ipv6Addr::from(octets)), 20)
+            }
+            DOMAIN_NAME if (data.len() > 4) && (data.len() == 7 + data[4] as usize) => {
+                let domain_length = data[4] as usize;
+                match String::from_utf8(Vec::from(&data[5..(5 + domain_length)])) {
+                    Ok(ref host_name) if !host_name.is_empty() => {
+                        (host_name.clone(), 5 + domain_length)
+                    }
+                    _ => return Err(GENERAL_FAILURE),
+                }
+            }
+            IPV4 | IPV6 | DOMAIN_NAME => return Err(GENERAL_FAILURE),
+            _ => return Err(ADDRESS_TYPE_NOT_SUPPORTED),
+        };
+        let port = ((data[port_offset] as u16) << 8) | (data[port_offset + 1] as u16);
+        Ok((host_name, port))
+    }
+}
+
+// The reply to a CONNECT request. We don't tell the client where we're connecting from, so the bound address is
+// always 0.0.0.0:0.
+pub fn make_reply(reply_code: u8) -> Vec<u8> {
+    vec![SOCKS_VERSION, reply_code, 0x00, IPV4, 0, 0, 0, 0, 0, 0]
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Socks5Step {
+    // Send this back to the client and wait for its next message
+    Reply(Vec<u8>),
+    // Send this back to the client and hang up
+    Refuse(Vec<u8>),
+    // The client has made its CONNECT request: route it like the start of any other tunnel
+    Connect,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Socks5State {
+    Greeting,
+    Requesting,
+    Connected,
+}
+
+// Answers a SOCKS5 client's method negotiation until it makes its CONNECT request. The Node has no credentials to
+// check a username and password against, so only clients willing to go without authentication are served.
+pub struct Socks5Handshake {
+    state: Socks5State,
+    message_count: u64,
+}
+
+impl Socks5Handshake {
+    pub fn new() -> Socks5Handshake {
+        Socks5Handshake {
+            state: Socks5State::Greeting,
+            message_count: 0,
+        }
+    }
+
+    pub fn step(&mut self, data: &[u8]) -> Socks5Step {
+        match self.state {
+            Socks5State::Greeting => self.greet(data),
+            Socks5State::Requesting => match Socks5ProtocolPack::find_target(data) {
+                Ok(_) => {
+                    self.state = Socks5State::Connected;
+                    Socks5Step::Connect
+                }
+                Err(reply_code) => Socks5Step::Refuse(make_reply(reply_code)),
+            },
+            Socks5State::Connected => panic!("SOCKS5 handshake is already complete"),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.state == Socks5State::Connected
+    }
+
+    // How many of the client's messages were answered before its CONNECT request
+    pub fn message_count(&self) -> u64 {
+        self.message_count
+    }
+
+    fn greet(&mut self, data: &[u8]) -> Socks5Step {
+        if (data.len() < 2) || (data[0] != SOCKS_VERSION) || (data.len() != 2 + data[1] as usize) {
+            return Socks5Step::Refuse(vec![SOCKS_VERSION, NO_ACCEPTABLE_METHODS]);
+        }
+        if !data[2..].contains(&NO_AUTHENTICATION_REQUIRED) {
+            return Socks5Step::Refuse(vec![SOCKS_VERSION, NO_ACCEPTABLE_METHODS]);
+        }
+        self.state = Socks5State::Requesting;
+        self.message_count += 1;
+        Socks5Step::Reply(vec![SOCKS_VERSION, NO_AUTHENTICATION_REQUIRED])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_its_protocol() {
+        let result = Socks5ProtocolPack {}.proxy_protocol();
+
+        assert_eq!(result, ProxyProtocol::Tunnel);
+    }
+
+    #[test]
+    fn finds_domain_name_and_port_in_connect_request() {
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, 10];
+        request.extend_from_slice(b"server.com");
+        request.extend_from_slice(&[0x20, 0xFB]);
+        let data = PlainData::new(&request[..]);
+
+        let host_name = Socks5ProtocolPack {}.find_host_name(&data);
+        let port = Socks5ProtocolPack {}.find_port(&data);
+
+        assert_eq!(host_name, Some(String::from("server.com")));
+        assert_eq!(port, Some(8443));
+    }
+
+    #[test]
+    fn finds_ipv4_address_and_port_in_connect_request() {
+        let data = PlainData::new(&[0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0x00, 0x16]);
+
+        let host_name = Socks5ProtocolPack {}.find_host_name(&data);
+        let port = Socks5ProtocolPack {}.find_port(&data);
+
+        assert_eq!(host_name, Some(String::from("1.2.3.4")));
+        assert_eq!(port, Some(22));
+    }
+
+    #[test]
+    fn finds_ipv6_address_and_port_in_connect_request() {
+        let mut request = vec![0x05, 0x01, 0x00, 0x04];
+        request.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        request.extend_from_slice(&[0x01, 0xBB]);
+        let data = PlainData::new(&request[..]);
+
+        let host_name = Socks5ProtocolPack {}.find_host_name(&data);
+        let port = Socks5ProtocolPack {}.find_port(&data);
+
+        assert_eq!(host_name, Some(String::from("2001:db8::1")));
+        assert_eq!(port, Some(443));
+    }
+
+    #[test]
+    fn finds_nothing_in_requests_that_are_malformed_or_not_connect() {
+        vec![
+            &[][..],
+            &[0x05, 0x01, 0x00][..],
+            &[0x04, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0x00, 0x16][..],
+            &[0x05, 0x02, 0x00, 0x01, 1, 2, 3, 4, 0x00, 0x16][..],
+            &[0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0x00][..],
+            &[0x05, 0x01, 0x00, 0x03][..],
+            &[0x05, 0x01, 0x00, 0x03, 0x00, 0x00, 0x16][..],
+            &[0x05, 0x01, 0x00, 0x03, 0x03, b'a', b'b', 0x00, 0x16][..],
+            &[0x05, 0x01, 0x00, 0x03, 0x02, 0xFF, 0xFE, 0x00, 0x16][..],
+            &[0x05, 0x01, 0x00, 0x02, 1, 2, 3, 4, 0x00, 0x16][..],
+        ]
+        .into_iter()
+        .for_each(|request| {
+            let data = PlainData::new(request);
+
+            let host_name = Socks5ProtocolPack {}.find_host_name(&data);
+
+            assert_eq!(host_name, None, "{:?}", request);
+        });
+    }
+
+    #[test]
+    fn handshake_without_authentication() {
+        let mut subject = Socks5Handshake::new();
+
+        let greeting_step = subject.step(&[0x05, 0x02, 0x00, 0x02]);
+        let request_step = subject.step(&[0x05, 0x01, 0x00, 0x01, 1, 2, 3, 4, 0x00, 0x16]);
+
+        assert_eq!(greeting_step, Socks5Step::Reply(vec![0x05, 0x00]));
+        assert_eq!(request_step, Socks5Step::Connect);
+        assert!(subject.is_complete());
+        assert_eq!(subject.message_count(), 1);
+    }
+
+    #[test]
+    fn handshake_refuses_client_without_a_method_we_support() {
+        vec![&[0x05, 0x01, 0x01][..], &[0x05, 0x02, 0x01, 0x02][..]]
+            .into_iter()
+            .for_each(|greeting| {
+                let mut subject = Socks5Handshake::new();
+
+                let result = subject.step(greeting);
+
+                assert_eq!(
+                    result,
+                    Socks5Step::Refuse(vec![0x05, 0xFF]),
+                    "{:?}",
+                    greeting
+                );
+            });
+    }
+
+    #[test]
+    fn handshake_refuses_greeting_that_is_malformed() {
+        vec![
+            &[0x05][..],
+            &[0x04, 0x01, 0x00][..],
+            &[0x05, 0x02, 0x00][..],
+            &[0x05, 0x01, 0x00, 0x02][..],
+        ]
+        .into_iter()
+        .for_each(|greeting| {
+            let mut subject = Socks5Handshake::new();
+
+            let result = subject.step(greeting);
+
+            assert_eq!(
+                result,
+                Socks5Step::Refuse(vec![0x05, 0xFF]),
+                "{:?}",
+                greeting
+            );
+        });
+    }
+
+    #[test]
+    fn handshake_refuses_requests_with_the_reply_code_that_says_why() {
+        vec![
+            (
+                &[0x05, 0x02, 0x00, 0x01, 1, 2, 3, 4, 0x00, 0x16][..],
+                COMMAND_NOT_SUPPORTED,
+            ),
+            (
+                &[0x05, 0x01, 0x00, 0x02, 1, 2, 3, 4, 0x00, 0x16][..],
+                ADDRESS_TYPE_NOT_SUPPORTED,
+            ),
+            (&[0x05, 0x01, 0x00, 0x01, 1, 2, 3][..], GENERAL_FAILURE),
+        ]
+        .into_iter()
+        .for_each(|(request, reply_code)| {
+            let mut subject = Socks5Handshake::new();
+            subject.step(&[0x05, 0x01, 0x00]);
+
+            let result = subject.step(request);
+
+            assert_eq!(
+                result,
+                Socks5Step::Refuse(make_reply(reply_code)),
+                "{:?}",
+                request
+            );
+            assert!(!subject.is_complete());
+        });
+    }
+
+    #[test]
+    fn make_reply_reports_an_unspecified_bound_address() {
+        let result = make_reply(NETWORK_UNREACHABLE);
+
+        assert_eq!(
+            result,
+            vec![0x05, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+}
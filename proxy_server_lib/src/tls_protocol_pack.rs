@@ -3,6 +3,11 @@ use protocol_pack::ProtocolPack;
 use sub_lib::cryptde::PlainData;
 use sub_lib::proxy_server::ProxyProtocol;
 
+const HANDSHAKE: u8 = 0x16;
+const CLIENT_HELLO: u8 = 0x01;
+const SERVER_NAME: u16 = 0x0000;
+const HOST_NAME: u8 = 0x00;
+
 pub struct TlsProtocolPack {}
 
 impl ProtocolPack for TlsProtocolPack {
@@ -11,90 +16,148 @@ impl ProtocolPack for TlsProtocolPack {
     }
 
     fn find_host_name(&self, data: &PlainData) -> Option<String> {
-        if !TlsProtocolPack::is_handshake(&data) {
-            return None;
-        }
-        if !TlsProtocolPack::is_client_hello(&data) {
-            return None;
+        match TlsProtocolPack::reassemble_handshake(&data.data[..]) {
+            HandshakeStatus::ClientHello(client_hello) => {
+                TlsProtocolPack::host_name_from_client_hello(&client_hello[..])
+            }
+            _ => None,
         }
-        TlsProtocolPack::find_host_name(&data)
+    }
+
+    fn find_port(&self, _data: &PlainData) -> Option<u16> {
+        None
     }
 }
 
 impl TlsProtocolPack {
-    fn is_handshake(data: &PlainData) -> bool {
-        data.data.first() == Some(&0x16)
-    }
-
-    fn is_client_hello(data: &PlainData) -> bool {
-        data.data[5] == 0x01
-    }
-
-    fn find_host_name(data: &PlainData) -> Option<String> {
-        let session_id_offset = 43;
-        let cipher_suites_offset = TlsProtocolPack::advance_past(data, session_id_offset, 1)?;
-        let compression_methods_offset =
-            TlsProtocolPack::advance_past(data, cipher_suites_offset, 2)?;
-        let extensions_offset = TlsProtocolPack::advance_past(data, compression_methods_offset, 1)?;
-        let extensions_end = TlsProtocolPack::advance_past(data, extensions_offset, 2)?;
-        let mut extension_offset = extensions_offset + 2;
-        while extension_offset < extensions_end {
-            let extension_type = TlsProtocolPack::u16_from(data, extension_offset);
-            if extension_type == 0x0000 {
-                return TlsProtocolPack::host_name_from_extension(data, extension_offset);
+    // True if the data starts a ClientHello that's bigger than the records so far can hold, so that whoever is
+    // framing them should wait for more before handing them on.
+    pub fn needs_more_records(data: &[u8]) -> bool {
+        TlsProtocolPack::reassemble_handshake(data) == HandshakeStatus::Incomplete
+    }
+
+    // A ClientHello can be split across as many handshake records as the client likes, so put the fragments back
+    // together before looking inside.
+    fn reassemble_handshake(data: &[u8]) -> HandshakeStatus {
+        if data.first() != Some(&HANDSHAKE) {
+            return HandshakeStatus::NotClientHello;
+        }
+        let mut records = Reader::new(data);
+        let mut handshake = vec![];
+        while !records.is_empty() {
+            // ContentType, ProtocolVersion, length, fragment
+            if records.u8() != Some(HANDSHAKE) {
+                return HandshakeStatus::NotClientHello;
+            }
+            let fragment = match (records.skip(2), records.vector(2)) {
+                (Some(_), Some(fragment)) => fragment,
+                _ => return HandshakeStatus::Incomplete,
+            };
+            handshake.extend_from_slice(fragment);
+            let mut message = Reader::new(&handshake[..]);
+            match (message.u8(), message.u24()) {
+                (Some(CLIENT_HELLO), Some(length)) => {
+                    if let Some(body) = message.bytes(length) {
+                        return HandshakeStatus::ClientHello(body.to_vec());
+                    }
+                }
+                (Some(CLIENT_HELLO), None) | (None, _) => (),
+                (Some(_), _) => return HandshakeStatus::NotClientHello,
             }
-            extension_offset = TlsProtocolPack::advance_past(data, extension_offset + 2, 2)?;
         }
-        None
+        HandshakeStatus::Incomplete
     }
 
-    fn host_name_from_extension(data: &PlainData, offset: usize) -> Option<String> {
-        let server_name_list_offset = offset + 4;
-        let server_name_list_end = TlsProtocolPack::advance_past(data, server_name_list_offset, 2)?;
-        let mut server_name_list_entry_offset = server_name_list_offset + 2;
-        while server_name_list_entry_offset < server_name_list_end {
-            let server_name_type = TlsProtocolPack::u8_from(data, server_name_list_entry_offset);
-            if server_name_type == 0x00 {
-                return TlsProtocolPack::host_name_from_list_entry(
-                    data,
-                    server_name_list_entry_offset,
-                );
+    // ProtocolVersion, Random, SessionID, CipherSuites, CompressionMethods, Extensions
+    fn host_name_from_client_hello(client_hello: &[u8]) -> Option<String> {
+        let mut reader = Reader::new(client_hello);
+        reader.skip(2 + 32)?;
+        reader.vector(1)?;
+        reader.vector(2)?;
+        reader.vector(1)?;
+        let mut extensions = Reader::new(reader.vector(2)?);
+        while !extensions.is_empty() {
+            let extension_type = extensions.u16()?;
+            let extension_data = extensions.vector(2)?;
+            if extension_type == SERVER_NAME {
+                return TlsProtocolPack::host_name_from_extension(extension_data);
             }
-            server_name_list_entry_offset =
-                TlsProtocolPack::advance_past(data, server_name_list_entry_offset + 1, 2)?;
         }
         None
     }
 
-    fn host_name_from_list_entry(data: &PlainData, offset: usize) -> Option<String> {
-        let server_name_length = TlsProtocolPack::u16_from(data, offset + 1);
-        let server_name_offset = offset + 3;
-        match String::from_utf8(Vec::from(
-            &data.data[(server_name_offset)..(server_name_offset + server_name_length)],
-        )) {
-            Ok(hostname) => Some(hostname),
-            Err(_) => None,
+    fn host_name_from_extension(extension_data: &[u8]) -> Option<String> {
+        let mut server_names = Reader::new(Reader::new(extension_data).vector(2)?);
+        while !server_names.is_empty() {
+            let name_type = server_names.u8()?;
+            let name = server_names.vector(2)?;
+            if name_type == HOST_NAME {
+                return String::from_utf8(name.to_vec()).ok();
+            }
         }
+        None
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum HandshakeStatus {
+    ClientHello(Vec<u8>),
+    Incomplete,
+    NotClientHello,
+}
+
+// Reads big-endian numbers and length-prefixed vectors without ever reading past the end of its data; anything that
+// would is None.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, offset: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offset >= self.data.len()
     }
 
-    fn advance_past(data: &PlainData, length_offset: usize, length_length: usize) -> Option<usize> {
-        if length_offset + length_length > data.data.len() {
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        if length > self.data.len() - self.offset {
             return None;
         }
-        let length = if length_length == 1 {
-            TlsProtocolPack::u8_from(data, length_offset)
-        } else {
-            TlsProtocolPack::u16_from(data, length_offset)
-        };
-        Some(length_offset + length_length + length)
+        let bytes = &self.data[self.offset..(self.offset + length)];
+        self.offset += length;
+        Some(bytes)
     }
 
-    fn u8_from(data: &PlainData, offset: usize) -> usize {
-        data.data[offset] as usize
+    fn skip(&mut self, length: usize) -> Option<()> {
+        self.bytes(length).map(|_| ())
     }
 
-    fn u16_from(data: &PlainData, offset: usize) -> usize {
-        (TlsProtocolPack::u8_from(data, offset) << 8) | TlsProtocolPack::u8_from(data, offset + 1)
+    fn number(&mut self, length: usize) -> Option<usize> {
+        self.bytes(length).map(|bytes| {
+            bytes
+                .iter()
+                .fold(0, |acc, byte| (acc << 8) | (*byte as usize))
+        })
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.number(2).map(|number| number as u16)
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.number(3)
+    }
+
+    fn vector(&mut self, length_length: usize) -> Option<&'a [u8]> {
+        let length = self.number(length_length)?;
+        self.bytes(length)
     }
 }
 
@@ -102,6 +165,43 @@ impl TlsProtocolPack {
 mod tests {
     use super::*;
 
+    fn make_handshake(handshake_type: u8, body: &[u8]) -> Vec<u8> {
+        let length = body.len();
+        let mut handshake = vec![
+            handshake_type,
+            (length >> 16) as u8,
+            (length >> 8) as u8,
+            length as u8,
+        ];
+        handshake.extend_from_slice(body);
+        handshake
+    }
+
+    fn make_records(handshake: &[u8], fragment_length: usize) -> Vec<u8> {
+        handshake
+            .chunks(fragment_length)
+            .flat_map(|fragment| {
+                let mut record = vec![
+                    0x16,
+                    0x03,
+                    0x01,
+                    (fragment.len() >> 8) as u8,
+                    fragment.len() as u8,
+                ];
+                record.extend_from_slice(fragment);
+                record
+            })
+            .collect()
+    }
+
+    fn make_record(handshake: &[u8]) -> Vec<u8> {
+        make_records(handshake, handshake.len())
+    }
+
+    fn client_hello_record(body: &[u8]) -> Vec<u8> {
+        make_record(&make_handshake(0x01, body))
+    }
+
     #[test]
     fn knows_its_protocol() {
         let result = TlsProtocolPack {}.proxy_protocol();
@@ -136,7 +236,10 @@ mod tests {
         vec![0u8, 2u8, 11u8, 12u8, 13u8, 14u8, 15u8, 16u8, 20u8]
             .iter()
             .for_each(|handshake_type| {
-                let data = PlainData::new(&[0x16, 0x00, 0x00, 0x00, 0x00, *handshake_type]);
+                let data = PlainData::new(&make_record(&make_handshake(
+                    *handshake_type,
+                    &[0x03, 0x03],
+                )));
 
                 let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -146,11 +249,8 @@ mod tests {
 
     #[test]
     fn rejects_packet_that_has_no_server_name_extension() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -159,7 +259,7 @@ mod tests {
             0x00, 0x00, // cipher_suites_length
             0x00, // compression_methods_length
             0x00, 0x00, // extensions_length
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -168,13 +268,10 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_preamble() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // truncated preamble
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -185,17 +282,14 @@ mod tests {
     fn does_not_panic_for_packet_with_truncated_session_id_length() {
         // Removing this directive will make the Windows and other builds argue over formatting
         #[cfg_attr(rustfmt, rustfmt_skip)]
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
                   // truncated session_id_length
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -204,18 +298,15 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_session_id() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0xFF, // session_id_length
             0x00, 0x00, // truncated session_id
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -224,18 +315,15 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_cipher_suites_length() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, // session_id_length
             0x00, // truncated cipher_suites_length
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -244,11 +332,8 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_cipher_suites() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -256,7 +341,7 @@ mod tests {
             0x00, // session_id_length
             0x00, 0xFF, // cipher_suites_length
             0x00, // truncated cipher_suites
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -265,11 +350,8 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_compression_methods_length() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -277,7 +359,7 @@ mod tests {
             0x00, // session_id_length
             0x00, 0x00, // cipher_suites_length
             0xFF, // truncated compression_methods_length
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -286,11 +368,8 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_compression_methods() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -299,7 +378,7 @@ mod tests {
             0x00, 0x00, // cipher_suites_length
             0xFF, // compression_methods_length
             0x00, 0x00, 0x00, // truncated compression_methods
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -308,11 +387,8 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_with_truncated_extensions_length() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -321,7 +397,7 @@ mod tests {
             0x00, 0x00, // cipher_suites_length
             0x00, // compression_methods_length
             0x00, // truncated extensions_length
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -330,11 +406,8 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_packet_truncated_amid_extensions() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -344,7 +417,7 @@ mod tests {
             0x00, // compression_methods_length
             0x00, 0xFF, // extensions_length
             0xFF, 0xFF, // truncated extensions
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -353,11 +426,8 @@ mod tests {
 
     #[test]
     fn does_not_panic_for_hostname_that_is_not_utf8() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -373,7 +443,7 @@ mod tests {
             0x00, 0x0A, // server_name_length
             's' as u8, 'e' as u8, 'r' as u8, 'v' as u8, 'e' as u8, 'r' as u8, '.' as u8, 0xC3,
             0x28, 'm' as u8, // bad server_name
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -382,11 +452,8 @@ mod tests {
 
     #[test]
     fn extracts_hostname_from_packet_with_only_server_name_extension() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -405,7 +472,7 @@ mod tests {
             0x00, 0x0A, // server_name_length
             's' as u8, 'e' as u8, 'r' as u8, 'v' as u8, 'e' as u8, 'r' as u8, '.' as u8, 'c' as u8,
             'o' as u8, 'm' as u8, // server_name
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -414,11 +481,8 @@ mod tests {
 
     #[test]
     fn extracts_hostname_from_packet_with_sections_and_multiple_extensions() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -443,7 +507,7 @@ mod tests {
             0x00, 0x0A, // server_name_length
             's' as u8, 'e' as u8, 'r' as u8, 'v' as u8, 'e' as u8, 'r' as u8, '.' as u8, 'c' as u8,
             'o' as u8, 'm' as u8, // server_name
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
@@ -452,11 +516,8 @@ mod tests {
 
     #[test]
     fn doesnt_see_host_name_extension_that_is_outside_extensions_section() {
-        let data = PlainData::new(&[
-            0x16, // content_type: Handshake
-            0x00, 0x00, 0x00, 0x00, // version, length: don't care
-            0x01, // handshake_type: ClientHello
-            0x00, 0x00, 0x00, 0x00, 0x00, // length, version: don't care
+        let data = PlainData::new(&client_hello_record(&[
+            0x03, 0x03, // client_version: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             0x00, 0x00, // random: don't care
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -475,10 +536,475 @@ mod tests {
             0x00, 0x0A, // server_name_length
             's' as u8, 'e' as u8, 'r' as u8, 'v' as u8, 'e' as u8, 'r' as u8, '.' as u8, 'c' as u8,
             'o' as u8, 'm' as u8, // server_name
-        ]);
+        ]));
 
         let result = TlsProtocolPack {}.find_host_name(&data);
 
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn extracts_hostname_from_client_hello_split_across_records() {
+        let client_hello = make_client_hello(1, &[(0x0000, 0)], "server.com");
+        let records = make_records(&client_hello, 20);
+        let first_record = &records[0..25];
+
+        let first_record_needs_more = TlsProtocolPack::needs_more_records(first_record);
+        let first_record_result = TlsProtocolPack {}.find_host_name(&PlainData::new(first_record));
+        let all_records_need_more = TlsProtocolPack::needs_more_records(&records[..]);
+        let all_records_result = TlsProtocolPack {}.find_host_name(&PlainData::new(&records[..]));
+
+        assert!(first_record_needs_more);
+        assert_eq!(first_record_result, None);
+        assert!(!all_records_need_more);
+        assert_eq!(all_records_result, Some(String::from("server.com")));
+    }
+
+    #[test]
+    fn needs_more_records_only_for_client_hello_that_is_not_all_there() {
+        let client_hello = make_client_hello(1, &[(0x0000, 0)], "server.com");
+        vec![
+            (Vec::new(), false),
+            (vec![0x17, 0x03, 0x03, 0x00, 0x01, 0xFF], false),
+            (make_records(&make_handshake(0x02, &[0x03; 100]), 10), false),
+            (make_record(&client_hello), false),
+            (make_record(&client_hello)[..30].to_vec(), true),
+            (make_records(&client_hello, 10)[..30].to_vec(), true),
+            (vec![0x16, 0x03, 0x01], true),
+        ]
+        .into_iter()
+        .for_each(|(data, expected)| {
+            let result = TlsProtocolPack::needs_more_records(&data[..]);
+
+            assert_eq!(result, expected, "{:?}", data);
+        });
+    }
+
+    #[test]
+    fn does_not_see_client_hello_in_records_that_are_not_all_handshake() {
+        let client_hello = make_client_hello(1, &[(0x0000, 0)], "server.com");
+        let mut data = make_records(&client_hello[0..20], 20);
+        data.extend_from_slice(&[0x17, 0x03, 0x03, 0x00, 0x01, 0xFF]);
+        data.extend(make_records(&client_hello[20..], 1000));
+
+        let result = TlsProtocolPack {}.find_host_name(&PlainData::new(&data[..]));
+        let needs_more = TlsProtocolPack::needs_more_records(&data[..]);
+
+        assert_eq!(result, None);
+        assert!(!needs_more);
+    }
+
+    // Builds a ClientHello handshake message with random, session ID, and cipher suites we don't care about, and the
+    // given extensions, each of which is (type, length). The server_name extension (type 0) is filled in with the
+    // host name; the rest are filled with junk.
+    fn make_client_hello(
+        cipher_suite_count: usize,
+        extensions: &[(u16, usize)],
+        host_name: &str,
+    ) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend((0..32).map(|n| n as u8));
+        body.push(32);
+        body.extend((0..32).map(|n| 0xFF - n as u8));
+        body.extend_from_slice(&[
+            (cipher_suite_count >> 7) as u8,
+            (cipher_suite_count << 1) as u8,
+        ]);
+        body.extend((0..(cipher_suite_count * 2)).map(|n| n as u8));
+        body.extend_from_slice(&[0x01, 0x00]);
+        let extensions_data: Vec<u8> = extensions
+            .iter()
+            .flat_map(|(extension_type, length)| {
+                let extension_data = if *extension_type == 0x0000 {
+                    let name_length = host_name.len();
+                    let mut data = vec![
+                        ((name_length + 3) >> 8) as u8,
+                        (name_length + 3) as u8,
+                        0x00,
+                        (name_length >> 8) as u8,
+                        name_length as u8,
+                    ];
+                    data.extend_from_slice(host_name.as_bytes());
+                    data
+                } else {
+                    vec![0x5A; *length]
+                };
+                let mut extension = vec![
+                    (*extension_type >> 8) as u8,
+                    *extension_type as u8,
+                    (extension_data.len() >> 8) as u8,
+                    extension_data.len() as u8,
+                ];
+                extension.extend(extension_data);
+                extension
+            })
+            .collect();
+        body.extend_from_slice(&[
+            (extensions_data.len() >> 8) as u8,
+            extensions_data.len() as u8,
+        ]);
+        body.extend(extensions_data);
+        make_handshake(0x01, &body)
+    }
+
+    // These are synthesized, not captured: each is modelled on the published layout of its client's ClientHello--its
+    // cipher suite count and its extensions, in order, with their lengths. Only the shape matters here: Chrome's
+    // post-quantum key share, for instance, makes its ClientHello too big for many middleboxes that expect it to fit
+    // in one small record.
+    fn modelled_client_hellos() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            (
+                "Chrome",
+                make_client_hello(
+                    16,
+                    &[
+                        (0x0A0A, 0),
+                        (0x0000, 0),
+                        (0x0017, 0),
+                        (0xFF01, 1),
+                        (0x000A, 12),
+                        (0x000B, 2),
+                        (0x0023, 0),
+                        (0x0010, 14),
+                        (0x0005, 5),
+                        (0x000D, 18),
+                        (0x0012, 0),
+                        (0x0033, 1263),
+                        (0x002D, 2),
+                        (0x002B, 7),
+                        (0x001B, 3),
+                        (0x4469, 5),
+                        (0xFE0D, 218),
+                        (0x1A1A, 1),
+                    ],
+                    "www.google.com",
+                ),
+            ),
+            (
+                "Firefox",
+                make_client_hello(
+                    17,
+                    &[
+                        (0x0000, 0),
+                        (0x0017, 0),
+                        (0xFF01, 1),
+                        (0x000A, 14),
+                        (0x000B, 2),
+                        (0x0023, 0),
+                        (0x0010, 14),
+                        (0x0005, 5),
+                        (0x0022, 10),
+                        (0x0033, 107),
+                        (0x002B, 5),
+                        (0x000D, 24),
+                        (0x002D, 2),
+                        (0x001C, 2),
+                        (0xFE0D, 281),
+                    ],
+                    "www.mozilla.org",
+                ),
+            ),
+            (
+                "Safari",
+                make_client_hello(
+                    26,
+                    &[
+                        (0x2A2A, 0),
+                        (0x0000, 0),
+                        (0x0017, 0),
+                        (0xFF01, 1),
+                        (0x000A, 12),
+                        (0x000B, 2),
+                        (0x0010, 14),
+                        (0x0005, 5),
+                        (0x000D, 24),
+                        (0x0012, 0),
+                        (0x0033, 43),
+                        (0x002D, 2),
+                        (0x002B, 11),
+                        (0x001B, 3),
+                        (0x3A3A, 1),
+                        (0x0015, 170),
+                    ],
+                    "www.apple.com",
+                ),
+            ),
+            (
+                "curl",
+                make_client_hello(
+                    31,
+                    &[
+                        (0x0000, 0),
+                        (0x000B, 4),
+                        (0x000A, 22),
+                        (0x0010, 14),
+                        (0x0016, 0),
+                        (0x0017, 0),
+                        (0x0031, 0),
+                        (0x000D, 42),
+                        (0x002B, 9),
+                        (0x002D, 2),
+                        (0x0033, 38),
+                        (0x0015, 174),
+                    ],
+                    "www.example.com",
+                ),
+            ),
+        ]
+    }
+
+    // These are captured: what curl 7.88.1 with OpenSSL 3.0.19, and openssl s_client from OpenSSL 3.5.6, sent to
+    // a listener on localhost, record header and all. OpenSSL 3.5 offers a post-quantum key share, which makes its
+    // ClientHello about as big as Chrome's. There are no captures from browsers, because no browser was at hand to
+    // capture from; the modelled ClientHellos above stand in for them.
+    const CURL_CLIENT_HELLO: &[u8] = &[
+        0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xFC, 0x03, 0x03, 0xEB, 0xBA, 0x85, 0x10,
+        0xF8, 0x21, 0x5B, 0x55, 0x94, 0x37, 0xFF, 0x4D, 0x9C, 0xD0, 0xE5, 0xD9, 0x82, 0x8F, 0x95,
+        0xAA, 0x53, 0xCB, 0xD1, 0x63, 0x8D, 0x2C, 0x52, 0xCA, 0xF0, 0xD8, 0x5E, 0x09, 0x20, 0xEE,
+        0xD2, 0xF5, 0x7A, 0xB3, 0x13, 0xB8, 0x72, 0xEE, 0xC9, 0xEF, 0xDB, 0xD2, 0x32, 0xE5, 0xD0,
+        0x89, 0x77, 0x59, 0x5D, 0x94, 0x25, 0x0C, 0x7F, 0x92, 0x7C, 0x0C, 0xA6, 0xC3, 0x0B, 0x74,
+        0x1A, 0x00, 0x3E, 0x13, 0x02, 0x13, 0x03, 0x13, 0x01, 0xC0, 0x2C, 0xC0, 0x30, 0x00, 0x9F,
+        0xCC, 0xA9, 0xCC, 0xA8, 0xCC, 0xAA, 0xC0, 0x2B, 0xC0, 0x2F, 0x00, 0x9E, 0xC0, 0x24, 0xC0,
+        0x28, 0x00, 0x6B, 0xC0, 0x23, 0xC0, 0x27, 0x00, 0x67, 0xC0, 0x0A, 0xC0, 0x14, 0x00, 0x39,
+        0xC0, 0x09, 0xC0, 0x13, 0x00, 0x33, 0x00, 0x9D, 0x00, 0x9C, 0x00, 0x3D, 0x00, 0x3C, 0x00,
+        0x35, 0x00, 0x2F, 0x00, 0xFF, 0x01, 0x00, 0x01, 0x75, 0x00, 0x00, 0x00, 0x14, 0x00, 0x12,
+        0x00, 0x00, 0x0F, 0x77, 0x77, 0x77, 0x2E, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x2E,
+        0x63, 0x6F, 0x6D, 0x00, 0x0B, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02, 0x00, 0x0A, 0x00, 0x16,
+        0x00, 0x14, 0x00, 0x1D, 0x00, 0x17, 0x00, 0x1E, 0x00, 0x19, 0x00, 0x18, 0x01, 0x00, 0x01,
+        0x01, 0x01, 0x02, 0x01, 0x03, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0E, 0x00, 0x0C, 0x02, 0x68,
+        0x32, 0x08, 0x68, 0x74, 0x74, 0x70, 0x2F, 0x31, 0x2E, 0x31, 0x00, 0x16, 0x00, 0x00, 0x00,
+        0x17, 0x00, 0x00, 0x00, 0x31, 0x00, 0x00, 0x00, 0x0D, 0x00, 0x2A, 0x00, 0x28, 0x04, 0x03,
+        0x05, 0x03, 0x06, 0x03, 0x08, 0x07, 0x08, 0x08, 0x08, 0x09, 0x08, 0x0A, 0x08, 0x0B, 0x08,
+        0x04, 0x08, 0x05, 0x08, 0x06, 0x04, 0x01, 0x05, 0x01, 0x06, 0x01, 0x03, 0x03, 0x03, 0x01,
+        0x03, 0x02, 0x04, 0x02, 0x05, 0x02, 0x06, 0x02, 0x00, 0x2B, 0x00, 0x09, 0x08, 0x03, 0x04,
+        0x03, 0x03, 0x03, 0x02, 0x03, 0x01, 0x00, 0x2D, 0x00, 0x02, 0x01, 0x01, 0x00, 0x33, 0x00,
+        0x26, 0x00, 0x24, 0x00, 0x1D, 0x00, 0x20, 0x57, 0xB8, 0xA2, 0x7B, 0x1C, 0x8B, 0xD4, 0xB7,
+        0xE3, 0x55, 0x6F, 0xF6, 0xE1, 0x84, 0x98, 0xA9, 0x1B, 0x69, 0xC9, 0x5C, 0xFB, 0xE6, 0x51,
+        0x61, 0xA8, 0x9A, 0xF3, 0x72, 0x0D, 0xE3, 0x4B, 0x6E, 0x00, 0x15, 0x00, 0xAE, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    const OPENSSL_S_CLIENT_CLIENT_HELLO: &[u8] = &[
+        0x16, 0x03, 0x01, 0x06, 0x07, 0x01, 0x00, 0x06, 0x03, 0x03, 0x03, 0x9A, 0xD3, 0xCE, 0xB2,
+        0x73, 0xF0, 0xC2, 0x7D, 0x60, 0x94, 0x7C, 0x53, 0xCB, 0x02, 0x12, 0x71, 0x7C, 0x0A, 0xCF,
+        0x8B, 0x39, 0x90, 0xB1, 0xD9, 0x26, 0x6A, 0x42, 0xA7, 0x81, 0x7D, 0x31, 0x1B, 0x20, 0x71,
+        0xDE, 0x17, 0x47, 0x0F, 0x48, 0x3F, 0x9B, 0x51, 0x4E, 0xFA, 0xFC, 0x93, 0x0C, 0x58, 0x5F,
+        0x2A, 0x8E, 0xCF, 0x4D, 0xB8, 0x28, 0xF0, 0x37, 0x34, 0xDA, 0xBD, 0xF2, 0xED, 0xFE, 0x36,
+        0x12, 0x00, 0x3C, 0x13, 0x02, 0x13, 0x03, 0x13, 0x01, 0xC0, 0x2C, 0xC0, 0x30, 0x00, 0x9F,
+        0xCC, 0xA9, 0xCC, 0xA8, 0xCC, 0xAA, 0xC0, 0x2B, 0xC0, 0x2F, 0x00, 0x9E, 0xC0, 0x24, 0xC0,
+        0x28, 0x00, 0x6B, 0xC0, 0x23, 0xC0, 0x27, 0x00, 0x67, 0xC0, 0x0A, 0xC0, 0x14, 0x00, 0x39,
+        0xC0, 0x09, 0xC0, 0x13, 0x00, 0x33, 0x00, 0x9D, 0x00, 0x9C, 0x00, 0x3D, 0x00, 0x3C, 0x00,
+        0x35, 0x00, 0x2F, 0x01, 0x00, 0x05, 0x7E, 0xFF, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x14, 0x00, 0x12, 0x00, 0x00, 0x0F, 0x77, 0x77, 0x77, 0x2E, 0x6F, 0x70, 0x65, 0x6E, 0x73,
+        0x73, 0x6C, 0x2E, 0x6F, 0x72, 0x67, 0x00, 0x0B, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02, 0x00,
+        0x0A, 0x00, 0x12, 0x00, 0x10, 0x11, 0xEC, 0x00, 0x1D, 0x00, 0x17, 0x00, 0x1E, 0x00, 0x18,
+        0x00, 0x19, 0x01, 0x00, 0x01, 0x01, 0x00, 0x23, 0x00, 0x00, 0x00, 0x16, 0x00, 0x00, 0x00,
+        0x17, 0x00, 0x00, 0x00, 0x0D, 0x00, 0x36, 0x00, 0x34, 0x09, 0x05, 0x09, 0x06, 0x09, 0x04,
+        0x04, 0x03, 0x05, 0x03, 0x06, 0x03, 0x08, 0x07, 0x08, 0x08, 0x08, 0x1A, 0x08, 0x1B, 0x08,
+        0x1C, 0x08, 0x09, 0x08, 0x0A, 0x08, 0x0B, 0x08, 0x04, 0x08, 0x05, 0x08, 0x06, 0x04, 0x01,
+        0x05, 0x01, 0x06, 0x01, 0x03, 0x03, 0x03, 0x01, 0x03, 0x02, 0x04, 0x02, 0x05, 0x02, 0x06,
+        0x02, 0x00, 0x2B, 0x00, 0x05, 0x04, 0x03, 0x04, 0x03, 0x03, 0x00, 0x2D, 0x00, 0x02, 0x01,
+        0x01, 0x00, 0x33, 0x04, 0xEA, 0x04, 0xE8, 0x11, 0xEC, 0x04, 0xC0, 0x9D, 0x76, 0x0F, 0x45,
+        0x02, 0x7D, 0xA8, 0x35, 0xCA, 0xFA, 0x46, 0xCC, 0xE5, 0xC7, 0x90, 0x25, 0x84, 0x3A, 0x2B,
+        0x93, 0xC3, 0xC3, 0x57, 0x63, 0x05, 0xD6, 0x9A, 0xE0, 0x5B, 0xCB, 0xAD, 0x64, 0x34, 0xF3,
+        0xDA, 0x5A, 0x29, 0x9A, 0x4D, 0x37, 0x21, 0x18, 0xC7, 0x8B, 0x28, 0x32, 0x28, 0xCA, 0x3F,
+        0x10, 0x10, 0x43, 0x61, 0x14, 0x55, 0x77, 0xAA, 0x03, 0x3C, 0x87, 0xE0, 0x32, 0x19, 0x3E,
+        0x67, 0x00, 0xFF, 0xBC, 0x1D, 0xAB, 0x0C, 0x8B, 0x83, 0xD8, 0x10, 0xD4, 0xA6, 0x58, 0x9C,
+        0x7B, 0x6B, 0x0F, 0x23, 0xC6, 0xA7, 0xC8, 0x5E, 0x47, 0xC6, 0xB7, 0xD1, 0xB1, 0xB7, 0xC3,
+        0x92, 0x9A, 0xB3, 0x37, 0x27, 0x9F, 0xE4, 0xB5, 0x28, 0x43, 0x15, 0xB5, 0xC2, 0x8A, 0x46,
+        0x63, 0x89, 0x0A, 0xFC, 0x2C, 0x61, 0xFB, 0xC0, 0x5F, 0xCC, 0xB7, 0x18, 0x44, 0x87, 0x81,
+        0xB1, 0x50, 0xF9, 0xD3, 0x2C, 0x0C, 0xCC, 0xBB, 0xA6, 0xA1, 0x84, 0x76, 0x56, 0x4B, 0x60,
+        0x79, 0x5B, 0xD6, 0x67, 0x73, 0x8A, 0xE8, 0xBC, 0xBA, 0xF1, 0x8C, 0xE8, 0x46, 0x91, 0xDB,
+        0x96, 0xB8, 0xA2, 0x92, 0x42, 0x3D, 0xF8, 0x9D, 0x92, 0xE9, 0x34, 0xF2, 0x23, 0x24, 0x10,
+        0x75, 0x44, 0x69, 0x75, 0xBF, 0x65, 0x2B, 0x7B, 0xD4, 0x60, 0xCE, 0x9D, 0x63, 0x63, 0xF8,
+        0x6A, 0x3C, 0xED, 0xE2, 0x32, 0x0B, 0x47, 0x97, 0xA9, 0x1C, 0x3E, 0x15, 0x7B, 0x78, 0xD6,
+        0xAA, 0x26, 0x95, 0xE5, 0x8F, 0x20, 0xEA, 0x34, 0x8F, 0xE8, 0x25, 0x8F, 0xFB, 0x94, 0xED,
+        0x30, 0xB9, 0xEF, 0x68, 0x01, 0x11, 0xC2, 0x59, 0x49, 0x74, 0x63, 0x3F, 0x46, 0xAE, 0x43,
+        0x0B, 0x8A, 0x06, 0x67, 0x47, 0x2A, 0xE2, 0xA3, 0x38, 0x73, 0x5E, 0x0F, 0x5A, 0x3B, 0xEE,
+        0xB0, 0x98, 0x2B, 0xD5, 0xCC, 0x3B, 0x55, 0x01, 0xA0, 0x39, 0xB1, 0xB0, 0x94, 0x8B, 0x7A,
+        0xD3, 0x67, 0x66, 0xA4, 0x82, 0x93, 0xA0, 0x85, 0xD0, 0x05, 0xA1, 0x59, 0x13, 0x65, 0x08,
+        0x37, 0x76, 0x3A, 0xE6, 0xB9, 0x54, 0x15, 0x3F, 0x42, 0x11, 0x2B, 0x76, 0xC1, 0x56, 0x8A,
+        0xC7, 0xA0, 0x62, 0xB1, 0x64, 0x80, 0x16, 0x3C, 0x32, 0xD1, 0x84, 0xA6, 0x2A, 0x8A, 0x2E,
+        0xC0, 0x57, 0x0E, 0x95, 0x66, 0x7D, 0x46, 0x00, 0x86, 0x7C, 0xC9, 0x10, 0xF9, 0x26, 0x8B,
+        0x67, 0x72, 0xE5, 0x88, 0x1D, 0x30, 0xDA, 0x2D, 0x11, 0x00, 0x1B, 0x1F, 0xD7, 0x9D, 0x1B,
+        0xA7, 0x0F, 0x51, 0x37, 0x6F, 0x59, 0x10, 0x71, 0xF0, 0x99, 0x51, 0x05, 0x33, 0x9D, 0x5A,
+        0x64, 0x65, 0x0F, 0x80, 0x9D, 0x30, 0xA5, 0x3D, 0x87, 0xF4, 0x50, 0x91, 0x10, 0x7A, 0x77,
+        0xCB, 0x7F, 0xEF, 0xD1, 0x31, 0x7E, 0x34, 0xC6, 0xB0, 0x55, 0x10, 0x73, 0x98, 0xCF, 0xDD,
+        0x60, 0x2A, 0x17, 0xD4, 0xCD, 0xFC, 0xB5, 0xCD, 0x07, 0x66, 0x40, 0xEF, 0x26, 0x73, 0x53,
+        0x82, 0xC0, 0xE2, 0x34, 0x67, 0xFB, 0x8A, 0x8A, 0xEA, 0xA0, 0x56, 0x8A, 0x1C, 0x5E, 0x66,
+        0xF1, 0x82, 0x42, 0x99, 0x06, 0x62, 0x2A, 0x29, 0x4D, 0xB0, 0x54, 0x8E, 0xE3, 0x2E, 0x61,
+        0x78, 0x01, 0x71, 0xEB, 0x82, 0x59, 0x51, 0x88, 0x07, 0xB4, 0xBB, 0xCC, 0x55, 0x97, 0x9C,
+        0x45, 0x72, 0x57, 0x92, 0x47, 0xA6, 0x74, 0x86, 0xD1, 0xD0, 0x7A, 0x9B, 0x07, 0x16, 0x98,
+        0x3C, 0x06, 0xAD, 0x45, 0x09, 0x87, 0xA0, 0x7C, 0xB8, 0x43, 0x7E, 0xD3, 0x78, 0x7A, 0x25,
+        0x99, 0x1E, 0x2B, 0x9C, 0x7F, 0x4A, 0x49, 0x3E, 0x55, 0x1C, 0xA0, 0x25, 0xDB, 0x75, 0x60,
+        0x61, 0xA2, 0x3E, 0xD8, 0x2F, 0x97, 0x70, 0x81, 0x74, 0x00, 0x78, 0x06, 0x37, 0x1F, 0x0C,
+        0x36, 0x39, 0x58, 0x61, 0x15, 0xAE, 0xA9, 0xA3, 0x6A, 0x33, 0xBF, 0xD6, 0x0B, 0xC9, 0xA3,
+        0x26, 0x87, 0x58, 0x78, 0xAC, 0xBB, 0xE4, 0x38, 0xE7, 0x27, 0xAC, 0x99, 0x0B, 0xB3, 0x9F,
+        0xE8, 0x10, 0x09, 0x8A, 0xA8, 0x31, 0x53, 0x03, 0x68, 0x49, 0x26, 0x4B, 0x20, 0x35, 0x55,
+        0xD0, 0x92, 0x7D, 0x64, 0x21, 0xA2, 0xD1, 0x1E, 0x26, 0xE3, 0x0A, 0x35, 0xD7, 0x9E, 0xB1,
+        0xB0, 0x31, 0x61, 0xA4, 0xCD, 0xBE, 0xA0, 0x2B, 0xFA, 0xF7, 0x3F, 0x5F, 0xEB, 0x18, 0x7C,
+        0xC4, 0x7F, 0x28, 0xFC, 0x94, 0x7F, 0x70, 0x80, 0xB8, 0xB3, 0xA8, 0xE0, 0xDA, 0x0E, 0xD4,
+        0x38, 0xBE, 0x98, 0x82, 0xC6, 0x78, 0xD0, 0x4D, 0x29, 0xF2, 0x05, 0xAE, 0x21, 0x9B, 0xE7,
+        0xEA, 0x80, 0x9E, 0xDB, 0x28, 0xDB, 0x75, 0x42, 0x04, 0xAB, 0x4C, 0xCD, 0xF8, 0x60, 0xC3,
+        0xAC, 0x30, 0x2E, 0x1A, 0xBE, 0x55, 0xD8, 0x5A, 0x64, 0xA6, 0x90, 0x9B, 0xD6, 0x65, 0xD6,
+        0x34, 0x38, 0x7D, 0xC7, 0x79, 0xF5, 0xB2, 0x41, 0xB4, 0xC4, 0x4A, 0x65, 0xC9, 0xA0, 0x1D,
+        0xE5, 0xB0, 0xF5, 0x60, 0x58, 0x8B, 0x26, 0x09, 0x41, 0xB7, 0x27, 0x41, 0x29, 0xAF, 0x93,
+        0xE7, 0x5C, 0x30, 0xB4, 0xAA, 0x44, 0x22, 0x28, 0xA7, 0x09, 0x42, 0x38, 0xE2, 0x48, 0xF9,
+        0x16, 0xCA, 0xCF, 0xAB, 0x7D, 0x2E, 0x30, 0xAE, 0xD9, 0x33, 0x21, 0xBB, 0xF9, 0xBA, 0x8A,
+        0xFB, 0x23, 0x27, 0x12, 0x38, 0xF4, 0x4C, 0x3F, 0xD1, 0x70, 0x16, 0xBA, 0xE4, 0x89, 0xDA,
+        0x8A, 0x52, 0xBD, 0x77, 0x91, 0x0E, 0x49, 0x30, 0xBE, 0xB5, 0x0A, 0x45, 0x9A, 0x62, 0xB2,
+        0x86, 0x46, 0x80, 0xE8, 0x8B, 0x19, 0x0B, 0x6E, 0x38, 0x8B, 0x7D, 0x06, 0xE7, 0x03, 0x9F,
+        0xD4, 0xB9, 0x80, 0xF4, 0xA7, 0x27, 0xC7, 0x72, 0x56, 0x43, 0x8A, 0x01, 0x96, 0xAF, 0x04,
+        0xA9, 0xAB, 0xB4, 0xD6, 0x79, 0x09, 0x97, 0x35, 0x00, 0x75, 0x52, 0x45, 0x26, 0x5F, 0xC5,
+        0x6A, 0x92, 0xFB, 0x63, 0x5F, 0x34, 0x0C, 0x5F, 0x62, 0x0B, 0xB1, 0x47, 0x1C, 0x2E, 0x46,
+        0x93, 0x38, 0x55, 0xF2, 0x22, 0xF6, 0x34, 0x0D, 0xE3, 0xDB, 0xBD, 0x99, 0xC8, 0x9D, 0x6D,
+        0xEB, 0x8A, 0x76, 0x56, 0x9C, 0x4C, 0x94, 0x09, 0x22, 0xD2, 0xAE, 0x03, 0xCC, 0x37, 0x7E,
+        0x1A, 0xB4, 0xC3, 0xD8, 0x59, 0x22, 0x93, 0xC8, 0x59, 0xB0, 0x91, 0xC3, 0xD7, 0xCF, 0x96,
+        0x6A, 0xAF, 0x52, 0xDB, 0x1E, 0xB1, 0xCA, 0x6C, 0x7E, 0x16, 0x0A, 0x53, 0x9A, 0x02, 0x31,
+        0xE4, 0xB2, 0x8A, 0xE0, 0x84, 0x56, 0xFA, 0x4B, 0x79, 0x8C, 0x0C, 0x8D, 0x60, 0x40, 0x45,
+        0x3A, 0x3B, 0x3E, 0xB8, 0x94, 0x57, 0x0A, 0xA0, 0xA3, 0x24, 0xBE, 0x91, 0x91, 0xA6, 0x94,
+        0xD0, 0x06, 0x97, 0xA6, 0x1E, 0x52, 0x75, 0x96, 0x1A, 0x1C, 0x7D, 0xDF, 0xF4, 0x27, 0x11,
+        0xCC, 0xB8, 0xA5, 0xFC, 0x4D, 0xF4, 0x45, 0x50, 0xDA, 0xA2, 0xA8, 0x9E, 0x55, 0x02, 0x72,
+        0xB3, 0xBA, 0x8F, 0xA4, 0x94, 0x5D, 0x89, 0xB9, 0x04, 0x9B, 0x81, 0xDC, 0xF6, 0xB5, 0xE8,
+        0xF6, 0xAC, 0x76, 0xD6, 0x12, 0x3F, 0x01, 0xCE, 0x7F, 0x36, 0xBE, 0x57, 0xA5, 0x4C, 0x5A,
+        0xA8, 0x7B, 0x56, 0x24, 0x0C, 0x30, 0xF0, 0x99, 0xD4, 0x32, 0xA6, 0x38, 0x64, 0x8C, 0xC6,
+        0x82, 0x76, 0x18, 0x42, 0x36, 0x5E, 0x47, 0xAD, 0x86, 0xB1, 0xC6, 0xAD, 0xCA, 0x3D, 0xC3,
+        0x44, 0xC2, 0x7A, 0x72, 0x52, 0x6B, 0xF4, 0x80, 0xDF, 0x75, 0x8D, 0x63, 0xEC, 0x52, 0x22,
+        0xB6, 0x55, 0x39, 0x74, 0x12, 0xA8, 0x87, 0xB1, 0xCD, 0x07, 0x63, 0xF6, 0xE4, 0xB8, 0x41,
+        0x00, 0xCB, 0x58, 0x2A, 0xA4, 0x5F, 0x50, 0x95, 0xE3, 0x61, 0x14, 0xCB, 0xF1, 0x38, 0xD4,
+        0xF2, 0x32, 0x4B, 0x47, 0x04, 0xE9, 0xB9, 0xAD, 0x3D, 0x63, 0x17, 0xE2, 0xA0, 0xCF, 0xCC,
+        0x54, 0x41, 0x90, 0xBB, 0xCA, 0x56, 0xE6, 0x43, 0xEC, 0x0B, 0x90, 0xF1, 0xD6, 0xB7, 0xD9,
+        0xFA, 0x51, 0xA2, 0xDA, 0x91, 0xCA, 0x93, 0x0C, 0xD5, 0x92, 0x76, 0x22, 0xF3, 0x3F, 0x22,
+        0x06, 0x85, 0x40, 0x24, 0x4F, 0x12, 0xE5, 0x2F, 0xCC, 0x3C, 0xCE, 0xAD, 0x20, 0x5D, 0x97,
+        0xD5, 0x78, 0x4A, 0xB3, 0x0B, 0x82, 0x15, 0x74, 0xC8, 0xE6, 0x31, 0x3C, 0x78, 0x43, 0x52,
+        0x44, 0x67, 0xB1, 0xFA, 0x7C, 0x0C, 0x31, 0xB8, 0xF7, 0x99, 0x9C, 0x4B, 0xB4, 0xA8, 0xC4,
+        0x88, 0x02, 0x97, 0x5A, 0x12, 0xBB, 0x36, 0x00, 0xC3, 0x9B, 0x22, 0xB4, 0x35, 0x80, 0x20,
+        0xF8, 0x65, 0xD9, 0xCA, 0x36, 0x5D, 0x19, 0xC9, 0x83, 0x82, 0xA9, 0x4D, 0xC0, 0xC9, 0xA2,
+        0xDA, 0x34, 0x9E, 0x68, 0x70, 0x7C, 0xEA, 0x89, 0xDC, 0x10, 0x56, 0xED, 0x09, 0x4D, 0xCC,
+        0xAC, 0x15, 0xD1, 0xF0, 0x88, 0xF3, 0x2B, 0x75, 0x33, 0x8E, 0x53, 0x9E, 0x0D, 0xA8, 0x60,
+        0x9F, 0x93, 0x8E, 0x2C, 0x2F, 0xF2, 0xF2, 0xBD, 0x24, 0xA6, 0x5C, 0x68, 0xC7, 0xE2, 0xE2,
+        0xE2, 0xED, 0x18, 0xBC, 0x2C, 0x6A, 0x67, 0x50, 0x6C, 0xBC, 0x05, 0x2D, 0x7D, 0x8E, 0xE1,
+        0xC8, 0x3C, 0xBD, 0x7D, 0xF2, 0x70, 0x58, 0x06, 0x74, 0x55, 0xE5, 0x5C, 0xE0, 0x0A, 0xFF,
+        0x7B, 0x28, 0x9C, 0x70, 0x22, 0xF2, 0xC8, 0x4D, 0xF5, 0xAD, 0x9A, 0x46, 0x00, 0x1D, 0x00,
+        0x20, 0x3E, 0x87, 0x28, 0xCA, 0xF2, 0xB0, 0x60, 0x79, 0x08, 0x1E, 0x4A, 0x53, 0x8E, 0x37,
+        0x8F, 0x47, 0x11, 0x7A, 0x05, 0xAC, 0xB1, 0x84, 0xF2, 0x97, 0x93, 0x32, 0xEE, 0x8F, 0x23,
+        0x80, 0x8F, 0x76,
+    ];
+
+    // Without their record headers, which make_records supplies
+    fn captured_client_hellos() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("captured curl", CURL_CLIENT_HELLO[5..].to_vec()),
+            (
+                "captured openssl s_client",
+                OPENSSL_S_CLIENT_CLIENT_HELLO[5..].to_vec(),
+            ),
+        ]
+    }
+
+    fn client_hellos() -> Vec<(&'static str, Vec<u8>)> {
+        modelled_client_hellos()
+            .into_iter()
+            .chain(captured_client_hellos().into_iter())
+            .collect()
+    }
+
+    fn expected_host_name(client: &str) -> Option<String> {
+        Some(String::from(match client {
+            "Chrome" => "www.google.com",
+            "Firefox" => "www.mozilla.org",
+            "Safari" => "www.apple.com",
+            "captured openssl s_client" => "www.openssl.org",
+            _ => "www.example.com",
+        }))
+    }
+
+    #[test]
+    fn extracts_hostname_from_captured_client_hellos_as_they_were_sent() {
+        vec![
+            ("captured curl", CURL_CLIENT_HELLO),
+            ("captured openssl s_client", OPENSSL_S_CLIENT_CLIENT_HELLO),
+        ]
+        .into_iter()
+        .for_each(|(client, data)| {
+            let result = TlsProtocolPack {}.find_host_name(&PlainData::new(data));
+
+            assert_eq!(result, expected_host_name(client), "{}", client);
+        });
+    }
+
+    #[test]
+    fn extracts_hostname_from_client_hellos_however_they_are_split_into_records() {
+        client_hellos()
+            .into_iter()
+            .for_each(|(client, client_hello)| {
+                (1..=client_hello.len()).for_each(|fragment_length| {
+                    let data = make_records(&client_hello, fragment_length);
+
+                    let result = TlsProtocolPack {}.find_host_name(&PlainData::new(&data[..]));
+
+                    assert_eq!(
+                        result,
+                        expected_host_name(client),
+                        "{} in {}-byte fragments",
+                        client,
+                        fragment_length
+                    );
+                });
+            });
+    }
+
+    #[test]
+    fn finds_nothing_in_client_hellos_that_are_cut_short() {
+        client_hellos()
+            .into_iter()
+            .for_each(|(client, client_hello)| {
+                vec![client_hello.len(), 100, 7]
+                    .into_iter()
+                    .for_each(|fragment_length| {
+                        let data = make_records(&client_hello, fragment_length);
+                        (1..data.len()).for_each(|length| {
+                            let result = TlsProtocolPack {}
+                                .find_host_name(&PlainData::new(&data[0..length]));
+                            let needs_more = TlsProtocolPack::needs_more_records(&data[0..length]);
+
+                            assert_eq!(result, None, "{} cut to {} bytes", client, length);
+                            assert!(needs_more, "{} cut to {} bytes", client, length);
+                        });
+                    });
+            });
+    }
+
+    #[test]
+    fn does_not_panic_for_client_hellos_with_any_byte_corrupted() {
+        client_hellos().into_iter().for_each(|(_, client_hello)| {
+            vec![client_hello.len(), 100]
+                .into_iter()
+                .for_each(|fragment_length| {
+                    let data = make_records(&client_hello, fragment_length);
+                    (0..data.len()).for_each(|index| {
+                        vec![0x00, 0x7F, 0x80, 0xFF].into_iter().for_each(|byte| {
+                            let mut corrupted = data.clone();
+                            corrupted[index] = byte;
+
+                            TlsProtocolPack {}.find_host_name(&PlainData::new(&corrupted[..]));
+                            TlsProtocolPack::needs_more_records(&corrupted[..]);
+                        });
+                    });
+                });
+        });
+
+        // no panic; test passes
+    }
 }
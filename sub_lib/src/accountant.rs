@@ -1,14 +1,73 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use actix::Message;
 use actix::Recipient;
 use actix::Syn;
 use peer_actors::BindMessage;
+use std::path::PathBuf;
+use wallet::Wallet;
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct AccountantConfig {
-    pub replace_me: String,
+    pub wallet: Option<Wallet>,
+    pub data_directory_opt: Option<PathBuf>,
 }
 
 #[derive(Clone)]
 pub struct AccountantSubs {
     pub bind: Recipient<Syn, BindMessage>,
+    pub report_routing_service: Recipient<Syn, ReportRoutingServiceMessage>,
+    pub report_exit_service: Recipient<Syn, ReportExitServiceMessage>,
+    pub report_routing_service_consumed: Recipient<Syn, ReportRoutingServiceConsumedMessage>,
+    pub report_exit_service_consumed: Recipient<Syn, ReportExitServiceConsumedMessage>,
+    pub ledger_query: Recipient<Syn, LedgerQueryMessage>,
+}
+
+/// This Node relayed payload_size bytes for the Node that owns consuming_wallet
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct ReportRoutingServiceMessage {
+    pub consuming_wallet: Wallet,
+    pub payload_size: usize,
+}
+
+/// This Node carried payload_size bytes between the Node that owns consuming_wallet and the Internet
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct ReportExitServiceMessage {
+    pub consuming_wallet: Wallet,
+    pub payload_size: usize,
+}
+
+/// The Node that owns earning_wallet relayed payload_size bytes for this Node
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct ReportRoutingServiceConsumedMessage {
+    pub earning_wallet: Wallet,
+    pub payload_size: usize,
+}
+
+/// The Node that owns earning_wallet carried payload_size bytes between this Node and the Internet
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct ReportExitServiceConsumedMessage {
+    pub earning_wallet: Wallet,
+    pub payload_size: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ServiceTotals {
+    pub bytes_routed: u64,
+    pub bytes_exited: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct LedgerTotals {
+    pub receivable: ServiceTotals,
+    pub payable: ServiceTotals,
+}
+
+/// Asks for the totals owed to and by one wallet, or by every wallet together if wallet_opt is None
+#[derive(Clone, PartialEq, Debug)]
+pub struct LedgerQueryMessage {
+    pub wallet_opt: Option<Wallet>,
+}
+
+impl Message for LedgerQueryMessage {
+    type Result = LedgerTotals;
 }
@@ -179,10 +179,16 @@ pub enum CryptdecError {
     EmptyKey,
     EmptyData,
     InvalidKey(String),
+    OpeningFailed,
 }
 
 pub trait CryptDE: Send + Sync {
     fn generate_key_pair(&mut self);
+    fn restore_key_pair(
+        &mut self,
+        private_key: &Key,
+        public_key: &Key,
+    ) -> Result<(), CryptdecError>;
     fn encode(&self, public_key: &Key, data: &PlainData) -> Result<CryptData, CryptdecError>;
     fn decode(&self, data: &CryptData) -> Result<PlainData, CryptdecError>;
     fn random(&self, dest: &mut [u8]);
@@ -21,6 +21,21 @@ impl CryptDE for CryptDENull {
         self.public_key = CryptDENull::other_key(&self.private_key())
     }
 
+    fn restore_key_pair(
+        &mut self,
+        private_key: &Key,
+        public_key: &Key,
+    ) -> Result<(), CryptdecError> {
+        if CryptDENull::other_key(public_key) != *private_key {
+            Err(CryptdecError::InvalidKey(String::from(
+                "Private key does not match public key",
+            )))
+        } else {
+            self.set_key_pair(public_key);
+            Ok(())
+        }
+    }
+
     fn encode(&self, public_key: &Key, data: &PlainData) -> Result<CryptData, CryptdecError> {
         if public_key.data.is_empty() {
             Err(CryptdecError::EmptyKey)
@@ -270,6 +285,34 @@ mod tests {
         assert_eq!(decrypted_data, expected_data);
     }
 
+    #[test]
+    fn restore_key_pair_accepts_matching_keys() {
+        let mut original = CryptDENull::new();
+        original.generate_key_pair();
+        let mut subject = CryptDENull::new();
+
+        let result = subject.restore_key_pair(&original.private_key(), &original.public_key());
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(subject.private_key(), original.private_key());
+        assert_eq!(subject.public_key(), original.public_key());
+    }
+
+    #[test]
+    fn restore_key_pair_rejects_mismatched_keys() {
+        let mut subject = CryptDENull::new();
+
+        let result = subject.restore_key_pair(&Key::new(b"private"), &Key::new(b"public"));
+
+        assert_eq!(
+            result,
+            Err(CryptdecError::InvalidKey(String::from(
+                "Private key does not match public key"
+            )))
+        );
+        assert_eq!(subject.private_key(), Key::new(b"uninitialized"));
+    }
+
     #[test]
     fn other_key_works() {
         let one_key = Key::new(b"The quick brown fox jumps over the lazy dog");
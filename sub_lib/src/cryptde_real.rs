@@ -0,0 +1,409 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use cryptde::CryptDE;
+use cryptde::CryptData;
+use cryptde::CryptdecError;
+use cryptde::Key;
+use cryptde::PlainData;
+use sodiumoxide;
+use sodiumoxide::crypto::box_;
+use sodiumoxide::crypto::sealedbox;
+use sodiumoxide::crypto::sign;
+use sodiumoxide::randombytes::randombytes_into;
+
+// A public key is the X25519 encryption public key followed by the Ed25519 verification key;
+// a private key is the X25519 decryption secret key followed by the Ed25519 signing key.
+pub const PUBLIC_KEY_LEN: usize = box_::PUBLICKEYBYTES + sign::PUBLICKEYBYTES;
+pub const PRIVATE_KEY_LEN: usize = box_::SECRETKEYBYTES + sign::SECRETKEYBYTES;
+
+pub struct CryptDEReal {
+    private_key: Key,
+    public_key: Key,
+}
+
+impl CryptDE for CryptDEReal {
+    fn generate_key_pair(&mut self) {
+        let (encryption_public, encryption_secret) = box_::gen_keypair();
+        let (signing_public, signing_secret) = sign::gen_keypair();
+        self.private_key =
+            Key::new(&[&encryption_secret.0[..], &signing_secret.0[..]].concat()[..]);
+        self.public_key = Key::new(&[&encryption_public.0[..], &signing_public.0[..]].concat()[..]);
+    }
+
+    fn restore_key_pair(
+        &mut self,
+        private_key: &Key,
+        public_key: &Key,
+    ) -> Result<(), CryptdecError> {
+        let (encryption_secret, signing_secret) = CryptDEReal::split_private_key(private_key)?;
+        let (encryption_public, signing_public) = CryptDEReal::split_public_key(public_key)?;
+        if (encryption_secret.public_key() != encryption_public)
+            || (signing_secret.public_key() != signing_public)
+        {
+            return Err(CryptdecError::InvalidKey(String::from(
+                "Private key does not match public key",
+            )));
+        }
+        self.private_key = private_key.clone();
+        self.public_key = public_key.clone();
+        Ok(())
+    }
+
+    fn encode(&self, public_key: &Key, data: &PlainData) -> Result<CryptData, CryptdecError> {
+        if public_key.data.is_empty() {
+            Err(CryptdecError::EmptyKey)
+        } else if data.data.is_empty() {
+            Err(CryptdecError::EmptyData)
+        } else {
+            let (encryption_public, _) = CryptDEReal::split_public_key(public_key)?;
+            Ok(CryptData::new(&sealedbox::seal(
+                &data.data[..],
+                &encryption_public,
+            )))
+        }
+    }
+
+    fn decode(&self, data: &CryptData) -> Result<PlainData, CryptdecError> {
+        if self.private_key.data.is_empty() {
+            Err(CryptdecError::EmptyKey)
+        } else if data.data.is_empty() {
+            Err(CryptdecError::EmptyData)
+        } else {
+            let (encryption_public, _) = CryptDEReal::split_public_key(&self.public_key)?;
+            let (encryption_secret, _) = CryptDEReal::split_private_key(&self.private_key)?;
+            match sealedbox::open(&data.data[..], &encryption_public, &encryption_secret) {
+                Ok(plain) => Ok(PlainData::new(&plain[..])),
+                Err(()) => Err(CryptdecError::OpeningFailed),
+            }
+        }
+    }
+
+    fn random(&self, dest: &mut [u8]) {
+        randombytes_into(dest)
+    }
+
+    fn private_key(&self) -> Key {
+        self.private_key.clone()
+    }
+
+    fn public_key(&self) -> Key {
+        self.public_key.clone()
+    }
+
+    // This is dup instead of clone because it returns a Box<CryptDE> instead of a CryptDEReal.
+    fn dup(&self) -> Box<CryptDE> {
+        Box::new(CryptDEReal {
+            private_key: self.private_key.clone(),
+            public_key: self.public_key.clone(),
+        })
+    }
+
+    fn sign(&self, data: &PlainData) -> Result<CryptData, CryptdecError> {
+        let (_, signing_secret) = CryptDEReal::split_private_key(&self.private_key)?;
+        let signature = sign::sign_detached(&data.data[..], &signing_secret);
+        Ok(CryptData::new(signature.as_ref()))
+    }
+
+    fn verify_signature(&self, data: &PlainData, signature: &CryptData, public_key: &Key) -> bool {
+        let signing_public = match CryptDEReal::split_public_key(public_key) {
+            Ok((_, signing_public)) => signing_public,
+            Err(_) => return false,
+        };
+        match sign::Signature::from_bytes(&signature.data[..]) {
+            Ok(signature) => sign::verify_detached(&signature, &data.data[..], &signing_public),
+            Err(_) => false,
+        }
+    }
+}
+
+impl CryptDEReal {
+    pub fn new() -> CryptDEReal {
+        sodiumoxide::init().expect("Could not initialize libsodium");
+        let mut result = CryptDEReal {
+            private_key: Key::new(&[]),
+            public_key: Key::new(&[]),
+        };
+        result.generate_key_pair();
+        result
+    }
+
+    fn split_public_key(
+        public_key: &Key,
+    ) -> Result<(box_::PublicKey, sign::PublicKey), CryptdecError> {
+        if public_key.data.len() != PUBLIC_KEY_LEN {
+            return Err(CryptdecError::InvalidKey(format!(
+                "Public key must be {} bytes long, not {}",
+                PUBLIC_KEY_LEN,
+                public_key.data.len()
+            )));
+        }
+        let (encryption, signing) = public_key.data.split_at(box_::PUBLICKEYBYTES);
+        Ok((
+            box_::PublicKey::from_slice(encryption).expect("Internal error"),
+            sign::PublicKey::from_slice(signing).expect("Internal error"),
+        ))
+    }
+
+    fn split_private_key(
+        private_key: &Key,
+    ) -> Result<(box_::SecretKey, sign::SecretKey), CryptdecError> {
+        if private_key.data.len() != PRIVATE_KEY_LEN {
+            return Err(CryptdecError::InvalidKey(format!(
+                "Private key must be {} bytes long, not {}",
+                PRIVATE_KEY_LEN,
+                private_key.data.len()
+            )));
+        }
+        let (encryption, signing) = private_key.data.split_at(box_::SECRETKEYBYTES);
+        Ok((
+            box_::SecretKey::from_slice(encryption).expect("Internal error"),
+            sign::SecretKey::from_slice(signing).expect("Internal error"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cryptde_null::CryptDENull;
+
+    #[test]
+    fn new_generates_keys_of_the_right_lengths() {
+        let subject = CryptDEReal::new();
+
+        assert_eq!(subject.public_key().data.len(), PUBLIC_KEY_LEN);
+        assert_eq!(subject.private_key().data.len(), PRIVATE_KEY_LEN);
+    }
+
+    #[test]
+    fn encode_with_empty_key() {
+        let subject = CryptDEReal::new();
+
+        let result = subject.encode(&Key::new(b""), &PlainData::new(b"data"));
+
+        assert_eq!(result.err().unwrap(), CryptdecError::EmptyKey);
+    }
+
+    #[test]
+    fn encode_with_empty_data() {
+        let subject = CryptDEReal::new();
+
+        let result = subject.encode(&subject.public_key(), &PlainData::new(b""));
+
+        assert_eq!(result.err().unwrap(), CryptdecError::EmptyData);
+    }
+
+    #[test]
+    fn encode_with_wrong_length_key() {
+        let subject = CryptDEReal::new();
+
+        let result = subject.encode(&Key::new(b"key"), &PlainData::new(b"data"));
+
+        assert_eq!(
+            result.err().unwrap(),
+            CryptdecError::InvalidKey(String::from("Public key must be 64 bytes long, not 3"))
+        );
+    }
+
+    #[test]
+    fn encoded_data_does_not_contain_plaintext() {
+        let subject = CryptDEReal::new();
+        let plain_data = PlainData::new(b"These are the times that try men's souls");
+
+        let result = subject.encode(&subject.public_key(), &plain_data).unwrap();
+
+        assert_eq!(
+            result
+                .data
+                .windows(plain_data.data.len())
+                .any(|window| window == &plain_data.data[..]),
+            false
+        );
+    }
+
+    #[test]
+    fn decode_with_empty_data() {
+        let subject = CryptDEReal::new();
+
+        let result = subject.decode(&CryptData::new(b""));
+
+        assert_eq!(result.err().unwrap(), CryptdecError::EmptyData);
+    }
+
+    #[test]
+    fn generated_keys_work_with_each_other() {
+        let subject = CryptDEReal::new();
+
+        let expected_data = PlainData::new(&b"These are the times that try men's souls"[..]);
+        let encrypted_data = subject
+            .encode(&subject.public_key(), &expected_data)
+            .unwrap();
+        let decrypted_data = subject.decode(&encrypted_data).unwrap();
+        assert_eq!(decrypted_data, expected_data);
+    }
+
+    #[test]
+    fn data_encoded_for_another_key_cannot_be_decoded() {
+        let subject = CryptDEReal::new();
+        let other = CryptDEReal::new();
+
+        let encrypted_data = subject
+            .encode(&other.public_key(), &PlainData::new(b"For your eyes only"))
+            .unwrap();
+        let result = subject.decode(&encrypted_data);
+
+        assert_eq!(result.err().unwrap(), CryptdecError::OpeningFailed);
+        assert_eq!(
+            other.decode(&encrypted_data).unwrap(),
+            PlainData::new(b"For your eyes only")
+        );
+    }
+
+    #[test]
+    fn tampered_data_cannot_be_decoded() {
+        let subject = CryptDEReal::new();
+        let mut encrypted_data = subject
+            .encode(&subject.public_key(), &PlainData::new(b"Booga"))
+            .unwrap();
+        let last = encrypted_data.data.len() - 1;
+        encrypted_data.data[last] ^= 0x01;
+
+        let result = subject.decode(&encrypted_data);
+
+        assert_eq!(result.err().unwrap(), CryptdecError::OpeningFailed);
+    }
+
+    #[test]
+    fn generation_produces_different_keys_each_time() {
+        let mut subject = CryptDEReal::new();
+        let first_public = subject.public_key();
+        let first_private = subject.private_key();
+
+        subject.generate_key_pair();
+
+        assert_ne!(subject.public_key(), first_public);
+        assert_ne!(subject.private_key(), first_private);
+    }
+
+    #[test]
+    fn restore_key_pair_accepts_matching_keys() {
+        let original = CryptDEReal::new();
+        let mut subject = CryptDEReal::new();
+
+        let result = subject.restore_key_pair(&original.private_key(), &original.public_key());
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(subject.private_key(), original.private_key());
+        assert_eq!(subject.public_key(), original.public_key());
+        let encrypted_data = original
+            .encode(&original.public_key(), &PlainData::new(b"Booga"))
+            .unwrap();
+        assert_eq!(
+            subject.decode(&encrypted_data).unwrap(),
+            PlainData::new(b"Booga")
+        );
+    }
+
+    #[test]
+    fn restore_key_pair_rejects_mismatched_keys() {
+        let original = CryptDEReal::new();
+        let other = CryptDEReal::new();
+        let mut subject = CryptDEReal::new();
+        let before = subject.private_key();
+
+        let result = subject.restore_key_pair(&original.private_key(), &other.public_key());
+
+        assert_eq!(
+            result,
+            Err(CryptdecError::InvalidKey(String::from(
+                "Private key does not match public key"
+            )))
+        );
+        assert_eq!(subject.private_key(), before);
+    }
+
+    #[test]
+    fn restore_key_pair_rejects_keys_of_the_wrong_length() {
+        let mut subject = CryptDEReal::new();
+
+        let result = subject.restore_key_pair(&Key::new(b"private"), &subject.public_key());
+
+        assert_eq!(
+            result,
+            Err(CryptdecError::InvalidKey(String::from(
+                "Private key must be 96 bytes long, not 7"
+            )))
+        );
+    }
+
+    #[test]
+    fn random_is_not_predictable() {
+        let subject = CryptDEReal::new();
+        let mut first: [u8; 32] = [0; 32];
+        let mut second: [u8; 32] = [0; 32];
+
+        subject.random(&mut first[..]);
+        subject.random(&mut second[..]);
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn dup_works() {
+        let subject = CryptDEReal::new();
+
+        let result = subject.dup();
+
+        assert_eq!(result.public_key(), subject.public_key());
+        assert_eq!(result.private_key(), subject.private_key());
+    }
+
+    #[test]
+    fn verifying_a_good_signature_works() {
+        let data = PlainData::new(b"Fourscore and seven years ago");
+        let subject = CryptDEReal::new();
+
+        let signature = subject.sign(&data).unwrap();
+        let result = subject.verify_signature(&data, &signature, &subject.public_key());
+
+        assert_eq!(result, true);
+    }
+
+    #[test]
+    fn verifying_a_signature_over_different_data_fails() {
+        let subject = CryptDEReal::new();
+
+        let signature = subject
+            .sign(&PlainData::new(b"Fourscore and seven years ago"))
+            .unwrap();
+        let result = subject.verify_signature(
+            &PlainData::new(b"Fourscore and eight years ago"),
+            &signature,
+            &subject.public_key(),
+        );
+
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn verifying_a_signature_against_the_wrong_key_fails() {
+        let data = PlainData::new(b"Fourscore and seven years ago");
+        let subject = CryptDEReal::new();
+        let other = CryptDEReal::new();
+
+        let signature = subject.sign(&data).unwrap();
+        let result = subject.verify_signature(&data, &signature, &other.public_key());
+
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn verifying_a_null_signature_fails() {
+        let data = PlainData::new(b"Fourscore and seven years ago");
+        let subject = CryptDEReal::new();
+        let signature = CryptDENull::new().sign(&data).unwrap();
+
+        let result = subject.verify_signature(&data, &signature, &subject.public_key());
+
+        assert_eq!(result, false);
+    }
+}
@@ -14,6 +14,7 @@ use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::time::Duration;
 use stream_handler_pool::TransmitDataMsg;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -132,10 +133,18 @@ impl Debug for InboundClientData {
     }
 }
 
+// Tells the Dispatcher to drop clandestine traffic from a misbehaving peer for a while.
+#[derive(Clone, Debug, PartialEq, Message)]
+pub struct BanPeerMessage {
+    pub ip_addr: IpAddr,
+    pub duration: Duration,
+}
+
 pub struct DispatcherSubs {
     pub ibcd_sub: Recipient<Syn, InboundClientData>,
     pub bind: Recipient<Syn, BindMessage>,
     pub from_dispatcher_client: Recipient<Syn, TransmitDataMsg>,
+    pub ban_peer: Recipient<Syn, BanPeerMessage>,
 }
 
 impl Clone for DispatcherSubs {
@@ -144,6 +153,7 @@ impl Clone for DispatcherSubs {
             ibcd_sub: self.ibcd_sub.clone(),
             bind: self.bind.clone(),
             from_dispatcher_client: self.from_dispatcher_client.clone(),
+            ban_peer: self.ban_peer.clone(),
         }
     }
 }
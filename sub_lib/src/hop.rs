@@ -1,16 +1,20 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use cryptde::CryptDE;
 use cryptde::CryptData;
-use cryptde::CryptdecError;
 use cryptde::Key;
 use cryptde::PlainData;
 use dispatcher::Component;
+use route::RouteError;
 use serde_cbor;
+use wallet::Wallet;
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Hop {
     pub public_key: Key,
     pub component: Component,
+    // The wallet that pays for this hop; Hops without it come from Nodes that don't keep accounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consuming_wallet: Option<Wallet>,
 }
 
 impl Hop {
@@ -18,25 +22,26 @@ impl Hop {
         Hop {
             public_key: key.clone(),
             component,
+            consuming_wallet: None,
         }
     }
 
-    pub fn decode(cryptde: &CryptDE, crypt_data: &CryptData) -> Result<Self, CryptdecError> {
-        let plain_data = cryptde.decode(crypt_data)?;
-        match serde_cbor::de::from_slice::<Hop>(&plain_data.data[..]) {
-            Ok(hop) => Ok(hop),
-            // crashpoint - need to figure out how to return deserialize error
-            Err(_) => unimplemented!("failed to deserialize hop"),
-        }
+    pub fn decode(cryptde: &CryptDE, crypt_data: &CryptData) -> Result<Self, RouteError> {
+        let plain_data = cryptde
+            .decode(crypt_data)
+            .map_err(RouteError::HopDecodingFailed)?;
+        serde_cbor::de::from_slice::<Hop>(&plain_data.data[..])
+            .map_err(|e| RouteError::HopDeserializationFailed(format!("{:?}", e)))
     }
 
-    pub fn encode(&self, public_key: &Key, cryptde: &CryptDE) -> Result<CryptData, CryptdecError> {
+    pub fn encode(&self, public_key: &Key, cryptde: &CryptDE) -> Result<CryptData, RouteError> {
         let plain_data = match serde_cbor::ser::to_vec(&self) {
             Ok(data) => PlainData::new(&data[..]),
-            // crashpoint - need to figure out how to return serialize error
-            Err(_) => unimplemented!(),
+            Err(e) => return Err(RouteError::HopSerializationFailed(format!("{:?}", e))),
         };
-        cryptde.encode(public_key, &plain_data)
+        cryptde
+            .encode(public_key, &plain_data)
+            .map_err(RouteError::HopEncodingFailed)
     }
 }
 
@@ -87,4 +92,31 @@ mod tests {
         );
         assert_eq!(Hop::decode(&cryptde, &none_hop_encoded).unwrap(), relay_hop);
     }
+
+    #[test]
+    fn decodes_hop_from_node_that_does_not_know_about_consuming_wallets() {
+        #[derive(Serialize)]
+        struct OldHop {
+            public_key: Key,
+            component: Component,
+        }
+        let cryptde = CryptDENull::new();
+        let old_hop = OldHop {
+            public_key: Key::new(&[1, 2, 3, 4]),
+            component: Component::ProxyClient,
+        };
+        let encoded = cryptde
+            .encode(
+                &cryptde.public_key(),
+                &PlainData::new(&serde_cbor::ser::to_vec(&old_hop).unwrap()[..]),
+            )
+            .unwrap();
+
+        let result = Hop::decode(&cryptde, &encoded).unwrap();
+
+        assert_eq!(
+            result,
+            Hop::new(&Key::new(&[1, 2, 3, 4]), Component::ProxyClient)
+        );
+    }
 }
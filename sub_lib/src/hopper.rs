@@ -10,6 +10,7 @@ use serde::de::Deserialize;
 use serde::ser::Serialize;
 use serde_cbor;
 use std::net::IpAddr;
+use wallet::Wallet;
 
 /// New CORES package about to be sent to the Hopper and thence put on the Substratum Network
 #[derive(Clone, Debug, PartialEq, Message)]
@@ -45,6 +46,7 @@ pub struct ExpiredCoresPackagePackage {
 pub struct ExpiredCoresPackage {
     pub remaining_route: Route,
     pub payload: PlainData,
+    pub consuming_wallet: Option<Wallet>,
 }
 
 impl ExpiredCoresPackage {
@@ -52,6 +54,7 @@ impl ExpiredCoresPackage {
         ExpiredCoresPackage {
             remaining_route,
             payload,
+            consuming_wallet: None,
         }
     }
 
@@ -95,6 +98,7 @@ mod tests {
                 RouteSegment::new(vec![&route_key, &route_key], Component::ProxyServer),
             ],
             &CryptDENull::new(),
+            None,
         )
         .unwrap();
         let payload = PayloadMock::new();
@@ -120,6 +124,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             &cryptde,
+            None,
         )
         .unwrap();
         let deserialized_payload = PayloadMock::new();
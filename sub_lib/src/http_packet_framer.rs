@@ -190,7 +190,7 @@ impl HttpPacketFramer {
     }
 
     fn check_for_content_length(&mut self, line: &Vec<u8>) {
-        if !line.starts_with("Content-Length:".as_bytes()) {
+        if !starts_with_ignoring_case(line, b"content-length:") {
             return;
         }
         let string = match String::from_utf8(line.clone()) {
@@ -200,7 +200,7 @@ impl HttpPacketFramer {
             }
             Ok(string) => string,
         };
-        let regex = Regex::new(r"^Content-Length: *(\d+)").expect("Could not create regex");
+        let regex = Regex::new(r"(?i)^Content-Length: *(\d+)").expect("Could not create regex");
         let captures = match regex.captures(&string[..]) {
             None => {
                 self.discard_current_request();
@@ -222,7 +222,7 @@ impl HttpPacketFramer {
     }
 
     fn check_for_transfer_encoding(&mut self, line: &Vec<u8>) {
-        if !line.starts_with("Transfer-Encoding:".as_bytes()) {
+        if !starts_with_ignoring_case(line, b"transfer-encoding:") {
             return;
         }
         let string = match String::from_utf8(line.clone()) {
@@ -232,7 +232,7 @@ impl HttpPacketFramer {
             }
             Ok(string) => string,
         };
-        let regex = Regex::new(r"^Transfer-Encoding: *(.+)").expect("Could not create regex");
+        let regex = Regex::new(r"(?i)^Transfer-Encoding: *(.+)").expect("Could not create regex");
         let captures = match regex.captures(&string[..]) {
             None => {
                 self.discard_current_request();
@@ -244,7 +244,7 @@ impl HttpPacketFramer {
             Some(thing) => thing.as_str(),
             None => return,
         };
-        if encodings.contains("chunked") {
+        if encodings.to_lowercase().contains("chunked") {
             self.framer_state.transfer_encoding_chunked = ChunkExistenceState::ChunkedResponse;
             self.framer_state.chunk_progress_state = ChunkProgressState::SeekingLengthHeader;
             self.framer_state.chunk_size = None;
@@ -255,6 +255,10 @@ impl HttpPacketFramer {
         if line.len() != 2 {
             return false;
         }
+        // A chunked body says how long it is chunk by chunk; any Content-Length alongside it is to be ignored
+        if self.framer_state.transfer_encoding_chunked == ChunkExistenceState::ChunkedResponse {
+            self.framer_state.content_length = 0;
+        }
         self.framer_state.packet_progress_state = PacketProgressState::SeekingBodyEnd;
         true
     }
@@ -368,6 +372,14 @@ const BYTES_TO_PRESERVE: usize = 9;
 const CRLF: &[u8; 2] = b"\r\n";
 const DOUBLE_CRLF: &[u8; 4] = b"\r\n\r\n";
 
+fn starts_with_ignoring_case(line: &[u8], lowercase_prefix: &[u8]) -> bool {
+    (line.len() >= lowercase_prefix.len())
+        && line[0..lowercase_prefix.len()]
+            .iter()
+            .zip(lowercase_prefix.iter())
+            .all(|(a, b)| a.to_ascii_lowercase() == *b)
+}
+
 pub fn summarize_http_packet(request: &Vec<u8>) -> String {
     let first_space_index = match index_of_from(request, &(' ' as u8), 0) {
         None => return String::from("<bad HTTP syntax: no spaces>"),
@@ -844,6 +856,57 @@ mod framer_tests {
         assert_eq!(none, None);
     }
 
+    #[test]
+    fn recognizes_header_names_in_any_case() {
+        let data = "GOOD_FIRST_LINE\r\ncontent-length: 5\r\n\r\nbooga\
+                    GOOD_FIRST_LINE\r\nTRANSFER-ENCODING: Chunked\r\n\r\n5\r\ngooba\r\n0\r\n\r\n"
+            .as_bytes();
+        let mut subject = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        subject.add_data(data);
+
+        let first_result = subject.take_frame().unwrap();
+        let second_result = subject.take_frame().unwrap();
+        let third_result = subject.take_frame().unwrap();
+        let fourth_result = subject.take_frame().unwrap();
+
+        assert_eq!(
+            to_string(&first_result.chunk),
+            String::from("GOOD_FIRST_LINE\r\ncontent-length: 5\r\n\r\nbooga")
+        );
+        assert_eq!(
+            to_string(&second_result.chunk),
+            String::from("GOOD_FIRST_LINE\r\nTRANSFER-ENCODING: Chunked\r\n\r\n")
+        );
+        assert_eq!(
+            to_string(&third_result.chunk),
+            String::from("5\r\ngooba\r\n")
+        );
+        assert_eq!(to_string(&fourth_result.chunk), String::from("0\r\n\r\n"));
+    }
+
+    #[test]
+    fn chunked_transfer_encoding_overrides_content_length() {
+        let data = "GOOD_FIRST_LINE\r\nContent-Length: 100\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    5\r\nbooga\r\n0\r\n\r\n"
+            .as_bytes();
+        let mut subject = HttpPacketFramer::new(Box::new(TameStartFinder {}));
+        subject.add_data(data);
+
+        let head = subject.take_frame().unwrap();
+        let chunk = subject.take_frame().unwrap();
+        let final_chunk = subject.take_frame().unwrap();
+
+        assert_eq!(
+            to_string(&head.chunk),
+            String::from(
+                "GOOD_FIRST_LINE\r\nContent-Length: 100\r\nTransfer-Encoding: chunked\r\n\r\n"
+            )
+        );
+        assert_eq!(to_string(&chunk.chunk), String::from("5\r\nbooga\r\n"));
+        assert_eq!(to_string(&final_chunk.chunk), String::from("0\r\n\r\n"));
+        assert_eq!(subject.framer_state.content_length, 0);
+    }
+
     #[test]
     fn summarize_http_packethandles_no_spaces() {
         let request = Vec::from("therearenospacesinthisbuffer\r\n".as_bytes());
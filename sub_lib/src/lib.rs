@@ -12,6 +12,7 @@ extern crate serde_cbor;
 #[macro_use]
 extern crate serde_derive;
 extern crate sha1;
+extern crate sodiumoxide;
 extern crate tokio;
 
 #[cfg(test)]
@@ -26,6 +27,7 @@ pub mod channel_wrappers;
 pub mod crash_point;
 pub mod cryptde;
 pub mod cryptde_null;
+pub mod cryptde_real;
 pub mod dispatcher;
 pub mod framer;
 pub mod framer_utils;
@@ -50,9 +52,12 @@ pub mod socket_server;
 pub mod stream_connector;
 pub mod stream_handler_pool;
 pub mod stream_key;
+pub mod tcp_listener_wrapper;
 pub mod tcp_wrappers;
 pub mod tls_framer;
+pub mod tls_server_impersonator;
 pub mod tokio_wrappers;
+pub mod tunnel_framer;
 pub mod udp_socket_wrapper;
 pub mod utils;
 pub mod wallet;
@@ -10,11 +10,16 @@ use peer_actors::BindMessage;
 use route::Route;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::Duration;
 use stream_handler_pool::DispatcherNodeQueryResponse;
 use stream_handler_pool::TransmitDataMsg;
 use wallet::Wallet;
 
 pub const SENTINEL_IP_OCTETS: [u8; 4] = [255, 255, 255, 255];
+pub const DEFAULT_ROUTE_RANDOMNESS: u8 = 10;
+pub const DEFAULT_MAX_NODE_AGE_HOURS: u64 = 168;
+pub const DEFAULT_MAX_GOSSIP_SIZE: usize = 262144;
 
 pub fn sentinel_ip_addr() -> IpAddr {
     IpAddr::V4(Ipv4Addr::new(
@@ -32,6 +37,12 @@ pub struct NeighborhoodConfig {
     pub local_ip_addr: IpAddr,
     pub clandestine_port_list: Vec<u16>,
     pub wallet: Option<Wallet>,
+    pub cell_size_opt: Option<usize>,
+    pub rate_pack: RatePack,
+    pub route_randomness: u8,
+    pub data_directory_opt: Option<PathBuf>,
+    pub max_node_age: Duration,
+    pub max_gossip_size: usize,
 }
 
 impl NeighborhoodConfig {
@@ -42,6 +53,32 @@ impl NeighborhoodConfig {
     }
 }
 
+/// What a Node charges for its services, in the smallest unit of its currency: so much for every CORES package,
+/// plus so much for every byte of payload in it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub struct RatePack {
+    pub routing_service_rate: u64,
+    pub routing_byte_rate: u64,
+    pub exit_service_rate: u64,
+    pub exit_byte_rate: u64,
+}
+
+impl RatePack {
+    pub fn routing_charge(&self, payload_size: u64) -> u64 {
+        self.routing_service_rate
+            .saturating_add(self.routing_byte_rate.saturating_mul(payload_size))
+    }
+
+    pub fn exit_charge(&self, payload_size: u64) -> u64 {
+        self.exit_service_rate
+            .saturating_add(self.exit_byte_rate.saturating_mul(payload_size))
+    }
+
+    pub fn is_free(&self) -> bool {
+        *self == RatePack::default()
+    }
+}
+
 #[derive(Clone)]
 pub struct NeighborhoodSubs {
     pub bind: Recipient<Syn, BindMessage>,
@@ -145,6 +182,14 @@ impl RouteQueryMessage {
 pub struct RouteQueryResponse {
     pub route: Route,
     pub segment_endpoints: Vec<Key>,
+    pub expected_services: Vec<ExpectedService>,
+}
+
+/// A service that another Node will provide to this one along a route, identified by the wallet it'll be owed to
+#[derive(PartialEq, Debug, Clone)]
+pub enum ExpectedService {
+    Routing(Wallet),
+    Exit(Wallet),
 }
 
 #[derive(PartialEq, Debug, Message, Clone)]
@@ -201,6 +246,12 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
             clandestine_port_list: vec![1234],
+            cell_size_opt: None,
+            rate_pack: RatePack::default(),
+            route_randomness: 0,
+            data_directory_opt: None,
+            max_node_age: Duration::from_secs(3600),
+            max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
         };
 
         let result = subject.is_decentralized();
@@ -219,6 +270,12 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: sentinel_ip_addr(),
             clandestine_port_list: vec![1234],
+            cell_size_opt: None,
+            rate_pack: RatePack::default(),
+            route_randomness: 0,
+            data_directory_opt: None,
+            max_node_age: Duration::from_secs(3600),
+            max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
         };
 
         let result = subject.is_decentralized();
@@ -237,6 +294,12 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
             clandestine_port_list: vec![],
+            cell_size_opt: None,
+            rate_pack: RatePack::default(),
+            route_randomness: 0,
+            data_directory_opt: None,
+            max_node_age: Duration::from_secs(3600),
+            max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
         };
 
         let result = subject.is_decentralized();
@@ -256,10 +319,44 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
             clandestine_port_list: vec![1234],
+            cell_size_opt: None,
+            rate_pack: RatePack::default(),
+            route_randomness: 0,
+            data_directory_opt: None,
+            max_node_age: Duration::from_secs(3600),
+            max_gossip_size: DEFAULT_MAX_GOSSIP_SIZE,
         };
 
         let result = subject.is_decentralized();
 
         assert_eq!(result, true);
     }
+
+    #[test]
+    fn rate_pack_charges_per_package_plus_per_byte() {
+        let subject = RatePack {
+            routing_service_rate: 100,
+            routing_byte_rate: 2,
+            exit_service_rate: 300,
+            exit_byte_rate: 4,
+        };
+
+        assert_eq!(subject.routing_charge(1000), 2100);
+        assert_eq!(subject.exit_charge(1000), 4300);
+        assert_eq!(subject.is_free(), false);
+        assert_eq!(RatePack::default().is_free(), true);
+    }
+
+    #[test]
+    fn rate_pack_charges_do_not_overflow() {
+        let subject = RatePack {
+            routing_service_rate: 1,
+            routing_byte_rate: u64::max_value(),
+            exit_service_rate: u64::max_value(),
+            exit_byte_rate: 1,
+        };
+
+        assert_eq!(subject.routing_charge(2), u64::max_value());
+        assert_eq!(subject.exit_charge(2), u64::max_value());
+    }
 }
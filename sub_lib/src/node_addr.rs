@@ -33,6 +33,28 @@ impl NodeAddr {
     pub fn ports(&self) -> Vec<u16> {
         self.ports.clone()
     }
+
+    // IPv6 addresses are full of colons, so they go in brackets to keep them apart from the ports.
+    fn bracketed_ip_addr(&self) -> String {
+        match self.ip_addr {
+            IpAddr::V4(ref ipv4_addr) => format!("{}", ipv4_addr),
+            IpAddr::V6(ref ipv6_addr) => format!("[{}]", ipv6_addr),
+        }
+    }
+
+    fn split_ip_addr_and_ports(input: &str) -> Option<(&str, &str)> {
+        if input.starts_with("[") {
+            let close = input.find("]:")?;
+            Some((&input[1..close], &input[(close + 2)..]))
+        } else {
+            let pieces: Vec<&str> = input.split(":").collect();
+            if pieces.len() != 2 {
+                None
+            } else {
+                Some((pieces[0], pieces[1]))
+            }
+        }
+    }
 }
 
 impl<'a> From<&'a SocketAddr> for NodeAddr {
@@ -58,7 +80,7 @@ impl Clone for NodeAddr {
 
 impl Debug for NodeAddr {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}:{:?}", self.ip_addr(), self.ports())
+        write!(f, "{}:{:?}", self.bracketed_ip_addr(), self.ports())
     }
 }
 
@@ -69,7 +91,7 @@ impl Display for NodeAddr {
             .iter()
             .map(|x| format!("{}", x))
             .collect::<Vec<String>>();
-        write!(f, "{}:{}", self.ip_addr(), port_list.join(","))
+        write!(f, "{}:{}", self.bracketed_ip_addr(), port_list.join(","))
     }
 }
 
@@ -77,23 +99,25 @@ impl FromStr for NodeAddr {
     type Err = String;
 
     fn from_str(input: &str) -> Result<NodeAddr, String> {
-        let pieces: Vec<&str> = input.split(":").collect();
-        if pieces.len() != 2 {
-            return Err(format!(
-                "NodeAddr should be expressed as '<IP address>:<port>,<port>,...', not '{}'",
-                input
-            ));
-        }
-        let ip_addr = match IpAddr::from_str(&pieces[0]) {
+        let (ip_addr_str, ports_str) = match NodeAddr::split_ip_addr_and_ports(input) {
+            Some(pieces) => pieces,
+            None => {
+                return Err(format!(
+                    "NodeAddr should be expressed as '<IP address>:<port>,<port>,...' or '[<IPv6 address>]:<port>,<port>,...', not '{}'",
+                    input
+                ))
+            }
+        };
+        let ip_addr = match IpAddr::from_str(ip_addr_str) {
             Err(_) => {
                 return Err(format!(
                     "NodeAddr must have a valid IP address, not '{}'",
-                    pieces[0]
+                    ip_addr_str
                 ))
             }
             Ok(ip_addr) => ip_addr,
         };
-        let ports: Vec<u16> = match pieces[1]
+        let ports: Vec<u16> = match ports_str
             .split(",")
             .map(|s| match s.parse::<u16>() {
                 Err(_) => Err(format!(
@@ -208,7 +232,7 @@ mod tests {
         assert_eq!(
             result,
             Err(String::from(
-                "NodeAddr should be expressed as '<IP address>:<port>,<port>,...', not 'Booga'"
+                "NodeAddr should be expressed as '<IP address>:<port>,<port>,...' or '[<IPv6 address>]:<port>,<port>,...', not 'Booga'"
             ))
         );
     }
@@ -261,4 +285,59 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn ipv6_node_addrs_produce_bracketed_strings() {
+        let ip_addr = IpAddr::from_str("2001:db8::1").unwrap();
+        let subject = NodeAddr::new(&ip_addr, &vec![9, 6]);
+
+        assert_eq!(format!("{}", subject), "[2001:db8::1]:6,9");
+        assert_eq!(format!("{:?}", subject), "[2001:db8::1]:[6, 9]");
+    }
+
+    #[test]
+    fn ipv6_node_addrs_from_str_needs_brackets() {
+        let result = NodeAddr::from_str("2001:db8::1:1234");
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "NodeAddr should be expressed as '<IP address>:<port>,<port>,...' or '[<IPv6 address>]:<port>,<port>,...', not '2001:db8::1:1234'"
+            ))
+        );
+    }
+
+    #[test]
+    fn ipv6_node_addrs_from_str_needs_good_ip_address() {
+        let result = NodeAddr::from_str("[2001:db8:::1]:1234");
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "NodeAddr must have a valid IP address, not '2001:db8:::1'"
+            ))
+        );
+    }
+
+    #[test]
+    fn ipv6_node_addrs_from_str_follows_the_happy_path() {
+        let result = NodeAddr::from_str("[2001:db8::1]:1234,2345,3456");
+
+        assert_eq!(
+            result,
+            Ok(NodeAddr::new(
+                &IpAddr::from_str("2001:db8::1").unwrap(),
+                &vec!(1234, 2345, 3456)
+            ))
+        );
+    }
+
+    #[test]
+    fn ipv6_node_addrs_survive_a_round_trip_through_a_string() {
+        let subject = NodeAddr::new(&IpAddr::from_str("fe80::1:2").unwrap(), &vec![4321]);
+
+        let result = NodeAddr::from_str(&format!("{}", subject));
+
+        assert_eq!(result, Ok(subject));
+    }
 }
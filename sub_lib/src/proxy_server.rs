@@ -12,6 +12,8 @@ use stream_key::StreamKey;
 pub enum ProxyProtocol {
     HTTP,
     TLS,
+    // Opaque bytes for a client that asked for a tunnel to its target, as with HTTP CONNECT
+    Tunnel,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
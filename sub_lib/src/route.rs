@@ -1,49 +1,45 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use cryptde::CryptDE;
 use cryptde::CryptData;
+use cryptde::CryptdecError;
 use cryptde::Key;
 use dispatcher::Component;
 use hop::Hop;
 use std::iter;
+use wallet::Wallet;
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Route {
     pub hops: Vec<CryptData>,
+    // Routes without this field come from Nodes that don't know about fixed-size cells
+    #[serde(default)]
+    pub cell_size_opt: Option<usize>,
 }
 
 impl Route {
-    // TODO: Drive out panic!s.
-    pub fn new(route_segments: Vec<RouteSegment>, cryptde: &CryptDE) -> Result<Route, RouteError> {
-        // crashpoint - send back a RouteError
+    pub fn new(
+        route_segments: Vec<RouteSegment>,
+        cryptde: &CryptDE,
+        consuming_wallet: Option<Wallet>,
+    ) -> Result<Route, RouteError> {
         if route_segments.is_empty() {
-            panic!("A route must have at least one segment")
+            return Err(RouteError::NoSegments);
         }
         let mut hops: Vec<Hop> = Vec::new();
         let mut pending_recipient: Option<Component> = None;
         for segment_index in 0..route_segments.len() {
             let route_segment = &route_segments[segment_index];
-            // crashpoint - send back a RouteError
             // TODO each route segment must have at least 2 keys
             if route_segment.keys.len() < 1 {
-                panic!(
-                    "Degenerate {}-element route segment",
-                    route_segment.keys.len()
-                )
+                return Err(RouteError::DegenerateSegment(segment_index));
             }
             for hop_index in 0..route_segment.keys.len() {
                 let key = &route_segment.keys[hop_index];
                 if (segment_index > 0) && (hop_index == 0) {
                     let last_segment = &route_segments[segment_index - 1];
                     let last_segment_last_key = &last_segment.keys[last_segment.keys.len() - 1];
-                    // crashpoint - send back a RouteError
                     if key != last_segment_last_key {
-                        panic!(
-                            "Route segment {} ({:?}) must begin where segment {} ({:?}) left off",
-                            segment_index,
-                            route_segment,
-                            segment_index - 1,
-                            last_segment
-                        )
+                        return Err(RouteError::DiscontinuousSegments(segment_index));
                     }
                     continue;
                 }
@@ -57,47 +53,35 @@ impl Route {
                 }
             }
         }
-        // crashpoint - should not be possible, can we restructure to remove the Option?
-        hops.push(Hop::new(
-            &Key::new(b""),
-            pending_recipient.expect("Route segment without recipient"),
-        ));
+        match pending_recipient {
+            Some(recipient) => hops.push(Hop::new(&Key::new(b""), recipient)),
+            None => return Err(RouteError::NoRecipient),
+        }
+        hops.iter_mut()
+            .for_each(|hop| hop.consuming_wallet = consuming_wallet.clone());
         Route::hops_to_route(hops[1..].to_vec(), &route_segments[0].keys[0], cryptde)
     }
 
-    // TODO: We should probably either have access to a Logger here, or return a Result instead of an Option.
-    pub fn next_hop(&self, cryptde: &CryptDE) -> Option<Hop> {
+    pub fn next_hop(&self, cryptde: &CryptDE) -> Result<Hop, RouteError> {
         match self.hops.first() {
-            None => None,
-            Some(first) => Route::decode_hop(cryptde, &first.clone()),
+            None => Err(RouteError::EmptyRoute),
+            Some(first) => Hop::decode(cryptde, first),
         }
     }
 
-    // TODO: We should probably either have access to a Logger here, or return a Result instead of an Option.
-    pub fn shift(&mut self, cryptde: &CryptDE) -> Option<Hop> {
+    pub fn shift(&mut self, cryptde: &CryptDE) -> Result<Hop, RouteError> {
         if self.hops.is_empty() {
-            return None;
+            return Err(RouteError::EmptyRoute);
         }
         let top_hop = self.hops.remove(0);
         let top_hop_len = top_hop.data.len();
-        let next_hop = match Route::decode_hop(cryptde, &top_hop) {
-            None => return None,
-            Some(h) => h,
-        };
+        let next_hop = Hop::decode(cryptde, &top_hop)?;
 
         let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(top_hop_len).collect();
         cryptde.random(&mut garbage_can[..]);
         self.hops.push(CryptData::new(&garbage_can[..]));
 
-        return Some(next_hop);
-    }
-
-    // TODO: We should probably either have access to a Logger here, or return a Result instead of an Option.
-    fn decode_hop(cryptde: &CryptDE, hop_enc: &CryptData) -> Option<Hop> {
-        match Hop::decode(cryptde, hop_enc) {
-            Err(_) => None,
-            Ok(h) => Some(h),
-        }
+        Ok(next_hop)
     }
 
     fn hops_to_route(
@@ -109,14 +93,13 @@ impl Route {
         let mut hop_key = top_hop_key;
         for hop_index in 0..hops.len() {
             let data_hop = &hops[hop_index];
-            // crashpoint - should not be possible, can this be restructured to remove Option?
-            hops_enc.push(match data_hop.encode(hop_key, cryptde) {
-                Ok(crypt_data) => crypt_data,
-                Err(_) => panic!("Couldn't encode hop"),
-            });
+            hops_enc.push(data_hop.encode(hop_key, cryptde)?);
             hop_key = &data_hop.public_key;
         }
-        Ok(Route { hops: hops_enc })
+        Ok(Route {
+            hops: hops_enc,
+            cell_size_opt: None,
+        })
     }
 }
 
@@ -135,14 +118,23 @@ impl RouteSegment {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum RouteError {
-    GenericRouteError, // TODO: replace this when we drive out the panic!s
+    NoSegments,
+    DegenerateSegment(usize),
+    DiscontinuousSegments(usize),
+    NoRecipient,
+    EmptyRoute,
+    HopEncodingFailed(CryptdecError),
+    HopDecodingFailed(CryptdecError),
+    HopSerializationFailed(String),
+    HopDeserializationFailed(String),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cryptde::PlainData;
     use cryptde_null::CryptDENull;
     use serde_cbor;
 
@@ -163,6 +155,7 @@ mod tests {
                 RouteSegment::new(vec![&d_key, &e_key, &f_key, &a_key], Component::ProxyServer),
             ],
             &cryptde,
+            None,
         )
         .unwrap();
 
@@ -207,6 +200,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             &cryptde,
+            None,
         )
         .unwrap();
 
@@ -223,6 +217,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn new_puts_consuming_wallet_in_every_hop() {
+        let a_key = Key::new(&[65, 65, 65]);
+        let b_key = Key::new(&[66, 66, 66]);
+        let mut cryptde = CryptDENull::new();
+        cryptde.generate_key_pair();
+        let wallet = Wallet::new("wallet");
+
+        let subject = Route::new(
+            vec![RouteSegment::new(
+                vec![&a_key, &b_key],
+                Component::Neighborhood,
+            )],
+            &cryptde,
+            Some(wallet.clone()),
+        )
+        .unwrap();
+
+        let mut first_hop = Hop::new(&b_key, Component::Hopper);
+        first_hop.consuming_wallet = Some(wallet.clone());
+        let mut second_hop = Hop::new(&Key::new(b""), Component::Neighborhood);
+        second_hop.consuming_wallet = Some(wallet);
+        assert_eq!(
+            subject.hops,
+            vec!(
+                first_hop.encode(&a_key, &cryptde).unwrap(),
+                second_hop.encode(&b_key, &cryptde).unwrap()
+            )
+        );
+    }
+
     #[test]
     fn next_hop_decodes_top_hop() {
         let mut cryptde = CryptDENull::new();
@@ -236,6 +261,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             &cryptde,
+            None,
         )
         .unwrap();
         assert_eq!(
@@ -285,6 +311,7 @@ mod tests {
                 Component::Neighborhood,
             )],
             &cryptde,
+            None,
         )
         .unwrap();
         assert_eq!(
@@ -323,25 +350,130 @@ mod tests {
     }
 
     #[test]
-    fn empty_route_says_none_when_asked_for_next_hop() {
+    fn empty_route_says_empty_route_when_asked_for_next_hop() {
         let mut cryptde = CryptDENull::new();
         cryptde.generate_key_pair();
-        let subject = Route { hops: Vec::new() };
+        let subject = Route {
+            hops: Vec::new(),
+            cell_size_opt: None,
+        };
 
         let result = subject.next_hop(&cryptde);
 
-        assert_eq!(result, None);
+        assert_eq!(result, Err(RouteError::EmptyRoute));
     }
 
     #[test]
-    fn shift_says_none_when_asked_for_next_hop() {
+    fn shift_says_empty_route_when_asked_for_next_hop() {
         let mut cryptde = CryptDENull::new();
         cryptde.generate_key_pair();
-        let mut subject = Route { hops: Vec::new() };
+        let mut subject = Route {
+            hops: Vec::new(),
+            cell_size_opt: None,
+        };
+
+        let result = subject.shift(&cryptde);
+
+        assert_eq!(result, Err(RouteError::EmptyRoute));
+    }
+
+    #[test]
+    fn new_complains_about_no_segments() {
+        let cryptde = CryptDENull::new();
+
+        let result = Route::new(vec![], &cryptde, None);
+
+        assert_eq!(result, Err(RouteError::NoSegments));
+    }
+
+    #[test]
+    fn new_complains_about_degenerate_segment() {
+        let cryptde = CryptDENull::new();
+        let key = Key::new(&[1, 2]);
+
+        let result = Route::new(
+            vec![
+                RouteSegment::new(vec![&key, &key], Component::ProxyClient),
+                RouteSegment::new(vec![], Component::ProxyServer),
+            ],
+            &cryptde,
+            None,
+        );
+
+        assert_eq!(result, Err(RouteError::DegenerateSegment(1)));
+    }
+
+    #[test]
+    fn new_complains_about_discontinuous_segments() {
+        let cryptde = CryptDENull::new();
+        let key1 = Key::new(&[1, 2]);
+        let key2 = Key::new(&[3, 4]);
+        let key3 = Key::new(&[5, 6]);
+
+        let result = Route::new(
+            vec![
+                RouteSegment::new(vec![&key1, &key2], Component::ProxyClient),
+                RouteSegment::new(vec![&key3, &key1], Component::ProxyServer),
+            ],
+            &cryptde,
+            None,
+        );
+
+        assert_eq!(result, Err(RouteError::DiscontinuousSegments(1)));
+    }
+
+    #[test]
+    fn new_complains_about_hop_that_cannot_be_encoded() {
+        let cryptde = CryptDENull::new();
+        let key = Key::new(&[1, 2]);
+
+        let result = Route::new(
+            vec![RouteSegment::new(
+                vec![&key, &Key::new(&[])],
+                Component::ProxyClient,
+            )],
+            &cryptde,
+            None,
+        );
+
+        assert_eq!(
+            result,
+            Err(RouteError::HopEncodingFailed(CryptdecError::EmptyKey))
+        );
+    }
+
+    #[test]
+    fn next_hop_complains_about_hop_that_cannot_be_decoded() {
+        let cryptde = CryptDENull::new();
+        let subject = Route {
+            hops: vec![CryptData::new(&[])],
+            cell_size_opt: None,
+        };
+
+        let result = subject.next_hop(&cryptde);
+
+        assert_eq!(
+            result,
+            Err(RouteError::HopDecodingFailed(CryptdecError::EmptyData))
+        );
+    }
+
+    #[test]
+    fn shift_complains_about_hop_that_cannot_be_deserialized() {
+        let cryptde = CryptDENull::new();
+        let mut subject = Route {
+            hops: vec![cryptde
+                .encode(&cryptde.public_key(), &PlainData::new(b"booga"))
+                .unwrap()],
+            cell_size_opt: None,
+        };
 
         let result = subject.shift(&cryptde);
 
-        assert_eq!(result, None);
+        match result {
+            Err(RouteError::HopDeserializationFailed(_)) => (),
+            x => panic!("Expected HopDeserializationFailed, got {:?}", x),
+        }
     }
 
     #[test]
@@ -355,6 +487,7 @@ mod tests {
                 RouteSegment::new(vec![&key2, &key1], Component::ProxyServer),
             ],
             &cryptde,
+            None,
         )
         .unwrap();
 
@@ -24,7 +24,7 @@ impl StreamKey {
         let mut hash = sha1::Sha1::new();
         match peer_addr.ip() {
             IpAddr::V4(ipv4) => hash.update(&ipv4.octets()),
-            IpAddr::V6(_ipv6) => unimplemented!(),
+            IpAddr::V6(ipv6) => hash.update(&ipv6.octets()),
         }
         hash.update(&[
             (peer_addr.port() >> 8) as u8,
@@ -35,6 +35,16 @@ impl StreamKey {
             hash: hash.digest().bytes(),
         }
     }
+
+    // Another stream from the same client connection, for a different host
+    pub fn for_host(&self, host_name: &str) -> StreamKey {
+        let mut hash = sha1::Sha1::new();
+        hash.update(&self.hash[..]);
+        hash.update(host_name.as_bytes());
+        StreamKey {
+            hash: hash.digest().bytes(),
+        }
+    }
 }
 
 type HashType = [u8; sha1::DIGEST_LENGTH];
@@ -101,4 +111,34 @@ mod tests {
 
         assert_eq!(result, String::from("X4SEhZulE9WrmSolWqKFErYBVgI"));
     }
+
+    #[test]
+    fn ipv6_addrs_make_stream_keys_too() {
+        let key = Key::new(&b"These are the times"[..]);
+        let one_addr = SocketAddr::from_str("[2001:db8::1]:6789").unwrap();
+        let another_addr = SocketAddr::from_str("[2001:db8::2]:6789").unwrap();
+
+        let one = StreamKey::new(key.clone(), one_addr);
+        let same = StreamKey::new(key.clone(), one_addr);
+        let another = StreamKey::new(key, another_addr);
+
+        assert_eq!(one, same);
+        assert_ne!(one, another);
+    }
+
+    #[test]
+    fn stream_keys_for_hosts_differ_from_each_other_and_from_the_original() {
+        let key = Key::new(&b"These are the times"[..]);
+        let addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
+        let original = StreamKey::new(key, addr);
+
+        let one = original.for_host("one.com");
+        let one_again = original.for_host("one.com");
+        let another = original.for_host("another.com");
+
+        assert_eq!(one, one_again);
+        assert_ne!(one, original);
+        assert_ne!(one, another);
+        assert_ne!(another, original);
+    }
 }
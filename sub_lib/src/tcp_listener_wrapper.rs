@@ -0,0 +1,53 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::io;
+use std::marker::Send;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::prelude::Async;
+use tokio::prelude::AsyncRead;
+use tokio_wrappers::ReadHalfWrapper;
+use tokio_wrappers::ReadHalfWrapperReal;
+use tokio_wrappers::WriteHalfWrapper;
+use tokio_wrappers::WriteHalfWrapperReal;
+
+pub type AcceptedConnection = (Box<ReadHalfWrapper>, Box<WriteHalfWrapper>, SocketAddr);
+
+pub trait TcpListenerWrapperTrait: Sync + Send {
+    fn bind(&mut self, addr: SocketAddr) -> io::Result<bool>;
+    fn poll_accept(&mut self) -> Result<Async<AcceptedConnection>, io::Error>;
+}
+
+pub struct TcpListenerWrapperReal {
+    delegate: Option<TcpListener>,
+}
+
+impl TcpListenerWrapperReal {
+    pub fn new() -> TcpListenerWrapperReal {
+        TcpListenerWrapperReal { delegate: None }
+    }
+}
+
+impl TcpListenerWrapperTrait for TcpListenerWrapperReal {
+    fn bind(&mut self, addr: SocketAddr) -> io::Result<bool> {
+        let listener = TcpListener::bind(&addr)?;
+        self.delegate = Some(listener);
+        Ok(true)
+    }
+
+    fn poll_accept(&mut self) -> Result<Async<AcceptedConnection>, io::Error> {
+        match self.delegate {
+            Some(ref mut listener) => match listener.poll_accept()? {
+                Async::Ready((stream, peer_addr)) => {
+                    let (read_half, write_half) = stream.split();
+                    Ok(Async::Ready((
+                        Box::new(ReadHalfWrapperReal::new(read_half)),
+                        Box::new(WriteHalfWrapperReal::new(write_half)),
+                        peer_addr,
+                    )))
+                }
+                Async::NotReady => Ok(Async::NotReady),
+            },
+            None => panic!("call bind before poll_accept"),
+        }
+    }
+}
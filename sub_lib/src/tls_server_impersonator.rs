@@ -0,0 +1,77 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+
+pub const HANDSHAKE_FAILURE: u8 = 40;
+pub const UNRECOGNIZED_NAME: u8 = 112;
+
+const ALERT_CONTENT_TYPE: u8 = 0x15;
+const FATAL: u8 = 0x02;
+// TLS 1.0, which is what nearly every client puts on the record that carries its ClientHello
+const DEFAULT_RECORD_VERSION: [u8; 2] = [0x03, 0x01];
+
+pub fn alert_name(description: u8) -> &'static str {
+    match description {
+        HANDSHAKE_FAILURE => "handshake_failure",
+        UNRECOGNIZED_NAME => "unrecognized_name",
+        _ => "unknown",
+    }
+}
+
+// A fatal alert record, in the same record version as the record the client sent us, so that the client will
+// understand it no matter which version of TLS it speaks.
+pub fn make_alert(client_record: &[u8], description: u8) -> Vec<u8> {
+    let record_version = if (client_record.len() >= 3) && (client_record[1] == 0x03) {
+        [client_record[1], client_record[2]]
+    } else {
+        DEFAULT_RECORD_VERSION
+    };
+    vec![
+        ALERT_CONTENT_TYPE,
+        record_version[0],
+        record_version[1],
+        0x00,
+        0x02,
+        FATAL,
+        description,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn makes_alert_in_the_record_version_of_the_client() {
+        let client_record = [0x16, 0x03, 0x03, 0x00, 0x05, 0x01];
+
+        let result = make_alert(&client_record, HANDSHAKE_FAILURE);
+
+        assert_eq!(result, vec![0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 40]);
+    }
+
+    #[test]
+    fn makes_alert_in_tls_1_0_if_the_client_record_has_no_version_we_recognize() {
+        vec![
+            &[][..],
+            &[0x16, 0x03][..],
+            &[0x80, 0x2E, 0x01, 0x03, 0x01][..],
+        ]
+        .into_iter()
+        .for_each(|client_record| {
+            let result = make_alert(client_record, UNRECOGNIZED_NAME);
+
+            assert_eq!(
+                result,
+                vec![0x15, 0x03, 0x01, 0x00, 0x02, 0x02, 112],
+                "{:?}",
+                client_record
+            );
+        });
+    }
+
+    #[test]
+    fn names_alerts() {
+        assert_eq!(alert_name(HANDSHAKE_FAILURE), "handshake_failure");
+        assert_eq!(alert_name(UNRECOGNIZED_NAME), "unrecognized_name");
+        assert_eq!(alert_name(0), "unknown");
+    }
+}
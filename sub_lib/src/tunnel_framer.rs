@@ -0,0 +1,66 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use framer::FramedChunk;
+use framer::Framer;
+
+// Tunnelled data has no structure we can see, so every chunk of it is a frame as soon as it arrives.
+pub struct TunnelFramer {
+    data_so_far: Vec<u8>,
+}
+
+impl Framer for TunnelFramer {
+    fn add_data(&mut self, data: &[u8]) {
+        self.data_so_far.extend(data);
+    }
+
+    fn take_frame(&mut self) -> Option<FramedChunk> {
+        if self.data_so_far.is_empty() {
+            None
+        } else {
+            Some(FramedChunk {
+                chunk: self.data_so_far.drain(..).collect(),
+                last_chunk: false,
+            })
+        }
+    }
+}
+
+impl TunnelFramer {
+    pub fn new() -> TunnelFramer {
+        TunnelFramer {
+            data_so_far: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_none_until_there_is_data() {
+        let mut subject = TunnelFramer::new();
+
+        let result = subject.take_frame();
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn returns_everything_that_has_arrived_as_one_frame() {
+        let mut subject = TunnelFramer::new();
+        subject.add_data(&b"booga"[..]);
+        subject.add_data(&[0x16, 0x03, 0x01]);
+
+        let first_result = subject.take_frame();
+        let second_result = subject.take_frame();
+
+        assert_eq!(
+            first_result,
+            Some(FramedChunk {
+                chunk: vec![b'b', b'o', b'o', b'g', b'a', 0x16, 0x03, 0x01],
+                last_chunk: false,
+            })
+        );
+        assert_eq!(second_result, None);
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use std::io::ErrorKind;
 use std::net::IpAddr;
+use std::net::Ipv4Addr;
 use std::str::FromStr;
 
 static DEAD_STREAM_ERRORS: [ErrorKind; 5] = [
@@ -17,6 +18,23 @@ pub fn localhost() -> IpAddr {
     IpAddr::from_str("127.0.0.1").expect("Something really crazy has happened")
 }
 
+// An IPv6 socket that also takes IPv4 connections reports their peers at IPv4-mapped addresses (::ffff:a.b.c.d),
+// but everything else knows those peers by their IPv4 addresses.
+pub fn unmapped(ip_addr: IpAddr) -> IpAddr {
+    match ip_addr {
+        IpAddr::V6(ipv6_addr) => match ipv6_addr.segments() {
+            [0, 0, 0, 0, 0, 0xFFFF, high, low] => IpAddr::V4(Ipv4Addr::new(
+                (high >> 8) as u8,
+                high as u8,
+                (low >> 8) as u8,
+                low as u8,
+            )),
+            _ => ip_addr,
+        },
+        IpAddr::V4(_) => ip_addr,
+    }
+}
+
 pub fn indicates_dead_stream(kind: ErrorKind) -> bool {
     DEAD_STREAM_ERRORS.contains(&kind)
 }
@@ -179,6 +197,24 @@ pub mod tests {
         });
     }
 
+    #[test]
+    fn unmapped_turns_ipv4_mapped_addresses_into_ipv4_addresses_and_leaves_others_alone() {
+        vec![
+            ("::ffff:1.2.3.4", "1.2.3.4"),
+            ("::ffff:255.0.128.1", "255.0.128.1"),
+            ("1.2.3.4", "1.2.3.4"),
+            ("::1", "::1"),
+            ("::1.2.3.4", "::1.2.3.4"),
+            ("2001:db8::ffff:1.2.3.4", "2001:db8::ffff:1.2.3.4"),
+        ]
+        .into_iter()
+        .for_each(|(given, expected)| {
+            let result = unmapped(IpAddr::from_str(given).unwrap());
+
+            assert_eq!(result, IpAddr::from_str(expected).unwrap(), "{}", given);
+        });
+    }
+
     #[test]
     fn index_of_fails_to_find_nonexistent_needle_in_haystack() {
         let result = index_of("haystack".as_bytes(), "needle".as_bytes());
@@ -23,5 +23,6 @@ pub mod data_hunk_framer;
 pub mod logging;
 pub mod recorder;
 pub mod stream_connector_mock;
+pub mod tcp_listener_wrapper_mock;
 pub mod tcp_wrapper_mocks;
 pub mod tokio_wrapper_mocks;
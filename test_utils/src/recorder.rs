@@ -12,6 +12,13 @@ use std::thread;
 use std::time::Duration;
 use std::time::Instant;
 use sub_lib::accountant::AccountantSubs;
+use sub_lib::accountant::LedgerQueryMessage;
+use sub_lib::accountant::LedgerTotals;
+use sub_lib::accountant::ReportExitServiceConsumedMessage;
+use sub_lib::accountant::ReportExitServiceMessage;
+use sub_lib::accountant::ReportRoutingServiceConsumedMessage;
+use sub_lib::accountant::ReportRoutingServiceMessage;
+use sub_lib::dispatcher::BanPeerMessage;
 use sub_lib::dispatcher::DispatcherSubs;
 use sub_lib::dispatcher::InboundClientData;
 use sub_lib::hopper::ExpiredCoresPackage;
@@ -38,6 +45,7 @@ pub struct Recorder {
     recording: Arc<Mutex<Recording>>,
     node_query_responses: Vec<Option<NodeDescriptor>>,
     route_query_responses: Vec<Option<RouteQueryResponse>>,
+    ledger_query_responses: Vec<LedgerTotals>,
 }
 
 pub struct Recording {
@@ -156,6 +164,14 @@ impl Handler<DispatcherNodeQueryMessage> for Recorder {
     }
 }
 
+impl Handler<BanPeerMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: BanPeerMessage, _ctx: &mut Self::Context) {
+        self.record(msg);
+    }
+}
+
 impl Handler<RemoveNeighborMessage> for Recorder {
     type Result = ();
 
@@ -164,6 +180,54 @@ impl Handler<RemoveNeighborMessage> for Recorder {
     }
 }
 
+impl Handler<ReportRoutingServiceMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportRoutingServiceMessage, _ctx: &mut Self::Context) {
+        self.record(msg);
+    }
+}
+
+impl Handler<ReportExitServiceMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportExitServiceMessage, _ctx: &mut Self::Context) {
+        self.record(msg);
+    }
+}
+
+impl Handler<ReportRoutingServiceConsumedMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportRoutingServiceConsumedMessage, _ctx: &mut Self::Context) {
+        self.record(msg);
+    }
+}
+
+impl Handler<ReportExitServiceConsumedMessage> for Recorder {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportExitServiceConsumedMessage, _ctx: &mut Self::Context) {
+        self.record(msg);
+    }
+}
+
+impl Handler<LedgerQueryMessage> for Recorder {
+    type Result = MessageResult<LedgerQueryMessage>;
+
+    fn handle(
+        &mut self,
+        msg: LedgerQueryMessage,
+        _ctx: &mut Self::Context,
+    ) -> <Self as Handler<LedgerQueryMessage>>::Result {
+        self.record(msg);
+        MessageResult(extract_response(
+            &mut self.ledger_query_responses,
+            "No LedgerTotals prepared for LedgerQueryMessage",
+        ))
+    }
+}
+
 fn extract_response<T>(responses: &mut Vec<T>, err_msg: &str) -> T
 where
     T: Clone,
@@ -181,6 +245,7 @@ impl Recorder {
             recording: Arc::new(Mutex::new(Recording { messages: vec![] })),
             node_query_responses: vec![],
             route_query_responses: vec![],
+            ledger_query_responses: vec![],
         }
     }
 
@@ -213,6 +278,11 @@ impl Recorder {
         self.route_query_responses.push(response);
         self
     }
+
+    pub fn ledger_query_response(mut self, response: LedgerTotals) -> Recorder {
+        self.ledger_query_responses.push(response);
+        self
+    }
 }
 
 impl Recording {
@@ -293,6 +363,7 @@ pub fn make_dispatcher_subs_from(addr: &Addr<Syn, Recorder>) -> DispatcherSubs {
         ibcd_sub: addr.clone().recipient::<InboundClientData>(),
         bind: addr.clone().recipient::<BindMessage>(),
         from_dispatcher_client: addr.clone().recipient::<TransmitDataMsg>(),
+        ban_peer: addr.clone().recipient::<BanPeerMessage>(),
     }
 }
 
@@ -326,6 +397,13 @@ pub fn make_neighborhood_subs_from(addr: &Addr<Syn, Recorder>) -> NeighborhoodSu
 pub fn make_accountant_subs_from(addr: &Addr<Syn, Recorder>) -> AccountantSubs {
     AccountantSubs {
         bind: addr.clone().recipient::<BindMessage>(),
+        report_routing_service: addr.clone().recipient::<ReportRoutingServiceMessage>(),
+        report_exit_service: addr.clone().recipient::<ReportExitServiceMessage>(),
+        report_routing_service_consumed: addr
+            .clone()
+            .recipient::<ReportRoutingServiceConsumedMessage>(),
+        report_exit_service_consumed: addr.clone().recipient::<ReportExitServiceConsumedMessage>(),
+        ledger_query: addr.clone().recipient::<LedgerQueryMessage>(),
     }
 }
 
@@ -0,0 +1,75 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use sub_lib::tcp_listener_wrapper::AcceptedConnection;
+use sub_lib::tcp_listener_wrapper::TcpListenerWrapperTrait;
+use tokio::prelude::Async;
+use tokio_wrapper_mocks::ReadHalfWrapperMock;
+use tokio_wrapper_mocks::WriteHalfWrapperMock;
+
+// Once it runs out of prepared results, this mock binds successfully and has no connections to accept.
+pub struct TcpListenerWrapperMock {
+    pub log: Arc<Mutex<Vec<String>>>,
+    bind_results: Mutex<Vec<io::Result<bool>>>,
+    poll_accept_results: Mutex<Vec<Result<Async<AcceptedConnection>, io::Error>>>,
+}
+
+impl TcpListenerWrapperTrait for TcpListenerWrapperMock {
+    fn bind(&mut self, addr: SocketAddr) -> io::Result<bool> {
+        self.log.lock().unwrap().push(format!("bind ({})", addr));
+        let mut bind_results = self.bind_results.lock().unwrap();
+        if bind_results.is_empty() {
+            Ok(true)
+        } else {
+            bind_results.remove(0)
+        }
+    }
+
+    fn poll_accept(&mut self) -> Result<Async<AcceptedConnection>, io::Error> {
+        self.log.lock().unwrap().push(String::from("poll_accept"));
+        let mut poll_accept_results = self.poll_accept_results.lock().unwrap();
+        if poll_accept_results.is_empty() {
+            Ok(Async::NotReady)
+        } else {
+            poll_accept_results.remove(0)
+        }
+    }
+}
+
+impl TcpListenerWrapperMock {
+    pub fn new() -> TcpListenerWrapperMock {
+        TcpListenerWrapperMock {
+            log: Arc::new(Mutex::new(vec![])),
+            bind_results: Mutex::new(vec![]),
+            poll_accept_results: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn bind_result(self, result: io::Result<bool>) -> TcpListenerWrapperMock {
+        self.bind_results.lock().unwrap().push(result);
+        self
+    }
+
+    pub fn poll_accept_result(
+        self,
+        result: Result<Async<AcceptedConnection>, io::Error>,
+    ) -> TcpListenerWrapperMock {
+        self.poll_accept_results.lock().unwrap().push(result);
+        self
+    }
+
+    pub fn poll_accept_ok(
+        self,
+        reader: ReadHalfWrapperMock,
+        writer: WriteHalfWrapperMock,
+        peer_addr: SocketAddr,
+    ) -> TcpListenerWrapperMock {
+        self.poll_accept_result(Ok(Async::Ready((
+            Box::new(reader),
+            Box::new(writer),
+            peer_addr,
+        ))))
+    }
+}
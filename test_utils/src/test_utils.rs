@@ -1,7 +1,9 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use regex::Regex;
 use std::cmp::min;
+use std::env::temp_dir;
 use std::fmt::Debug;
+use std::fs;
 use std::io;
 use std::io::Error;
 use std::io::Read;
@@ -10,6 +12,7 @@ use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::net::UdpSocket;
+use std::path::PathBuf;
 use std::str::from_utf8;
 use std::str::FromStr;
 use std::sync::mpsc;
@@ -38,6 +41,19 @@ pub fn cryptde() -> &'static CryptDENull {
     &CRYPT_DE_NULL
 }
 
+pub fn ensure_node_home_directory_exists(module: &str, name: &str) -> PathBuf {
+    let home_dir_string = format!(
+        "{}/SubstratumNode_test/{}/{}",
+        temp_dir().display(),
+        module,
+        name
+    );
+    let home_dir = PathBuf::from(home_dir_string.as_str());
+    let _ = fs::remove_dir_all(&home_dir);
+    let _ = fs::create_dir_all(&home_dir);
+    home_dir
+}
+
 pub struct ByteArrayWriter {
     pub byte_array: Vec<u8>,
     pub next_error: Option<Error>,
@@ -208,6 +224,7 @@ pub fn make_meaningless_route() -> Route {
             Component::ProxyClient,
         )],
         &CryptDENull::new(),
+        None,
     )
     .unwrap()
 }
@@ -233,14 +250,16 @@ pub fn zero_hop_route_response(public_key: &Key, cryptde: &CryptDE) -> RouteQuer
                 RouteSegment::new(vec![public_key, public_key], Component::ProxyServer),
             ],
             cryptde,
+            None,
         )
         .unwrap(),
         segment_endpoints: vec![public_key.clone(), public_key.clone()],
+        expected_services: vec![],
     }
 }
 
 fn shift_one_hop(mut route: Route, cryptde: &CryptDE) -> Route {
-    route.shift(cryptde);
+    route.shift(cryptde).unwrap();
     route
 }
 